@@ -0,0 +1,145 @@
+// Benchmark of placement pruning and heatmap accumulation on a standard 10x10
+// board, comparing position lists (the previous representation), bitsets
+// processed a word at a time, and the chunked bitsets the solver uses. The
+// 10x10 sets take 2 to 3 words, so this is the case the chunking has to help.
+//
+// Run with: cargo run --release --example bitset_bench
+
+extern crate battleship_ai;
+
+use battleship_ai::bitset::PlacementSet;
+use battleship_ai::rng::Rng;
+use std::time::Instant;
+
+const SIZE: usize = 10;
+const FLEET: [usize; 5] = [5, 4, 3, 3, 2];
+const ROUNDS: usize = 2000;
+
+// A bitset with the same layout, minus the padding, whose operations go a
+// word at a time
+struct ScalarSet(Vec<u64>);
+
+impl ScalarSet {
+	fn from_ids(len: usize, ids: &[usize]) -> ScalarSet {
+		let mut words = vec![0; len.div_ceil(64)];
+		for &id in ids {
+			words[id / 64] |= 1 << (id % 64);
+		}
+		ScalarSet(words)
+	}
+
+	fn subtract(&mut self, other: &ScalarSet) {
+		for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+			*a &= !b;
+		}
+	}
+
+	fn intersection_count(&self, other: &ScalarSet) -> usize {
+		self.0.iter().zip(other.0.iter()).map(|(a, b)| (a & b).count_ones() as usize).sum()
+	}
+}
+
+// Every placement of a ship of the given length on the 10x10 board, as its squares
+fn placements(len: usize) -> Vec<Vec<usize>> {
+	let mut out = Vec::new();
+	for row in 0..SIZE {
+		for col in 0..SIZE - len + 1 {
+			out.push((0..len).map(|i| row * SIZE + col + i).collect());
+		}
+	}
+	for row in 0..SIZE - len + 1 {
+		for col in 0..SIZE {
+			out.push((0..len).map(|i| (row + i) * SIZE + col).collect());
+		}
+	}
+	out
+}
+
+fn main() {
+	let fleet: Vec<Vec<Vec<usize>>> = FLEET.iter().map(|&len| placements(len)).collect();
+	let cover: Vec<Vec<PlacementSet>> = fleet.iter().map(|ship| {
+		(0..SIZE * SIZE).map(|square| {
+			let mut mask = PlacementSet::new_empty(ship.len());
+			for (id, squares) in ship.iter().enumerate() {
+				if squares.contains(&square) {
+					mask.insert(id);
+				}
+			}
+			mask
+		}).collect()
+	}).collect();
+
+	let scalar_cover: Vec<Vec<ScalarSet>> = fleet.iter().zip(cover.iter()).map(|(ship, masks)| {
+		masks.iter().map(|mask| ScalarSet::from_ids(ship.len(), &mask.iter().collect::<Vec<_>>())).collect()
+	}).collect();
+
+	// The same random sequences of misses for every representation
+	let mut rng = Rng::new(1);
+	let games: Vec<Vec<usize>> = (0..ROUNDS).map(|_| (0..30).map(|_| rng.gen_range(SIZE * SIZE)).collect()).collect();
+
+	let start = Instant::now();
+	let mut list_total = 0;
+	for misses in &games {
+		let mut lists: Vec<Vec<usize>> = fleet.iter().map(|ship| (0..ship.len()).collect()).collect();
+		for &miss in misses {
+			for (ship, list) in fleet.iter().zip(lists.iter_mut()) {
+				list.retain(|&id| !ship[id].contains(&miss));
+			}
+		}
+		let mut heat = [0usize; SIZE * SIZE];
+		for (ship, list) in fleet.iter().zip(lists.iter()) {
+			for &id in list {
+				for &square in &ship[id] {
+					heat[square] += 1;
+				}
+			}
+		}
+		list_total += heat.iter().sum::<usize>();
+	}
+	let list_time = start.elapsed();
+
+	let start = Instant::now();
+	let mut scalar_total = 0;
+	for misses in &games {
+		let mut sets: Vec<ScalarSet> = fleet.iter().map(|ship| ScalarSet::from_ids(ship.len(), &(0..ship.len()).collect::<Vec<_>>())).collect();
+		for &miss in misses {
+			for (set, masks) in sets.iter_mut().zip(scalar_cover.iter()) {
+				set.subtract(&masks[miss]);
+			}
+		}
+		let mut heat = [0usize; SIZE * SIZE];
+		for (set, masks) in sets.iter().zip(scalar_cover.iter()) {
+			for (square, cell) in heat.iter_mut().enumerate() {
+				*cell += set.intersection_count(&masks[square]);
+			}
+		}
+		scalar_total += heat.iter().sum::<usize>();
+	}
+	let scalar_time = start.elapsed();
+
+	let start = Instant::now();
+	let mut bitset_total = 0;
+	for misses in &games {
+		let mut sets: Vec<PlacementSet> = fleet.iter().map(|ship| PlacementSet::new_full(ship.len())).collect();
+		for &miss in misses {
+			for (set, masks) in sets.iter_mut().zip(cover.iter()) {
+				set.subtract(&masks[miss]);
+			}
+		}
+		let mut heat = [0usize; SIZE * SIZE];
+		for (set, masks) in sets.iter().zip(cover.iter()) {
+			for (square, cell) in heat.iter_mut().enumerate() {
+				*cell += set.intersection_count(&masks[square]);
+			}
+		}
+		bitset_total += heat.iter().sum::<usize>();
+	}
+	let bitset_time = start.elapsed();
+
+	assert_eq!(list_total, scalar_total, "Representations disagree");
+	assert_eq!(list_total, bitset_total, "Representations disagree");
+	println!("{} games of 30 misses on 10x10:", ROUNDS);
+	println!("  position lists:  {:?}", list_time);
+	println!("  scalar bitsets:  {:?} ({:.1}x faster)", scalar_time, list_time.as_secs_f64() / scalar_time.as_secs_f64());
+	println!("  chunked bitsets: {:?} ({:.1}x faster, {:.2}x the scalar bitsets)", bitset_time, list_time.as_secs_f64() / bitset_time.as_secs_f64(), scalar_time.as_secs_f64() / bitset_time.as_secs_f64());
+}
@@ -0,0 +1,19 @@
+// The moves file parser must reject malformed lines with an error, never a
+// panic, whatever encoding and line endings the file was saved with
+#![no_main]
+
+use battleship_ai::gamelog::GameLog;
+use battleship_ai::text;
+use battleship_ai::{Board, SHIP_TYPES};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(contents) = text::decode(data) {
+		let contents = contents.as_str();
+		let board = Board::new(5, &SHIP_TYPES).unwrap();
+		if let Ok(log) = GameLog::from_moves_string("demo", &board, contents) {
+			// Whatever parses must round-trip
+			assert_eq!(GameLog::from_moves_string("demo", &board, &log.to_moves_string()), Ok(log));
+		}
+	}
+});
@@ -0,0 +1,18 @@
+// The placement file parser must reject malformed layouts with an error, never a panic
+#![no_main]
+
+use battleship_ai::placement::{Constraints, Layout};
+use battleship_ai::{Board, SHIP_TYPES};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(contents) = std::str::from_utf8(data) {
+		let board = Board::new(5, &SHIP_TYPES).unwrap();
+		for &allow_touching in &[true, false] {
+			if let Ok(layout) = Layout::from_file_string(contents, Constraints::new(&board, allow_touching)) {
+				// Whatever parses must round-trip
+				assert_eq!(Layout::from_file_string(&layout.to_file_string(), Constraints::new(&board, allow_touching)), Ok(layout));
+			}
+		}
+	}
+});
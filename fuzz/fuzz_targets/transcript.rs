@@ -0,0 +1,21 @@
+// The transcript importers must reject malformed input with an error, never a
+// panic. The first byte chooses the format.
+#![no_main]
+
+use battleship_ai::import::{self, Format};
+use battleship_ai::text;
+use battleship_ai::{Board, SHIP_TYPES};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	let (format, rest) = match data.split_first() {
+		Some((&0, rest)) => (Format::Csv, rest),
+		Some((&1, rest)) => (Format::Grid, rest),
+		Some((&2, rest)) => (Format::Json, rest),
+		_ => return,
+	};
+
+	if let Ok(contents) = text::decode(rest) {
+		let _ = import::import(format, "demo", &Board::new(5, &SHIP_TYPES).unwrap(), &contents);
+	}
+});
@@ -0,0 +1,225 @@
+// Adaptation to a repeated opponent. Over a session of games against the same
+// opponent, an OpponentModel learns where they tend to place their fleet and
+// in what order they shoot, with older games decaying in influence. The
+// placement prior tilts our shots towards their favorite squares, and the
+// shooting-pattern model steers our own layout away from the squares they
+// shoot early.
+
+use super::{Board, BoardPos, GameState, Heatmap};
+use cancel::CancelToken;
+use placement::{Constraints, Layout};
+use rng::Rng;
+use rules::Rules;
+use sim;
+use strategy::{Greedy, Strategy, TieBreak, TieBreaker};
+
+// How many games' worth of weight the uninformed prior gets when blending it
+// with what has been learned
+const PRIOR_GAMES: f64 = 2.0;
+
+// How many random layouts to consider when choosing our own
+const LAYOUT_CANDIDATES: usize = 50;
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct OpponentModel {
+	// The board the opponent's fleet is on
+	board: Board,
+
+	// Each game's weight is multiplied by this after every later game
+	decay: f64,
+
+	// The decayed number of games observed
+	games: f64,
+
+	// Per square, the decayed number of games in which it held one of their ships
+	occupied: Vec<f64>,
+
+	// Per square, the decayed sum of how early they shot it (1 for their first
+	// shot, falling linearly to 0 for their last; 0 if never shot)
+	earliness: Vec<f64>,
+}
+
+impl OpponentModel {
+	pub fn new(board: &Board, decay: f64) -> OpponentModel {
+		let squares = board.num_squares();
+		OpponentModel { board: board.clone(), decay, games: 0.0, occupied: vec![0.0; squares], earliness: vec![0.0; squares] }
+	}
+
+	pub fn games(&self) -> f64 {
+		self.games
+	}
+
+	// Learn from one finished game: the opponent's layout, and their shots at ours in order
+	pub fn observe(&mut self, layout: &Layout, their_shots: &[BoardPos]) {
+		self.games = self.games * self.decay + 1.0;
+		for count in self.occupied.iter_mut().chain(self.earliness.iter_mut()) {
+			*count *= self.decay;
+		}
+
+		for (idx, cell) in layout.occupancy().iter().enumerate() {
+			if cell.is_some() {
+				self.occupied[idx] += 1.0;
+			}
+		}
+		for (turn, pos) in their_shots.iter().enumerate() {
+			self.earliness[pos.index()] += 1.0 - turn as f64 / their_shots.len() as f64;
+		}
+	}
+
+	// The learned probability that the square holds a ship, blended with the
+	// given uninformed probability
+	pub fn occupancy(&self, pos: BoardPos, uninformed: f64) -> f64 {
+		(self.occupied[pos.index()] + PRIOR_GAMES * uninformed) / (self.games + PRIOR_GAMES)
+	}
+
+	// Choose a layout for our fleet, preferring squares the opponent has tended
+	// to shoot late. Returns None if no valid layout was found.
+	pub fn choose_layout(&self, rng: &mut Rng, constraints: Constraints) -> Option<Layout> {
+		let exposure = |layout: &Layout| -> f64 {
+			layout.occupancy().iter().enumerate().filter(|&(_, cell)| cell.is_some()).map(|(idx, _)| self.earliness[idx]).sum()
+		};
+
+		let mut best: Option<(Layout, f64)> = None;
+		for _ in 0..LAYOUT_CANDIDATES {
+			let layout = Layout::random(rng, constraints.clone())?;
+			let score = exposure(&layout);
+			if best.as_ref().is_none_or(|&(_, best_score)| score < best_score) {
+				best = Some((layout, score));
+			}
+		}
+
+		best.map(|(layout, _)| layout)
+	}
+}
+
+// Greedy shooting, with each square's probability scaled by how much more (or
+// less) often the opponent has used it than an uninformed opponent would.
+// Squares the observations rule out stay ruled out.
+pub struct Adaptive {
+	tie_break: TieBreaker,
+
+	// Per square, the learned occupancy divided by the uninformed occupancy
+	tilt: Vec<f64>,
+}
+
+impl Adaptive {
+	pub fn new(model: &OpponentModel, tie_break: TieBreak) -> Adaptive {
+		Adaptive::with_prior(&model.board, |pos, uninformed| model.occupancy(pos, uninformed), tie_break)
+	}
+
+	// Tilt towards a different prior, given as a function from a square and its
+	// uninformed occupancy probability to its occupancy probability under the
+	// prior, on the given board
+	pub fn with_prior<F: Fn(BoardPos, f64) -> f64>(board: &Board, prior: F, tie_break: TieBreak) -> Adaptive {
+		let opening = GameState::new_cached(board).heatmap();
+		let tilt = board.squares().map(|pos| {
+			let uninformed = opening.probability(pos);
+			if uninformed > 0.0 { prior(pos, uninformed) / uninformed } else { 1.0 }
+		}).collect();
+
+		Adaptive { tie_break: TieBreaker::new(tie_break), tilt }
+	}
+
+	fn best_in(&mut self, state: &GameState, heatmap: &Heatmap) -> Option<BoardPos> {
+		let mut candidates = Vec::new();
+		let mut best_score = 0.0;
+		for pos in state.board().squares() {
+			if state.is_shot(pos) {
+				continue;
+			}
+
+			let score = heatmap.probability(pos) * self.tilt[pos.index()];
+			if candidates.is_empty() || score > best_score {
+				candidates.clear();
+				best_score = score;
+			}
+			if score == best_score {
+				candidates.push(pos);
+			}
+		}
+
+		if candidates.is_empty() {
+			return None;
+		}
+
+		Some(self.tie_break.select(&candidates, state))
+	}
+}
+
+impl Strategy for Adaptive {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		let (heatmap, _) = state.heatmap_best_effort(cancel);
+		self.best_in(state, &heatmap)
+	}
+}
+
+// A simulated opponent with habits: they place one of a few favorite layouts
+// each game, and shoot with the greedy strategy
+pub struct HabitualOpponent {
+	favorites: Vec<Layout>,
+}
+
+impl HabitualOpponent {
+	// Generate an opponent with the given number of favorite layouts, or None if
+	// no valid layout exists under the rules
+	pub fn random(rng: &mut Rng, favorites: usize, constraints: Constraints) -> Option<HabitualOpponent> {
+		let favorites = (0..favorites.max(1)).map(|_| Layout::random(rng, constraints.clone())).collect::<Option<_>>()?;
+		Some(HabitualOpponent { favorites })
+	}
+
+	pub fn layout(&self, rng: &mut Rng) -> &Layout {
+		&self.favorites[rng.gen_range(self.favorites.len())]
+	}
+}
+
+// The outcome of a best-of-N match
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct MatchReport {
+	pub wins: usize,
+	pub losses: usize,
+	pub ties: usize,
+
+	// The total number of shots we needed across all games
+	pub shots: usize,
+}
+
+// Play a session of games against a habitual opponent, who is rebuilt from the
+// seed so every call faces the same one. Each game, both sides attack the
+// other's layout and whoever needs fewer shots wins. If adapt is set, we learn
+// from each game with the given decay; otherwise we play greedy shots and
+// uniformly random layouts throughout.
+pub fn play_match(games: usize, seed: u64, rules: &Rules, adapt: bool, decay: f64,
+                  on_game: &mut dyn FnMut(usize, usize, usize)) -> Option<MatchReport> {
+	// Separate generators for each side, so adapting doesn't change the opponent's choices
+	let mut rng = Rng::new(seed);
+	let opponent = HabitualOpponent::random(&mut rng, 3, rules.constraints())?;
+	let mut our_rng = Rng::new(rng.next_u64());
+	let board = rules.board().ok()?;
+	let mut model = OpponentModel::new(&board, decay);
+	let mut report = MatchReport::default();
+
+	for game in 0..games {
+		let theirs = opponent.layout(&mut rng).clone();
+		let ours = if adapt { model.choose_layout(&mut our_rng, rules.constraints())? } else { Layout::random(&mut our_rng, rules.constraints())? };
+
+		let our_shots = if adapt {
+			sim::play_game(&mut Adaptive::new(&model, TieBreak::Lexicographic), &theirs, rules)
+		} else {
+			sim::play_game(&mut Greedy::new(TieBreak::Lexicographic), &theirs, rules)
+		};
+		let their_shots = sim::play_game(&mut Greedy::new(TieBreak::Lexicographic), &ours, rules);
+
+		match our_shots.len().cmp(&their_shots.len()) {
+			std::cmp::Ordering::Less => report.wins += 1,
+			std::cmp::Ordering::Greater => report.losses += 1,
+			std::cmp::Ordering::Equal => report.ties += 1,
+		}
+		report.shots += our_shots.len();
+		on_game(game, our_shots.len(), their_shots.len());
+
+		let their_order: Vec<BoardPos> = their_shots.iter().map(|&(pos, _)| pos).collect();
+		model.observe(&theirs, &their_order);
+	}
+
+	Some(report)
+}
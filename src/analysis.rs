@@ -0,0 +1,134 @@
+// Similarity metrics between placements and between shot sequences, used to
+// cluster opponents by style and to detect reused layouts across games, and a
+// post-game review that flags wasted shots.
+
+use super::{BoardPos, ShotResult};
+use placement::Layout;
+use rules::Rules;
+
+// Jaccard similarity of two sets of squares: shared squares over total distinct squares
+fn jaccard(a: &[BoardPos], b: &[BoardPos]) -> f64 {
+	let shared = a.iter().filter(|square| b.contains(square)).count();
+	let distinct = a.len() + b.len() - shared;
+	if distinct == 0 {
+		return 1.0;
+	}
+
+	shared as f64 / distinct as f64
+}
+
+// The squares occupied by a layout
+fn occupied_squares(layout: &Layout) -> Vec<BoardPos> {
+	layout.occupancy().iter().enumerate().filter(|&(_, cell)| cell.is_some()).filter_map(|(pos, _)| layout.board().square_at_index(pos)).collect()
+}
+
+// Board edit distance: the number of squares whose occupant (or lack thereof) differs
+pub fn layout_distance(a: &Layout, b: &Layout) -> usize {
+	a.occupancy().iter().zip(b.occupancy().iter()).filter(|&(cell_a, cell_b)| cell_a != cell_b).count()
+}
+
+// The number of ships whose positions differ between the layouts
+pub fn ships_moved(a: &Layout, b: &Layout) -> usize {
+	a.ships().iter().filter(|ship| !b.ships().contains(ship)).count()
+}
+
+// Similarity of two layouts in [0, 1], ignoring which ship occupies each square
+pub fn layout_similarity(a: &Layout, b: &Layout) -> f64 {
+	jaccard(&occupied_squares(a), &occupied_squares(b))
+}
+
+// Similarity of two shot sequences in [0, 1], ignoring the order of the shots
+pub fn shot_set_similarity(a: &[BoardPos], b: &[BoardPos]) -> f64 {
+	jaccard(a, b)
+}
+
+// Levenshtein distance between two shot sequences: the minimum number of
+// inserted, removed, or replaced shots needed to turn one into the other
+pub fn shot_sequence_distance(a: &[BoardPos], b: &[BoardPos]) -> usize {
+	let mut prev_row: Vec<usize> = (0..=b.len()).collect();
+	for (i, &shot_a) in a.iter().enumerate() {
+		let mut cur_row = Vec::with_capacity(b.len() + 1);
+		cur_row.push(i + 1);
+		for (j, &shot_b) in b.iter().enumerate() {
+			let replace = prev_row[j] + if shot_a == shot_b { 0 } else { 1 };
+			cur_row.push(replace.min(prev_row[j + 1] + 1).min(cur_row[j] + 1));
+		}
+		prev_row = cur_row;
+	}
+
+	prev_row[b.len()]
+}
+
+// Similarity of two shot sequences in [0, 1], taking their order into account
+pub fn shot_sequence_similarity(a: &[BoardPos], b: &[BoardPos]) -> f64 {
+	let longest = a.len().max(b.len());
+	if longest == 0 {
+		return 1.0;
+	}
+
+	1.0 - shot_sequence_distance(a, b) as f64 / longest as f64
+}
+
+// Why a shot was wasted
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Waste {
+	// No configuration consistent with the earlier moves occupied the square
+	Impossible,
+
+	// The given unshot square was occupied in every configuration that
+	// occupied the shot square, and in more besides, so shooting it instead
+	// was strictly more likely to hit. Squares certain to be hit don't count:
+	// they can be collected at any time.
+	Dominated(BoardPos),
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct WastedShot {
+	// The (1-based) turn of the shot
+	pub turn: usize,
+
+	pub pos: BoardPos,
+	pub waste: Waste,
+
+	// The chance the shot had of hitting, and the chance the dominating square
+	// had (equal for impossible shots)
+	pub probability: f64,
+	pub better: f64,
+}
+
+// Replay a finished game's moves under the given rules, flagging every shot
+// that was wasted given what was known when it was fired. Where several
+// squares dominate a shot, the most likely is named.
+pub fn wasted_shots(moves: &[(BoardPos, ShotResult)], rules: &Rules) -> Vec<WastedShot> {
+	let mut state = rules.new_game();
+	let mut wasted = Vec::new();
+	for (idx, &(pos, result)) in moves.iter().enumerate() {
+		let heatmap = state.heatmap();
+		let probability = heatmap.probability(pos);
+		let waste = if heatmap.total > 0 && heatmap.counts[pos.index()] == 0 {
+			Some(Waste::Impossible)
+		} else {
+			// The squares occupied whenever pos is: those the hypothetical hit
+			// at pos leaves occupied in every configuration
+			let mut hit = state.clone();
+			hit.apply_move((pos, ShotResult::Hit(None)));
+			let given_hit = hit.heatmap();
+			state.board().squares()
+				.filter(|&other| other != pos && !state.is_shot(other) && given_hit.total > 0 && given_hit.counts[other.index()] == given_hit.total)
+				.filter(|&other| heatmap.counts[other.index()] > heatmap.counts[pos.index()] && heatmap.counts[other.index()] < heatmap.total)
+				.max_by_key(|&other| (heatmap.counts[other.index()], std::cmp::Reverse(other)))
+				.map(Waste::Dominated)
+		};
+
+		if let Some(waste) = waste {
+			let better = match waste {
+				Waste::Impossible => probability,
+				Waste::Dominated(other) => heatmap.probability(other),
+			};
+			wasted.push(WastedShot { turn: idx + 1, pos, waste, probability, better });
+		}
+		state.apply_move((pos, result));
+	}
+
+	wasted
+}
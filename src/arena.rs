@@ -0,0 +1,128 @@
+// A generational arena for search-node state.
+//
+// Searches that explore many hypothetical belief states can allocate their
+// nodes here and refer to them by Handle instead of by reference, which keeps
+// the borrow checker out of the way when nodes refer to each other. Removing
+// a node keeps its value around so the next allocation can reuse its heap
+// buffers (via Clone::clone_from) instead of allocating fresh ones. Each slot
+// has a generation that is bumped on removal, so stale handles to a reused
+// slot are detected rather than silently aliasing the new node.
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct Handle {
+	index: u32,
+	generation: u32,
+}
+
+struct Slot<T> {
+	generation: u32,
+
+	// Whether the slot currently holds a live node
+	live: bool,
+
+	// The node, or (when not live) a retired node whose buffers can be reused.
+	// None only if the slot has never held a value.
+	value: Option<T>,
+}
+
+pub struct Arena<T> {
+	slots: Vec<Slot<T>>,
+
+	// Indices of slots that are not live
+	free: Vec<u32>,
+}
+
+impl<T> Arena<T> {
+	pub fn new() -> Arena<T> {
+		Arena { slots: Vec::new(), free: Vec::new() }
+	}
+
+	// The number of live nodes
+	pub fn len(&self) -> usize {
+		self.slots.len() - self.free.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	// Take a free slot (or make a new one), returning its index
+	fn claim(&mut self) -> u32 {
+		match self.free.pop() {
+			Some(index) => index,
+			None => {
+				self.slots.push(Slot { generation: 0, live: false, value: None });
+				(self.slots.len() - 1) as u32
+			},
+		}
+	}
+
+	// Add a node
+	pub fn insert(&mut self, value: T) -> Handle {
+		let index = self.claim();
+		let slot = &mut self.slots[index as usize];
+		slot.live = true;
+		slot.value = Some(value);
+		Handle { index, generation: slot.generation }
+	}
+
+	// Remove a node, returning false if the handle was stale. The node's
+	// buffers are kept for reuse by alloc_from.
+	pub fn remove(&mut self, handle: Handle) -> bool {
+		if self.get(handle).is_none() {
+			return false;
+		}
+
+		let slot = &mut self.slots[handle.index as usize];
+		slot.live = false;
+		slot.generation = slot.generation.wrapping_add(1);
+		self.free.push(handle.index);
+		true
+	}
+
+	pub fn get(&self, handle: Handle) -> Option<&T> {
+		match self.slots.get(handle.index as usize) {
+			Some(slot) if slot.live && slot.generation == handle.generation => slot.value.as_ref(),
+			_ => None,
+		}
+	}
+
+	pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+		match self.slots.get_mut(handle.index as usize) {
+			Some(slot) if slot.live && slot.generation == handle.generation => slot.value.as_mut(),
+			_ => None,
+		}
+	}
+
+	// Remove every node, keeping their buffers for reuse
+	pub fn clear(&mut self) {
+		self.free.clear();
+		for (index, slot) in self.slots.iter_mut().enumerate() {
+			if slot.live {
+				slot.live = false;
+				slot.generation = slot.generation.wrapping_add(1);
+			}
+			self.free.push(index as u32);
+		}
+	}
+}
+
+impl<T: Clone> Arena<T> {
+	// Add a node holding a copy of src, reusing a retired node's buffers if one is available
+	pub fn alloc_from(&mut self, src: &T) -> Handle {
+		let index = self.claim();
+		let slot = &mut self.slots[index as usize];
+		match slot.value {
+			Some(ref mut retired) => retired.clone_from(src),
+			None => slot.value = Some(src.clone()),
+		}
+		slot.live = true;
+		Handle { index, generation: slot.generation }
+	}
+}
+
+impl<T> Default for Arena<T> {
+	fn default() -> Arena<T> {
+		Arena::new()
+	}
+}
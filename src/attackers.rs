@@ -0,0 +1,155 @@
+// Simulated opponents for judging our own placements. A layout that holds
+// out against our best AI may still fall quickly to the attackers people
+// actually face, so the vulnerability report can be run against a model of
+// each: random shots, hunt-and-target on a checkerboard, our greedy AI with
+// some of its shots gone astray, or a human-like player.
+//
+// The human-like model follows habits commonly seen in casual players: while
+// hunting, they favor the middle of the board over its edges and corners,
+// loosely keep to a checkerboard, and avoid shooting right next to their
+// misses, all at random rather than by inference; once they hit, they target
+// around the hit like the parity hunter.
+
+use super::{BoardPos, GameState};
+use cancel::CancelToken;
+use rng::Rng;
+use strategy::{open_hits, Greedy, ParityHunter, RandomShots, Strategy, TieBreak};
+
+// The names parse accepts, with "noisy:<rate>" for a noisy greedy attacker
+pub const ATTACKER_NAMES: &[&str] = &["greedy", "random", "parity", "noisy:<rate>", "human"];
+
+// The human-like model's weight for a hunting shot on an edge square, and
+// on a corner (which is on two edges)
+const EDGE_WEIGHT: f64 = 0.5;
+
+// Its weight for a hunting shot off the checkerboard it keeps to
+const OFF_PARITY_WEIGHT: f64 = 0.3;
+
+// Its weight for a hunting shot next to one of its misses
+const NEAR_MISS_WEIGHT: f64 = 0.5;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Attacker {
+	// Our greedy AI, with random tie-breaking
+	Greedy,
+
+	// Uniformly random unshot squares
+	Random,
+
+	// Hunt-and-target on a checkerboard (see strategy::ParityHunter)
+	Parity,
+
+	// The greedy AI, with each shot going to a random unshot square instead
+	// at the given rate
+	Noisy(f64),
+
+	// The human-like model described above
+	Human,
+}
+
+impl Attacker {
+	pub fn parse(desc: &str) -> Option<Attacker> {
+		match desc {
+			"greedy" => Some(Attacker::Greedy),
+			"random" => Some(Attacker::Random),
+			"parity" => Some(Attacker::Parity),
+			"human" => Some(Attacker::Human),
+			_ => {
+				let rate: f64 = desc.strip_prefix("noisy:")?.parse().ok()?;
+				if (0.0..=1.0).contains(&rate) { Some(Attacker::Noisy(rate)) } else { None }
+			},
+		}
+	}
+
+	// The attacker as a strategy, with seed driving its randomness
+	pub fn strategy(self, seed: u64) -> Box<dyn Strategy> {
+		match self {
+			Attacker::Greedy => Box::new(Greedy::new(TieBreak::Random(seed))),
+			Attacker::Random => Box::new(RandomShots::new(seed)),
+			Attacker::Parity => Box::new(ParityHunter::new(seed)),
+			Attacker::Noisy(rate) => Box::new(NoisyGreedy::new(rate, seed)),
+			Attacker::Human => Box::new(HumanLike::new(seed)),
+		}
+	}
+}
+
+// Pick one of the squares at random, in proportion to its weight
+fn weighted_choice(rng: &mut Rng, squares: &[(BoardPos, f64)]) -> Option<BoardPos> {
+	let total: f64 = squares.iter().map(|&(_, weight)| weight).sum();
+	let mut target = rng.next_f64() * total;
+	for &(pos, weight) in squares {
+		if target < weight {
+			return Some(pos);
+		}
+		target -= weight;
+	}
+	squares.last().map(|&(pos, _)| pos)
+}
+
+// The greedy AI, but each shot goes to a random unshot square instead at the
+// given rate
+pub struct NoisyGreedy {
+	greedy: Greedy,
+	rate: f64,
+	rng: Rng,
+}
+
+impl NoisyGreedy {
+	pub fn new(rate: f64, seed: u64) -> NoisyGreedy {
+		let mut rng = Rng::new(seed);
+		NoisyGreedy { greedy: Greedy::new(TieBreak::Random(rng.next_u64())), rate, rng }
+	}
+}
+
+impl Strategy for NoisyGreedy {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		if self.rng.next_f64() < self.rate {
+			let unshot: Vec<BoardPos> = state.board().squares().filter(|&pos| !state.is_shot(pos)).collect();
+			if !unshot.is_empty() {
+				return Some(unshot[self.rng.gen_range(unshot.len())]);
+			}
+		}
+
+		self.greedy.choose_shot_cancellable(state, cancel)
+	}
+}
+
+// The human-like model described at the top of the module
+pub struct HumanLike {
+	hunter: ParityHunter,
+	rng: Rng,
+}
+
+impl HumanLike {
+	pub fn new(seed: u64) -> HumanLike {
+		let mut rng = Rng::new(seed);
+		HumanLike { hunter: ParityHunter::new(rng.next_u64()), rng }
+	}
+
+	// How much the model likes a hunting shot at the square
+	fn weight(&self, state: &GameState, pos: BoardPos) -> f64 {
+		let last = state.board().size() - 1;
+		let edges = [pos.row() == 0 || pos.row() == last, pos.col() == 0 || pos.col() == last].iter().filter(|&&edge| edge).count();
+		let mut weight = EDGE_WEIGHT.powi(edges as i32);
+		if !(pos.row() + pos.col()).is_multiple_of(2) {
+			weight *= OFF_PARITY_WEIGHT;
+		}
+		let near_miss = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().filter_map(|&(drow, dcol)| pos.offset(drow, dcol))
+			.any(|next| state.is_shot(next) && !state.is_hit(next));
+		if near_miss {
+			weight *= NEAR_MISS_WEIGHT;
+		}
+		weight
+	}
+}
+
+impl Strategy for HumanLike {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		if !open_hits(state).is_empty() {
+			return self.hunter.choose_shot_cancellable(state, cancel);
+		}
+
+		let squares: Vec<(BoardPos, f64)> = state.board().squares().filter(|&pos| !state.is_shot(pos)).map(|pos| (pos, self.weight(state, pos))).collect();
+		weighted_choice(&mut self.rng, &squares)
+	}
+}
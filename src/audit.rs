@@ -0,0 +1,135 @@
+// Determinism audits: seeded games whose every decision is hashed, so the
+// hashes can be stored as a fixture and compared on another platform or
+// release. Ratings and tuning runs are only comparable if the AI makes the
+// same shots everywhere, and a differing hash pins down the first game where
+// it didn't.
+//
+// Each game's layout and strategy seed come from an RNG seeded with the
+// audit's seed, in order. A game's hash is the 64-bit FNV-1a hash of its moves
+// in the moves file format, one per line.
+//
+// The fixture file has "rules <name>", "strategy <name>", and "seed <seed>"
+// lines, followed by a "game <number> <hash>" line per game. The repository's
+// fixture, data/audit.txt, is replayed by the tests as well as by the audit
+// command.
+
+use super::format_move;
+use cache::fnv1a;
+use durable;
+use placement::Layout;
+use rng::Rng;
+use rules::Rules;
+use sim;
+use std::path::Path;
+use strategy;
+
+// The fixture the audit command replays unless given another
+pub const DEFAULT_FIXTURE: &str = "data/audit.txt";
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Fixture {
+	pub rules: String,
+	pub strategy: String,
+	pub seed: u64,
+
+	// Each game's hash, in order
+	pub hashes: Vec<u64>,
+}
+
+impl Fixture {
+	// Play the given number of games with the named strategy (anything
+	// strategy::load takes) and record their hashes
+	pub fn record(rules: &Rules, strategy_name: &str, games: usize, seed: u64) -> Result<Fixture, String> {
+		let mut rng = Rng::new(seed);
+		let mut hashes = Vec::with_capacity(games);
+		for _ in 0..games {
+			let layout = Layout::random(&mut rng, rules.constraints()).ok_or_else(|| format!("No valid layout exists under the {} rules", rules.name))?;
+			let mut strategy = strategy::load(strategy_name, rng.next_u64())?;
+			let moves: String = sim::play_game(&mut *strategy, &layout, rules).into_iter().map(|cur_move| format_move(cur_move) + "\n").collect();
+			hashes.push(fnv1a(moves.as_bytes()));
+		}
+
+		Ok(Fixture { rules: rules.name.clone(), strategy: strategy_name.to_string(), seed, hashes })
+	}
+
+	// A single hash over every game's
+	pub fn digest(&self) -> u64 {
+		let hashes: Vec<u8> = self.hashes.iter().flat_map(|hash| hash.to_le_bytes()).collect();
+		fnv1a(&hashes)
+	}
+
+	// Check that a replay of the fixture made the same decisions, naming the
+	// first game that differs otherwise
+	pub fn check(&self, replay: &Fixture) -> Result<(), String> {
+		if let Some(game) = self.hashes.iter().zip(&replay.hashes).position(|(expected, actual)| expected != actual) {
+			return Err(format!("game {} differs from the fixture: expected {:016x}, got {:016x}", game + 1, self.hashes[game], replay.hashes[game]));
+		}
+		if self.hashes.len() != replay.hashes.len() {
+			return Err(format!("the fixture has {} games, but {} were played", self.hashes.len(), replay.hashes.len()));
+		}
+
+		Ok(())
+	}
+
+	pub fn to_file_string(&self) -> String {
+		let mut out = format!("rules {}\nstrategy {}\nseed {}\n", self.rules, self.strategy, self.seed);
+		for (game, hash) in self.hashes.iter().enumerate() {
+			out += &format!("game {} {:016x}\n", game + 1, hash);
+		}
+		out
+	}
+
+	pub fn from_file_string(contents: &str) -> Result<Fixture, String> {
+		let mut fixture = Fixture { rules: String::new(), strategy: String::new(), seed: 0, hashes: Vec::new() };
+		for (line_num, line) in contents.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+			match key {
+				"rules" => fixture.rules = value.trim().to_string(),
+				"strategy" => fixture.strategy = value.trim().to_string(),
+				"seed" => fixture.seed = value.trim().parse().map_err(|_| format!("Line {}: invalid seed", line_num + 1))?,
+				"game" => {
+					let hash = match value.split_whitespace().collect::<Vec<_>>().as_slice() {
+						[game, hash] if game.parse() == Ok(fixture.hashes.len() + 1) => u64::from_str_radix(hash, 16).ok(),
+						_ => None,
+					};
+					fixture.hashes.push(hash.ok_or_else(|| format!("Line {}: expected \"game {} <hash>\"", line_num + 1, fixture.hashes.len() + 1))?);
+				},
+				_ => return Err(format!("Line {}: unknown key {}", line_num + 1, key)),
+			}
+		}
+
+		if fixture.rules.is_empty() || fixture.strategy.is_empty() {
+			return Err(String::from("Missing the rules or strategy line"));
+		}
+		Ok(fixture)
+	}
+
+	pub fn load(path: &str, allow_unsealed: bool) -> Result<Fixture, String> {
+		let contents = durable::read(Path::new(path), allow_unsealed).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+		Fixture::from_file_string(&contents).map_err(|e| format!("{}: {}", path, e))
+	}
+
+	pub fn save(&self, path: &str) -> Result<(), String> {
+		durable::write(Path::new(path), &self.to_file_string()).map_err(|e| format!("Unable to write {}: {}", path, e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rules::RulesRegistry;
+
+	#[test]
+	fn replays_the_repository_fixture() {
+		let contents = include_str!("../data/audit.txt");
+		let fixture = Fixture::from_file_string(durable::unseal(contents, false).unwrap()).unwrap();
+		let rules = RulesRegistry::new().get(&fixture.rules).unwrap().clone();
+		let replay = Fixture::record(&rules, &fixture.strategy, fixture.hashes.len(), fixture.seed).unwrap();
+		assert_eq!(fixture.check(&replay), Ok(()));
+	}
+}
@@ -0,0 +1,233 @@
+// Belief dumps: the inference's state in a few short lines, for pasting into
+// bug reports. Unlike a save file, a dump leaves out the move history and the
+// heatmaps, keeping only what the inference believes: which squares have been
+// shot with what result, and which placements of each ship remain.
+//
+//   belief 5 P D S B C
+//   shots ..o../.xd../..D../...../.....
+//   P 0-3,7,12-15
+//   D 4
+//   ...
+//
+// The shots line is the board row by row, one character per square: '.' for
+// unshot, '?' for a shot whose result is withheld, 'o' for a miss, 'x' for a
+// hit on an unknown ship, a lowercase ship letter for a hit on that ship and
+// an uppercase one for the shot that sank it. Each ship's line lists its
+// remaining placement IDs (see Board::placement) as ranges, or '-' for none.
+//
+// The rest of what the inference goes by follows, where it applies:
+//   sweep <square> found|clear    each radar sweep, in order
+//   orientations <orientation>... how each ship type may be oriented, as in
+//                                 a save file, if any is restricted
+//   touching no                   if ships may not touch each other
+//   relocated <sweeps> <misses>...  if the opponent relocated a ship, with
+//                                 how many of the sweeps came before that
+//                                 and the squares missed before it
+// Misses from before a relocation no longer stand, so they're left out of
+// the shots line; only the ship that moved can be on them, which is why the
+// relocated line lists them.
+//
+// Loading a dump doesn't run the inference again, so the placements are just
+// as they were, even if they're wrong. The shots are replayed with the
+// sinking shots last, and their records (see the record module) only hold
+// what the results say.
+
+use super::{encode_shiptype, parse_shiptype, parse_square, Board, BoardPos, GameState, Orientation, Relocation, ShipType, ShotResult};
+use bitset::{PlacementSet, SharedSet};
+use diagnostic::{closest, find_span, Diagnostic};
+use radar::Sweep;
+use record::ShotRecord;
+use save::{orientation_name, parse_orientation};
+
+// The keys of the lines after the board, besides the ship letters
+const KEYS: &[&str] = &["shots", "sweep", "orientations", "touching", "relocated"];
+
+// The character for a square's latest shot in the shots line
+fn shot_char(result: ShotResult) -> char {
+	match result {
+		ShotResult::Miss => 'o',
+		ShotResult::Hit(None) => 'x',
+		ShotResult::Hit(Some(stype)) => encode_shiptype(stype).to_ascii_lowercase(),
+		ShotResult::Sunk(stype) => encode_shiptype(stype),
+	}
+}
+
+fn parse_shot_char(c: char) -> Option<ShotResult> {
+	match c {
+		'o' => Some(ShotResult::Miss),
+		'x' => Some(ShotResult::Hit(None)),
+		_ if c.is_ascii_lowercase() => parse_shiptype(c.to_ascii_uppercase()).map(|stype| ShotResult::Hit(Some(stype))),
+		_ => parse_shiptype(c).map(ShotResult::Sunk),
+	}
+}
+
+// The IDs in the set as ranges, e.g. "0-3,7"
+fn format_ids(plist: &PlacementSet) -> String {
+	let mut ranges: Vec<(usize, usize)> = Vec::new();
+	for id in plist.iter() {
+		match ranges.last_mut() {
+			Some(&mut (_, ref mut end)) if *end + 1 == id => *end = id,
+			_ => ranges.push((id, id)),
+		}
+	}
+	if ranges.is_empty() {
+		return String::from("-");
+	}
+
+	let parts: Vec<String> = ranges.iter().map(|&(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) }).collect();
+	parts.join(",")
+}
+
+fn parse_ids(desc: &str, capacity: usize) -> Result<PlacementSet, String> {
+	let mut plist = PlacementSet::new_empty(capacity);
+	if desc == "-" {
+		return Ok(plist);
+	}
+
+	let id = |n: &str| match n.parse::<usize>() {
+		Ok(id) if id < capacity => Ok(id),
+		_ => Err(format!("invalid placement {} (the ship has {})", n, capacity)),
+	};
+	for part in desc.split(',') {
+		let (start, end) = match part.split_once('-') {
+			Some((start, end)) => (id(start)?, id(end)?),
+			None => (id(part)?, id(part)?),
+		};
+		for id in start..=end {
+			plist.insert(id);
+		}
+	}
+	Ok(plist)
+}
+
+impl GameState {
+	// Dump the inference's state
+	pub fn to_belief_string(&self) -> String {
+		let mut cells = vec!['.'; self.board.num_squares()];
+		for &pos in &self.pending {
+			cells[pos.index()] = '?';
+		}
+		for &(pos, result) in self.standing_shots() {
+			cells[pos.index()] = shot_char(result);
+		}
+		let rows: Vec<String> = cells.chunks(self.board.size() as usize).map(|row| row.iter().collect()).collect();
+
+		let mut out = format!("belief {}\nshots {}\n", self.board.describe(), rows.join("/"));
+		for (&stype, plist) in self.board.fleet().iter().zip(self.pos_positions.iter()) {
+			out += &format!("{} {}\n", encode_shiptype(stype), format_ids(plist));
+		}
+		for sweep in &self.sweeps {
+			out += &format!("sweep {}\n", sweep);
+		}
+		if self.orientations.iter().any(|&orientation| orientation != Orientation::Any) {
+			let names: Vec<&str> = self.orientations.iter().map(|&orientation| orientation_name(orientation)).collect();
+			out += &format!("orientations {}\n", names.join(" "));
+		}
+		if !self.allow_touching {
+			out += "touching no\n";
+		}
+		if let Some(relocation) = self.relocation {
+			let misses: Vec<String> = self.shots[..relocation.shots].iter().filter(|&&(_, result)| result == ShotResult::Miss).map(|&(pos, _)| pos.to_string()).collect();
+			out += &format!("relocated {}\n", [vec![relocation.sweeps.to_string()], misses].concat().join(" "));
+		}
+		out
+	}
+
+	// Load a belief dump, its board in base's context
+	pub fn from_belief_string(contents: &str, base: &Board) -> Result<GameState, String> {
+		let mut lines = contents.lines().enumerate().map(|(line_num, line)| (line_num + 1, line)).filter(|&(_, line)| !line.trim().is_empty());
+		let board = match lines.next() {
+			Some((line_num, line)) => match line.trim().strip_prefix("belief ") {
+				Some(desc) => Board::parse(desc, base.context()).map_err(|e| Diagnostic::new(line_num, line, &e).at(find_span(line, desc)).to_string())?,
+				None => return Err(Diagnostic::new(line_num, line, "Not a belief dump: expected a \"belief <board>\" line").at_field(0).to_string()),
+			},
+			None => return Err(String::from("Not a belief dump: the file is empty")),
+		};
+
+		let mut state = GameState::new(&board);
+		let mut seen = vec![false; board.fleet().len()];
+		let mut seen_shots = false;
+		let mut relocated = None;
+		for (line_num, line) in lines {
+			let fields: Vec<&str> = line.split_whitespace().collect();
+			let err = |msg: &str, field: usize| Diagnostic::new(line_num, line, msg).at_field(field).to_string();
+			match fields.as_slice() {
+				["shots", mask] => {
+					let rows: Vec<&str> = mask.split('/').collect();
+					if rows.len() != board.size() as usize || rows.iter().any(|row| row.chars().count() != board.size() as usize) {
+						return Err(err(&format!("expected {} rows of {} squares", board.size(), board.size()), 1));
+					}
+					let (mut sinking, mut others) = (Vec::new(), Vec::new());
+					for (pos, c) in board.squares().zip(rows.concat().chars()) {
+						match (c, parse_shot_char(c)) {
+							('.', _) => {},
+							('?', _) => state.pending.push(pos),
+							(_, Some(ShotResult::Sunk(stype))) => sinking.push((pos, ShotResult::Sunk(stype))),
+							(_, Some(result)) => others.push((pos, result)),
+							(_, None) => return Err(err(&format!("invalid square {}", c), 1)),
+						}
+					}
+					state.shots = others.into_iter().chain(sinking).collect();
+					seen_shots = true;
+				},
+				["sweep", _, _] => {
+					let desc = fields[1..].join(" ");
+					state.sweeps.push(Sweep::parse(&board, &desc).map_err(|e| Diagnostic::new(line_num, line, &e).at(find_span(line, fields[1])).to_string())?);
+				},
+				["orientations", names @ ..] if names.len() == board.fleet().len() => {
+					state.orientations = names.iter().enumerate()
+						.map(|(idx, name)| parse_orientation(name).ok_or_else(|| err(&format!("invalid orientation {} (expected any, horizontal or vertical)", name), idx + 1)))
+						.collect::<Result<_, _>>()?;
+				},
+				["orientations", ..] => return Err(err(&format!("expected an orientation for each of the {} ships", board.fleet().len()), 0)),
+				["touching", "no"] => state.forbid_touching(),
+				["relocated", sweeps, misses @ ..] => {
+					let sweeps = sweeps.parse::<usize>().map_err(|_| err(&format!("invalid number of sweeps {}", sweeps), 1))?;
+					let misses = misses.iter().enumerate().map(|(idx, square)| {
+						parse_square(&board, square).ok_or_else(|| err(&format!("invalid square {}", square), idx + 2))
+					}).collect::<Result<Vec<BoardPos>, String>>()?;
+					relocated = Some((sweeps, misses));
+				},
+				[letter, ids] => {
+					let stype = letter.chars().next().filter(|_| letter.chars().count() == 1).and_then(parse_shiptype);
+					let stype_idx = match stype.and_then(|stype| board.ship_index(stype)) {
+						Some(stype_idx) => stype_idx,
+						None => {
+							let keys: Vec<String> = board.fleet().iter().map(|&stype| encode_shiptype(stype).to_string()).chain(KEYS.iter().map(|key| key.to_string())).collect();
+							let keys: Vec<&str> = keys.iter().map(|key| key.as_str()).collect();
+							return Err(Diagnostic::new(line_num, line, &format!("unexpected {}", letter)).at_field(0).suggest(closest(letter, &keys)).to_string());
+						},
+					};
+					let stype = board.fleet()[stype_idx];
+					state.pos_positions[stype_idx] = SharedSet::new(parse_ids(ids, board.num_positions(stype)).map_err(|e| err(&e, 1))?);
+					seen[stype_idx] = true;
+				},
+				_ => return Err(err(&format!("expected \"shots <squares>\", \"<ship> <placements>\", or a line starting with one of {}", KEYS[1..].join(", ")), 0)),
+			}
+		}
+
+		if let Some(stype_idx) = seen.iter().position(|&seen| !seen) {
+			return Err(format!("Missing the placements of the {:?}", board.fleet()[stype_idx]));
+		}
+		if !seen_shots {
+			return Err(String::from("Missing the shots"));
+		}
+		if let Some((sweeps, misses)) = relocated {
+			if sweeps > state.sweeps.len() {
+				return Err(format!("{} sweeps came before the relocation, but there are only {}", sweeps, state.sweeps.len()));
+			}
+			state.relocation = Some(Relocation { shots: misses.len(), sweeps });
+			state.shots.splice(0..0, misses.into_iter().map(|pos| (pos, ShotResult::Miss)));
+		}
+		let fleet: Vec<ShipType> = board.fleet().to_vec();
+		state.records = state.shots.iter().enumerate().map(|(idx, &(pos, result))| {
+			let ship = match result {
+				ShotResult::Hit(ship) => ship,
+				ShotResult::Sunk(stype) => Some(stype),
+				ShotResult::Miss => None,
+			};
+			ShotRecord { turn: idx + 1, pos, result, ship, inferred: false, eliminated: fleet.iter().map(|&stype| (stype, 0)).collect() }
+		}).collect();
+		Ok(state)
+	}
+}
@@ -0,0 +1,309 @@
+// A compact binary log of simulated games, for experiments too large to keep
+// as text transcripts. Each game is stored as its layout (one position ID per
+// ship) and the squares shot in order; the results are left out, since they
+// follow from the layout and the rules recorded in the header.
+//
+// The file starts with the header: the magic bytes "BSAILOG", a format version
+// byte, the board size, a byte holding the number of ships followed by each
+// ship's type letter, and a flags byte (bit 0: labeled hits, bit 1: sinking
+// announced). Version 1 logs have no fleet bytes, and are of the standard
+// fleet. Each game follows as two bytes holding the number of shots, most
+// significant first (one byte before version 3), then a bit-packed record, padded with zeros to a whole byte: each ship's position ID
+// in fleet order, then each shot's square index, every field using just enough
+// bits for its largest possible value, most significant bit first. On the
+// standard 5x5 board a typical game takes about 17 bytes.
+
+use super::{encode_shiptype, parse_shiptype, Board, BoardPos, PlacementId, ShotResult, SHIP_TYPES};
+use context::Context;
+use durable::Replacement;
+use placement::{Constraints, Layout};
+use rules::Rules;
+use std::io::{Read, Write};
+
+const MAGIC: &[u8] = b"BSAILOG";
+const VERSION: u8 = 3;
+
+const LABELED_HITS: u8 = 1;
+const ANNOUNCE_SUNK: u8 = 2;
+
+// The number of bits needed to store values below count
+fn bits_for(count: usize) -> u32 {
+	usize::BITS - count.saturating_sub(1).leading_zeros()
+}
+
+// The number of bytes in a game's record after its shot count
+fn record_bytes(board: &Board, shots: usize) -> usize {
+	let layout_bits: u32 = board.fleet().iter().map(|&stype| bits_for(board.num_positions(stype))).sum();
+	(layout_bits as usize + shots * bits_for(board.num_squares()) as usize).div_ceil(8)
+}
+
+struct BitWriter {
+	bytes: Vec<u8>,
+	bits: usize,
+}
+
+impl BitWriter {
+	fn push(&mut self, value: usize, width: u32) {
+		for bit in (0..width).rev() {
+			if self.bits.is_multiple_of(8) {
+				self.bytes.push(0);
+			}
+			if (value >> bit) & 1 == 1 {
+				*self.bytes.last_mut().expect("No byte to write to") |= 0x80 >> (self.bits % 8);
+			}
+			self.bits += 1;
+		}
+	}
+}
+
+struct BitReader<'a> {
+	bytes: &'a [u8],
+	bits: usize,
+}
+
+impl<'a> BitReader<'a> {
+	fn pull(&mut self, width: u32) -> usize {
+		let mut value = 0;
+		for _ in 0..width {
+			value = (value << 1) | ((self.bytes[self.bits / 8] >> (7 - self.bits % 8)) & 1) as usize;
+			self.bits += 1;
+		}
+		value
+	}
+}
+
+// One game read back from a log
+#[derive(Clone,Debug,PartialEq)]
+pub struct GameRecord {
+	pub layout: Layout,
+	pub shots: Vec<(BoardPos, ShotResult)>,
+}
+
+// Where a log is written: a stream, or a file that replaces the one at its path
+// once the log is finished
+enum Output {
+	Stream(Box<dyn Write>),
+	File(std::io::BufWriter<Replacement>),
+}
+
+impl Output {
+	fn writer(&mut self) -> &mut dyn Write {
+		match *self {
+			Output::Stream(ref mut out) => out,
+			Output::File(ref mut out) => out,
+		}
+	}
+}
+
+pub struct LogWriter {
+	out: Output,
+	board: Board,
+}
+
+impl LogWriter {
+	// Start a log of games played under the rules, writing the header
+	pub fn new(out: Box<dyn Write>, rules: &Rules) -> std::io::Result<LogWriter> {
+		LogWriter::start(Output::Stream(out), rules)
+	}
+
+	// Start a new log file at path, which replaces any existing one once the
+	// log is finished (see finish)
+	pub fn create(path: &str, rules: &Rules) -> Result<LogWriter, String> {
+		let file = Replacement::create(std::path::Path::new(path)).map_err(|e| format!("Unable to create {}: {}", path, e))?;
+		LogWriter::start(Output::File(std::io::BufWriter::new(file)), rules).map_err(|e| format!("Unable to write {}: {}", path, e))
+	}
+
+	fn start(mut out: Output, rules: &Rules) -> std::io::Result<LogWriter> {
+		let board = rules.board().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+		let flags = if rules.labeled_hits { LABELED_HITS } else { 0 } | if rules.announce_sunk { ANNOUNCE_SUNK } else { 0 };
+		let writer = out.writer();
+		writer.write_all(MAGIC)?;
+		writer.write_all(&[VERSION, board.size(), board.fleet().len() as u8])?;
+		writer.write_all(&board.fleet().iter().map(|&stype| encode_shiptype(stype) as u8).collect::<Vec<u8>>())?;
+		writer.write_all(&[flags])?;
+		Ok(LogWriter { out, board })
+	}
+
+	// Append a finished game: the layout shot at (which must be complete), and the shots in order
+	pub fn write_game(&mut self, layout: &Layout, shots: &[(BoardPos, ShotResult)]) -> std::io::Result<()> {
+		let board = &self.board;
+		let mut positions = vec![0; board.fleet().len()];
+		for &(stype, pos) in layout.ships() {
+			positions[board.ship_index(stype).expect("The layout has a ship outside the fleet")] = pos.index();
+		}
+
+		if shots.len() > u16::MAX as usize {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("a game of {} shots is too long to log", shots.len())));
+		}
+		let mut bits = BitWriter { bytes: (shots.len() as u16).to_be_bytes().to_vec(), bits: 16 };
+		for (stype_idx, &stype) in board.fleet().iter().enumerate() {
+			bits.push(positions[stype_idx], bits_for(board.num_positions(stype)));
+		}
+		for &(pos, _) in shots {
+			bits.push(pos.index(), bits_for(board.num_squares()));
+		}
+
+		self.out.writer().write_all(&bits.bytes)
+	}
+
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		self.out.writer().flush()
+	}
+
+	// Flush the log, and put a log file in place of any existing one
+	pub fn finish(self) -> std::io::Result<()> {
+		match self.out {
+			Output::Stream(mut out) => out.flush(),
+			Output::File(out) => out.into_inner().map_err(|e| e.into_error())?.finish(),
+		}
+	}
+}
+
+// Reads the games of a log in order
+pub struct LogReader<R: Read> {
+	input: R,
+	board: Board,
+	labeled_hits: bool,
+	announce_sunk: bool,
+
+	// The bytes in each game's shot count: one before version 3
+	count_bytes: usize,
+}
+
+impl<R: Read> LogReader<R> {
+	// Read the header, checking that it describes a valid board, which is
+	// given the context
+	pub fn new(mut input: R, context: &Context) -> Result<LogReader<R>, String> {
+		let truncated = |_| String::from("Not a game log: the header is truncated");
+		let mut header = [0; 9];
+		input.read_exact(&mut header).map_err(truncated)?;
+		if &header[..MAGIC.len()] != MAGIC {
+			return Err(String::from("Not a game log"));
+		}
+
+		let fleet = match header[7] {
+			1 => SHIP_TYPES.to_vec(),
+			2 | VERSION => {
+				let mut count = [0];
+				input.read_exact(&mut count).map_err(truncated)?;
+				let mut letters = vec![0; count[0] as usize];
+				input.read_exact(&mut letters).map_err(truncated)?;
+				letters.iter().map(|&letter| parse_shiptype(letter as char).ok_or_else(|| format!("Unknown ship type {} in the header", letter as char)))
+					.collect::<Result<Vec<_>, String>>()?
+			},
+			version => return Err(format!("Unsupported game log version {}", version)),
+		};
+		let board = Board::new(header[8], &fleet).map_err(|e| format!("Invalid board in the header: {}", e))?.with_context(context.clone());
+
+		let mut flags = [0];
+		input.read_exact(&mut flags).map_err(truncated)?;
+		let count_bytes = if header[7] < 3 { 1 } else { 2 };
+		Ok(LogReader { input, board, labeled_hits: flags[0] & LABELED_HITS != 0, announce_sunk: flags[0] & ANNOUNCE_SUNK != 0, count_bytes })
+	}
+
+	// The board the logged games were played on
+	pub fn board(&self) -> &Board {
+		&self.board
+	}
+
+	// The result reported for a true one under the logged rules
+	fn reported(&self, result: ShotResult) -> ShotResult {
+		match result {
+			ShotResult::Sunk(stype) if !self.announce_sunk => self.reported(ShotResult::Hit(Some(stype))),
+			ShotResult::Hit(Some(_)) if !self.labeled_hits => ShotResult::Hit(None),
+			_ => result,
+		}
+	}
+
+	// Read the next game, or None at the end of the log
+	pub fn read_game(&mut self) -> Result<Option<GameRecord>, String> {
+		let mut count = [0; 2];
+		match self.input.read(&mut count[..1]) {
+			Ok(0) => return Ok(None),
+			Ok(_) => {},
+			Err(e) => return Err(format!("Unable to read the log: {}", e)),
+		}
+
+		let shots = match self.count_bytes {
+			1 => count[0] as usize,
+			_ => {
+				self.input.read_exact(&mut count[1..]).map_err(|_| String::from("The last game is truncated"))?;
+				u16::from_be_bytes(count) as usize
+			},
+		};
+		let board = &self.board;
+		let mut bytes = vec![0; record_bytes(board, shots)];
+		self.input.read_exact(&mut bytes).map_err(|_| String::from("The last game is truncated"))?;
+		let mut bits = BitReader { bytes: &bytes, bits: 0 };
+
+		let mut layout = Layout::new(Constraints::new(board, true));
+		for &stype in board.fleet() {
+			let pos = bits.pull(bits_for(board.num_positions(stype)));
+			if pos >= board.num_positions(stype) {
+				return Err(format!("Invalid position {} for the {:?}", pos, stype));
+			}
+
+			let pos = PlacementId::new(pos);
+			layout.place(stype, board.ship_range(stype, pos)[0], board.is_horizontal(stype, pos)).map_err(|e| format!("Invalid layout: {}", e))?;
+		}
+
+		let mut fired = Vec::with_capacity(shots);
+		let mut moves = Vec::with_capacity(shots);
+		for _ in 0..shots {
+			let pos = board.square_at_index(bits.pull(bits_for(board.num_squares()))).ok_or("A shot is off the board")?;
+			moves.push((pos, self.reported(layout.shot_result_after(pos, &fired))));
+			fired.push(pos);
+		}
+
+		Ok(Some(GameRecord { layout, shots: moves }))
+	}
+}
+
+impl<R: Read> Iterator for LogReader<R> {
+	type Item = Result<GameRecord, String>;
+
+	fn next(&mut self) -> Option<Result<GameRecord, String>> {
+		self.read_game().transpose()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rng::Rng;
+	use rules::RulesRegistry;
+
+	#[test]
+	fn logs_games_of_more_than_255_shots() {
+		let rules = RulesRegistry::new().get("demo").unwrap().clone();
+		let layout = Layout::random(&mut Rng::new(1), rules.constraints()).unwrap();
+		let board = rules.known_board();
+		let shots: Vec<_> = (0..300).map(|idx| board.square_at_index(idx % board.num_squares()).unwrap())
+			.map(|pos| (pos, ShotResult::Miss)).collect();
+
+		let out = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+		let mut writer = LogWriter::new(Box::new(SharedVec(out.clone())), &rules).unwrap();
+		writer.write_game(&layout, &shots).unwrap();
+		writer.finish().unwrap();
+
+		let bytes = out.borrow().clone();
+		let games: Vec<GameRecord> = LogReader::new(&bytes[..], &rules.context).unwrap().collect::<Result<_, _>>().unwrap();
+		assert_eq!(games.len(), 1);
+		assert_eq!(games[0].layout, layout);
+		assert_eq!(games[0].shots.len(), 300);
+	}
+
+	// A writer into a buffer the test keeps a handle to
+	struct SharedVec(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+	impl Write for SharedVec {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			self.0.borrow_mut().extend_from_slice(buf);
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+}
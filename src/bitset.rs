@@ -0,0 +1,259 @@
+// Fixed-size bitsets of placement IDs, used for the per-ship sets of possible
+// positions and the masks that prune them.
+//
+// The bulk operations work on 2-word (128-bit) chunks, and every set's words
+// are padded with zeros to a whole number of chunks, so there is no scalar
+// tail: on a 10x10 board the sets take 2 to 4 words, all of them chunked. The
+// fixed-size chunk loops have no cross-iteration dependencies, so LLVM turns
+// them into SIMD instructions on targets that have them (SSE2 and NEON both
+// have 128-bit registers), without needing nightly std::simd or
+// target-specific intrinsics.
+//
+// Game states keep their sets as SharedSets, which clones share until one of
+// them changes, so the states a lookahead search branches into only copy the
+// sets a move actually prunes. A state recycled from the search's arena (see
+// arena::Arena::alloc_from) copies into the sets it already owns instead, so
+// pruning them doesn't allocate either.
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+#[derive(Debug,PartialEq,Eq)]
+pub struct PlacementSet {
+	words: Vec<u64>,
+
+	// The number of valid bits (placement IDs 0..len)
+	len: usize,
+}
+
+// Implemented by hand so clone_from reuses the existing word buffer
+impl Clone for PlacementSet {
+	fn clone(&self) -> PlacementSet {
+		PlacementSet { words: self.words.clone(), len: self.len }
+	}
+
+	fn clone_from(&mut self, source: &PlacementSet) {
+		self.words.clone_from(&source.words);
+		self.len = source.len;
+	}
+}
+
+// Words per chunk in the bulk operations
+const CHUNK: usize = 2;
+
+// The number of words, padded to whole chunks, for len placement IDs
+fn words_for(len: usize) -> usize {
+	len.div_ceil(64).div_ceil(CHUNK) * CHUNK
+}
+
+// Apply op to every pair of corresponding words, a chunk at a time
+#[inline]
+fn zip_words<F: Fn(u64, u64) -> u64>(dst: &mut [u64], src: &[u64], op: F) {
+	for (d, s) in dst.chunks_exact_mut(CHUNK).zip(src.chunks_exact(CHUNK)) {
+		for i in 0..CHUNK {
+			d[i] = op(d[i], s[i]);
+		}
+	}
+}
+
+// Sum op over every pair of corresponding words, a chunk at a time
+#[inline]
+fn sum_words<F: Fn(u64, u64) -> u64>(a: &[u64], b: &[u64], op: F) -> usize {
+	let mut total = 0;
+	for (x, y) in a.chunks_exact(CHUNK).zip(b.chunks_exact(CHUNK)) {
+		for i in 0..CHUNK {
+			total += op(x[i], y[i]).count_ones() as usize;
+		}
+	}
+	total
+}
+
+// Check whether op is zero for every pair of corresponding words, testing a
+// whole chunk at a time
+#[inline]
+fn all_zero<F: Fn(u64, u64) -> u64>(a: &[u64], b: &[u64], op: F) -> bool {
+	a.chunks_exact(CHUNK).zip(b.chunks_exact(CHUNK)).all(|(x, y)| {
+		let mut any = 0;
+		for i in 0..CHUNK {
+			any |= op(x[i], y[i]);
+		}
+		any == 0
+	})
+}
+
+impl PlacementSet {
+	// A set with no placements out of len possible
+	pub fn new_empty(len: usize) -> PlacementSet {
+		PlacementSet { words: vec![0; words_for(len)], len }
+	}
+
+	// A set holding every placement ID in 0..len
+	pub fn new_full(len: usize) -> PlacementSet {
+		let mut set = PlacementSet::new_empty(len);
+		for word in set.words.iter_mut().take(len / 64) {
+			*word = !0;
+		}
+		if !len.is_multiple_of(64) {
+			set.words[len / 64] = (1 << (len % 64)) - 1;
+		}
+
+		set
+	}
+
+	// The bytes a set of len possible placement IDs takes, buffer included
+	pub fn bytes_for(len: usize) -> usize {
+		std::mem::size_of::<PlacementSet>() + words_for(len) * std::mem::size_of::<u64>()
+	}
+
+	// The number of possible placement IDs (not the number in the set)
+	pub fn capacity(&self) -> usize {
+		self.len
+	}
+
+	pub fn contains(&self, id: usize) -> bool {
+		id < self.len && self.words[id / 64] & (1 << (id % 64)) != 0
+	}
+
+	pub fn insert(&mut self, id: usize) {
+		self.words[id / 64] |= 1 << (id % 64);
+	}
+
+	pub fn remove(&mut self, id: usize) {
+		self.words[id / 64] &= !(1 << (id % 64));
+	}
+
+	// The number of placements in the set
+	pub fn count(&self) -> usize {
+		sum_words(&self.words, &self.words, |a, _| a)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		all_zero(&self.words, &self.words, |a, _| a)
+	}
+
+	// Keep only the placements also in other
+	pub fn intersect_with(&mut self, other: &PlacementSet) {
+		zip_words(&mut self.words, &other.words, |a, b| a & b);
+	}
+
+	// Add every placement in other
+	pub fn union_with(&mut self, other: &PlacementSet) {
+		zip_words(&mut self.words, &other.words, |a, b| a | b);
+	}
+
+	// Remove every placement in other
+	pub fn subtract(&mut self, other: &PlacementSet) {
+		zip_words(&mut self.words, &other.words, |a, b| a & !b);
+	}
+
+	// The number of placements in both sets
+	pub fn intersection_count(&self, other: &PlacementSet) -> usize {
+		sum_words(&self.words, &other.words, |a, b| a & b)
+	}
+
+	// Check whether the sets share any placement
+	pub fn intersects(&self, other: &PlacementSet) -> bool {
+		!all_zero(&self.words, &other.words, |a, b| a & b)
+	}
+
+	// Check whether every placement in the set is also in other
+	pub fn is_subset(&self, other: &PlacementSet) -> bool {
+		all_zero(&self.words, &other.words, |a, b| a & !b)
+	}
+
+	// Iterate over the placement IDs in the set, in increasing order
+	pub fn iter(&self) -> Iter<'_> {
+		Iter { words: &self.words, word_idx: 0, cur: self.words.first().cloned().unwrap_or(0) }
+	}
+}
+
+pub struct Iter<'a> {
+	words: &'a [u64],
+	word_idx: usize,
+
+	// The not-yet-returned bits of the current word
+	cur: u64,
+}
+
+impl<'a> Iterator for Iter<'a> {
+	type Item = usize;
+
+	fn next(&mut self) -> Option<usize> {
+		while self.cur == 0 {
+			self.word_idx += 1;
+			if self.word_idx >= self.words.len() {
+				return None;
+			}
+			self.cur = self.words[self.word_idx];
+		}
+
+		let bit = self.cur.trailing_zeros() as usize;
+		self.cur &= self.cur - 1;
+		Some(self.word_idx * 64 + bit)
+	}
+}
+
+// A PlacementSet shared copy-on-write between clones. Reads go straight to
+// the set; a change copies it first if it's still shared, unless the change
+// would leave it as it is.
+#[derive(Debug,PartialEq,Eq)]
+pub struct SharedSet(Arc<PlacementSet>);
+
+// Implemented by hand so clone_from copies into a set no other clone shares,
+// keeping its buffer, rather than sharing the source's
+impl Clone for SharedSet {
+	fn clone(&self) -> SharedSet {
+		SharedSet(Arc::clone(&self.0))
+	}
+
+	fn clone_from(&mut self, source: &SharedSet) {
+		match Arc::get_mut(&mut self.0) {
+			Some(set) => set.clone_from(&source.0),
+			None => self.0 = Arc::clone(&source.0),
+		}
+	}
+}
+
+impl SharedSet {
+	pub fn new(set: PlacementSet) -> SharedSet {
+		SharedSet(Arc::new(set))
+	}
+
+	pub fn insert(&mut self, id: usize) {
+		if !self.contains(id) {
+			Arc::make_mut(&mut self.0).insert(id);
+		}
+	}
+
+	pub fn remove(&mut self, id: usize) {
+		if self.contains(id) {
+			Arc::make_mut(&mut self.0).remove(id);
+		}
+	}
+
+	pub fn intersect_with(&mut self, other: &PlacementSet) {
+		if !self.is_subset(other) {
+			Arc::make_mut(&mut self.0).intersect_with(other);
+		}
+	}
+
+	pub fn union_with(&mut self, other: &PlacementSet) {
+		if !other.is_subset(self) {
+			Arc::make_mut(&mut self.0).union_with(other);
+		}
+	}
+
+	pub fn subtract(&mut self, other: &PlacementSet) {
+		if self.intersects(other) {
+			Arc::make_mut(&mut self.0).subtract(other);
+		}
+	}
+}
+
+impl Deref for SharedSet {
+	type Target = PlacementSet;
+
+	fn deref(&self) -> &PlacementSet {
+		&self.0
+	}
+}
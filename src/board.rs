@@ -0,0 +1,235 @@
+// The board a game is played on: its size and the fleet hidden on it. Every
+// piece of geometry (squares, ship positions, the squares a ship covers)
+// derives from a Board value rather than from this build's constants, so
+// games on different boards can run side by side in one process.
+//
+// The fleet is a set of distinct ship types, each at its usual size (see
+// ship_size). Per-ship tables are indexed in fleet order (see ship_index).
+//
+// A board also carries the run's settings (see context::Context): its
+// squares are written in the context's coordinate convention, and the game
+// states on it count their memory against the context's meter.
+
+use super::{encode_shiptype, parse_shiptype, ship_size, BoardPos, PlacementId, ShipType};
+use context::Context;
+use coords::Coordinates;
+use geometry::{self, MAX_CHECKED_BOARD_SIZE};
+
+#[derive(Clone,Debug)]
+pub struct Board {
+	// Width and height
+	size: u8,
+
+	// The ship types in the fleet, in the order per-ship tables use
+	fleet: Vec<ShipType>,
+
+	context: Context,
+}
+
+// Boards are the same if games on them are: the context only changes how the
+// squares are written and where memory is counted
+impl PartialEq for Board {
+	fn eq(&self, other: &Board) -> bool {
+		self.size == other.size && self.fleet == other.fleet
+	}
+}
+
+impl Board {
+	// A board of the given size holding the given fleet. Fails unless a
+	// BoardPos can address the board, the fleet has a ship, every ship fits on
+	// the board, no ship type is in the fleet twice, and the ships don't need
+	// more squares than the board has. The board has the default context
+	// (see with_context).
+	pub fn new(size: u8, fleet: &[ShipType]) -> Result<Board, String> {
+		if size == 0 || size > MAX_CHECKED_BOARD_SIZE {
+			return Err(format!("the board size must be from 1 to {}, not {}", MAX_CHECKED_BOARD_SIZE, size));
+		}
+		if fleet.is_empty() {
+			return Err(String::from("the fleet must have at least one ship"));
+		}
+		for (idx, &stype) in fleet.iter().enumerate() {
+			geometry::check_fits(size, ship_size(stype)).map_err(|e| format!("ship {}: {}", encode_shiptype(stype), e))?;
+			if fleet[..idx].contains(&stype) {
+				return Err(format!("ship {} is in the fleet twice", encode_shiptype(stype)));
+			}
+		}
+
+		let board = Board { size, fleet: fleet.to_vec(), context: Context::new() };
+		if board.fleet_squares() > board.num_squares() as u64 {
+			return Err(format!("the fleet covers {} squares, more than the {} on a {}x{} board", board.fleet_squares(), board.num_squares(), size, size));
+		}
+		Ok(board)
+	}
+
+	// The same board in the given context
+	pub fn with_context(mut self, context: Context) -> Board {
+		self.context = context;
+		self
+	}
+
+	// Parse a board from its description: the size, then a letter per ship,
+	// e.g. "5 P D S B C". The board has the given context, as descriptions
+	// don't record one.
+	pub fn parse(desc: &str, context: &Context) -> Result<Board, String> {
+		let mut values = desc.split_whitespace();
+		let size = values.next().ok_or("expected a board size")?;
+		let size = size.parse().map_err(|_| format!("invalid board size {}", size))?;
+		let fleet = values.map(|letter| {
+			let mut chars = letter.chars();
+			match (chars.next().and_then(parse_shiptype), chars.next()) {
+				(Some(stype), None) => Ok(stype),
+				_ => Err(format!("unknown ship type {}", letter)),
+			}
+		}).collect::<Result<Vec<_>, String>>()?;
+		Board::new(size, &fleet).map(|board| board.with_context(context.clone()))
+	}
+
+	// The description parse reads back
+	pub fn describe(&self) -> String {
+		let mut out = self.size.to_string();
+		for &stype in &self.fleet {
+			out.push(' ');
+			out.push(encode_shiptype(stype));
+		}
+		out
+	}
+
+	pub fn size(&self) -> u8 {
+		self.size
+	}
+
+	pub fn fleet(&self) -> &[ShipType] {
+		&self.fleet
+	}
+
+	pub fn context(&self) -> &Context {
+		&self.context
+	}
+
+	// The convention the board's squares are written in
+	pub fn coordinates(&self) -> Coordinates {
+		self.context.coordinates
+	}
+
+	// Where the ship type is in the fleet, if it's in it
+	pub fn ship_index(&self, stype: ShipType) -> Option<usize> {
+		self.fleet.iter().position(|&t| t == stype)
+	}
+
+	// The number of squares on the board
+	pub fn num_squares(&self) -> usize {
+		self.size as usize * self.size as usize
+	}
+
+	// The number of squares the whole fleet occupies
+	pub fn fleet_squares(&self) -> u64 {
+		self.fleet.iter().map(|&stype| ship_size(stype) as u64).sum()
+	}
+
+	// The square at the given row and column, if it's on the board
+	pub fn square(&self, row: u8, col: u8) -> Result<BoardPos, String> {
+		if row >= self.size || col >= self.size {
+			return Err(format!("Row {} column {} is off the {}x{} board", row, col, self.size, self.size));
+		}
+
+		Ok(BoardPos::on_board(self.size, self.size * row + col, self.coordinates()))
+	}
+
+	// The square with the given row-major index
+	pub fn square_at_index(&self, index: usize) -> Option<BoardPos> {
+		if index >= self.num_squares() {
+			return None;
+		}
+
+		Some(BoardPos::on_board(self.size, index as u8, self.coordinates()))
+	}
+
+	// Every square on the board, in row-major order
+	pub fn squares(&self) -> impl Iterator<Item = BoardPos> {
+		let (size, coordinates) = (self.size, self.coordinates());
+		(0..size * size).map(move |index| BoardPos::on_board(size, index, coordinates))
+	}
+
+	// The square at the given row and column, which must be on the board
+	pub fn square_at(&self, row: u8, col: u8) -> BoardPos {
+		self.square(row, col).expect("Square off the board")
+	}
+
+	// The number of columns the ship can be in oriented horizontally
+	// or the number of rows it can be in oriented vertically
+	pub fn reduced_poscount(&self, shiptype: ShipType) -> u8 {
+		geometry::reduced_poscount(self.size, ship_size(shiptype))
+	}
+
+	// Computes the number of valid positions for the given ship type: the
+	// position IDs are 0..num_positions, encoded as described in geometry.rs
+	pub fn num_positions(&self, shiptype: ShipType) -> usize {
+		geometry::num_positions(self.size, ship_size(shiptype))
+	}
+
+	// Every position ID of the given ship type, in order
+	pub fn positions(&self, shiptype: ShipType) -> impl Iterator<Item = PlacementId> {
+		geometry::positions(self.size, ship_size(shiptype))
+	}
+
+	// Every placement of the given ship type, in position ID order
+	pub fn placements(&self, shiptype: ShipType) -> impl Iterator<Item = Placement> {
+		let (board_size, coordinates) = (self.size, self.coordinates());
+		self.positions(shiptype).map(move |id| Placement { stype: shiptype, id, board_size, coordinates })
+	}
+
+	// The placement of the ship type at the given position ID (which must be
+	// below num_positions), e.g. one taken from a PlacementSet
+	pub fn placement(&self, shiptype: ShipType, id: PlacementId) -> Placement {
+		assert!(id.index() < self.num_positions(shiptype), "Position {} is past the {} positions of ship {}", id, self.num_positions(shiptype), encode_shiptype(shiptype));
+		Placement { stype: shiptype, id, board_size: self.size, coordinates: self.coordinates() }
+	}
+
+	// Whether the given position ID of the ship type is horizontal
+	pub fn is_horizontal(&self, shiptype: ShipType, pos: PlacementId) -> bool {
+		geometry::is_horizontal(self.size, ship_size(shiptype), pos)
+	}
+
+	// Compute the occupied squares for the given ship type and position ID (which
+	// must be below num_positions), starting from the ship's top-left end
+	pub fn ship_range(&self, shiptype: ShipType, pos: PlacementId) -> Vec<BoardPos> {
+		self.placement(shiptype, pos).cells().collect()
+	}
+
+	// Compute the position ID for a ship starting at the given square (its top-left end),
+	// or None if the ship would run off the board
+	pub fn position_id(&self, shiptype: ShipType, start: BoardPos, horizontal: bool) -> Option<PlacementId> {
+		geometry::position_id(self.size, ship_size(shiptype), start.row(), start.col(), horizontal)
+	}
+}
+
+// A ship type at one of its positions on a board
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Placement {
+	pub stype: ShipType,
+	pub id: PlacementId,
+
+	// The size of the board the position ID is for, and the convention its
+	// squares are written in
+	board_size: u8,
+	coordinates: Coordinates,
+}
+
+impl Placement {
+	pub fn is_horizontal(self) -> bool {
+		geometry::is_horizontal(self.board_size, ship_size(self.stype), self.id)
+	}
+
+	// The squares the ship occupies, starting from its top-left end
+	pub fn cells(self) -> impl Iterator<Item = BoardPos> {
+		let (size, length, coordinates) = (self.board_size, ship_size(self.stype), self.coordinates);
+		let (row, col) = geometry::ship_start(size, length, self.id);
+		let (row_step, col_step) = if self.is_horizontal() { (0, 1) } else { (1, 0) };
+		(0..length).map(move |v| BoardPos::on_board(size, (row + v * row_step) * size + col + v * col_step, coordinates))
+	}
+
+	// Whether the ship occupies the square
+	pub fn covers(self, pos: BoardPos) -> bool {
+		self.cells().any(|square| square == pos)
+	}
+}
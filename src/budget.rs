@@ -0,0 +1,100 @@
+// Shot planning for the limited-ammunition variant, where the attacker has a
+// fixed number of shots and tries to hit as much of the fleet as possible
+// rather than to win quickly. The planner maximizes the expected number of
+// hits within the budget by looking a few shots ahead over every result each
+// shot could have, assuming (beyond the lookahead) that the remaining shots go
+// to the most likely squares.
+
+use super::{Board, BoardPos, GameState, Heatmap, ShotResult};
+use cancel::CancelToken;
+use strategy::Strategy;
+
+pub struct BudgetPlanner {
+	// The total number of shots allowed in the game
+	budget: usize,
+
+	// How many shots ahead to search over results
+	lookahead: usize,
+
+	// How many of the most likely squares to consider at each step
+	beam: usize,
+
+	// Whether hits report which ship was hit
+	labeled_hits: bool,
+}
+
+impl BudgetPlanner {
+	pub fn new(budget: usize, labeled_hits: bool) -> BudgetPlanner {
+		BudgetPlanner { budget, lookahead: 1, beam: 5, labeled_hits }
+	}
+
+	// Search deeper (slower, but plans more carefully)
+	pub fn with_lookahead(mut self, lookahead: usize, beam: usize) -> BudgetPlanner {
+		self.lookahead = lookahead;
+		self.beam = beam.max(1);
+		self
+	}
+
+	// The shots remaining in the budget after the moves so far
+	pub fn remaining(&self, state: &GameState) -> usize {
+		self.budget.saturating_sub(state.shots().len())
+	}
+
+	// The results a shot on the board could report
+	fn results(&self, board: &Board) -> Vec<ShotResult> {
+		let mut results = vec![ShotResult::Miss];
+		if self.labeled_hits {
+			results.extend(board.fleet().iter().map(|&stype| ShotResult::Hit(Some(stype))));
+		} else {
+			results.push(ShotResult::Hit(None));
+		}
+		results
+	}
+
+	// The expected hits achievable with the given shots left, and the shot achieving it
+	fn plan(&self, state: &GameState, heatmap: &Heatmap, remaining: usize, depth: usize) -> (f64, Option<BoardPos>) {
+		let mut candidates: Vec<BoardPos> = state.board().squares().filter(|&pos| !state.is_shot(pos)).collect();
+		candidates.sort_by(|&a, &b| heatmap.counts[b.index()].cmp(&heatmap.counts[a.index()]));
+		if remaining == 0 || candidates.is_empty() || heatmap.total == 0 {
+			return (0.0, None);
+		}
+
+		if depth == 0 || remaining == 1 {
+			let expected = candidates.iter().take(remaining).map(|&pos| heatmap.probability(pos)).sum();
+			return (expected, Some(candidates[0]));
+		}
+
+		let mut best = (-1.0, None);
+		for &pos in candidates.iter().take(self.beam) {
+			let mut expected = heatmap.probability(pos);
+			for result in self.results(state.board()) {
+				let mut child = state.clone();
+				child.apply_move((pos, result));
+				let child_heatmap = child.heatmap();
+				if child_heatmap.total == 0 {
+					continue;
+				}
+
+				let probability = child_heatmap.total as f64 / heatmap.total as f64;
+				expected += probability * self.plan(&child, &child_heatmap, remaining - 1, depth - 1).0;
+			}
+			if expected > best.0 {
+				best = (expected, Some(pos));
+			}
+		}
+
+		best
+	}
+
+	// The expected number of hits with the rest of the budget, playing this planner
+	pub fn expected_hits(&self, state: &GameState) -> f64 {
+		self.plan(state, &state.heatmap(), self.remaining(state), self.lookahead).0
+	}
+}
+
+impl Strategy for BudgetPlanner {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		let (heatmap, _) = state.heatmap_best_effort(cancel);
+		self.plan(state, &heatmap, self.remaining(state), self.lookahead).1
+	}
+}
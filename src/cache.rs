@@ -0,0 +1,118 @@
+// On-disk cache of precomputed analysis that depends only on the game configuration.
+//
+// Each entry is stored in its own file, named after a hash of the configuration.
+// The file starts with the full configuration description, so entries from a
+// different configuration or cache format (or a hash collision) are detected
+// and recomputed rather than used.
+
+use super::{encode_shiptype, ship_size, Board, GameState, Heatmap, PlacementId};
+use cancel::CancelToken;
+use durable;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Bump this whenever the contents or format of cached analysis changes
+const CACHE_VERSION: u32 = 1;
+
+// How long computing an opening heatmap that isn't cached may take. Larger
+// boards take far longer to count exactly, so their games start without one.
+pub const MAX_OPENING_TIME: Duration = Duration::from_secs(2);
+
+// A description of everything the cached analysis on the board depends on
+pub fn config_description(board: &Board) -> String {
+	let fleet = board.fleet().iter().map(|&stype| format!("{}{}", encode_shiptype(stype), ship_size(stype))).collect::<Vec<_>>().join(",");
+	format!("version={};board={};fleet={}", CACHE_VERSION, board.size(), fleet)
+}
+
+// The configuration description for analysis of the given fresh game state,
+// which additionally names any ship types restricted to one orientation, and
+// whether ships may touch if they may not
+fn state_description(fresh: &GameState) -> String {
+	let board = fresh.board();
+	let restricted: Vec<String> = board.fleet().iter().filter_map(|&stype| {
+		let (horizontal, vertical): (Vec<PlacementId>, Vec<PlacementId>) = fresh.positions(stype).iter().map(PlacementId::new).partition(|&pos| board.is_horizontal(stype, pos));
+		match (horizontal.is_empty(), vertical.is_empty()) {
+			(false, true) => Some(format!("{}H", encode_shiptype(stype))),
+			(true, false) => Some(format!("{}V", encode_shiptype(stype))),
+			_ => None,
+		}
+	}).collect();
+
+	let mut description = config_description(board);
+	if !restricted.is_empty() {
+		description += &format!(";orientations={}", restricted.join(","));
+	}
+	if !fresh.allows_touching() {
+		description += ";touching=no";
+	}
+	description
+}
+
+// 64-bit FNV-1a hash. Used instead of std's hasher because its output must
+// stay stable across Rust releases for the cache file names to be reusable.
+pub fn fnv1a(data: &[u8]) -> u64 {
+	data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+// The directory cache files are stored in: $BATTLESHIP_AI_CACHE if set,
+// otherwise $XDG_CACHE_HOME/battleship_ai or ~/.cache/battleship_ai
+pub fn cache_dir() -> Option<PathBuf> {
+	use std::env::var_os;
+
+	if let Some(dir) = var_os("BATTLESHIP_AI_CACHE") {
+		return Some(PathBuf::from(dir));
+	}
+
+	var_os("XDG_CACHE_HOME").map(PathBuf::from)
+		.or_else(|| var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+		.map(|dir| dir.join("battleship_ai"))
+}
+
+// The cache file for the given kind of analysis under the described configuration
+fn entry_path(kind: &str, description: &str) -> Option<PathBuf> {
+	cache_dir().map(|dir| dir.join(format!("{}-{:016x}.txt", kind, fnv1a(description.as_bytes()))))
+}
+
+// Parse a cached heatmap, returning None if it is for a different configuration or malformed
+fn parse_heatmap(board: &Board, contents: &str, description: &str) -> Option<Heatmap> {
+	let mut lines = contents.lines();
+	if lines.next() != Some(description) {
+		return None;
+	}
+
+	let total = lines.next()?.parse().ok()?;
+	let counts = lines.next()?.split_whitespace().map(|count| count.parse().ok()).collect::<Option<Vec<u64>>>()?;
+	if counts.len() != board.num_squares() {
+		return None;
+	}
+
+	Some(Heatmap { counts, total })
+}
+
+// Serialize a heatmap in the format read by parse_heatmap
+fn format_heatmap(heatmap: &Heatmap, description: &str) -> String {
+	let counts = heatmap.counts.iter().map(|count| count.to_string()).collect::<Vec<_>>().join(" ");
+	format!("{}\n{}\n{}\n", description, heatmap.total, counts)
+}
+
+// Load the opening heatmap from the cache, or compute it from the given fresh
+// game state and save it. Returns None if computing it would take longer than
+// MAX_OPENING_TIME. Cache failures are not fatal; they only cost time.
+pub fn opening_heatmap(fresh: &GameState) -> Option<Heatmap> {
+	let description = state_description(fresh);
+	let path = entry_path("opening", &description);
+
+	if let Some(heatmap) = path.as_ref().and_then(|path| durable::read(path, fresh.board().context().allow_unsealed).ok()).and_then(|contents| parse_heatmap(fresh.board(), &contents, &description)) {
+		return Some(heatmap);
+	}
+
+	let heatmap = fresh.heatmap_cancellable(&CancelToken::with_timeout(MAX_OPENING_TIME))?;
+	if let Some(path) = path {
+		if let Some(dir) = path.parent() {
+			let _ = std::fs::create_dir_all(dir);
+		}
+		let _ = durable::write(&path, &format_heatmap(&heatmap, &description));
+	}
+
+	Some(heatmap)
+}
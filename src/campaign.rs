@@ -0,0 +1,178 @@
+// A campaign against the built-in difficulties, easiest first (see
+// strategy::DIFFICULTY_NAMES). Winning a game at the current level moves the
+// campaign on to the next one, and every level beaten unlocks a stronger hint
+// for the player's own shots (see the hint module), up to the strongest.
+// Every game counts towards its level's statistics, won or lost.
+//
+// The progress file starts with a "level <number>" line (counting from 0, and
+// one past the last level once the campaign is won), followed by one line per
+// difficulty played: "<difficulty> <wins> <losses> <shots> <best>", with the
+// player's shots summed over the games and best the fewest shots of a win, or
+// "-" if there is none yet.
+
+use durable;
+use hint::MAX_HINT_STRENGTH;
+use std::path::Path;
+use strategy::{Difficulty, DIFFICULTY_NAMES};
+
+pub const DEFAULT_PATH: &str = "campaign.txt";
+
+// The player's record against one difficulty
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct LevelStats {
+	pub wins: u64,
+	pub losses: u64,
+
+	// The player's shots, summed over the games
+	pub shots: u64,
+
+	// The fewest shots the player needed to win
+	pub best: Option<u64>,
+}
+
+impl LevelStats {
+	pub fn games(&self) -> u64 {
+		self.wins + self.losses
+	}
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Campaign {
+	// The index into DIFFICULTY_NAMES of the level being played
+	level: usize,
+
+	// Per difficulty, in the order of DIFFICULTY_NAMES
+	stats: Vec<LevelStats>,
+}
+
+impl Campaign {
+	pub fn new() -> Campaign {
+		Campaign { level: 0, stats: vec![LevelStats::default(); DIFFICULTY_NAMES.len()] }
+	}
+
+	// The level being played, counting from 0
+	pub fn level(&self) -> usize {
+		self.level
+	}
+
+	pub fn levels(&self) -> usize {
+		DIFFICULTY_NAMES.len()
+	}
+
+	// The difficulty of the level being played, or None once every level is beaten
+	pub fn difficulty(&self) -> Option<Difficulty> {
+		DIFFICULTY_NAMES.get(self.level).map(|name| Difficulty::parse(name).expect("Unknown difficulty name"))
+	}
+
+	pub fn is_complete(&self) -> bool {
+		self.level >= DIFFICULTY_NAMES.len()
+	}
+
+	// The strongest hint unlocked so far: one strength per level beaten, and 0
+	// before the first
+	pub fn hint_strength(&self) -> u8 {
+		self.level.min(MAX_HINT_STRENGTH as usize) as u8
+	}
+
+	pub fn stats(&self, level: usize) -> LevelStats {
+		self.stats[level]
+	}
+
+	// Record a finished game at the current level, in which the player fired
+	// the given number of shots. Returns whether it moved the campaign to the
+	// next level.
+	pub fn record(&mut self, won: bool, shots: usize) -> bool {
+		let stats = match self.stats.get_mut(self.level) {
+			Some(stats) => stats,
+			None => return false,
+		};
+		stats.shots += shots as u64;
+		if !won {
+			stats.losses += 1;
+			return false;
+		}
+
+		stats.wins += 1;
+		stats.best = Some(stats.best.map_or(shots as u64, |best| best.min(shots as u64)));
+		self.level += 1;
+		true
+	}
+
+	// The statistics as a table, one row per level
+	pub fn render(&self) -> String {
+		let mut out = format!("{:<12} {:>5} {:>6} {:>10} {:>5}\n", "Level", "Wins", "Losses", "Avg shots", "Best");
+		for (level, (name, stats)) in DIFFICULTY_NAMES.iter().zip(self.stats.iter()).enumerate() {
+			let marker = if level == self.level { "> " } else { "  " };
+			let average = match stats.games() {
+				0 => String::from("-"),
+				games => format!("{:.1}", stats.shots as f64 / games as f64),
+			};
+			let best = stats.best.map_or(String::from("-"), |best| best.to_string());
+			out += &format!("{}{:<10} {:>5} {:>6} {:>10} {:>5}\n", marker, name, stats.wins, stats.losses, average, best);
+		}
+		out
+	}
+
+	pub fn to_file_string(&self) -> String {
+		let mut out = format!("level {}\n", self.level);
+		for (name, stats) in DIFFICULTY_NAMES.iter().zip(self.stats.iter()).filter(|(_, stats)| stats.games() > 0) {
+			out += &format!("{} {} {} {} {}\n", name, stats.wins, stats.losses, stats.shots, stats.best.map_or(String::from("-"), |best| best.to_string()));
+		}
+		out
+	}
+
+	pub fn from_file_string(contents: &str) -> Result<Campaign, String> {
+		let mut campaign = Campaign::new();
+		let mut seen_level = false;
+		for (line_num, line) in contents.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let parts: Vec<&str> = line.split_whitespace().collect();
+			match parts.as_slice() {
+				["level", level] => {
+					campaign.level = level.parse().ok().filter(|&level| level <= DIFFICULTY_NAMES.len())
+						.ok_or_else(|| format!("Line {}: invalid level {}", line_num + 1, level))?;
+					seen_level = true;
+				},
+				[name, wins, losses, shots, best] => {
+					let level = DIFFICULTY_NAMES.iter().position(|known| known == name).ok_or_else(|| format!("Line {}: unknown difficulty {}", line_num + 1, name))?;
+					let numbers = [wins, losses, shots].iter().map(|n| n.parse::<u64>()).collect::<Result<Vec<u64>, _>>()
+						.map_err(|_| format!("Line {}: invalid counts", line_num + 1))?;
+					let best = match *best {
+						"-" => None,
+						best => Some(best.parse().map_err(|_| format!("Line {}: invalid best {}", line_num + 1, best))?),
+					};
+					campaign.stats[level] = LevelStats { wins: numbers[0], losses: numbers[1], shots: numbers[2], best };
+				},
+				_ => return Err(format!("Line {}: expected \"<difficulty> <wins> <losses> <shots> <best>\"", line_num + 1)),
+			}
+		}
+
+		if !seen_level {
+			return Err(String::from("Missing the level line"));
+		}
+		Ok(campaign)
+	}
+
+	// Load the campaign at path, or start a new one if the file doesn't exist
+	pub fn load(path: &str, allow_unsealed: bool) -> Result<Campaign, String> {
+		match durable::read(Path::new(path), allow_unsealed) {
+			Ok(contents) => Campaign::from_file_string(&contents).map_err(|e| format!("{}: {}", path, e)),
+			Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Campaign::new()),
+			Err(e) => Err(format!("Unable to read {}: {}", path, e)),
+		}
+	}
+
+	pub fn save(&self, path: &str) -> Result<(), String> {
+		durable::write(Path::new(path), &self.to_file_string()).map_err(|e| format!("Unable to write {}: {}", path, e))
+	}
+}
+
+impl Default for Campaign {
+	fn default() -> Campaign {
+		Campaign::new()
+	}
+}
@@ -0,0 +1,48 @@
+// Cooperative cancellation of long-running computations.
+//
+// A token is cancelled either explicitly (from any clone of it, e.g. on another
+// thread) or once its optional deadline passes. Computations poll it and stop
+// early, returning the best answer found so far where they can.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Clone,Debug,Default)]
+pub struct CancelToken {
+	flag: Arc<AtomicBool>,
+	deadline: Option<Instant>,
+}
+
+impl CancelToken {
+	// A token that is only cancelled explicitly
+	pub fn new() -> CancelToken {
+		CancelToken::default()
+	}
+
+	// A token that is also cancelled once the given time has elapsed
+	pub fn with_timeout(timeout: Duration) -> CancelToken {
+		CancelToken { flag: Arc::new(AtomicBool::new(false)), deadline: Some(Instant::now() + timeout) }
+	}
+
+	// Request cancellation. Affects every clone of this token.
+	pub fn cancel(&self) {
+		self.flag.store(true, Ordering::Relaxed);
+	}
+
+	// Check whether the computation should stop
+	pub fn is_cancelled(&self) -> bool {
+		if self.flag.load(Ordering::Relaxed) {
+			return true;
+		}
+
+		match self.deadline {
+			Some(deadline) if Instant::now() >= deadline => {
+				// Latch the expiry so later checks are cheap
+				self.cancel();
+				true
+			},
+			_ => false,
+		}
+	}
+}
@@ -0,0 +1,26 @@
+// Detection of move results that are near-impossible given the AI's belief state,
+// which indicates either a cheating opponent or a data-entry error.
+
+use super::{BoardPos, GameState, ShotResult};
+
+pub struct CheatDetector {
+	// Results with a probability below this are flagged
+	pub threshold: f64,
+}
+
+impl CheatDetector {
+	pub fn new(threshold: f64) -> CheatDetector {
+		CheatDetector { threshold }
+	}
+
+	// Check a move result before applying it to the state. Returns the
+	// probability of the claimed result if it is suspiciously unlikely.
+	pub fn check(&self, state: &GameState, move_val: (BoardPos, ShotResult)) -> Option<f64> {
+		let probability = state.result_probability(move_val);
+		if probability < self.threshold {
+			Some(probability)
+		} else {
+			None
+		}
+	}
+}
@@ -0,0 +1,151 @@
+// Explaining unlabeled hits with the ships that are left. When hits don't say
+// which ship they struck, the lengths of the remaining ships still limit how
+// the hits can be covered: two separate clusters of hits need two ships, and a
+// cluster of five hits in a row can't be covered by two patrol boats alone.
+//
+// The hits are grouped so that no remaining placement covers hits of two
+// groups, so each ship serves at most one group. A group needs at least as
+// many ships as it takes for the ships that can reach it, longest reach
+// first, to cover all of its hits. Whether every group can get its ships is a
+// bipartite matching between ships and the groups' slots. A placement is
+// kept only if the matching still succeeds with its ship given to the group
+// it covers (or to none, for a placement covering no unlabeled hit), and if
+// no matching exists at all, no configuration does, and every placement goes.
+
+use super::{BoardPos, CoverMasks, PlacementSet};
+use bitset::SharedSet;
+
+// Prune the placements no assignment of ships to the hit groups can use,
+// returning whether any were removed
+pub fn prune(pos_positions: &mut [SharedSet], hits: &[BoardPos], cover: &CoverMasks) -> bool {
+	if hits.is_empty() {
+		return false;
+	}
+
+	let groups = group_hits(pos_positions, hits, cover);
+	let ships = pos_positions.len();
+
+	// Each ship's reach into each group: the most of its hits one placement covers
+	let reach: Vec<Vec<usize>> = (0..ships).map(|stype_idx| {
+		groups.iter().map(|group| {
+			let mut covered = vec![0; pos_positions[stype_idx].capacity()];
+			for &hit in group {
+				for pos in pos_positions[stype_idx].iter().filter(|&pos| cover[stype_idx][hit.index()].contains(pos)) {
+					covered[pos] += 1;
+				}
+			}
+			covered.into_iter().max().unwrap_or(0)
+		}).collect()
+	}).collect();
+	let needs: Vec<usize> = groups.iter().enumerate().map(|(group_idx, group)| {
+		let mut reaches: Vec<usize> = reach.iter().map(|ship| ship[group_idx]).filter(|&n| n > 0).collect();
+		reaches.sort_unstable_by(|a, b| b.cmp(a));
+		let mut covered = 0;
+		reaches.iter().position(|&n| {
+			covered += n;
+			covered >= group.len()
+		}).map_or(ships + 1, |last| last + 1)
+	}).collect();
+	let edges: Vec<Vec<bool>> = reach.iter().map(|ship| ship.iter().map(|&n| n > 0).collect()).collect();
+
+	let mut changed = false;
+	if !assignable(&edges, &needs, None) {
+		for plist in pos_positions.iter_mut().filter(|plist| !plist.is_empty()) {
+			*plist = SharedSet::new(PlacementSet::new_empty(plist.capacity()));
+			changed = true;
+		}
+		return changed;
+	}
+
+	for stype_idx in 0..ships {
+		let old_len = pos_positions[stype_idx].count();
+		let mut any_hit = PlacementSet::new_empty(pos_positions[stype_idx].capacity());
+		for (group_idx, group) in groups.iter().enumerate() {
+			let mut covering = PlacementSet::new_empty(pos_positions[stype_idx].capacity());
+			for &hit in group {
+				covering.union_with(&cover[stype_idx][hit.index()]);
+			}
+			any_hit.union_with(&covering);
+			if edges[stype_idx][group_idx] && !assignable(&edges, &needs, Some((stype_idx, Some(group_idx)))) {
+				pos_positions[stype_idx].subtract(&covering);
+			}
+		}
+		if !assignable(&edges, &needs, Some((stype_idx, None))) {
+			let mut off_hits = PlacementSet::clone(&pos_positions[stype_idx]);
+			off_hits.subtract(&any_hit);
+			pos_positions[stype_idx].subtract(&off_hits);
+		}
+		changed |= pos_positions[stype_idx].count() != old_len;
+	}
+	changed
+}
+
+// Group the hits so no remaining placement covers hits of two groups
+fn group_hits(pos_positions: &[SharedSet], hits: &[BoardPos], cover: &CoverMasks) -> Vec<Vec<BoardPos>> {
+	let mut parent: Vec<usize> = (0..hits.len()).collect();
+	fn root(parent: &mut [usize], mut idx: usize) -> usize {
+		while parent[idx] != idx {
+			parent[idx] = parent[parent[idx]];
+			idx = parent[idx];
+		}
+		idx
+	}
+
+	for (stype_idx, plist) in pos_positions.iter().enumerate() {
+		// The first hit seen in each placement, joined with every later one
+		let mut first: Vec<Option<usize>> = vec![None; plist.capacity()];
+		for (hit_idx, &hit) in hits.iter().enumerate() {
+			for pos in plist.iter().filter(|&pos| cover[stype_idx][hit.index()].contains(pos)) {
+				match first[pos] {
+					Some(other) => {
+						let (a, b) = (root(&mut parent, other), root(&mut parent, hit_idx));
+						parent[a] = b;
+					},
+					None => first[pos] = Some(hit_idx),
+				}
+			}
+		}
+	}
+
+	let mut groups: Vec<(usize, Vec<BoardPos>)> = Vec::new();
+	for (hit_idx, &hit) in hits.iter().enumerate() {
+		let group_root = root(&mut parent, hit_idx);
+		match groups.iter_mut().find(|(seen, _)| *seen == group_root) {
+			Some((_, group)) => group.push(hit),
+			None => groups.push((group_root, vec![hit])),
+		}
+	}
+	groups.into_iter().map(|(_, group)| group).collect()
+}
+
+// Whether each group can be given as many ships as it needs, each ship going
+// to at most one group it can reach. If fixed is Some((ship, group)), the
+// ship is given to that group (or to none) first.
+fn assignable(edges: &[Vec<bool>], needs: &[usize], fixed: Option<(usize, Option<usize>)>) -> bool {
+	let mut needs = needs.to_vec();
+	if let Some((_, Some(group_idx))) = fixed {
+		needs[group_idx] = needs[group_idx].saturating_sub(1);
+	}
+	let excluded = fixed.map(|(stype_idx, _)| stype_idx);
+	let slots: Vec<usize> = needs.iter().enumerate().flat_map(|(group_idx, &need)| std::iter::repeat_n(group_idx, need)).collect();
+	if slots.len() > edges.len() {
+		return false;
+	}
+
+	// Kuhn's augmenting paths, from each slot to the ships
+	let mut matched: Vec<Option<usize>> = vec![None; edges.len()];
+	fn augment(slot: usize, slots: &[usize], edges: &[Vec<bool>], excluded: Option<usize>, seen: &mut [bool], matched: &mut [Option<usize>]) -> bool {
+		for ship in 0..edges.len() {
+			if Some(ship) == excluded || seen[ship] || !edges[ship][slots[slot]] {
+				continue;
+			}
+			seen[ship] = true;
+			if matched[ship].is_none_or(|other| augment(other, slots, edges, excluded, seen, matched)) {
+				matched[ship] = Some(slot);
+				return true;
+			}
+		}
+		false
+	}
+	(0..slots.len()).all(|slot| augment(slot, &slots, edges, excluded, &mut vec![false; edges.len()], &mut matched))
+}
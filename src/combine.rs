@@ -0,0 +1,315 @@
+// Strategies built out of other strategies, so compound behaviors can be
+// assembled from simple ones instead of being written out each time:
+//   fallback(a, b)       a's shot, or b's when a has none (or picks a shot square)
+//   weighted(a, b, w)    each turn, a's shot with probability w and b's otherwise
+//   phase(hunt, target)  target's shot while a hit may belong to a ship afloat,
+//                        and hunt's otherwise
+//   phase(opening, hunt, target, endgame)
+//                        a strategy for each phase of the game (see the phase
+//                        module)
+// The arguments are strategy names as strategy::load takes them, including
+// "script:<file>" and other combinations, e.g.
+// "phase(parity, fallback(script:edges.txt, greedy))". A combination can also
+// be kept in a file and loaded as "compose:<file>"; there, whitespace and
+// line breaks are free and '#' starts a comment.
+//
+// Memoized isn't a combinator of its own: it wraps a strategy so repeated
+// requests for the same game state get the same answer without repeating the
+// search.
+
+use super::{BoardPos, GameState, Relocation, ShotResult};
+use cancel::CancelToken;
+use forced::ForcedWin;
+use phase::{GamePhase, PHASE_NAMES};
+use radar::Sweep;
+use rng::Rng;
+use rules::Rules;
+use std::path::Path;
+use strategy::{self, GameResult, Strategy};
+use text;
+
+// Uses the first strategy's shot, or the second's when the first has none to
+// give or names a square already shot
+pub struct Fallback {
+	first: Box<dyn Strategy>,
+	second: Box<dyn Strategy>,
+}
+
+impl Fallback {
+	pub fn new(first: Box<dyn Strategy>, second: Box<dyn Strategy>) -> Fallback {
+		Fallback { first, second }
+	}
+}
+
+impl Strategy for Fallback {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		match self.first.choose_shot_cancellable(state, cancel) {
+			Some(pos) if !state.is_shot(pos) => Some(pos),
+			_ => self.second.choose_shot_cancellable(state, cancel),
+		}
+	}
+
+	fn needs_heatmap(&self) -> bool {
+		self.first.needs_heatmap() || self.second.needs_heatmap()
+	}
+
+	fn on_game_start(&mut self, rules: &Rules) {
+		self.first.on_game_start(rules);
+		self.second.on_game_start(rules);
+	}
+
+	fn on_opponent_shot(&mut self, shot: (BoardPos, ShotResult)) {
+		self.first.on_opponent_shot(shot);
+		self.second.on_opponent_shot(shot);
+	}
+
+	fn on_game_end(&mut self, result: &GameResult) {
+		self.first.on_game_end(result);
+		self.second.on_game_end(result);
+	}
+}
+
+// Mixes two strategies: each turn uses the first with the given probability,
+// and the second otherwise
+pub struct Weighted {
+	first: Box<dyn Strategy>,
+	second: Box<dyn Strategy>,
+	weight: f64,
+	rng: Rng,
+}
+
+impl Weighted {
+	pub fn new(first: Box<dyn Strategy>, second: Box<dyn Strategy>, weight: f64, seed: u64) -> Weighted {
+		Weighted { first, second, weight, rng: Rng::new(seed) }
+	}
+}
+
+impl Strategy for Weighted {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		if self.rng.next_f64() < self.weight {
+			self.first.choose_shot_cancellable(state, cancel)
+		} else {
+			self.second.choose_shot_cancellable(state, cancel)
+		}
+	}
+
+	fn needs_heatmap(&self) -> bool {
+		self.first.needs_heatmap() || self.second.needs_heatmap()
+	}
+
+	fn on_game_start(&mut self, rules: &Rules) {
+		self.first.on_game_start(rules);
+		self.second.on_game_start(rules);
+	}
+
+	fn on_opponent_shot(&mut self, shot: (BoardPos, ShotResult)) {
+		self.first.on_opponent_shot(shot);
+		self.second.on_opponent_shot(shot);
+	}
+
+	fn on_game_end(&mut self, result: &GameResult) {
+		self.first.on_game_end(result);
+		self.second.on_game_end(result);
+	}
+}
+
+// Shoots with the strategy for the game's phase (see GameState::phase)
+pub struct PhaseSwitch {
+	policies: Vec<Box<dyn Strategy>>,
+
+	// Per phase, in the order of PHASE_NAMES, the index of its policy
+	assignment: [usize; 4],
+}
+
+impl PhaseSwitch {
+	// Hunts with one strategy until something is hit, then finishes the ship
+	// off with the other, going back to hunting once every hit ship is sunk
+	pub fn new(hunt: Box<dyn Strategy>, target: Box<dyn Strategy>) -> PhaseSwitch {
+		PhaseSwitch { policies: vec![hunt, target], assignment: [0, 0, 1, 0] }
+	}
+
+	// A strategy per phase
+	pub fn by_phase(opening: Box<dyn Strategy>, hunt: Box<dyn Strategy>, target: Box<dyn Strategy>, endgame: Box<dyn Strategy>) -> PhaseSwitch {
+		PhaseSwitch { policies: vec![opening, hunt, target, endgame], assignment: [0, 1, 2, 3] }
+	}
+
+	fn policy(&mut self, phase: GamePhase) -> &mut dyn Strategy {
+		&mut *self.policies[self.assignment[phase.index()]]
+	}
+}
+
+impl Strategy for PhaseSwitch {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		self.policy(state.phase()).choose_shot_cancellable(state, cancel)
+	}
+
+	fn needs_heatmap(&self) -> bool {
+		self.policies.iter().any(|policy| policy.needs_heatmap())
+	}
+
+	fn on_game_start(&mut self, rules: &Rules) {
+		for policy in &mut self.policies {
+			policy.on_game_start(rules);
+		}
+	}
+
+	fn on_opponent_shot(&mut self, shot: (BoardPos, ShotResult)) {
+		for policy in &mut self.policies {
+			policy.on_opponent_shot(shot);
+		}
+	}
+
+	fn on_game_end(&mut self, result: &GameResult) {
+		for policy in &mut self.policies {
+			policy.on_game_end(result);
+		}
+	}
+}
+
+// How often a Memoized strategy answered from its memo
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct MemoStats {
+	// Requests answered from the memo
+	pub hits: u64,
+
+	// Requests that ran the search
+	pub misses: u64,
+
+	// Remembered recommendations dropped because the game moved on
+	pub invalidations: u64,
+}
+
+// Remembers the recommendation for the latest game state, so asking again
+// before any new result (a hint after the report, or a display polling for
+// the best shot) doesn't redo the search. A recommendation cut short by
+// cancellation isn't remembered, as a later request may have time to finish.
+pub struct Memoized {
+	inner: Box<dyn Strategy>,
+	memo: Option<Memo>,
+	stats: MemoStats,
+}
+
+// A remembered recommendation and the moves, withheld shots, sweeps, and
+// relocation it was for
+struct Memo {
+	shots: Vec<(BoardPos, ShotResult)>,
+	pending: Vec<BoardPos>,
+	sweeps: Vec<Sweep>,
+	relocation: Option<Relocation>,
+	shot: Option<BoardPos>,
+}
+
+impl Memoized {
+	pub fn new(inner: Box<dyn Strategy>) -> Memoized {
+		Memoized { inner, memo: None, stats: MemoStats::default() }
+	}
+
+	pub fn stats(&self) -> MemoStats {
+		self.stats
+	}
+}
+
+impl Strategy for Memoized {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		if let Some(ref memo) = self.memo {
+			if memo.shots[..] == *state.shots() && memo.pending[..] == *state.pending() && memo.sweeps[..] == *state.sweeps() && memo.relocation == state.relocation() {
+				self.stats.hits += 1;
+				return memo.shot;
+			}
+			self.memo = None;
+			self.stats.invalidations += 1;
+		}
+
+		self.stats.misses += 1;
+		let shot = self.inner.choose_shot_cancellable(state, cancel);
+		if !cancel.is_cancelled() {
+			self.memo = Some(Memo { shots: state.shots().to_vec(), pending: state.pending().to_vec(), sweeps: state.sweeps().to_vec(), relocation: state.relocation(), shot });
+		}
+		shot
+	}
+
+	fn forced_win(&self) -> Option<ForcedWin> {
+		self.inner.forced_win()
+	}
+
+	fn needs_heatmap(&self) -> bool {
+		self.inner.needs_heatmap()
+	}
+
+	// A recommendation from the last game is no use in the next
+	fn on_game_start(&mut self, rules: &Rules) {
+		self.memo = None;
+		self.inner.on_game_start(rules);
+	}
+
+	fn on_opponent_shot(&mut self, shot: (BoardPos, ShotResult)) {
+		self.inner.on_opponent_shot(shot);
+	}
+
+	fn on_game_end(&mut self, result: &GameResult) {
+		self.inner.on_game_end(result);
+	}
+}
+
+// Split a combinator's arguments at the commas outside any parentheses
+fn split_args(args: &str) -> Result<Vec<&str>, String> {
+	let mut parts = Vec::new();
+	let mut depth = 0;
+	let mut start = 0;
+	for (idx, c) in args.char_indices() {
+		match c {
+			'(' => depth += 1,
+			')' if depth == 0 => return Err(format!("unbalanced parentheses in {}", args)),
+			')' => depth -= 1,
+			',' if depth == 0 => {
+				parts.push(args[start..idx].trim());
+				start = idx + 1;
+			},
+			_ => {},
+		}
+	}
+	if depth != 0 {
+		return Err(format!("unbalanced parentheses in {}", args));
+	}
+
+	parts.push(args[start..].trim());
+	Ok(parts)
+}
+
+// Build the strategy a combination describes. seed drives the randomness of
+// the combinators and, through seeds drawn from it, their strategies.
+pub fn parse(spec: &str, seed: u64) -> Result<Box<dyn Strategy>, String> {
+	let spec = spec.trim();
+	let (name, args) = match spec.split_once('(') {
+		Some((name, rest)) => {
+			let args = rest.trim_end().strip_suffix(')').ok_or_else(|| format!("expected ')' at the end of {}", spec))?;
+			(name.trim(), split_args(args)?)
+		},
+		None => return strategy::load(spec, seed),
+	};
+
+	let mut rng = Rng::new(seed);
+	let mut arg = |idx: usize| strategy::load(args[idx], rng.next_u64());
+	match (name, args.len()) {
+		("fallback", 2) => Ok(Box::new(Fallback::new(arg(0)?, arg(1)?))),
+		("phase", 2) => Ok(Box::new(PhaseSwitch::new(arg(0)?, arg(1)?))),
+		("phase", 4) => Ok(Box::new(PhaseSwitch::by_phase(arg(0)?, arg(1)?, arg(2)?, arg(3)?))),
+		("weighted", 3) => {
+			let weight = args[2].parse().ok().filter(|w| (0.0..=1.0).contains(w))
+				.ok_or_else(|| format!("the weight in {} must be a number from 0 to 1", spec))?;
+			let (first, second) = (arg(0)?, arg(1)?);
+			Ok(Box::new(Weighted::new(first, second, weight, rng.next_u64())))
+		},
+		("fallback", _) => Err(format!("{} takes two strategies", name)),
+		("phase", _) => Err(format!("phase takes two strategies (hunt, target) or four ({})", PHASE_NAMES.join(", "))),
+		("weighted", _) => Err(String::from("weighted takes two strategies and a weight")),
+		_ => Err(format!("Unknown combinator {} (available: fallback, weighted, phase)", name)),
+	}
+}
+
+// Build the strategy described in a file
+pub fn load(path: &str, seed: u64) -> Result<Box<dyn Strategy>, String> {
+	let contents = text::read(Path::new(path)).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+	let spec: Vec<&str> = text::lines(&contents).map(|line| line.split('#').next().unwrap_or("").trim()).collect();
+	parse(&spec.join(" "), seed).map_err(|e| format!("{}: {}", path, e))
+}
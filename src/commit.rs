@@ -0,0 +1,187 @@
+// Commitments to a fleet layout, so two players (or AIs) who don't trust each
+// other can prove afterwards that neither moved their ships or misreported a
+// shot. Before the game each side publishes a commitment: the SHA-256 hash of
+// their layout and a random nonce. After the game each side publishes the
+// reveal (the nonce and the layout), and the other checks it against the
+// commitment and against every result reported during the game.
+//
+// A reveal file is a "nonce <64 hex digits>" line followed by the layout in the
+// placement file format.
+
+use super::{format_move, BoardPos, ShotResult};
+use placement::{Constraints, Layout};
+use rules::Rules;
+use std::io::Read;
+
+// Prefixed to the hashed data, so a commitment can't be mistaken for a hash of
+// anything else
+const DOMAIN: &str = "battleship_ai layout commitment v1\n";
+
+const NONCE_BYTES: usize = 32;
+
+// SHA-256 (FIPS 180-4)
+fn sha256(data: &[u8]) -> [u8; 32] {
+	const K: [u32; 64] = [
+		0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+		0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+		0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+		0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+		0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+		0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+		0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+		0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+	];
+	let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+	// Pad to a whole number of 64-byte blocks: a 1 bit, zeros, then the bit length
+	let mut message = data.to_vec();
+	message.push(0x80);
+	while message.len() % 64 != 56 {
+		message.push(0);
+	}
+	message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+	for block in message.chunks(64) {
+		let mut w = [0u32; 64];
+		for (i, word) in block.chunks(4).enumerate() {
+			w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+		}
+		for i in 16..64 {
+			let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+			let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+			w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+		}
+
+		let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+		for i in 0..64 {
+			let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+			let ch = (e & f) ^ (!e & g);
+			let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+			let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+			let maj = (a & b) ^ (a & c) ^ (b & c);
+			let t2 = s0.wrapping_add(maj);
+			hh = g;
+			g = f;
+			f = e;
+			e = d.wrapping_add(t1);
+			d = c;
+			c = b;
+			b = a;
+			a = t1.wrapping_add(t2);
+		}
+
+		for (state, value) in h.iter_mut().zip(&[a, b, c, d, e, f, g, hh]) {
+			*state = state.wrapping_add(*value);
+		}
+	}
+
+	let mut digest = [0; 32];
+	for (i, word) in h.iter().enumerate() {
+		digest[4 * i..4 * i + 4].copy_from_slice(&word.to_be_bytes());
+	}
+	digest
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+	if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+		return None;
+	}
+
+	(0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+// The hash a layout was committed to
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Commitment {
+	digest: [u8; 32],
+}
+
+impl Commitment {
+	fn of(nonce: &[u8], layout: &Layout) -> Commitment {
+		let data = format!("{}{}\n{}", DOMAIN, to_hex(nonce), layout.to_file_string());
+		Commitment { digest: sha256(data.as_bytes()) }
+	}
+
+	// Parse a commitment from its 64 hex digits
+	pub fn parse(hex: &str) -> Result<Commitment, String> {
+		let bytes = from_hex(hex.trim()).filter(|bytes| bytes.len() == 32).ok_or("A commitment must be 64 hex digits")?;
+		let mut digest = [0; 32];
+		digest.copy_from_slice(&bytes);
+		Ok(Commitment { digest })
+	}
+}
+
+impl std::fmt::Display for Commitment {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", to_hex(&self.digest))
+	}
+}
+
+// What a player keeps secret until the game is over
+#[derive(Clone,Debug,PartialEq)]
+pub struct Reveal {
+	nonce: Vec<u8>,
+	layout: Layout,
+}
+
+impl Reveal {
+	// Prepare to commit to the layout, with a fresh nonce from the operating
+	// system. The nonce must be unpredictable: the number of possible layouts is
+	// small enough to try them all against a commitment without one.
+	pub fn new(layout: Layout) -> Result<Reveal, String> {
+		let mut nonce = vec![0; NONCE_BYTES];
+		std::fs::File::open("/dev/urandom").and_then(|mut urandom| urandom.read_exact(&mut nonce))
+			.map_err(|e| format!("Unable to read a random nonce from /dev/urandom: {}", e))?;
+		Ok(Reveal { nonce, layout })
+	}
+
+	pub fn layout(&self) -> &Layout {
+		&self.layout
+	}
+
+	pub fn commitment(&self) -> Commitment {
+		Commitment::of(&self.nonce, &self.layout)
+	}
+
+	pub fn to_file_string(&self) -> String {
+		format!("nonce {}\n{}", to_hex(&self.nonce), self.layout.to_file_string())
+	}
+
+	// Parse a reveal file, validating the layout against the constraints
+	pub fn from_file_string(contents: &str, constraints: Constraints) -> Result<Reveal, String> {
+		let mut lines = contents.splitn(2, '\n');
+		let nonce = lines.next().and_then(|line| line.trim().strip_prefix("nonce ")).and_then(|hex| from_hex(hex.trim()))
+			.filter(|nonce| nonce.len() == NONCE_BYTES).ok_or("The first line must be \"nonce <64 hex digits>\"")?;
+		let layout = Layout::from_file_string(lines.next().unwrap_or(""), constraints).map_err(|e| format!("layout {}", e))?;
+		if !layout.is_complete() {
+			return Err(String::from("The layout is missing ships"));
+		}
+
+		Ok(Reveal { nonce, layout })
+	}
+
+	// Check the reveal against the commitment published before the game, and
+	// every result the player reported for the shots at their fleet. A bare hit
+	// is accepted where the rules call for more detail.
+	pub fn verify(&self, commitment: &Commitment, rules: &Rules, moves: &[(BoardPos, ShotResult)]) -> Result<(), String> {
+		if self.commitment() != *commitment {
+			return Err(String::from("The revealed layout doesn't match the commitment"));
+		}
+
+		for (turn, &(pos, claimed)) in moves.iter().enumerate() {
+			let earlier: Vec<BoardPos> = moves[..turn].iter().map(|&(pos, _)| pos).collect();
+			let actual = rules.reported(self.layout.shot_result_after(pos, &earlier));
+			let compatible = claimed == actual || (claimed == ShotResult::Hit(None) && actual != ShotResult::Miss);
+			if !compatible {
+				return Err(format!("Move {} was reported as {}, but the revealed layout gives {}", turn + 1,
+				                   format_move((pos, claimed)), format_move((pos, actual))));
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,83 @@
+// Per-square comparison of heatmaps computed under different modeling choices,
+// to show what each choice changes about the AI's beliefs
+
+use super::{Board, GameState};
+
+// A way of turning the observations into per-square occupancy probabilities
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Model {
+	// Exact enumeration of every consistent fleet configuration
+	Joint,
+
+	// Each ship type's remaining positions treated as independent
+	Independent,
+}
+
+impl Model {
+	// Parse a model from its command-line name: "joint" or "independent"
+	pub fn parse(desc: &str) -> Option<Model> {
+		match desc {
+			"joint" => Some(Model::Joint),
+			"independent" => Some(Model::Independent),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Model::Joint => "joint",
+			Model::Independent => "independent",
+		}
+	}
+
+	// The occupancy probability of every square under this model
+	pub fn probabilities(self, state: &GameState) -> Vec<f64> {
+		match self {
+			Model::Joint => {
+				let heatmap = state.heatmap();
+				state.board().squares().map(|pos| heatmap.probability(pos)).collect()
+			},
+			Model::Independent => state.independent_heatmap(),
+		}
+	}
+}
+
+// The per-square probability under right minus that under left
+pub fn diff(state: &GameState, left: Model, right: Model) -> Vec<f64> {
+	let left = left.probabilities(state);
+	right.probabilities(state).iter().zip(left.iter()).map(|(r, l)| r - l).collect()
+}
+
+// Render a difference as a grid of signed percentage points
+pub fn render_diff(board: &Board, diff: &[f64]) -> String {
+	let coordinates = board.coordinates();
+	let mut out = String::from(" ");
+	for col in 0..board.size() {
+		out += &format!(" {:>4}", coordinates.column_label(col));
+	}
+	out.push('\n');
+
+	for row in 0..board.size() {
+		out.push(coordinates.row_label(board.size(), row));
+		for col in 0..board.size() {
+			out += &format!(" {:>+4.0}", 100.0 * diff[board.square_at(row, col).index()]);
+		}
+		out.push('\n');
+	}
+
+	out
+}
+
+// Export a comparison as CSV, one "square,left,right,difference" row per square
+pub fn to_csv(state: &GameState, left: Model, right: Model) -> String {
+	let left_probs = left.probabilities(state);
+	let right_probs = right.probabilities(state);
+
+	let mut out = format!("square,{},{},difference\n", left.name(), right.name());
+	for pos in state.board().squares() {
+		let (l, r) = (left_probs[pos.index()], right_probs[pos.index()]);
+		out += &format!("{},{:.6},{:.6},{:.6}\n", pos, l, r, r - l);
+	}
+
+	out
+}
@@ -0,0 +1,95 @@
+// Shell completion scripts, generated from the command-line interface's
+// table of subcommands and options so they never fall out of step with it.
+// Each script completes the subcommand names (with their summaries, where
+// the shell shows them), then the options of the subcommand given, and the
+// global options anywhere.
+
+// A subcommand, for help and completions
+pub struct Command {
+	pub name: &'static str,
+	pub summary: &'static str,
+	pub options: &'static [&'static str],
+}
+
+// The shells scripts can be generated for
+pub const SHELL_NAMES: &[&str] = &["bash", "zsh", "fish"];
+
+// The interface a script completes: the subcommands, the options taken
+// anywhere, and those of the interactive mode run without a subcommand
+pub struct Interface<'a> {
+	pub program: &'a str,
+	pub commands: &'a [Command],
+	pub global_options: &'a [&'a str],
+	pub default_options: &'a [&'a str],
+}
+
+// The script for the named shell
+pub fn script(shell: &str, interface: &Interface) -> Result<String, String> {
+	match shell {
+		"bash" => Ok(bash(interface)),
+		"zsh" => Ok(zsh(interface)),
+		"fish" => Ok(fish(interface)),
+		_ => Err(format!("Unknown shell {} (available: {})", shell, SHELL_NAMES.join(", "))),
+	}
+}
+
+// A shell function name for the program
+fn function_name(program: &str) -> String {
+	format!("_{}", program.replace(|c: char| !c.is_ascii_alphanumeric(), "_"))
+}
+
+fn bash(interface: &Interface) -> String {
+	let function = function_name(interface.program);
+	let names: Vec<&str> = interface.commands.iter().map(|command| command.name).collect();
+	let mut out = format!("{}() {{\n", function);
+	out += "\tlocal cur=${COMP_WORDS[COMP_CWORD]} command=\"\" word opts\n";
+	out += "\tfor word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n";
+	out += &format!("\t\tcase \"$word\" in\n\t\t\t{})\n\t\t\t\tcommand=$word\n\t\t\t\tbreak;;\n\t\tesac\n", names.join("|"));
+	out += "\tdone\n\tcase \"$command\" in\n";
+	for command in interface.commands {
+		out += &format!("\t\t{}) opts=\"{}\";;\n", command.name, command.options.join(" "));
+	}
+	out += &format!("\t\t*) opts=\"{} {}\";;\n\tesac\n", names.join(" "), interface.default_options.join(" "));
+	out += &format!("\tCOMPREPLY=($(compgen -W \"$opts {}\" -- \"$cur\"))\n}}\n", interface.global_options.join(" "));
+	out += &format!("complete -F {} {}\n", function, interface.program);
+	out
+}
+
+// Escape a string for a single-quoted sh or zsh word
+fn quoted(text: &str) -> String {
+	text.replace('\'', "'\\''")
+}
+
+fn zsh(interface: &Interface) -> String {
+	let function = function_name(interface.program);
+	let mut out = format!("#compdef {}\n\n{}() {{\n\tlocal -a commands\n\tcommands=(\n", interface.program, function);
+	for command in interface.commands {
+		out += &format!("\t\t'{}:{}'\n", command.name, quoted(&command.summary.replace(':', "\\:")));
+	}
+	out += "\t)\n\tif (( CURRENT == 2 )); then\n\t\t_describe 'command' commands\n";
+	out += &format!("\t\tcompadd -- {} {}\n\t\treturn\n\tfi\n\tcase $words[2] in\n", interface.default_options.join(" "), interface.global_options.join(" "));
+	for command in interface.commands {
+		out += &format!("\t\t{}) compadd -- {};;\n", command.name, command.options.join(" "));
+	}
+	out += &format!("\t\t*) compadd -- {};;\n\tesac\n", interface.default_options.join(" "));
+	out += &format!("\tcompadd -- {}\n}}\n\n{} \"$@\"\n", interface.global_options.join(" "), function);
+	out
+}
+
+fn fish(interface: &Interface) -> String {
+	let program = interface.program;
+	let mut out = String::new();
+	for option in interface.global_options {
+		out += &format!("complete -c {} -l {}\n", program, option.trim_start_matches('-'));
+	}
+	for option in interface.default_options {
+		out += &format!("complete -c {} -n __fish_use_subcommand -l {}\n", program, option.trim_start_matches('-'));
+	}
+	for command in interface.commands {
+		out += &format!("complete -c {} -n __fish_use_subcommand -a {} -d '{}'\n", program, command.name, command.summary.replace('\\', "\\\\").replace('\'', "\\'"));
+		for option in command.options {
+			out += &format!("complete -c {} -n '__fish_seen_subcommand_from {}' -l {}\n", program, command.name, option.trim_start_matches('-'));
+		}
+	}
+	out
+}
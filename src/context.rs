@@ -0,0 +1,36 @@
+// The settings a run chooses once, at startup, that aren't part of the game:
+// how squares are written (--coords), the memory accounting and its cap
+// (--max-memory), and whether files from before checksums are read
+// (--legacy-files). Each Board carries them, as do the Rules that make
+// boards, so everything derived from a board (its squares, game states,
+// renderings) follows them without any process-wide state, and runs with
+// different settings can share a process.
+
+use coords::{self, Coordinates};
+use memory::Meter;
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Context {
+	// The convention squares are parsed and formatted in
+	pub coordinates: Coordinates,
+
+	// The accounts the inference's structures are counted against
+	pub memory: Meter,
+
+	// Whether files without a checksum line are read as they are (see
+	// durable::read)
+	pub allow_unsealed: bool,
+}
+
+impl Context {
+	// The default convention, an uncapped meter of its own, and sealed files only
+	pub fn new() -> Context {
+		Context { coordinates: coords::DEFAULT, memory: Meter::new(), allow_unsealed: false }
+	}
+}
+
+impl Default for Context {
+	fn default() -> Context {
+		Context::new()
+	}
+}
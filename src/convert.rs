@@ -0,0 +1,112 @@
+// Converting game records between coordinate conventions and formats, e.g. to
+// bring in logs kept by players who letter the rows from the bottom. Records
+// are read in one notation, checked move by move, and written in another.
+// Layouts convert the same way, between placement files and grids.
+
+use super::format_move;
+use context::Context;
+use coords::Coordinates;
+use gamelog::GameLog;
+use import;
+use placement::Layout;
+use rules::{Reshot, Rules};
+use schema;
+
+// The formats a record can be read from: the moves file format, or any
+// transcript format the importers understand
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Input {
+	Moves,
+	Transcript(import::Format),
+
+	// A layout, as a placement file or a grid
+	Layout,
+}
+
+impl Input {
+	pub fn parse(desc: &str) -> Option<Input> {
+		match desc {
+			"moves" => Some(Input::Moves),
+			"layout" => Some(Input::Layout),
+			_ => import::Format::parse(desc).map(Input::Transcript),
+		}
+	}
+}
+
+// The formats a record can be written in
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Output {
+	Moves,
+
+	// A JSON game log of the given schema version
+	Json(u32),
+
+	// A grid of the board (see import and placement for the cells)
+	Grid,
+
+	// A placement file, for layouts
+	Placement,
+}
+
+impl Output {
+	// Parse an output format other than JSON from its command-line name
+	pub fn parse(desc: &str) -> Option<Output> {
+		match desc {
+			"moves" => Some(Output::Moves),
+			"grid" => Some(Output::Grid),
+			"placement" => Some(Output::Placement),
+			_ => None,
+		}
+	}
+}
+
+// Check that a log could have happened under the rules: no square is shot
+// twice (unless the rules allow repeats, which must report the same result),
+// and every result is possible given the moves before it
+pub fn validate(log: &GameLog, rules: &Rules) -> Result<(), String> {
+	let mut state = rules.new_game();
+	for (idx, &cur_move) in log.moves.iter().enumerate() {
+		if let Some(&(_, result)) = state.shots().iter().find(|&&(pos, _)| pos == cur_move.0) {
+			if rules.reshot == Reshot::Error || result != cur_move.1 {
+				return Err(format!("move {} ({}): {} was already shot", idx + 1, format_move(cur_move), cur_move.0));
+			}
+			continue;
+		}
+		if state.result_probability(cur_move) == 0.0 {
+			return Err(format!("move {} ({}): the result contradicts the earlier moves", idx + 1, format_move(cur_move)));
+		}
+		state.apply_move(cur_move);
+	}
+
+	Ok(())
+}
+
+// Read a record written in one notation, validate it, and write it in another
+pub fn convert(contents: &str, input: Input, from: Coordinates, output: Output, to: Coordinates, rules: &Rules) -> Result<String, String> {
+	let with_coordinates = |coordinates| Context { coordinates, ..rules.context.clone() };
+	let from_rules = Rules { context: with_coordinates(from), ..rules.clone() };
+
+	if input == Input::Layout {
+		let layout = Layout::from_file_string(contents, from_rules.constraints())?.with_context(with_coordinates(to));
+		return match output {
+			Output::Placement => Ok(layout.to_file_string()),
+			Output::Grid => Ok(layout.to_grid_string()),
+			_ => Err(String::from("a layout can only be written as a placement file or a grid")),
+		};
+	}
+
+	let board = from_rules.board()?;
+	let log = match input {
+		Input::Transcript(format) => import::import(format, &rules.name, &board, contents),
+		_ => GameLog::from_moves_string(&rules.name, &board, contents),
+	}?;
+	validate(&log, &from_rules)?;
+
+	let log = log.with_context(with_coordinates(to));
+	match output {
+		Output::Moves => Ok(log.to_moves_string()),
+		Output::Json(version) => schema::export_log_version(&log, version).map(|doc| doc + "\n"),
+		Output::Grid => Ok(log.to_grid_string()),
+		Output::Placement => Err(String::from("only layouts can be written as placement files")),
+	}
+}
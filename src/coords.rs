@@ -0,0 +1,119 @@
+// The coordinate convention for writing squares. By default rows are lettered
+// from A at the top and columns numbered from 1 at the left, but some players
+// letter the rows from the bottom or number the columns from 0, and boards
+// from Russian paper games letter them in Cyrillic. Squares are
+// parsed and formatted in the chosen convention everywhere text meets the
+// user, so the AI's recommendations read the way the opponent calls shots.
+//
+// The convention is part of a board's context (see context::Context), and
+// the squares of a board are written in its convention. Code converting
+// between conventions reads with one board and writes with another.
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Coordinates {
+	// Whether row A is the bottom row of the board rather than the top
+	pub rows_from_bottom: bool,
+
+	// The number of the leftmost column: 1, or 0
+	pub first_column: u8,
+
+	// The letters of the rows
+	pub alphabet: Alphabet,
+}
+
+// The letters rows are labeled with, in order
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Alphabet {
+	// A, B, C, ...
+	Latin,
+
+	// А, Б, В, ..., as on Russian boards: the letters no word starts with
+	// are left out, and so are Ё and Й
+	Cyrillic,
+}
+
+impl Alphabet {
+	// Parse an alphabet from its command-line name
+	pub fn parse(name: &str) -> Option<Alphabet> {
+		match name {
+			"latin" => Some(Alphabet::Latin),
+			"cyrillic" => Some(Alphabet::Cyrillic),
+			_ => None,
+		}
+	}
+
+	// The row letters, enough for the largest board
+	pub fn letters(self) -> &'static [char] {
+		match self {
+			Alphabet::Latin => &['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z'],
+			Alphabet::Cyrillic => &['А', 'Б', 'В', 'Г', 'Д', 'Е', 'Ж', 'З', 'И', 'К', 'Л', 'М', 'Н', 'О', 'П', 'Р', 'С', 'Т', 'У', 'Ф', 'Х', 'Ц', 'Ч', 'Ш', 'Щ', 'Э', 'Ю', 'Я'],
+		}
+	}
+}
+
+// A as the top row, columns from 1
+pub const DEFAULT: Coordinates = Coordinates { rows_from_bottom: false, first_column: 1, alphabet: Alphabet::Latin };
+
+// The names accepted by Coordinates::parse, each of which may be followed by
+// the name of an alphabet from ALPHABET_NAMES, e.g. "top-1-cyrillic"
+pub const ORIGIN_NAMES: &[&str] = &["top-1", "top-0", "bottom-1", "bottom-0"];
+pub const ALPHABET_NAMES: &[&str] = &["latin", "cyrillic"];
+
+impl Coordinates {
+	// Parse a convention from its command-line name: where row A is ("top" or
+	// "bottom"), then the first column number, then optionally the alphabet,
+	// e.g. "bottom-0" or "top-1-cyrillic"
+	pub fn parse(desc: &str) -> Option<Coordinates> {
+		let mut parts = desc.split('-');
+		let rows_from_bottom = match parts.next()? {
+			"top" => false,
+			"bottom" => true,
+			_ => return None,
+		};
+		let first_column = match parts.next()? {
+			"0" => 0,
+			"1" => 1,
+			_ => return None,
+		};
+		let alphabet = match parts.next() {
+			Some(name) => Alphabet::parse(name)?,
+			None => Alphabet::Latin,
+		};
+		if parts.next().is_some() {
+			return None;
+		}
+		Some(Coordinates { rows_from_bottom, first_column, alphabet })
+	}
+
+	// The letter of the given (top-down) row on a board of the given size
+	pub fn row_label(self, board_size: u8, row: u8) -> char {
+		let letter = if self.rows_from_bottom { board_size - 1 - row } else { row };
+		self.alphabet.letters()[letter as usize]
+	}
+
+	// The (top-down) row with the given letter, if it's on the board
+	pub fn row_of_label(self, board_size: u8, label: char) -> Option<u8> {
+		let letter = self.alphabet.letters().iter().position(|&c| c == label)?;
+		self.row_of_letter(board_size, letter as u32)
+	}
+
+	// The (top-down) row with the given letter index (0 for A), if it's on the board
+	pub fn row_of_letter(self, board_size: u8, letter: u32) -> Option<u8> {
+		if letter >= board_size as u32 {
+			return None;
+		}
+
+		Some(if self.rows_from_bottom { board_size - 1 - letter as u8 } else { letter as u8 })
+	}
+
+	// The number of the given (left-to-right) column
+	pub fn column_label(self, col: u8) -> u32 {
+		col as u32 + self.first_column as u32
+	}
+
+	// The column with the given number, if it's on the board
+	pub fn column_of_number(self, board_size: u8, number: u32) -> Option<u8> {
+		let col = number.checked_sub(self.first_column as u32)?;
+		if col < board_size as u32 { Some(col as u8) } else { None }
+	}
+}
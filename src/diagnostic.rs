@@ -0,0 +1,187 @@
+// Compiler-style errors for the line-based file parsers (moves, placements,
+// rules presets). Besides the line number and what's wrong, a diagnostic shows
+// the offending line with a caret under the bad part, and a likely fix when
+// there is one:
+//
+//   line 3: Invalid move B11H: B11 is not a square on the board
+//     3 | B11H
+//       | ^^^
+//     did you mean B10H?
+//
+// The first line is the plain error message, so anything matching on it
+// still works.
+
+use super::{parse_move, parse_square, Board, BoardPos, ShotResult};
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Diagnostic {
+	// The line's number, counted from 1, and its text
+	line_num: usize,
+	line: String,
+
+	// The columns (in characters) of the bad part, if it's narrower than the line
+	span: Option<(usize, usize)>,
+
+	message: String,
+	suggestion: Option<String>,
+}
+
+impl Diagnostic {
+	pub fn new(line_num: usize, line: &str, message: &str) -> Diagnostic {
+		Diagnostic { line_num, line: line.to_string(), span: None, message: message.to_string(), suggestion: None }
+	}
+
+	// Point at the columns from start up to end
+	pub fn at(mut self, span: Option<(usize, usize)>) -> Diagnostic {
+		self.span = span;
+		self
+	}
+
+	// Point at the field'th whitespace-separated field of the line
+	pub fn at_field(self, field: usize) -> Diagnostic {
+		let span = field_span(&self.line, field);
+		self.at(span)
+	}
+
+	pub fn suggest(mut self, suggestion: Option<String>) -> Diagnostic {
+		self.suggestion = suggestion;
+		self
+	}
+}
+
+impl std::fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let number = self.line_num.to_string();
+		let gutter = " ".repeat(number.len());
+
+		// Tabs are shown as single spaces so the caret lines up
+		let shown: String = self.line.chars().map(|c| if c == '\t' { ' ' } else { c }).collect();
+		let (start, end) = self.span.unwrap_or((0, shown.chars().count()));
+		writeln!(f, "line {}: {}", self.line_num, self.message)?;
+		writeln!(f, "  {} | {}", number, shown)?;
+		write!(f, "  {} | {}{}", gutter, " ".repeat(start), "^".repeat(end.saturating_sub(start).max(1)))?;
+		if let Some(ref suggestion) = self.suggestion {
+			write!(f, "\n  did you mean {}?", suggestion)?;
+		}
+		Ok(())
+	}
+}
+
+// The columns of the field'th whitespace-separated field of the line
+pub fn field_span(line: &str, field: usize) -> Option<(usize, usize)> {
+	let chars: Vec<char> = line.chars().collect();
+	let mut starts = (0..chars.len()).filter(|&idx| !chars[idx].is_whitespace() && (idx == 0 || chars[idx - 1].is_whitespace()));
+	let start = starts.nth(field)?;
+	let end = (start..chars.len()).find(|&idx| chars[idx].is_whitespace()).unwrap_or(chars.len());
+	Some((start, end))
+}
+
+// The columns of the first occurrence of part in the line
+pub fn find_span(line: &str, part: &str) -> Option<(usize, usize)> {
+	let start = line.find(part)?;
+	let start = line[..start].chars().count();
+	Some((start, start + part.chars().count()))
+}
+
+// The number of single-character edits between two words, ignoring case
+fn edit_distance(a: &str, b: &str) -> usize {
+	let a: Vec<char> = a.chars().flat_map(char::to_lowercase).collect();
+	let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, &ca) in a.iter().enumerate() {
+		let mut diagonal = row[0];
+		row[0] = i + 1;
+		for (j, &cb) in b.iter().enumerate() {
+			let substituted = diagonal + if ca == cb { 0 } else { 1 };
+			diagonal = row[j + 1];
+			row[j + 1] = substituted.min(row[j] + 1).min(row[j + 1] + 1);
+		}
+	}
+	row[b.len()]
+}
+
+// The candidate the word is most likely a misspelling of, if any is close
+pub fn closest(word: &str, candidates: &[&str]) -> Option<String> {
+	// Short words only get their case corrected
+	let limit = word.chars().count() / 3;
+	candidates.iter().map(|&candidate| (edit_distance(word, candidate), candidate))
+		.filter(|&(distance, candidate)| distance <= limit && candidate != word)
+		.min_by_key(|&(distance, _)| distance)
+		.map(|(_, candidate)| candidate.to_string())
+}
+
+// The square on the board a mistyped square most likely meant: the same row
+// at the nearest column, or the same column at the nearest row (so B11 on a
+// 10x10 board suggests B10), or the square written the other way round
+pub fn nearest_square(board: &Board, desc: &str) -> Option<BoardPos> {
+	let desc = desc.to_uppercase();
+	let split = desc.find(|c: char| c.is_ascii_digit()).unwrap_or(desc.len());
+	let (row, col) = match (desc[..split].chars().collect::<Vec<_>>().as_slice(), desc[split..].parse::<i64>()) {
+		(&[row], Ok(col)) => (row, col),
+
+		// A square written number first, e.g. 3B
+		_ => match desc.find(|c: char| !c.is_ascii_digit()) {
+			Some(split) if split > 0 && desc[split..].chars().count() == 1 => {
+				return parse_square(board, &format!("{}{}", &desc[split..], &desc[..split]));
+			},
+			_ => return None,
+		},
+	};
+
+	let coordinates = board.coordinates();
+	board.squares().filter_map(|square| {
+		let (label, number) = (coordinates.row_label(board.size(), square.row()), coordinates.column_label(square.col()) as i64);
+		let distance = match (label == row, number == col) {
+			(true, _) => (number - col).abs(),
+			(false, true) => (label as i64 - row as i64).abs(),
+			(false, false) => return None,
+		};
+		Some((distance, square))
+	}).min_by_key(|&(distance, _)| distance).map(|(_, square)| square)
+}
+
+// Explain why the line, which parse_move rejected with the given message,
+// isn't a move: where in the line the problem is, and a fix if one is likely
+pub fn diagnose_move(board: &Board, line_num: usize, line: &str, message: &str) -> Diagnostic {
+	let diagnostic = Diagnostic::new(line_num, line, message);
+
+	// The line's characters other than whitespace, with their columns
+	let chars: Vec<(usize, char)> = line.chars().enumerate().filter(|&(_, c)| !c.is_whitespace()).collect();
+	let compact: String = chars.iter().map(|&(_, c)| c).collect();
+	let span = |from: usize, to: usize| chars.get(from).map(|&(start, _)| (start, chars[to - 1].0 + 1));
+
+	let upper = compact.to_uppercase();
+	if upper != compact && parse_move(board, &upper).is_ok() {
+		return diagnostic.at(span(0, chars.len())).suggest(Some(upper));
+	}
+
+	let (body, sunk) = match compact.strip_suffix('!') {
+		Some(body) => (body, "!"),
+		None => (compact.as_str(), ""),
+	};
+	let body_len = body.chars().count();
+	if !sunk.is_empty() && matches!(parse_move(board, body), Ok((_, ShotResult::Miss)) | Ok((_, ShotResult::Hit(None)))) {
+		return diagnostic.at(span(body_len, chars.len()));
+	}
+
+	// The result letter, if there is one, then the square
+	let (square, result) = match body.chars().last() {
+		Some(c) if c.is_ascii_alphabetic() && body_len > 1 => (&body[..body.len() - c.len_utf8()], c.to_string()),
+		_ => (body, String::new()),
+	};
+	let square_len = square.chars().count();
+	let square_ok = parse_square(board, square).is_some();
+	if !result.is_empty() && square_ok {
+		return diagnostic.at(span(square_len, body_len));
+	}
+	if !square_ok {
+		// The result letter may really be the end of a square written the other way round
+		let suggestion = match (nearest_square(board, square), nearest_square(board, body)) {
+			(Some(pos), _) => Some(format!("{}{}{}", pos, result, sunk)),
+			(None, Some(pos)) => return diagnostic.at(span(0, body_len)).suggest(Some(format!("{}{}", pos, sunk))),
+			(None, None) => None,
+		};
+		return diagnostic.at(span(0, square_len.max(1))).suggest(suggestion);
+	}
+	diagnostic.at(span(0, chars.len()))
+}
@@ -0,0 +1,245 @@
+// Distributing a tournament across worker processes over TCP or a Unix domain
+// socket (see transport.rs). A coordinator
+// splits the tournament into batches of consecutive games, hands them to
+// whichever workers connect, and merges the shot counts they send back, so
+// the result is the same as running the tournament in one process.
+//
+// Each game's layout and tie-breaking seed come from the tournament's RNG, in
+// order. Generating the layouts is cheap compared to playing the games, so the
+// coordinator replays the RNG up front to find where each batch starts, and a
+// worker plays a batch as a small tournament resumed from that RNG state.
+//
+// The protocol is line-based text. A worker opens with
+// "battleship_ai worker <version> <rules preset>"; the coordinator answers
+// with "batch <first game> <count> <rng state>" lines, each answered by
+// "result <first game> <shots per game...>", and finally "done". Any other
+// line from the coordinator is an error message. A batch whose worker
+// disconnects is handed to the next worker that asks.
+
+use placement::Layout;
+use rng::Rng;
+use rules::Rules;
+use sim::{self, Checkpoint, TournamentSummary, Verbosity};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use transport::{Listener, Stream};
+
+const PROTOCOL_VERSION: u32 = 1;
+
+// How long a new connection has to identify itself
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long an idle worker waits before asking again whether a batch came back
+// from a disconnected worker
+const IDLE_POLL: Duration = Duration::from_millis(100);
+
+// A run of consecutive games
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Batch {
+	// The (0-based) index of the first game
+	pub first: usize,
+
+	pub count: usize,
+
+	// The tournament's RNG state before the first game
+	pub rng: u64,
+}
+
+// Split a tournament into batches of at most batch_size games
+pub fn plan_batches(games: usize, seed: u64, rules: &Rules, batch_size: usize) -> Result<Vec<Batch>, String> {
+	let batch_size = batch_size.max(1);
+	let mut rng = Rng::new(seed);
+	let mut batches = Vec::new();
+	for game in 0..games {
+		if game % batch_size == 0 {
+			batches.push(Batch { first: game, count: batch_size.min(games - game), rng: rng.state() });
+		}
+
+		// The same draws run_tournament makes per game: the layout, then the attacker's seed
+		Layout::random(&mut rng, rules.constraints()).ok_or_else(|| format!("No valid layout exists under the {} rules", rules.name))?;
+		rng.next_u64();
+	}
+
+	Ok(batches)
+}
+
+// Play a batch, returning the shots each game took
+pub fn play_batch(batch: &Batch, rules: &Rules) -> Result<Vec<usize>, String> {
+	let summary = sim::run_tournament(Checkpoint::new(batch.count, batch.rng, rules), rules, Verbosity::Silent, &mut std::io::sink(), None, None, Default::default())
+		.map_err(|e| format!("Batch starting at game {} failed: {}", batch.first + 1, e))?;
+	Ok(summary.shots)
+}
+
+// Called with each batch and its shots as it comes back
+pub type OnBatch<'a> = dyn FnMut(&Batch, &[usize]) + Send + 'a;
+
+// The coordinator's bookkeeping, shared between the connections
+struct Work {
+	// Batches not currently assigned to any worker
+	queue: Vec<Batch>,
+
+	// The shots of each finished batch, by its first game
+	results: HashMap<usize, Vec<usize>>,
+
+	total_batches: usize,
+}
+
+impl Work {
+	fn is_done(&self) -> bool {
+		self.results.len() == self.total_batches
+	}
+}
+
+fn read_line(reader: &mut dyn BufRead) -> Result<String, String> {
+	let mut line = String::new();
+	match reader.read_line(&mut line) {
+		Ok(0) => Err(String::from("Connection closed")),
+		Ok(_) => Ok(line.trim().to_string()),
+		Err(e) => Err(format!("Unable to read from the connection: {}", e)),
+	}
+}
+
+fn write_line(stream: &mut Stream, line: &str) -> Result<(), String> {
+	stream.write_all(format!("{}\n", line).as_bytes()).map_err(|e| format!("Unable to write to the connection: {}", e))
+}
+
+// Parse a "result <first game> <shots...>" line for the given batch
+fn parse_result(line: &str, batch: &Batch) -> Result<Vec<usize>, String> {
+	let mut parts = line.split_whitespace();
+	if parts.next() != Some("result") || parts.next() != Some(batch.first.to_string().as_str()) {
+		return Err(format!("Expected the result of the batch starting at game {}, got \"{}\"", batch.first + 1, line));
+	}
+
+	let shots = parts.map(|n| n.parse().map_err(|_| format!("Invalid shot count {}", n))).collect::<Result<Vec<usize>, String>>()?;
+	if shots.len() != batch.count {
+		return Err(format!("Expected {} results, got {}", batch.count, shots.len()));
+	}
+	Ok(shots)
+}
+
+// Serve one worker until the tournament is done or the worker goes away,
+// putting back any batch it was playing
+fn serve_worker(stream: Stream, rules: &Rules, work: &Mutex<Work>, on_batch: &Mutex<&mut OnBatch>) -> Result<(), String> {
+	let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+	let mut stream = stream;
+
+	stream.set_read_timeout(Some(HELLO_TIMEOUT)).map_err(|e| e.to_string())?;
+	let hello = read_line(&mut reader)?;
+	stream.set_read_timeout(None).map_err(|e| e.to_string())?;
+	let expected = format!("battleship_ai worker {} {}", PROTOCOL_VERSION, rules.name);
+	if hello != expected {
+		let _ = write_line(&mut stream, &format!("error: expected \"{}\"", expected));
+		return Err(format!("Rejected a worker that sent \"{}\"", hello));
+	}
+
+	loop {
+		let batch = {
+			let mut work = work.lock().expect("Coordinator state poisoned");
+			if work.is_done() {
+				return write_line(&mut stream, "done");
+			}
+			work.queue.pop()
+		};
+		let batch = match batch {
+			Some(batch) => batch,
+			None => {
+				thread::sleep(IDLE_POLL);
+				continue;
+			},
+		};
+
+		let outcome = write_line(&mut stream, &format!("batch {} {} {}", batch.first, batch.count, batch.rng))
+			.and_then(|_| read_line(&mut reader))
+			.and_then(|line| parse_result(&line, &batch));
+		let mut work = work.lock().expect("Coordinator state poisoned");
+		match outcome {
+			Ok(shots) => {
+				(on_batch.lock().expect("Progress callback poisoned"))(&batch, &shots);
+				work.results.insert(batch.first, shots);
+			},
+			Err(e) => {
+				work.queue.push(batch);
+				return Err(e);
+			},
+		}
+	}
+}
+
+// Run the tournament described by the batches, handing them to the workers that
+// connect to listener, and return the merged summary. on_batch is called as each
+// batch comes back; on_error with problems on individual connections, which
+// don't stop the tournament.
+pub fn coordinate(listener: Listener, batches: Vec<Batch>, rules: &Rules, on_batch: &mut OnBatch,
+                  on_error: &(dyn Fn(String) + Sync)) -> Result<TournamentSummary, String> {
+	// Hand out the earliest games first
+	let mut queue = batches;
+	queue.reverse();
+	let work = Mutex::new(Work { total_batches: queue.len(), queue, results: HashMap::new() });
+	let on_batch = Mutex::new(on_batch);
+
+	listener.set_nonblocking(true).map_err(|e| format!("Unable to configure the listener: {}", e))?;
+	thread::scope(|scope| {
+		while !work.lock().expect("Coordinator state poisoned").is_done() {
+			match listener.accept() {
+				Ok((stream, addr)) => {
+					let (work, on_batch) = (&work, &on_batch);
+					scope.spawn(move || {
+						let _ = stream.set_nonblocking(false);
+						if let Err(e) = serve_worker(stream, rules, work, on_batch) {
+							on_error(format!("worker {}: {}", addr, e));
+						}
+					});
+				},
+				Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(IDLE_POLL),
+				Err(e) => on_error(format!("Unable to accept a worker: {}", e)),
+			}
+		}
+
+		// Tell any workers still waiting to be accepted that there's nothing left
+		while let Ok((mut stream, _)) = listener.accept() {
+			let _ = stream.set_nonblocking(false);
+			let _ = stream.set_read_timeout(Some(HELLO_TIMEOUT));
+			let hello = stream.try_clone().map_err(|e| e.to_string()).and_then(|clone| read_line(&mut BufReader::new(clone)));
+			if hello.is_ok() {
+				let _ = stream.write_all(b"done\n");
+			}
+		}
+	});
+
+	let mut work = work.lock().expect("Coordinator state poisoned");
+	let mut firsts: Vec<usize> = work.results.keys().cloned().collect();
+	firsts.sort();
+	let shots = firsts.iter().flat_map(|first| work.results.remove(first).unwrap_or_default()).collect();
+	Ok(TournamentSummary { shots })
+}
+
+// Connect to a coordinator and play batches until it says the tournament is
+// done. Returns the number of games played.
+pub fn work(addr: &str, rules: &Rules) -> Result<usize, String> {
+	let mut stream = Stream::connect(addr)?;
+	let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+	write_line(&mut stream, &format!("battleship_ai worker {} {}", PROTOCOL_VERSION, rules.name))?;
+
+	let mut played = 0;
+	loop {
+		let line = read_line(&mut reader)?;
+		let parts: Vec<&str> = line.split_whitespace().collect();
+		match parts.as_slice() {
+			["done"] => return Ok(played),
+			["batch", first, count, rng] => {
+				let batch = match (first.parse(), count.parse(), rng.parse()) {
+					(Ok(first), Ok(count), Ok(rng)) => Batch { first, count, rng },
+					_ => return Err(format!("Invalid batch \"{}\"", line)),
+				};
+				let shots = play_batch(&batch, rules)?;
+				let shots: Vec<String> = shots.iter().map(|n| n.to_string()).collect();
+				write_line(&mut stream, &format!("result {} {}", batch.first, shots.join(" ")))?;
+				played += batch.count;
+			},
+			_ => return Err(format!("The coordinator said \"{}\"", line)),
+		}
+	}
+}
@@ -0,0 +1,180 @@
+// Crash-safe files: saved games, checkpoints, profiles, histories, ratings,
+// and cache entries are written to a temporary file beside their destination,
+// flushed to disk, and renamed over it, and the rename is flushed too, so a
+// crash mid-write leaves the old file intact. Each file ends with a
+// "checksum <hash>" line (a 64-bit FNV-1a hash of everything before it), so a
+// file damaged some other way, or cut short, is rejected rather than
+// half-read. Files without the line, from versions before checksums or
+// written by hand, are only read when the caller allows it (--legacy-files,
+// see context::Context).
+//
+// Files meant for other programs or for people, such as placement files,
+// exports and binary logs, are replaced the same way but left unsealed (see
+// write_unsealed and Replacement).
+
+use cache::fnv1a;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const CHECKSUM_PREFIX: &str = "checksum ";
+
+// Distinguishes the temporary files of concurrent writes from this process
+static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+
+// Append the checksum line to a file's contents
+pub fn seal(contents: &str) -> String {
+	format!("{}{}{:016x}\n", contents, CHECKSUM_PREFIX, fnv1a(contents.as_bytes()))
+}
+
+// Check and strip the checksum line. A file without one is returned as it is
+// if allow_unsealed is set, and rejected otherwise.
+pub fn unseal(contents: &str, allow_unsealed: bool) -> Result<&str, String> {
+	let body_len = contents.trim_end_matches('\n').rfind('\n').map_or(0, |idx| idx + 1);
+	let (body, last) = contents.split_at(body_len);
+	let expected = match last.trim_end().strip_prefix(CHECKSUM_PREFIX) {
+		Some(expected) => expected,
+		None if allow_unsealed => return Ok(contents),
+		None => return Err(String::from("the file has no checksum line, so it may be damaged or cut short (--legacy-files reads files from before checksums)")),
+	};
+
+	if u64::from_str_radix(expected, 16).ok() != Some(fnv1a(body.as_bytes())) {
+		return Err(String::from("the checksum doesn't match, so the file is damaged"));
+	}
+	Ok(body)
+}
+
+// Write the contents, sealed, to path, replacing any existing file atomically
+pub fn write(path: &Path, contents: &str) -> std::io::Result<()> {
+	write_unsealed(path, seal(contents).as_bytes())
+}
+
+// Write the bytes to path as they are, replacing any existing file atomically
+pub fn write_unsealed(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+	let mut file = Replacement::create(path)?;
+	file.write_all(bytes)?;
+	file.finish()
+}
+
+// A file written in pieces to replace path: the pieces go to a temporary file
+// beside it, which finish flushes to disk and renames over path. Dropped
+// unfinished, the temporary file is removed and path left as it was.
+pub struct Replacement {
+	path: PathBuf,
+	tmp: PathBuf,
+	file: File,
+	finished: bool,
+}
+
+impl Replacement {
+	pub fn create(path: &Path) -> std::io::Result<Replacement> {
+		let (tmp, file) = create_tmp(path)?;
+		Ok(Replacement { path: path.to_path_buf(), tmp, file, finished: false })
+	}
+
+	// Put the file in place of path
+	pub fn finish(mut self) -> std::io::Result<()> {
+		self.file.sync_all()?;
+		std::fs::rename(&self.tmp, &self.path)?;
+		self.finished = true;
+		sync_dir(&self.path)
+	}
+}
+
+impl Write for Replacement {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.file.write(buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.file.flush()
+	}
+}
+
+impl Drop for Replacement {
+	fn drop(&mut self) {
+		if !self.finished {
+			let _ = std::fs::remove_file(&self.tmp);
+		}
+	}
+}
+
+// Create a temporary file beside path that no other write, from this process
+// or another, is using
+fn create_tmp(path: &Path) -> std::io::Result<(PathBuf, File)> {
+	loop {
+		let mut tmp = path.as_os_str().to_owned();
+		tmp.push(format!(".{}-{}.tmp", std::process::id(), NEXT_TMP.fetch_add(1, Ordering::Relaxed)));
+		match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+			Ok(file) => return Ok((PathBuf::from(tmp), file)),
+			Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+// Flush the directory holding path, so the rename onto it survives a crash
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> std::io::Result<()> {
+	let dir = match path.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir,
+		_ => Path::new("."),
+	};
+	File::open(dir)?.sync_all()
+}
+
+// Elsewhere a directory can't be opened to flush it, and the rename is as
+// durable as the platform makes it
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> std::io::Result<()> {
+	Ok(())
+}
+
+// Read a file written by write, checking its checksum. A damaged file, or one
+// without a checksum unless allow_unsealed is set, is an InvalidData error.
+pub fn read(path: &Path, allow_unsealed: bool) -> std::io::Result<String> {
+	let contents = std::fs::read_to_string(path)?;
+	unseal(&contents, allow_unsealed).map(String::from).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unseals_what_it_seals() {
+		let sealed = seal("shots 3\nA1 M\n");
+		assert_eq!(unseal(&sealed, false), Ok("shots 3\nA1 M\n"));
+	}
+
+	#[test]
+	fn rejects_damage() {
+		let sealed = seal("shots 3\nA1 M\n").replace("A1", "A2");
+		assert!(unseal(&sealed, true).unwrap_err().contains("doesn't match"));
+	}
+
+	#[test]
+	fn needs_the_checksum_unless_allowed() {
+		let truncated = "shots 3\nA1 M\n";
+		assert!(unseal(truncated, false).unwrap_err().contains("no checksum line"));
+		assert_eq!(unseal(truncated, true), Ok(truncated));
+	}
+
+	#[test]
+	fn replaces_only_when_finished() {
+		let path = std::env::temp_dir().join(format!("battleship_ai-durable-{}", std::process::id()));
+		write_unsealed(&path, b"old").unwrap();
+
+		let mut abandoned = Replacement::create(&path).unwrap();
+		abandoned.write_all(b"new").unwrap();
+		drop(abandoned);
+		assert_eq!(std::fs::read(&path).unwrap(), b"old");
+
+		let mut replacement = Replacement::create(&path).unwrap();
+		replacement.write_all(b"new").unwrap();
+		replacement.finish().unwrap();
+		assert_eq!(std::fs::read(&path).unwrap(), b"new");
+		std::fs::remove_file(&path).unwrap();
+	}
+}
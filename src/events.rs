@@ -0,0 +1,386 @@
+// Event sourcing for a game against one opponent board: everything that
+// happens is appended to a log of events, and the GameState is derived from
+// it. Shots and results apply to the derived state directly; corrections
+// (retracting or fixing an earlier result) rebuild it from the latest
+// snapshot they leave valid. Undo, replaying a game to any point, and
+// journaling a game to disk for crash recovery all work off the log.
+//
+// A shot at a square that already has a result follows the rules' Reshot
+// option: it is refused, let go, or kept as a turn spent on nothing.
+//
+// The text format has one event per line: "fire <square>" for a shot whose
+// result is withheld, "result <move>", "retract <square>", and
+// "correct <move>", with moves as in the moves file format, plus
+// "sweep <sweep>" for a radar sweep (see the radar module),
+// "unsweep <square>" to withdraw the latest sweep centered there, and
+// "relocate" and "unrelocate" for the opponent moving a ship (under
+// moving-ships rules) and withdrawing that.
+
+use super::{format_move, parse_move, parse_square, Board, BoardPos, GameState, ShotResult};
+use radar::Sweep;
+use rules::Reshot;
+use text;
+
+// How many applied moves apart the snapshots are taken
+const SNAPSHOT_INTERVAL: usize = 8;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Event {
+	// A shot whose result is withheld (under fog-of-war rules)
+	Fired(BoardPos),
+
+	// A shot's result, for a new square or one fired at earlier
+	Result((BoardPos, ShotResult)),
+
+	// A correction: the square's latest shot is withdrawn. If that was a
+	// repeat, only the repeat goes; otherwise the square is as if it had never
+	// been shot.
+	Retract(BoardPos),
+
+	// A correction: the square's earlier result was misreported, and is
+	// really this one
+	Correct((BoardPos, ShotResult)),
+
+	// A radar sweep's result
+	Sweep(Sweep),
+
+	// A correction: the latest sweep centered on the square is withdrawn
+	Unsweep(BoardPos),
+
+	// The opponent relocated a ship (see GameState::apply_relocation)
+	Relocated,
+
+	// A correction: the relocation is withdrawn
+	Unrelocate,
+}
+
+impl Event {
+	// Parse an event on the given board
+	pub fn parse(board: &Board, line: &str) -> Result<Event, String> {
+		match line.trim() {
+			"relocate" => return Ok(Event::Relocated),
+			"unrelocate" => return Ok(Event::Unrelocate),
+			_ => {},
+		}
+		let (kind, arg) = line.trim().split_once(' ').ok_or_else(|| format!("expected \"<event> <square or move>\", not {}", line.trim()))?;
+		let square = |arg: &str| parse_square(board, arg).ok_or_else(|| format!("invalid square {}", arg));
+		match kind {
+			"fire" => square(arg.trim()).map(Event::Fired),
+			"result" => parse_move(board, arg).map(Event::Result),
+			"retract" => square(arg.trim()).map(Event::Retract),
+			"correct" => parse_move(board, arg).map(Event::Correct),
+			"sweep" => Sweep::parse(board, arg).map(Event::Sweep),
+			"unsweep" => square(arg.trim()).map(Event::Unsweep),
+			_ => Err(format!("unknown event {}", kind)),
+		}
+	}
+}
+
+impl std::fmt::Display for Event {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match *self {
+			Event::Fired(pos) => write!(f, "fire {}", pos),
+			Event::Result(cur_move) => write!(f, "result {}", format_move(cur_move)),
+			Event::Retract(pos) => write!(f, "retract {}", pos),
+			Event::Correct(cur_move) => write!(f, "correct {}", format_move(cur_move)),
+			Event::Sweep(sweep) => write!(f, "sweep {}", sweep),
+			Event::Unsweep(pos) => write!(f, "unsweep {}", pos),
+			Event::Relocated => write!(f, "relocate"),
+			Event::Unrelocate => write!(f, "unrelocate"),
+		}
+	}
+}
+
+// Why an event doesn't apply to the log
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum EventError {
+	// The square already has a result, and the rules refuse repeats (or the
+	// repeat's result differs)
+	AlreadyShot(BoardPos),
+
+	// The square has no shot in the log to retract or correct (shots from
+	// before the log started can't be)
+	NotShot(BoardPos),
+
+	// The square has no sweep in the log to withdraw
+	NotSwept(BoardPos),
+
+	// The opponent has already relocated a ship this game
+	AlreadyRelocated,
+
+	// There is no relocation in the log to withdraw
+	NotRelocated,
+
+	// An undo with nothing in the log to undo
+	NothingToUndo,
+}
+
+impl std::fmt::Display for EventError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match *self {
+			EventError::AlreadyShot(pos) => write!(f, "{} already has a result", pos),
+			EventError::NotShot(pos) => write!(f, "{} has no shot in the log", pos),
+			EventError::NotSwept(pos) => write!(f, "{} has no sweep in the log", pos),
+			EventError::AlreadyRelocated => write!(f, "a ship has already been relocated"),
+			EventError::NotRelocated => write!(f, "there is no relocation in the log"),
+			EventError::NothingToUndo => write!(f, "there is nothing to undo"),
+		}
+	}
+}
+
+// What the events amount to: the shots still standing, in order, each
+// withheld, applied, or a repeat, and the sweeps and relocation among them.
+// The derived state is the base state after these.
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum Op {
+	Fired(BoardPos),
+	Move((BoardPos, ShotResult)),
+	Reshot(BoardPos),
+	Sweep(Sweep),
+	Relocated,
+}
+
+impl Op {
+	// The square shot, unless it's a sweep or relocation
+	fn pos(self) -> Option<BoardPos> {
+		match self {
+			Op::Fired(pos) | Op::Move((pos, _)) | Op::Reshot(pos) => Some(pos),
+			Op::Sweep(_) | Op::Relocated => None,
+		}
+	}
+
+	fn apply(self, state: &mut GameState) {
+		match self {
+			Op::Fired(pos) => state.mark_pending(pos),
+			Op::Move(cur_move) => state.apply_move(cur_move),
+			Op::Reshot(_) => state.record_reshot(),
+			Op::Sweep(sweep) => state.apply_sweep(sweep),
+			Op::Relocated => state.apply_relocation(),
+		}
+	}
+}
+
+// How an event changed the ops
+enum Change {
+	// It appended an op
+	Appended,
+
+	// It was let go
+	Unchanged,
+
+	// It changed the ops from the given index on
+	Rewound(usize),
+}
+
+// The result the square has, under the base state or the ops. A miss from
+// before a relocation no longer stands.
+fn result_at(ops: &[Op], base: &GameState, pos: BoardPos) -> Option<ShotResult> {
+	let mut standing = base.standing_shots().find(|&&(shot, _)| shot == pos).map(|&(_, result)| result);
+	for &op in ops {
+		match op {
+			Op::Move((shot, result)) if shot == pos && standing.is_none() => standing = Some(result),
+			Op::Relocated if standing == Some(ShotResult::Miss) => standing = None,
+			_ => {},
+		}
+	}
+	standing
+}
+
+// Fold one event into the ops, refusing or letting go of repeats by the rule
+fn fold(ops: &mut Vec<Op>, base: &GameState, reshot: Reshot, event: Event) -> Result<Change, EventError> {
+	// A shot at a square shot before (with the given result, if known)
+	let repeat = |ops: &mut Vec<Op>, pos: BoardPos, same: bool| match reshot {
+		Reshot::Error => Err(EventError::AlreadyShot(pos)),
+		_ if !same => Err(EventError::AlreadyShot(pos)),
+		Reshot::Ignore => Ok(Change::Unchanged),
+		Reshot::Turn => {
+			ops.push(Op::Reshot(pos));
+			Ok(Change::Appended)
+		},
+	};
+
+	match event {
+		Event::Fired(pos) => {
+			let since = ops.iter().rposition(|&op| op == Op::Relocated).map_or(0, |idx| idx + 1);
+			if base.is_shot(pos) || result_at(ops, base, pos).is_some() || ops[since..].iter().any(|&op| op.pos() == Some(pos)) {
+				return repeat(ops, pos, true);
+			}
+			ops.push(Op::Fired(pos));
+			Ok(Change::Appended)
+		},
+		Event::Result(cur_move) => {
+			if let Some(result) = result_at(ops, base, cur_move.0) {
+				return repeat(ops, cur_move.0, result == cur_move.1);
+			}
+			ops.push(Op::Move(cur_move));
+			Ok(Change::Appended)
+		},
+		Event::Retract(pos) => {
+			let latest = ops.iter().rposition(|&op| op.pos() == Some(pos)).ok_or(EventError::NotShot(pos))?;
+			if ops[latest] == Op::Reshot(pos) {
+				ops.remove(latest);
+				return Ok(Change::Rewound(latest));
+			}
+			// A shot since a relocation leaves the square's shots from before it
+			let since = ops[..latest].iter().rposition(|&op| op == Op::Relocated).map_or(0, |idx| idx + 1);
+			let first = since + ops[since..].iter().position(|&op| op.pos() == Some(pos)).ok_or(EventError::NotShot(pos))?;
+			let mut idx = 0;
+			ops.retain(|&op| {
+				idx += 1;
+				idx <= since || op.pos() != Some(pos)
+			});
+			Ok(Change::Rewound(first))
+		},
+		Event::Correct(cur_move) => {
+			let idx = ops.iter().position(|&op| matches!(op, Op::Move((shot, _)) if shot == cur_move.0)).ok_or(EventError::NotShot(cur_move.0))?;
+			ops[idx] = Op::Move(cur_move);
+			Ok(Change::Rewound(idx))
+		},
+		Event::Sweep(sweep) => {
+			ops.push(Op::Sweep(sweep));
+			Ok(Change::Appended)
+		},
+		Event::Unsweep(pos) => {
+			let latest = ops.iter().rposition(|&op| matches!(op, Op::Sweep(sweep) if sweep.center == pos)).ok_or(EventError::NotSwept(pos))?;
+			ops.remove(latest);
+			Ok(Change::Rewound(latest))
+		},
+		Event::Relocated => {
+			if base.relocation().is_some() || ops.contains(&Op::Relocated) {
+				return Err(EventError::AlreadyRelocated);
+			}
+			ops.push(Op::Relocated);
+			Ok(Change::Appended)
+		},
+		Event::Unrelocate => {
+			let idx = ops.iter().position(|&op| op == Op::Relocated).ok_or(EventError::NotRelocated)?;
+			ops.remove(idx);
+			Ok(Change::Rewound(idx))
+		},
+	}
+}
+
+#[derive(Clone)]
+pub struct EventLog {
+	// The state before any event, e.g. a fresh game or a restored save file
+	base: GameState,
+
+	// What a shot at a square with a result does
+	reshot: Reshot,
+
+	events: Vec<Event>,
+
+	// What the events amount to, and the state they derive
+	ops: Vec<Op>,
+	head: GameState,
+
+	// States after the first n ops, oldest first, for rebuilding the head
+	// after a correction without replaying everything
+	snapshots: Vec<(usize, GameState)>,
+}
+
+impl EventLog {
+	pub fn new(base: GameState, reshot: Reshot) -> EventLog {
+		EventLog { head: base.clone(), base, reshot, events: Vec::new(), ops: Vec::new(), snapshots: Vec::new() }
+	}
+
+	// Replay a log in the text format on top of the base state
+	pub fn from_file_string(contents: &str, base: GameState, reshot: Reshot) -> Result<EventLog, String> {
+		let mut log = EventLog::new(base, reshot);
+		for (line_num, line) in text::lines(contents).enumerate().filter(|&(_, line)| !line.trim().is_empty()) {
+			let event = Event::parse(log.base.board(), line).map_err(|e| format!("line {}: {}", line_num + 1, e))?;
+			log.append(event).map_err(|e| format!("line {}: {}", line_num + 1, e))?;
+		}
+
+		Ok(log)
+	}
+
+	pub fn to_file_string(&self) -> String {
+		self.events.iter().map(|event| format!("{}\n", event)).collect()
+	}
+
+	pub fn events(&self) -> &[Event] {
+		&self.events
+	}
+
+	// The state after every event so far
+	pub fn state(&self) -> &GameState {
+		&self.head
+	}
+
+	// The state, mutably, only for keeping what is derived from it (e.g. with
+	// precompute_heatmap); moves must be appended as events instead
+	pub fn state_mut(&mut self) -> &mut GameState {
+		&mut self.head
+	}
+
+	// The latest snapshot over at most the first n ops, or the base state
+	fn snapshot_within(&self, n: usize) -> (usize, &GameState) {
+		self.snapshots.iter().rev().find(|&&(count, _)| count <= n).map_or((0, &self.base), |&(count, ref state)| (count, state))
+	}
+
+	pub fn append(&mut self, event: Event) -> Result<(), EventError> {
+		match fold(&mut self.ops, &self.base, self.reshot, event)? {
+			Change::Appended => {
+				let op = *self.ops.last().expect("An appended event left no ops");
+				op.apply(&mut self.head);
+			},
+			Change::Unchanged => {},
+			Change::Rewound(changed) => {
+				self.snapshots.retain(|&(count, _)| count <= changed);
+				let (count, snapshot) = self.snapshot_within(changed);
+				let mut head = snapshot.clone();
+				for &op in &self.ops[count..] {
+					op.apply(&mut head);
+				}
+				self.head = head;
+			},
+		}
+		self.events.push(event);
+
+		let newest = self.snapshots.last().map_or(0, |&(count, _)| count);
+		if self.ops.len() >= newest + SNAPSHOT_INTERVAL {
+			self.snapshots.push((self.ops.len(), self.head.clone()));
+		}
+		Ok(())
+	}
+
+	// Undo the latest shot, sweep, or relocation still standing, by
+	// retracting or withdrawing it. Returns its square (the center, for a
+	// sweep), or None for a relocation.
+	pub fn undo(&mut self) -> Result<Option<BoardPos>, EventError> {
+		match *self.ops.last().ok_or(EventError::NothingToUndo)? {
+			Op::Sweep(sweep) => {
+				self.append(Event::Unsweep(sweep.center))?;
+				Ok(Some(sweep.center))
+			},
+			Op::Relocated => {
+				self.append(Event::Unrelocate)?;
+				Ok(None)
+			},
+			op => {
+				let pos = op.pos().expect("A shot op has no square");
+				self.append(Event::Retract(pos))?;
+				Ok(Some(pos))
+			},
+		}
+	}
+
+	// The state after the first n events, e.g. for replaying the game
+	pub fn state_at(&self, n: usize) -> GameState {
+		let mut ops = Vec::new();
+		for &event in &self.events[..n.min(self.events.len())] {
+			fold(&mut ops, &self.base, self.reshot, event).expect("A logged event no longer applies");
+		}
+
+		// Snapshots hold prefixes of the current ops, so pick one within the
+		// prefix shared with the ops after n events
+		let shared = ops.iter().zip(self.ops.iter()).take_while(|&(a, b)| a == b).count();
+		let (count, snapshot) = self.snapshot_within(shared);
+		let mut state = snapshot.clone();
+		for &op in &ops[count..] {
+			op.apply(&mut state);
+		}
+		state
+	}
+}
@@ -0,0 +1,185 @@
+// Why a ship's remaining placements are what they are, for teaching and for
+// debugging the inference. Every placement the ship has lost is put down to
+// the first thing that rules it out on its own:
+//
+// - the rules only allowing the other orientation,
+// - a shot it covers (a miss, or a hit on another ship),
+// - a hit it doesn't cover on the ship itself,
+// - the ship being sunk while the placement isn't entirely on hits,
+//
+// in the order the results came in, and anything left over to deduction from
+// the other ships (such as placements that leave no room for them, or a hit
+// no other ship could then cover). Misses from before a relocation no longer
+// rule anything out (see GameState::apply_relocation).
+
+use super::{encode_shiptype, render_board, Board, BoardPos, GameState, ShipType, ShotResult};
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Cause {
+	// The rules only allow the ship the other orientation
+	Orientation,
+
+	// The placement covers the move (an index into GameState::records), a
+	// miss or a hit on another ship
+	Covers(usize),
+
+	// The placement doesn't cover the move, a hit on this ship
+	Avoids(usize),
+
+	// The move sank this ship, and the placement isn't entirely on hits
+	OffHits(usize),
+
+	// Nothing on its own rules the placement out, but the inference deduced
+	// it from the other ships
+	Deduced,
+}
+
+impl Cause {
+	// The move behind the cause, if it's a move
+	pub fn record(self) -> Option<usize> {
+		match self {
+			Cause::Covers(idx) | Cause::Avoids(idx) | Cause::OffHits(idx) => Some(idx),
+			Cause::Orientation | Cause::Deduced => None,
+		}
+	}
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Explanation {
+	pub stype: ShipType,
+
+	// The number of placements the ship has on the board, and how many remain
+	pub total: usize,
+	pub remaining: usize,
+
+	// How many placements each cause ruled out, in the order of the moves,
+	// with orientation first and deduction last, leaving out causes that
+	// ruled out nothing
+	pub causes: Vec<(Cause, usize)>,
+
+	// For each square, how many remaining placements cover it
+	pub coverage: Vec<usize>,
+
+	// The square and result of every move, as in GameState::records
+	moves: Vec<(BoardPos, ShotResult, usize)>,
+	cells: Vec<char>,
+}
+
+// Explain the remaining placements of the given ship type, which must be in
+// the fleet
+pub fn explain(state: &GameState, stype: ShipType) -> Explanation {
+	let board = state.board();
+	let remaining = state.positions(stype);
+	let since = state.relocation().map_or(0, |relocation| relocation.shots);
+	let records = state.records();
+	let hits: Vec<BoardPos> = board.squares().filter(|&square| state.is_hit(square)).collect();
+
+	let (mut orientation, mut deduced) = (0, 0);
+	let mut shot_causes: Vec<(Cause, usize)> = Vec::new();
+	let mut coverage = vec![0; board.num_squares()];
+	for ship in board.placements(stype) {
+		let squares: Vec<BoardPos> = ship.cells().collect();
+		if remaining.contains(ship.id.index()) {
+			for square in squares {
+				coverage[square.index()] += 1;
+			}
+			continue;
+		}
+
+		if !state.orientation(stype).allows(ship.is_horizontal()) {
+			orientation += 1;
+			continue;
+		}
+		let cause = records.iter().enumerate().find_map(|(idx, record)| {
+			let covers = squares.contains(&record.pos);
+			match record.result {
+				ShotResult::Miss if covers && idx >= since => Some(Cause::Covers(idx)),
+				ShotResult::Hit(Some(other)) | ShotResult::Sunk(other) if covers && other != stype => Some(Cause::Covers(idx)),
+				ShotResult::Hit(Some(ship)) if !covers && ship == stype => Some(Cause::Avoids(idx)),
+				ShotResult::Sunk(ship) if ship == stype && !covers => Some(Cause::Avoids(idx)),
+				ShotResult::Sunk(ship) if ship == stype && !squares.iter().all(|square| hits.contains(square)) => Some(Cause::OffHits(idx)),
+				_ => None,
+			}
+		});
+		match (cause, cause.and_then(|cause| shot_causes.iter().position(|&(listed, _)| listed == cause))) {
+			(_, Some(listed)) => shot_causes[listed].1 += 1,
+			(Some(cause), None) => shot_causes.push((cause, 1)),
+			(None, _) => deduced += 1,
+		}
+	}
+	shot_causes.sort_by_key(|&(cause, _)| cause.record());
+
+	let mut listed = Vec::new();
+	if orientation > 0 {
+		listed.push((Cause::Orientation, orientation));
+	}
+	listed.extend(shot_causes);
+	if deduced > 0 {
+		listed.push((Cause::Deduced, deduced));
+	}
+
+	Explanation {
+		stype,
+		total: board.num_positions(stype),
+		remaining: remaining.count(),
+		causes: listed,
+		coverage,
+		moves: records.iter().map(|record| (record.pos, record.result, record.turn)).collect(),
+		cells: state.render_cells(),
+	}
+}
+
+// The letter marking the cause's move on the grid
+fn label(order: usize) -> char {
+	(b'a'..=b'z').map(char::from).nth(order).unwrap_or('*')
+}
+
+impl Explanation {
+	// The explanation as an annotated grid: each unshot square shows how many
+	// remaining placements cover it ('.' for none, '+' for more than 9), each
+	// shot that ruled any out a letter keyed to the list below the grid, and
+	// every other shot its mark as on the board.
+	pub fn render(&self, board: &Board) -> String {
+		let mut cells: Vec<char> = self.cells.iter().zip(self.coverage.iter()).map(|(&cell, &count)| match (cell, count) {
+			('.', 0) => '.',
+			('.', 1..=9) => char::from(b'0' + count as u8),
+			('.', _) => '+',
+			_ => cell,
+		}).collect();
+		let mut legend = String::new();
+		let mut labeled: Vec<usize> = Vec::new();
+		for &(cause, count) in &self.causes {
+			let idx = match cause.record() {
+				Some(idx) => idx,
+				None => continue,
+			};
+			if !labeled.contains(&idx) {
+				labeled.push(idx);
+			}
+			let letter = label(labeled.len() - 1);
+			let (pos, result, turn) = self.moves[idx];
+			cells[pos.index()] = letter;
+			let what = match result {
+				ShotResult::Miss => String::from("miss"),
+				ShotResult::Hit(None) => String::from("hit"),
+				ShotResult::Hit(Some(stype)) => format!("hit on {}", encode_shiptype(stype)),
+				ShotResult::Sunk(stype) => format!("sank {}", encode_shiptype(stype)),
+			};
+			let why = match cause {
+				Cause::Covers(_) => "covering it",
+				Cause::Avoids(_) => "not covering it",
+				_ => "not entirely on hits",
+			};
+			legend += &format!("{}  {} {} (move {}): {} ruled out for {}\n", letter, pos, what, turn, count, why);
+		}
+		for &(cause, count) in &self.causes {
+			match cause {
+				Cause::Orientation => legend += &format!("   {} ruled out by the rules' orientation\n", count),
+				Cause::Deduced => legend += &format!("   {} ruled out by deduction from the other ships\n", count),
+				_ => {},
+			}
+		}
+
+		format!("{:?}: {} of {} placements remain\n", self.stype, self.remaining, self.total) + &render_board(board, &cells) + &legend
+	}
+}
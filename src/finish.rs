@@ -0,0 +1,181 @@
+// Planning the shots that finish off a ship after it has been hit. Rather than
+// probing adjacent squares one at a time, this searches every adaptive shot
+// sequence for the one that sinks the ship in the fewest shots in the worst
+// case, over every placement (orientation and ship type) still consistent with
+// the hit. Each ship type's placements are considered on their own, ignoring
+// the other ships, so the worst case is over a slight superset of the truly
+// possible placements.
+
+use super::{Board, BoardPos, GameState, PlacementId, ShotResult};
+use std::collections::HashMap;
+
+// Squares as bits of a mask (boards up to 11x11 fit)
+type SquareMask = u128;
+
+fn square_bit(pos: BoardPos) -> SquareMask {
+	1 << pos.index()
+}
+
+// What to do after one result of a planned shot
+#[derive(Clone,Debug,PartialEq)]
+pub enum Branch {
+	// No consistent placement gives this result
+	Impossible,
+
+	// The ship is certainly sunk
+	Sunk,
+
+	Continue(Box<Plan>),
+}
+
+// An adaptive plan: the shot to take, then what to do after each result
+#[derive(Clone,Debug,PartialEq)]
+pub struct Plan {
+	pub shot: BoardPos,
+	pub on_hit: Branch,
+	pub on_miss: Branch,
+}
+
+impl Plan {
+	// Render the plan as an indented decision tree
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		self.render_into(&mut out, 0);
+		out
+	}
+
+	fn render_into(&self, out: &mut String, depth: usize) {
+		*out += &format!("{}shoot {}\n", "  ".repeat(depth), self.shot);
+		for &(label, branch) in &[("hit", &self.on_hit), ("miss", &self.on_miss)] {
+			match *branch {
+				Branch::Impossible => {},
+				Branch::Sunk => *out += &format!("{}if {}: sunk\n", "  ".repeat(depth + 1), label),
+				Branch::Continue(ref plan) => {
+					*out += &format!("{}if {}:\n", "  ".repeat(depth + 1), label);
+					plan.render_into(out, depth + 2);
+				},
+			}
+		}
+	}
+}
+
+// The result of planning: how many more shots the worst case needs, and the
+// plan achieving it (None if the ship is already sunk)
+#[derive(Clone,Debug,PartialEq)]
+pub struct FinishPlan {
+	pub worst_case: u32,
+	pub plan: Option<Plan>,
+}
+
+struct Planner {
+	// The board the ship is on
+	board: Board,
+
+	// The squares of each candidate placement
+	candidates: Vec<SquareMask>,
+
+	// Best (worst-case shots, shot) for each (live candidates, hit squares)
+	memo: HashMap<(u64, SquareMask), (u32, Option<BoardPos>)>,
+}
+
+impl Planner {
+	// The worst-case number of shots to sink the ship given the live candidates
+	// (a bitmask over self.candidates) and the squares hit so far
+	fn solve(&mut self, live: u64, hits: SquareMask) -> u32 {
+		if let Some(&(worst, _)) = self.memo.get(&(live, hits)) {
+			return worst;
+		}
+
+		let live_squares: Vec<SquareMask> = (0..self.candidates.len()).filter(|&idx| live & (1 << idx) != 0)
+			.map(|idx| self.candidates[idx]).collect();
+		let unhit = live_squares.iter().fold(0, |acc, &squares| acc | squares) & !hits;
+
+		// Sunk once every remaining candidate is fully hit
+		let mut best = (0, None);
+		if live_squares.iter().any(|&squares| squares & !hits != 0) {
+			best = (u32::MAX, None);
+			for pos in self.board.squares().filter(|&pos| unhit & square_bit(pos) != 0) {
+				let (on_hit, on_miss) = self.split(live, pos);
+				let mut worst = self.solve(on_hit, hits | square_bit(pos));
+				if on_miss != 0 {
+					worst = worst.max(self.solve(on_miss, hits));
+				}
+				if worst + 1 < best.0 {
+					best = (worst + 1, Some(pos));
+				}
+			}
+		}
+
+		self.memo.insert((live, hits), best);
+		best.0
+	}
+
+	// Split the live candidates into those covering the square and the rest
+	fn split(&self, live: u64, pos: BoardPos) -> (u64, u64) {
+		let covering = (0..self.candidates.len()).filter(|&idx| self.candidates[idx] & square_bit(pos) != 0)
+			.fold(0, |acc, idx| acc | (1 << idx));
+		(live & covering, live & !covering)
+	}
+
+	// Rebuild the plan from the memo after solve
+	fn plan(&self, live: u64, hits: SquareMask) -> Option<Plan> {
+		let shot = self.memo[&(live, hits)].1?;
+		let (on_hit, on_miss) = self.split(live, shot);
+		Some(Plan {
+			shot,
+			on_hit: self.branch(on_hit, hits | square_bit(shot)),
+			on_miss: self.branch(on_miss, hits),
+		})
+	}
+
+	fn branch(&self, live: u64, hits: SquareMask) -> Branch {
+		if live == 0 {
+			return Branch::Impossible;
+		}
+
+		match self.plan(live, hits) {
+			Some(plan) => Branch::Continue(Box::new(plan)),
+			None => Branch::Sunk,
+		}
+	}
+}
+
+// Plan the finishing shots for the ship hit at the given square, or None if
+// the square isn't a hit (or too many placements remain to plan over)
+pub fn plan_finish(state: &GameState, target: BoardPos) -> Option<FinishPlan> {
+	let board = state.board();
+	if board.num_squares() > SquareMask::BITS as usize {
+		return None;
+	}
+	let label = match state.shots().iter().find(|&&(pos, _)| pos == target) {
+		Some(&(_, result)) if result != ShotResult::Miss => result.label(),
+		_ => return None,
+	};
+
+	let hits = state.shots().iter().filter(|&&(_, result)| result != ShotResult::Miss)
+		.fold(0, |acc, &(pos, _)| acc | square_bit(pos));
+	let mut candidates = Vec::new();
+	for &stype in board.fleet().iter().filter(|&&stype| label.is_none() || label == Some(stype)) {
+		for pos in state.positions(stype).iter() {
+			let ship = board.placement(stype, PlacementId::new(pos));
+			if ship.covers(target) {
+				candidates.push(ship.cells().fold(0, |acc, square| acc | square_bit(square)));
+			}
+		}
+	}
+	if candidates.is_empty() || candidates.len() > 64 {
+		return None;
+	}
+
+	let live = if candidates.len() == 64 { u64::MAX } else { (1 << candidates.len()) - 1 };
+	let mut planner = Planner { board: board.clone(), candidates, memo: HashMap::new() };
+	let worst_case = planner.solve(live, hits);
+	Some(FinishPlan { worst_case, plan: planner.plan(live, hits) })
+}
+
+// The most recent hit whose ship may not be sunk yet, to plan for by default
+pub fn default_target(state: &GameState) -> Option<BoardPos> {
+	state.shots().iter().rev().filter(|&&(_, result)| result != ShotResult::Miss)
+		.map(|&(pos, _)| pos)
+		.find(|&pos| plan_finish(state, pos).is_some_and(|finish| finish.worst_case > 0))
+}
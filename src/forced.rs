@@ -0,0 +1,318 @@
+// Forced wins late in a game: positions where some line of play sinks every
+// remaining ship within K shots whatever the layout, like "mate in K" in
+// chess. Once few enough layouts remain, they're listed exactly, and a
+// bounded game-tree search looks for the shot policy with the fewest shots in
+// the worst case over them: each shot splits the layouts by the result it
+// would be reported with, and each part must then be won in one shot fewer.
+// It's the exact solver's worst-case objective (see the solver module), but
+// started from the game so far rather than an empty board.
+//
+// A layout needs at least one shot per unshot ship square, so the search only
+// starts once no layout has more of those than K, and is cut off at a budget
+// of states. The layouts are only listed once the product of the ships'
+// placement counts, which bounds how many there are, is small enough for the
+// listing to be quick. Positions with shots awaiting their results, or where
+// a ship may still be relocated, have no forced line.
+//
+// Both the listing and the search stop once their token is cancelled, as if
+// there were no forced line.
+
+use super::{BoardPos, GameState, PlacementId, ShipType, ShotResult};
+use cancel::CancelToken;
+use rules::Rules;
+use std::collections::HashMap;
+use strategy::{GameResult, Strategy};
+
+// The most layouts a position may have for the search to run
+pub const MAX_LAYOUTS: usize = 2_000;
+
+// The largest product of the placement counts for which the layouts are listed
+const MAX_PLACEMENT_PRODUCT: u64 = 1_000_000;
+
+// The longest forced line looked for by default
+pub const DEFAULT_MAX_SHOTS: u32 = 5;
+
+// The most states the search will remember before giving up
+const MAX_STATES: usize = 200_000;
+
+// Squares as bits of a mask (boards up to 11x11 fit)
+type SquareMask = u128;
+
+fn square_bit(pos: BoardPos) -> SquareMask {
+	1 << pos.index()
+}
+
+// A forced win: every layout sinks within shots shots, starting with first
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct ForcedWin {
+	pub shots: u32,
+	pub first: BoardPos,
+
+	// How many layouts the line was proven against
+	pub layouts: usize,
+}
+
+// One layout still possible: each ship's type and squares
+struct Candidate {
+	ships: Vec<(ShipType, SquareMask)>,
+	occupied: SquareMask,
+}
+
+// The best known about a searched state
+#[derive(Clone,Copy)]
+enum Bound {
+	// The fewest shots that surely win, and the shot to start with
+	Exact(u32, Option<BoardPos>),
+
+	// Winning surely takes more shots than this
+	Above(u32),
+}
+
+struct Search<'a> {
+	rules: &'a Rules,
+	cancel: &'a CancelToken,
+	squares: Vec<BoardPos>,
+	candidates: Vec<Candidate>,
+	memo: HashMap<(Vec<usize>, SquareMask), Bound>,
+}
+
+// Look for a forced win of at most max_shots shots from the position, giving
+// up once the token is cancelled
+pub fn find(state: &GameState, rules: &Rules, max_shots: u32, cancel: &CancelToken) -> Option<ForcedWin> {
+	let board = state.board();
+	if max_shots == 0 || board.num_squares() > SquareMask::BITS as usize || !state.pending().is_empty() {
+		return None;
+	}
+	if rules.moving_ships && state.relocation().is_none() {
+		return None;
+	}
+	let product = state.placement_counts().iter().fold(1u64, |product, &(_, count)| product.saturating_mul(count as u64));
+	if product > MAX_PLACEMENT_PRODUCT {
+		return None;
+	}
+
+	let candidates = layouts(state, rules, cancel)?;
+	let fired = state.standing_shots().fold(0, |mask, &(pos, _)| mask | square_bit(pos));
+	let mut search = Search { rules, cancel, squares: board.squares().collect(), candidates, memo: HashMap::new() };
+	let live: Vec<usize> = (0..search.candidates.len()).filter(|&idx| search.candidates[idx].occupied & !fired != 0).collect();
+	if live.is_empty() {
+		return None;
+	}
+	match search.solve(&live, fired, max_shots)? {
+		(shots, Some(first)) => Some(ForcedWin { shots, first, layouts: live.len() }),
+		(_, None) => None,
+	}
+}
+
+// Every layout consistent with the moves so far, or None if there are more
+// than MAX_LAYOUTS or the token was cancelled
+fn layouts(state: &GameState, rules: &Rules, cancel: &CancelToken) -> Option<Vec<Candidate>> {
+	let board = state.board();
+	let fleet = board.fleet();
+	let positions: Vec<Vec<SquareMask>> = fleet.iter().map(|&stype| {
+		state.positions(stype).iter().map(|idx| board.placement(stype, PlacementId::new(idx)).cells().fold(0, |mask, square| mask | square_bit(square))).collect()
+	}).collect();
+	let required: Vec<SquareMask> = state.cover_requirements().iter().map(|group| group.iter().fold(0, |mask, &square| mask | square_bit(square))).collect();
+	let shots: Vec<(BoardPos, ShotResult)> = state.standing_shots().cloned().collect();
+
+	let mut listing = Listing { fleet, positions, required, shots, rules, cancel, chosen: Vec::with_capacity(fleet.len()), found: Vec::new() };
+	if !listing.extend(0) {
+		return None;
+	}
+	Some(listing.found)
+}
+
+// The search behind layouts
+struct Listing<'a> {
+	fleet: &'a [ShipType],
+
+	// The squares of each remaining placement, per ship type in fleet order
+	positions: Vec<Vec<SquareMask>>,
+
+	// The groups of squares some ship must cover (see GameState::cover_requirements)
+	required: Vec<SquareMask>,
+
+	shots: Vec<(BoardPos, ShotResult)>,
+	rules: &'a Rules,
+	cancel: &'a CancelToken,
+	chosen: Vec<(ShipType, SquareMask)>,
+	found: Vec<Candidate>,
+}
+
+impl<'a> Listing<'a> {
+	// Add every consistent layout extending the ships chosen so far, which
+	// occupy the given squares, returning false once there are too many or
+	// the token is cancelled
+	fn extend(&mut self, occupied: SquareMask) -> bool {
+		let next = self.chosen.len();
+		if next == self.fleet.len() {
+			if self.required.iter().all(|&group| group & occupied != 0) && consistent(&self.chosen, &self.shots, self.rules) {
+				self.found.push(Candidate { ships: self.chosen.clone(), occupied });
+			}
+			return self.found.len() <= MAX_LAYOUTS;
+		}
+		if self.cancel.is_cancelled() {
+			return false;
+		}
+
+		let stype = self.fleet[next];
+		for idx in 0..self.positions[next].len() {
+			let ship = self.positions[next][idx];
+			if ship & occupied != 0 {
+				continue;
+			}
+			self.chosen.push((stype, ship));
+			let more = self.extend(occupied | ship);
+			self.chosen.pop();
+			if !more {
+				return false;
+			}
+		}
+		true
+	}
+}
+
+// What a shot at the square reports against the ships, after the fired squares
+fn observe(ships: &[(ShipType, SquareMask)], fired: SquareMask, pos: BoardPos, rules: &Rules) -> ShotResult {
+	let bit = square_bit(pos);
+	let result = match ships.iter().find(|&&(_, ship)| ship & bit != 0) {
+		Some(&(stype, ship)) if ship & !(fired | bit) == 0 => ShotResult::Sunk(stype),
+		Some(&(stype, _)) => ShotResult::Hit(Some(stype)),
+		None => ShotResult::Miss,
+	};
+	rules.reported(result)
+}
+
+// Whether the ships would have given every result so far
+fn consistent(ships: &[(ShipType, SquareMask)], shots: &[(BoardPos, ShotResult)], rules: &Rules) -> bool {
+	let mut fired = 0;
+	shots.iter().all(|&(pos, result)| {
+		let same = observe(ships, fired, pos, rules) == result;
+		fired |= square_bit(pos);
+		same
+	})
+}
+
+impl<'a> Search<'a> {
+	// The fewest shots that surely sink the fleet in every live layout, and the
+	// shot to start with, if that's at most limit
+	fn solve(&mut self, live: &[usize], fired: SquareMask, limit: u32) -> Option<(u32, Option<BoardPos>)> {
+		if live.is_empty() {
+			return Some((0, None));
+		}
+		if self.cancel.is_cancelled() {
+			return None;
+		}
+		let key = (live.to_vec(), fired);
+		match self.memo.get(&key) {
+			Some(&Bound::Exact(shots, first)) => return if shots <= limit { Some((shots, first)) } else { None },
+			Some(&Bound::Above(known)) if known >= limit => return None,
+			_ => {},
+		}
+		if self.memo.len() >= MAX_STATES {
+			return None;
+		}
+
+		// Every layout needs a shot at each of its unshot squares
+		let lower = live.iter().map(|&idx| (self.candidates[idx].occupied & !fired).count_ones()).max().unwrap_or(0);
+		if lower > limit {
+			self.memo.insert(key, Bound::Above(lower - 1));
+			return None;
+		}
+
+		// Try the squares the most layouts occupy first
+		let mut squares: Vec<(BoardPos, usize)> = self.squares.iter().filter(|&&pos| fired & square_bit(pos) == 0).map(|&pos| {
+			(pos, live.iter().filter(|&&idx| self.candidates[idx].occupied & square_bit(pos) != 0).count())
+		}).filter(|&(_, occupying)| occupying > 0).collect();
+		squares.sort_by_key(|&(_, occupying)| std::cmp::Reverse(occupying));
+
+		let mut best: Option<(u32, Option<BoardPos>)> = None;
+		let mut budget = limit;
+		for (pos, _) in squares {
+			let after = fired | square_bit(pos);
+			let mut parts: Vec<(ShotResult, Vec<usize>)> = Vec::new();
+			for &idx in live {
+				let candidate = &self.candidates[idx];
+				if candidate.occupied & !after == 0 {
+					continue;
+				}
+				let result = observe(&candidate.ships, fired, pos, self.rules);
+				match parts.iter_mut().find(|(seen, _)| *seen == result) {
+					Some((_, part)) => part.push(idx),
+					None => parts.push((result, vec![idx])),
+				}
+			}
+
+			let mut worst = 1;
+			for (_, part) in &parts {
+				match self.solve(part, after, budget - 1) {
+					Some((shots, _)) => worst = worst.max(shots + 1),
+					None => {
+						worst = budget + 1;
+						break;
+					},
+				}
+			}
+			if worst <= budget {
+				best = Some((worst, Some(pos)));
+				if worst == lower {
+					break;
+				}
+				budget = worst - 1;
+			}
+		}
+
+		self.memo.insert(key, match best {
+			Some((shots, first)) => Bound::Exact(shots, first),
+			None => Bound::Above(limit),
+		});
+		best
+	}
+}
+
+// Plays the forced line whenever there is one within max_shots shots, and
+// the inner strategy's shot otherwise
+pub struct Forcing {
+	inner: Box<dyn Strategy>,
+	rules: Rules,
+	max_shots: u32,
+	latest: Option<ForcedWin>,
+}
+
+impl Forcing {
+	pub fn new(inner: Box<dyn Strategy>, rules: &Rules, max_shots: u32) -> Forcing {
+		Forcing { inner, rules: rules.clone(), max_shots, latest: None }
+	}
+}
+
+impl Strategy for Forcing {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		self.latest = find(state, &self.rules, self.max_shots, cancel);
+		match self.latest {
+			Some(win) => Some(win.first),
+			None => self.inner.choose_shot_cancellable(state, cancel),
+		}
+	}
+
+	fn forced_win(&self) -> Option<ForcedWin> {
+		self.latest
+	}
+
+	fn needs_heatmap(&self) -> bool {
+		self.inner.needs_heatmap()
+	}
+
+	fn on_game_start(&mut self, rules: &Rules) {
+		self.rules = rules.clone();
+		self.latest = None;
+		self.inner.on_game_start(rules);
+	}
+
+	fn on_opponent_shot(&mut self, shot: (BoardPos, ShotResult)) {
+		self.inner.on_opponent_shot(shot);
+	}
+
+	fn on_game_end(&mut self, result: &GameResult) {
+		self.inner.on_game_end(result);
+	}
+}
@@ -0,0 +1,157 @@
+// Forecasts of how the rest of a game goes for each ship still afloat: how
+// likely it is to be the next one sunk, and how many more shots sinking it
+// takes. They come from playing the game out with the greedy strategy
+// against layouts drawn from those consistent with the moves so far, so they
+// give some strategic context beyond the single recommended square.
+
+use super::{BoardPos, GameState, PlacementId, ShipType, ShotResult};
+use placement::{Constraints, Layout};
+use rng::Rng;
+use rules::Rules;
+use strategy::{Greedy, Strategy, TieBreak};
+use timing::{self, Phase};
+
+// How many games are played out by default
+pub const DEFAULT_SAMPLES: usize = 100;
+
+// How many draws sample_layout makes before giving up
+const MAX_ATTEMPTS: usize = 1_000_000;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct SinkForecast {
+	pub stype: ShipType,
+
+	// The probability that it's the next ship sunk
+	pub next: f64,
+
+	// The expected number of shots from now until it's sunk
+	pub shots: f64,
+}
+
+// Draw a layout uniformly from those consistent with the moves so far, or
+// None if none turned up. Each ship's position is drawn independently from
+// those remaining, starting over whenever the ships overlap (or touch, if
+// they may not) or leave an unlabeled hit (or a sweep that found something)
+// uncovered, so every consistent layout is equally likely.
+pub fn sample_layout(state: &GameState, rng: &mut Rng) -> Option<Layout> {
+	timing::time(Phase::Sampling, || draw_layout(state, rng))
+}
+
+fn draw_layout(state: &GameState, rng: &mut Rng) -> Option<Layout> {
+	let board = state.board();
+	let positions: Vec<Vec<usize>> = board.fleet().iter().map(|&stype| state.positions(stype).iter().collect()).collect();
+	if positions.iter().any(Vec::is_empty) {
+		return None;
+	}
+	let required = state.cover_requirements();
+	let moved = state.moved_positions();
+
+	'restart: for _ in 0..MAX_ATTEMPTS {
+		let mut occupied = vec![false; board.num_squares()];
+		let mut ships = Vec::with_capacity(board.fleet().len());
+		let mut movers = 0;
+		for ((&stype, stype_positions), moved) in board.fleet().iter().zip(positions.iter()).zip(moved.iter()) {
+			let pos = PlacementId::new(stype_positions[rng.gen_range(stype_positions.len())]);
+
+			// Only one ship can have moved onto the misses from before a relocation
+			if moved.contains(pos.index()) {
+				movers += 1;
+				if movers > 1 {
+					continue 'restart;
+				}
+			}
+			for square in board.placement(stype, pos).cells() {
+				if occupied[square.index()] {
+					continue 'restart;
+				}
+				occupied[square.index()] = true;
+			}
+			ships.push((stype, pos));
+		}
+		if required.iter().any(|group| !group.iter().any(|square| occupied[square.index()])) {
+			continue;
+		}
+
+		// The ships are already known not to overlap, so a ship only fails to
+		// fit by touching another
+		let mut layout = Layout::new(Constraints::new(board, state.allows_touching()));
+		for (stype, pos) in ships {
+			if layout.place(stype, board.ship_range(stype, pos)[0], board.is_horizontal(stype, pos)).is_err() {
+				continue 'restart;
+			}
+		}
+		return Some(layout);
+	}
+
+	None
+}
+
+// Play the game out against the layout, returning how many shots from now
+// each ship still afloat was sunk after (0 for one already fully hit)
+fn play_out(state: &GameState, layout: &Layout, rules: &Rules, afloat: &[ShipType], seed: u64) -> Vec<(ShipType, usize)> {
+	let mut state = state.clone();
+	let mut fired: Vec<BoardPos> = state.shots().iter().map(|&(pos, _)| pos).collect();
+
+	// Withheld results arrive first, as they were fired before anything else
+	for &pos in &state.pending().to_vec() {
+		state.apply_move((pos, rules.reported(layout.shot_result_after(pos, &fired))));
+		fired.push(pos);
+	}
+
+	let ship_squares: Vec<(ShipType, Vec<BoardPos>)> = layout.ships().iter().map(|&(stype, pos)| (stype, state.board().ship_range(stype, pos))).collect();
+	let is_sunk = |stype: ShipType, fired: &[BoardPos]| {
+		ship_squares.iter().any(|&(ship, ref squares)| ship == stype && squares.iter().all(|square| fired.contains(square)))
+	};
+
+	let mut sunk: Vec<(ShipType, usize)> = afloat.iter().filter(|&&stype| is_sunk(stype, &fired)).map(|&stype| (stype, 0)).collect();
+	let mut strategy = Greedy::new(TieBreak::Random(seed));
+	let mut shots = 0;
+	while sunk.len() < afloat.len() {
+		let pos = match strategy.choose_shot(&state) {
+			Some(pos) => pos,
+			None => break,
+		};
+		state.apply_move((pos, rules.reported(layout.shot_result_after(pos, &fired))));
+		fired.push(pos);
+		shots += 1;
+
+		for &stype in afloat {
+			if !sunk.iter().any(|&(ship, _)| ship == stype) && is_sunk(stype, &fired) {
+				sunk.push((stype, shots));
+			}
+		}
+	}
+
+	sunk
+}
+
+// Forecast each ship not yet reported sunk, in fleet order, over the
+// given number of played-out games. Fails if no layout fits the moves.
+pub fn forecast(state: &GameState, rules: &Rules, samples: usize, seed: u64) -> Result<Vec<SinkForecast>, String> {
+	let afloat: Vec<ShipType> = state.board().fleet().iter().cloned().filter(|&stype| {
+		!state.shots().iter().any(|&(_, result)| result == ShotResult::Sunk(stype))
+	}).collect();
+
+	let mut rng = Rng::new(seed);
+	let mut next = vec![0.0; afloat.len()];
+	let mut shots = vec![0.0; afloat.len()];
+	for _ in 0..samples {
+		let layout = sample_layout(state, &mut rng).ok_or("No layout is consistent with the moves so far")?;
+		let sunk = play_out(state, &layout, rules, &afloat, rng.next_u64());
+
+		// The next ship sunk is the first one, with ties (ships already fully
+		// hit) sharing the credit
+		let first = sunk.iter().map(|&(_, after)| after).min().unwrap_or(0);
+		let tied = sunk.iter().filter(|&&(_, after)| after == first).count();
+		for &(stype, after) in &sunk {
+			let idx = afloat.iter().position(|&ship| ship == stype).expect("A sunk ship wasn't afloat");
+			shots[idx] += after as f64;
+			if after == first {
+				next[idx] += 1.0 / tied as f64;
+			}
+		}
+	}
+
+	let samples = samples.max(1) as f64;
+	Ok(afloat.iter().enumerate().map(|(idx, &stype)| SinkForecast { stype, next: next[idx] / samples, shots: shots[idx] / samples }).collect())
+}
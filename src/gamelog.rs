@@ -0,0 +1,78 @@
+// A record of the moves made against one opponent board, in order
+
+use super::{encode_shiptype, format_move, parse_move, Board, BoardPos, ShotResult};
+use context::Context;
+use text;
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct GameLog {
+	// Name of the rules preset the game was played under
+	pub rules: String,
+
+	// The board the moves were made on
+	pub board: Board,
+
+	// Every move, in the order it was made
+	pub moves: Vec<(BoardPos, ShotResult)>,
+}
+
+impl GameLog {
+	pub fn new(rules: &str, board: &Board) -> GameLog {
+		GameLog { rules: rules.to_string(), board: board.clone(), moves: Vec::new() }
+	}
+
+	// Parse a log from the moves file format
+	pub fn from_moves_string(rules: &str, board: &Board, contents: &str) -> Result<GameLog, String> {
+		let mut log = GameLog::new(rules, board);
+		for (line_num, line) in text::lines(contents).enumerate().filter(|&(_, line)| !line.trim().is_empty()) {
+			log.moves.push(parse_move(board, line).map_err(|e| format!("line {}: {}", line_num + 1, e))?);
+		}
+
+		Ok(log)
+	}
+
+	// The same log in another context, e.g. to write it in other coordinates
+	pub fn with_context(&self, context: Context) -> GameLog {
+		let board = self.board.clone().with_context(context);
+		let moves = self.moves.iter().map(|&(pos, result)| (board.square_at_index(pos.index()).expect("the board is the same size"), result)).collect();
+		GameLog { rules: self.rules.clone(), board, moves }
+	}
+
+	// Serialize the log in the moves file format
+	pub fn to_moves_string(&self) -> String {
+		self.moves.iter().map(|&cur_move| format_move(cur_move) + "\n").collect()
+	}
+
+	// Serialize the log as a CSV shot log (see the import module), one
+	// "turn,coord,result,ship" row per move after a header row
+	pub fn to_csv_string(&self) -> String {
+		let mut out = String::from("turn,coord,result,ship\n");
+		for (turn, &(pos, result)) in self.moves.iter().enumerate() {
+			let (result, ship) = match result {
+				ShotResult::Miss => ("miss", None),
+				ShotResult::Hit(ship) => ("hit", ship),
+				ShotResult::Sunk(stype) => ("sunk", Some(stype)),
+			};
+			out += &format!("{},{},{},{}\n", turn + 1, pos, result, ship.map_or(String::new(), |stype| encode_shiptype(stype).to_string()));
+		}
+		out
+	}
+
+	// Serialize the board the log leaves as a grid dump (see the import module):
+	// '.' unshot, 'o' a miss, 'x' an unlabeled hit, the ship letter for a
+	// labeled hit, and the lowercase letter for a sinking hit. The order of the
+	// moves is lost, and a square shot more than once shows its last result.
+	pub fn to_grid_string(&self) -> String {
+		let mut cells = vec!['.'; self.board.num_squares()];
+		for &(pos, result) in &self.moves {
+			cells[pos.index()] = match result {
+				ShotResult::Miss => 'o',
+				ShotResult::Hit(None) => 'x',
+				ShotResult::Hit(Some(stype)) => encode_shiptype(stype),
+				ShotResult::Sunk(stype) => encode_shiptype(stype).to_ascii_lowercase(),
+			};
+		}
+
+		cells.chunks(self.board.size() as usize).map(|row| row.iter().collect::<String>() + "\n").collect()
+	}
+}
@@ -0,0 +1,115 @@
+// Random game transcripts for seeding fuzzers, benchmarks, and parser tests
+// with realistic data: a random layout, and the shots a hunt-and-target
+// player fires at it, with every result as the rules would report it. The
+// transcripts are consistent by construction, so each one is a game that
+// could have happened.
+
+use gamelog::GameLog;
+use placement::Layout;
+use rng::Rng;
+use rules::Rules;
+use schema;
+use sim;
+use strategy::ParityHunter;
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Transcript {
+	pub layout: Layout,
+	pub log: GameLog,
+}
+
+// How many shots each transcript has, drawn uniformly from min..=max; a game
+// that sinks the fleet sooner ends there
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Length {
+	pub min: usize,
+	pub max: usize,
+}
+
+impl Length {
+	// Parse a length from its command-line form, "<n>" or "<min>-<max>"
+	pub fn parse(desc: &str) -> Option<Length> {
+		let (min, max) = desc.split_once('-').unwrap_or((desc, desc));
+		let (min, max) = (min.parse().ok()?, max.parse().ok()?);
+		if min > max {
+			return None;
+		}
+
+		Some(Length { min, max })
+	}
+}
+
+// The formats a transcript can be written in
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Format {
+	// The placement file, a blank line, then the moves file
+	Moves,
+	Csv,
+	Grid,
+	Json,
+}
+
+impl Format {
+	pub fn parse(desc: &str) -> Option<Format> {
+		match desc {
+			"moves" => Some(Format::Moves),
+			"csv" => Some(Format::Csv),
+			"grid" => Some(Format::Grid),
+			"json" => Some(Format::Json),
+			_ => None,
+		}
+	}
+
+	pub fn extension(self) -> &'static str {
+		match self {
+			Format::Moves => "txt",
+			Format::Csv => "csv",
+			Format::Grid => "grid",
+			Format::Json => "json",
+		}
+	}
+}
+
+impl Transcript {
+	pub fn to_format_string(&self, format: Format) -> String {
+		match format {
+			Format::Moves => self.layout.to_file_string() + "\n" + &self.log.to_moves_string(),
+			Format::Csv => self.log.to_csv_string(),
+			Format::Grid => self.log.to_grid_string(),
+			Format::Json => schema::export_log(&self.log) + "\n",
+		}
+	}
+}
+
+// Generate one transcript under the given rules, playing the game to the end
+// if no length is given
+pub fn random_transcript(rng: &mut Rng, rules: &Rules, length: Option<Length>) -> Result<Transcript, String> {
+	let layout = Layout::random(rng, rules.constraints())
+		.ok_or_else(|| format!("No valid layout exists under the {} rules", rules.name))?;
+	let limit = length.map(|length| length.min + rng.gen_range(length.max - length.min + 1));
+
+	let mut log = GameLog::new(&rules.name, layout.board());
+	let moves = sim::play_game(&mut ParityHunter::new(rng.next_u64()), &layout, rules);
+	log.moves = moves.into_iter().take(limit.unwrap_or(usize::MAX)).collect();
+	Ok(Transcript { layout, log })
+}
+
+// Generate the given number of transcripts from a seed
+pub fn random_transcripts(count: usize, seed: u64, rules: &Rules, length: Option<Length>) -> Result<Vec<Transcript>, String> {
+	let mut rng = Rng::new(seed);
+	(0..count).map(|_| random_transcript(&mut rng, rules, length)).collect()
+}
+
+// The inputs the fuzz targets (see fuzz/) take for a transcript, by target
+// name: the moves file, each importable format behind its format byte, and the
+// placement file
+pub fn fuzz_inputs(transcript: &Transcript) -> Vec<(&'static str, Vec<u8>)> {
+	let mut inputs = vec![("moves", transcript.log.to_moves_string().into_bytes())];
+	for &(byte, format) in &[(0, Format::Csv), (1, Format::Grid), (2, Format::Json)] {
+		let mut input = vec![byte];
+		input.extend(transcript.to_format_string(format).into_bytes());
+		inputs.push(("transcript", input));
+	}
+	inputs.push(("placement", transcript.layout.to_file_string().into_bytes()));
+	inputs
+}
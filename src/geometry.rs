@@ -0,0 +1,201 @@
+// Ship geometry: how a ship's position ID maps to the squares it occupies, for
+// any board size. Board's methods in board.rs (ship_range, num_positions, ...)
+// apply these to a board and its fleet.
+//
+// A ship of size s on an n x n board has 2 * (n - s + 1) * n position IDs:
+//  - the first half are horizontal, numbered row-major by the ship's leftmost
+//    square over the n - s + 1 columns it can start in, so horizontal ID p
+//    starts at row p / (n - s + 1), column p % (n - s + 1);
+//  - the second half are vertical, numbered by the board index (row * n + col)
+//    of the ship's top square, whose row is at most n - s.
+// Squares here are (row, column) pairs, counted from 0.
+
+// The largest board size the checks cover: the largest a BoardPos can address.
+// A size-2 ship on it has 420 position IDs.
+pub const MAX_CHECKED_BOARD_SIZE: u8 = 15;
+
+// A position ID. Boards past 11 x 11 have more than 256 positions for short
+// ships, so these are wider than the squares' u8s.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub struct PlacementId(u16);
+
+impl PlacementId {
+	// The position ID with the given number, which must fit in 16 bits
+	pub fn new(index: usize) -> PlacementId {
+		assert!(index <= u16::MAX as usize, "Position ID {} is out of range", index);
+		PlacementId(index as u16)
+	}
+
+	// The ID's number, e.g. for indexing a PlacementSet
+	pub fn index(self) -> usize {
+		self.0 as usize
+	}
+}
+
+impl std::fmt::Display for PlacementId {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+// Check that a ship of the given size fits on the board at all
+pub fn check_fits(board_size: u8, ship_size: u8) -> Result<(), String> {
+	if ship_size == 0 {
+		return Err(String::from("a ship must be at least one square long"));
+	}
+	if ship_size > board_size {
+		return Err(format!("a size-{} ship can't fit on a {}x{} board", ship_size, board_size, board_size));
+	}
+
+	Ok(())
+}
+
+// The number of columns a horizontal ship of the given size can start in, which
+// is also the number of rows a vertical one can start in: 0 if it doesn't fit
+pub fn reduced_poscount(board_size: u8, ship_size: u8) -> u8 {
+	if ship_size > board_size {
+		return 0;
+	}
+
+	board_size - ship_size + 1
+}
+
+// The number of position IDs for a ship of the given size
+pub fn num_positions(board_size: u8, ship_size: u8) -> usize {
+	2 * reduced_poscount(board_size, ship_size) as usize * board_size as usize
+}
+
+// Every position ID for a ship of the given size, in order
+pub fn positions(board_size: u8, ship_size: u8) -> impl Iterator<Item = PlacementId> {
+	(0..num_positions(board_size, ship_size)).map(PlacementId::new)
+}
+
+// Whether the position ID is one of the horizontal ones
+pub fn is_horizontal(board_size: u8, ship_size: u8, pos: PlacementId) -> bool {
+	pos.index() < num_positions(board_size, ship_size) / 2
+}
+
+// The square of the ship's top-left end at the given position ID
+pub fn ship_start(board_size: u8, ship_size: u8, pos: PlacementId) -> (u8, u8) {
+	if is_horizontal(board_size, ship_size, pos) {
+		let reduced = reduced_poscount(board_size, ship_size) as usize;
+		((pos.index() / reduced) as u8, (pos.index() % reduced) as u8)
+	} else {
+		let idx = pos.index() - num_positions(board_size, ship_size) / 2;
+		((idx / board_size as usize) as u8, (idx % board_size as usize) as u8)
+	}
+}
+
+// The squares occupied by a ship at the given position ID, starting from its
+// top-left end
+pub fn ship_squares(board_size: u8, ship_size: u8, pos: PlacementId) -> Vec<(u8, u8)> {
+	let (row, col) = ship_start(board_size, ship_size, pos);
+	if is_horizontal(board_size, ship_size, pos) {
+		(0..ship_size).map(|v| (row, col + v)).collect()
+	} else {
+		(0..ship_size).map(|v| (row + v, col)).collect()
+	}
+}
+
+// The position ID of a ship whose top-left end is at (row, col), or None if the
+// ship would run off the board
+pub fn position_id(board_size: u8, ship_size: u8, row: u8, col: u8, horizontal: bool) -> Option<PlacementId> {
+	let reduced = reduced_poscount(board_size, ship_size);
+	if row >= board_size || col >= board_size {
+		return None;
+	}
+
+	if horizontal {
+		if col >= reduced {
+			return None;
+		}
+
+		Some(PlacementId::new(row as usize * reduced as usize + col as usize))
+	} else {
+		if row >= reduced {
+			return None;
+		}
+
+		Some(PlacementId::new(num_positions(board_size, ship_size) / 2 + row as usize * board_size as usize + col as usize))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Exhaustively check the encoding for one ship size: every position ID must be
+	// a straight run of squares on the board that doesn't wrap across a row or
+	// column boundary, the IDs must be distinct and cover every such run, and
+	// position_id must invert ship_squares (and reject every start that would run
+	// off the board). Returns the number of positions checked.
+	fn check(board_size: u8, ship_size: u8) -> Result<usize, String> {
+		let describe = |pos: PlacementId| format!("{}x{} board, size {} ship, position {}", board_size, board_size, ship_size, pos);
+		let mut seen: Vec<Vec<(u8, u8)>> = Vec::new();
+
+		for pos in positions(board_size, ship_size) {
+			let squares = ship_squares(board_size, ship_size, pos);
+			let horizontal = is_horizontal(board_size, ship_size, pos);
+			if squares.len() != ship_size as usize {
+				return Err(format!("{}: covers {} squares", describe(pos), squares.len()));
+			}
+			if let Some(&(row, col)) = squares.iter().find(|&&(row, col)| row >= board_size || col >= board_size) {
+				return Err(format!("{}: square ({}, {}) is off the board", describe(pos), row, col));
+			}
+
+			let (row, col) = squares[0];
+			let straight = squares.iter().enumerate().all(|(v, &square)| {
+				let v = v as u8;
+				square == if horizontal { (row, col + v) } else { (row + v, col) }
+			});
+			if !straight {
+				return Err(format!("{}: {:?} isn't a straight {} run", describe(pos), squares, if horizontal { "horizontal" } else { "vertical" }));
+			}
+			if position_id(board_size, ship_size, row, col, horizontal) != Some(pos) {
+				return Err(format!("{}: position_id of its start gives {:?}", describe(pos), position_id(board_size, ship_size, row, col, horizontal)));
+			}
+			if seen.contains(&squares) {
+				return Err(format!("{}: {:?} duplicates an earlier position", describe(pos), squares));
+			}
+
+			seen.push(squares);
+		}
+
+		// Every start that fits must have been covered, and every other start rejected
+		for row in 0..board_size {
+			for col in 0..board_size {
+				for &horizontal in &[true, false] {
+					let fits = if horizontal { col + ship_size <= board_size } else { row + ship_size <= board_size };
+					match position_id(board_size, ship_size, row, col, horizontal) {
+						Some(pos) if !fits => {
+							return Err(format!("{}: accepted for a start at ({}, {}) that runs off the board", describe(pos), row, col));
+						},
+						None if fits => {
+							return Err(format!("{}x{} board, size {} ship: no position starts at ({}, {}) {}", board_size, board_size, ship_size,
+							                   row, col, if horizontal { "horizontally" } else { "vertically" }));
+						},
+						_ => {},
+					}
+				}
+			}
+		}
+
+		Ok(seen.len())
+	}
+
+	// Check every ship size from 2 up to the board size, returning the total number
+	// of positions checked. (A size-1 ship's horizontal and vertical positions are
+	// the same squares, so the encoding doesn't suit it.)
+	fn check_board(board_size: u8) -> Result<usize, String> {
+		(2..=board_size).map(|ship_size| check(board_size, ship_size)).sum()
+	}
+
+	#[test]
+	fn every_board() {
+		for board_size in 2..=MAX_CHECKED_BOARD_SIZE {
+			if let Err(e) = check_board(board_size) {
+				panic!("{}", e);
+			}
+		}
+	}
+}
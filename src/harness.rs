@@ -0,0 +1,132 @@
+// An automated hidden-information test harness: the opponent follows a scripted
+// layout and lies about shot results with a given probability, so robustness
+// features (such as cheat detection) can be measured without hand-crafting
+// moves files.
+
+use super::{BoardPos, ShotResult};
+use cheat::CheatDetector;
+use placement::Layout;
+use rng::Rng;
+use rules::Rules;
+use strategy::{Greedy, Strategy, TieBreak};
+
+// An opponent that answers shots from a fixed layout, sometimes lying
+pub struct ScriptedOpponent {
+	layout: Layout,
+
+	// The probability that any given answer is a lie
+	lie_probability: f64,
+	rng: Rng,
+}
+
+impl ScriptedOpponent {
+	pub fn new(layout: Layout, lie_probability: f64, seed: u64) -> ScriptedOpponent {
+		ScriptedOpponent { layout, lie_probability, rng: Rng::new(seed) }
+	}
+
+	pub fn layout(&self) -> &Layout {
+		&self.layout
+	}
+
+	// Answer a shot, returning the true result and the claimed one. A lie
+	// turns a hit into a miss, or a miss into a hit on a random ship type.
+	pub fn answer(&mut self, pos: BoardPos) -> (ShotResult, ShotResult) {
+		let truth = self.layout.shot_result(pos);
+		if self.rng.next_f64() >= self.lie_probability {
+			return (truth, truth);
+		}
+
+		let lie = match truth {
+			ShotResult::Miss => {
+				let fleet = self.layout.board().fleet();
+				ShotResult::Hit(Some(fleet[self.rng.gen_range(fleet.len())]))
+			},
+			ShotResult::Hit(_) | ShotResult::Sunk(_) => ShotResult::Miss,
+		};
+		(truth, lie)
+	}
+}
+
+// What happened in one game against a scripted opponent
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct TrialReport {
+	// The number of shots fired
+	pub shots: usize,
+
+	// The (0-based) turns on which the opponent lied
+	pub lies: Vec<usize>,
+
+	// The turns the cheat detector flagged
+	pub flagged: Vec<usize>,
+
+	// The first turn after which no fleet configuration was consistent with
+	// the claimed results, if any
+	pub contradiction: Option<usize>,
+}
+
+impl TrialReport {
+	// Lies that were flagged
+	pub fn caught(&self) -> usize {
+		self.flagged.iter().filter(|turn| self.lies.contains(turn)).count()
+	}
+
+	// Truthful answers that were flagged
+	pub fn false_alarms(&self) -> usize {
+		self.flagged.len() - self.caught()
+	}
+}
+
+// Play the greedy strategy against the opponent until every ship square has
+// truly been hit (or every square has been shot), checking each claimed result
+// with the detector before applying it. Once the claims contradict each other
+// every result looks impossible, so the detector is no longer consulted.
+pub fn run_trial(opponent: &mut ScriptedOpponent, rules: &Rules, detector: &CheatDetector, seed: u64) -> TrialReport {
+	let mut attacker = Greedy::new(TieBreak::Random(seed));
+	let mut state = rules.new_game();
+	let ship_squares = opponent.layout().occupancy().iter().filter(|cell| cell.is_some()).count();
+	let mut hit_squares = Vec::new();
+	let mut report = TrialReport::default();
+
+	while hit_squares.len() < ship_squares && state.shots().len() < state.board().num_squares() {
+		let pos = match attacker.choose_shot(&state) {
+			Some(pos) => pos,
+			None => break,
+		};
+
+		let turn = state.shots().len();
+		let (truth, claimed) = opponent.answer(pos);
+		if truth != ShotResult::Miss {
+			hit_squares.push(pos);
+		}
+		if truth != claimed {
+			report.lies.push(turn);
+		}
+
+		let cur_move = (pos, rules.reported(claimed));
+		if report.contradiction.is_none() && detector.check(&state, cur_move).is_some() {
+			report.flagged.push(turn);
+		}
+		state.apply_move(cur_move);
+		if report.contradiction.is_none() && (state.is_contradictory() || state.heatmap().total == 0) {
+			report.contradiction = Some(turn);
+		}
+	}
+
+	report.shots = state.shots().len();
+	report
+}
+
+// Run the given number of trials against random layouts, each opponent lying
+// with the given probability
+pub fn run_trials(games: usize, seed: u64, rules: &Rules, lie_probability: f64, detector: &CheatDetector) -> Result<Vec<TrialReport>, String> {
+	let mut rng = Rng::new(seed);
+	let mut reports = Vec::with_capacity(games);
+	for _ in 0..games {
+		let layout = Layout::random(&mut rng, rules.constraints())
+			.ok_or_else(|| format!("No valid layout exists under the {} rules", rules.name))?;
+		let mut opponent = ScriptedOpponent::new(layout, lie_probability, rng.next_u64());
+		reports.push(run_trial(&mut opponent, rules, detector, rng.next_u64()));
+	}
+
+	Ok(reports)
+}
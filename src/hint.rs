@@ -0,0 +1,63 @@
+// Graded hints for human players, from a nudge towards part of the board up to
+// the shot itself, so the assistant can help without spoiling the game. Every
+// hint agrees with the recommended shot: the quadrant holds it, and it is one
+// of the candidates.
+
+use super::{BoardPos, GameState};
+
+// The strongest hint, which gives the shot away
+pub const MAX_HINT_STRENGTH: u8 = 3;
+
+// How many squares a candidates hint names
+const HINT_CANDIDATES: usize = 3;
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum Hint {
+	// Strength 1: the quadrant to look in. On boards of odd size, the
+	// quadrants share the middle row and column.
+	Quadrant { top: bool, left: bool },
+
+	// Strength 2: a few squares, one of them the recommended shot, in
+	// row-major order so the order gives nothing away
+	Candidates(Vec<BoardPos>),
+
+	// Strength 3: the recommended shot
+	Shot(BoardPos),
+}
+
+// Whether the square lies in the given quadrant
+fn in_quadrant(pos: BoardPos, top: bool, left: bool) -> bool {
+	let size = pos.board_size();
+	let half = size.div_ceil(2);
+	let row_ok = if top { pos.row() < half } else { pos.row() >= size - half };
+	let col_ok = if left { pos.col() < half } else { pos.col() >= size - half };
+	row_ok && col_ok
+}
+
+// The hint of the given strength (1 to MAX_HINT_STRENGTH) towards the
+// recommended shot. Ties between quadrants holding the shot go to the one
+// with the most total probability over its unshot squares.
+pub fn hint(state: &GameState, best: BoardPos, strength: u8) -> Hint {
+	let heatmap = state.heatmap();
+	match strength {
+		0 | 1 => {
+			let promise = |top: bool, left: bool| -> u64 {
+				state.board().squares().filter(|&pos| in_quadrant(pos, top, left) && !state.is_shot(pos)).map(|pos| heatmap.counts[pos.index()]).sum()
+			};
+			let (top, left) = vec![(true, true), (true, false), (false, true), (false, false)].into_iter()
+				.filter(|&(top, left)| in_quadrant(best, top, left))
+				.max_by_key(|&(top, left)| promise(top, left))
+				.expect("The shot lies in no quadrant");
+			Hint::Quadrant { top, left }
+		},
+		2 => {
+			let mut others: Vec<BoardPos> = state.board().squares().filter(|&pos| pos != best && !state.is_shot(pos)).collect();
+			others.sort_by_key(|pos| std::cmp::Reverse(heatmap.counts[pos.index()]));
+			let mut candidates: Vec<BoardPos> = others.into_iter().take(HINT_CANDIDATES - 1).collect();
+			candidates.push(best);
+			candidates.sort();
+			Hint::Candidates(candidates)
+		},
+		_ => Hint::Shot(best),
+	}
+}
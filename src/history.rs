@@ -0,0 +1,171 @@
+// A persistent history of how the AI's games progressed, stage by stage, and
+// an estimate of how many more shots a game in progress will take. The
+// interactive mode's statistics panel compares a game against the history at
+// the same number of shots fired.
+//
+// The history file starts with a "games <count>" line, followed by one line
+// per stage: "<shots fired> <games reaching it> <hits> <ships sunk> <shots to go>",
+// with the last three summed over the games that reached the stage unfinished.
+
+use super::{BoardPos, GameState, ShotResult};
+use durable;
+use std::path::Path;
+
+// The totals over the games that reached one stage
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+struct Totals {
+	games: u64,
+
+	// Summed over the games: the hits and ships sunk so far, and the shots to go
+	hits: u64,
+	sunk: u64,
+	to_go: u64,
+}
+
+// The AI's average progress at one stage of its games
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Stage {
+	// The number of games that reached the stage unfinished
+	pub games: u64,
+
+	// The hits and ships sunk so far
+	pub hits: f64,
+	pub sunk: f64,
+
+	// The shots the games went on to need
+	pub to_go: f64,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct History {
+	games: u64,
+
+	// Indexed by the number of shots fired
+	stages: Vec<Totals>,
+}
+
+impl History {
+	pub fn new() -> History {
+		History { games: 0, stages: Vec::new() }
+	}
+
+	pub fn games(&self) -> u64 {
+		self.games
+	}
+
+	// Record a finished game's shots, in order
+	pub fn record(&mut self, shots: &[(BoardPos, ShotResult)]) {
+		self.games += 1;
+		if self.stages.len() < shots.len() {
+			self.stages.resize(shots.len(), Totals::default());
+		}
+
+		let (mut hits, mut sunk) = (0, 0);
+		for (fired, &(_, result)) in shots.iter().enumerate() {
+			let totals = &mut self.stages[fired];
+			totals.games += 1;
+			totals.hits += hits;
+			totals.sunk += sunk;
+			totals.to_go += (shots.len() - fired) as u64;
+
+			match result {
+				ShotResult::Miss => {},
+				ShotResult::Hit(_) => hits += 1,
+				ShotResult::Sunk(_) => {
+					hits += 1;
+					sunk += 1;
+				},
+			}
+		}
+	}
+
+	// The average progress of the games that had fired the given number of
+	// shots without finishing, or None if none ever did
+	pub fn stage(&self, fired: usize) -> Option<Stage> {
+		let totals = self.stages.get(fired).filter(|totals| totals.games > 0)?;
+		let games = totals.games as f64;
+		Some(Stage { games: totals.games, hits: totals.hits as f64 / games, sunk: totals.sunk as f64 / games, to_go: totals.to_go as f64 / games })
+	}
+
+	pub fn to_file_string(&self) -> String {
+		let mut out = format!("games {}\n", self.games);
+		for (fired, totals) in self.stages.iter().enumerate() {
+			out += &format!("{} {} {} {} {}\n", fired, totals.games, totals.hits, totals.sunk, totals.to_go);
+		}
+		out
+	}
+
+	pub fn from_file_string(contents: &str) -> Result<History, String> {
+		let mut history = History::new();
+		let mut seen_games = false;
+		for (line_num, line) in contents.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let parts: Vec<&str> = line.split_whitespace().collect();
+			match parts.as_slice() {
+				["games", games] => {
+					history.games = games.parse().map_err(|_| format!("Line {}: invalid game count", line_num + 1))?;
+					seen_games = true;
+				},
+				[fired, games, hits, sunk, to_go] => {
+					let numbers = [fired, games, hits, sunk, to_go].iter().map(|n| n.parse::<u64>()).collect::<Result<Vec<u64>, _>>()
+						.map_err(|_| format!("Line {}: invalid counts", line_num + 1))?;
+					let fired = numbers[0] as usize;
+					if history.stages.len() <= fired {
+						history.stages.resize(fired + 1, Totals::default());
+					}
+					history.stages[fired] = Totals { games: numbers[1], hits: numbers[2], sunk: numbers[3], to_go: numbers[4] };
+				},
+				_ => return Err(format!("Line {}: expected \"<shots fired> <games> <hits> <sunk> <shots to go>\"", line_num + 1)),
+			}
+		}
+
+		if !seen_games {
+			return Err(String::from("Missing the games line"));
+		}
+		Ok(history)
+	}
+
+	// Load the history at path, or start a new one if the file doesn't exist
+	pub fn load(path: &str, allow_unsealed: bool) -> Result<History, String> {
+		match durable::read(Path::new(path), allow_unsealed) {
+			Ok(contents) => History::from_file_string(&contents).map_err(|e| format!("{}: {}", path, e)),
+			Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(History::new()),
+			Err(e) => Err(format!("Unable to read {}: {}", path, e)),
+		}
+	}
+
+	pub fn save(&self, path: &str) -> Result<(), String> {
+		durable::write(Path::new(path), &self.to_file_string()).map_err(|e| format!("Unable to write {}: {}", path, e))
+	}
+}
+
+impl Default for History {
+	fn default() -> History {
+		History::new()
+	}
+}
+
+// Estimate the number of shots still needed to sink the fleet: the expected
+// number of unhit ship squares (the sum of the unshot squares' probabilities),
+// divided by the fraction of the remaining shots expected to hit. That
+// fraction comes from the AI's games at the same stage if the history has
+// any, or otherwise is the best unshot square's probability, which makes the
+// estimate optimistic.
+pub fn estimate_remaining(state: &GameState, history: &History) -> f64 {
+	let heatmap = state.heatmap();
+	let unshot: Vec<f64> = state.board().squares().filter(|&pos| !state.is_shot(pos)).map(|pos| heatmap.probability(pos)).collect();
+	let unhit: f64 = unshot.iter().sum();
+	if unhit <= 0.0 {
+		return 0.0;
+	}
+
+	let hit_rate = match history.stage(state.shots().len()) {
+		Some(stage) if stage.to_go > 0.0 => (state.board().fleet_squares() as f64 - stage.hits) / stage.to_go,
+		_ => unshot.iter().cloned().fold(0.0, f64::max),
+	};
+	unhit / hit_rate
+}
@@ -0,0 +1,203 @@
+// Importers for transcript formats used by other Battleship AIs, normalizing
+// them into a GameLog.
+//
+// CSV shot logs have one shot per row, with columns for the coordinate, the
+// result ("hit", "miss", or "sunk"), and optionally the ship that was hit, in
+// any order (e.g. "turn,coord,result,ship"). A header row is skipped.
+//
+// Grid dumps have one row of cells per board row, optionally preceded by a row
+// label and with a header row of column numbers. Cells are '.', '~', or '_'
+// for unshot squares, 'o', 'O', '-', or 'M' for misses, 'x', 'X', '*', or 'H'
+// for hits on an unknown ship, the ship type letter for labeled hits, and the
+// lowercase letter for the hit that sank the ship. Grids do not record shot
+// order, so moves are imported in row-major order, with the sinking hits last
+// (after every other hit on their ships). GameLog::to_grid_string writes them.
+//
+// JSON documents in this crate's own interchange format (see schema.rs) can
+// also be imported.
+//
+// Neither CSV logs nor grids say what size of board they're for. infer_board
+// works it out from the coordinates a log reaches or the rows a grid has, so
+// a log from a bigger board isn't read as this one's.
+
+use super::{decode_shiptype, parse_square, Board, ShipType, ShotResult};
+use gamelog::GameLog;
+use geometry::MAX_CHECKED_BOARD_SIZE;
+use schema;
+use text;
+
+// The transcript formats that can be imported
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Format {
+	Csv,
+	Grid,
+	Json,
+}
+
+impl Format {
+	pub fn parse(desc: &str) -> Option<Format> {
+		match desc {
+			"csv" => Some(Format::Csv),
+			"grid" => Some(Format::Grid),
+			"json" => Some(Format::Json),
+			_ => None,
+		}
+	}
+}
+
+// Import a transcript in the given format, of a game on the given board
+pub fn import(format: Format, rules: &str, board: &Board, contents: &str) -> Result<GameLog, String> {
+	match format {
+		Format::Csv => import_csv(rules, board, contents),
+		Format::Grid => import_grid(rules, board, contents),
+		Format::Json => schema::import_log(board, contents.strip_prefix('\u{feff}').unwrap_or(contents)),
+	}
+}
+
+// The board a headerless transcript must be for, with the given board's
+// fleet, when that differs from the given board: the smallest one holding
+// every square a CSV log names, or one with as many rows as a grid has. None
+// if the given board fits (and for JSON, which records its board).
+pub fn infer_board(format: Format, board: &Board, contents: &str) -> Result<Option<Board>, String> {
+	let size = match format {
+		Format::Csv => {
+			let largest = Board::new(MAX_CHECKED_BOARD_SIZE, board.fleet())?.with_context(board.context().clone());
+			let extent = text::lines(contents).flat_map(|line| line.split(','))
+				.filter_map(|field| parse_square(&largest, &field.trim().to_uppercase()))
+				.map(|pos| pos.row().max(pos.col()) + 1).max().unwrap_or(0);
+			if extent <= board.size() {
+				return Ok(None);
+			}
+			extent
+		},
+		Format::Grid => {
+			let rows = text::lines(contents).filter(|line| {
+				let cells: Vec<char> = line.chars().filter(|c| !c.is_whitespace() && *c != '|').collect();
+				!cells.is_empty() && !cells.iter().all(|c| c.is_ascii_digit())
+			}).count();
+			if rows == board.size() as usize || rows == 0 {
+				return Ok(None);
+			}
+			if rows > MAX_CHECKED_BOARD_SIZE as usize {
+				return Err(format!("the grid has {} rows, more than a board can have", rows));
+			}
+			rows as u8
+		},
+		Format::Json => return Ok(None),
+	};
+
+	Board::new(size, board.fleet()).map(|inferred| Some(inferred.with_context(board.context().clone()))).map_err(|e| format!("the transcript looks like it's for a {}x{} board, but {}", size, size, e))
+}
+
+// Decode a ship from its name or type letter, ignoring case
+fn decode_ship_name(name: &str) -> Option<ShipType> {
+	let name = name.trim().to_ascii_uppercase();
+	match name.as_str() {
+		"P" | "PATROL" | "PATROL BOAT" => Some(ShipType::Patrol),
+		"D" | "DESTROYER" => Some(ShipType::Destroyer),
+		"S" | "SUBMARINE" => Some(ShipType::Submarine),
+		"B" | "BATTLESHIP" => Some(ShipType::Battleship),
+		"C" | "CARRIER" | "AIRCRAFT CARRIER" => Some(ShipType::Carrier),
+		_ => None,
+	}
+}
+
+// Check that a result only names ships in the board's fleet
+fn in_fleet(board: &Board, result: ShotResult) -> Result<ShotResult, String> {
+	match result.label() {
+		Some(stype) if board.ship_index(stype).is_none() => Err(format!("there is no {:?} in the fleet", stype)),
+		_ => Ok(result),
+	}
+}
+
+// Import a CSV shot log
+pub fn import_csv(rules: &str, board: &Board, contents: &str) -> Result<GameLog, String> {
+	let mut log = GameLog::new(rules, board);
+
+	for (line_num, line) in text::lines(contents).enumerate() {
+		let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+		if fields.iter().all(|field| field.is_empty()) {
+			continue;
+		}
+
+		let pos = fields.iter().filter_map(|field| parse_square(board, &field.to_uppercase())).next();
+		let ship = fields.iter().filter_map(|field| decode_ship_name(field)).next();
+		let result = fields.iter().filter_map(|field| match field.to_ascii_lowercase().as_str() {
+			"miss" => Some(ShotResult::Miss),
+			"hit" => Some(ShotResult::Hit(ship)),
+
+			// A sinking is only useful to the inference if the ship is named
+			"sunk" => Some(ship.map_or(ShotResult::Hit(None), ShotResult::Sunk)),
+			_ => None,
+		}).next();
+
+		match (pos, result) {
+			(Some(pos), Some(result)) => log.moves.push((pos, in_fleet(board, result).map_err(|e| format!("line {}: {}", line_num + 1, e))?)),
+			// Skip the header row
+			_ if line_num == 0 => {},
+			_ => return Err(format!("line {}: expected a coordinate and a hit/miss result", line_num + 1)),
+		}
+	}
+
+	Ok(log)
+}
+
+// Decode a single grid cell: None for unshot squares
+fn decode_cell(cell: char) -> Result<Option<ShotResult>, String> {
+	match cell {
+		'.' | '~' | '_' => Ok(None),
+		'o' | 'O' | '-' | 'M' => Ok(Some(ShotResult::Miss)),
+		'x' | 'X' | '*' | 'H' => Ok(Some(ShotResult::Hit(None))),
+		'P' | 'D' | 'S' | 'B' | 'C' => Ok(Some(ShotResult::Hit(Some(decode_shiptype(cell as u8))))),
+		'p' | 'd' | 's' | 'b' | 'c' => Ok(Some(ShotResult::Sunk(decode_shiptype(cell.to_ascii_uppercase() as u8)))),
+		_ => Err(format!("unknown cell '{}'", cell)),
+	}
+}
+
+// Import a grid dump
+pub fn import_grid(rules: &str, board: &Board, contents: &str) -> Result<GameLog, String> {
+	let mut log = GameLog::new(rules, board);
+	let size = board.size();
+	let mut sinking = Vec::new();
+	let mut row = 0;
+
+	for (line_num, line) in text::lines(contents).enumerate() {
+		let mut cells: Vec<char> = line.chars().filter(|c| !c.is_whitespace() && *c != '|').collect();
+
+		// Skip blank lines and column-number headers
+		if cells.is_empty() || cells.iter().all(|c| c.is_ascii_digit()) {
+			continue;
+		}
+
+		// Drop the row label, if present
+		if cells.len() == size as usize + 1 {
+			cells.remove(0);
+		}
+		if cells.len() != size as usize {
+			return Err(format!("line {}: expected {} cells", line_num + 1, size));
+		}
+		if row == size {
+			return Err(format!("line {}: too many rows", line_num + 1));
+		}
+
+		for (col, &cell) in cells.iter().enumerate() {
+			let result = decode_cell(cell).and_then(|result| result.map(|result| in_fleet(board, result)).transpose())
+				.map_err(|e| format!("line {}: {}", line_num + 1, e))?;
+			if let Some(result) = result {
+				let pos = board.square(row, col as u8).map_err(|e| format!("line {}: {}", line_num + 1, e))?;
+				match result {
+					ShotResult::Sunk(_) => sinking.push((pos, result)),
+					_ => log.moves.push((pos, result)),
+				}
+			}
+		}
+		row += 1;
+	}
+
+	if row != size {
+		return Err(format!("expected {} rows, found {}", size, row));
+	}
+
+	log.moves.extend(sinking);
+	Ok(log)
+}
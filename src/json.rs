@@ -0,0 +1,266 @@
+// A minimal JSON value type with a parser and serializer, enough for this
+// crate's interchange formats without pulling in an external dependency.
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Value>),
+
+	// Members are kept in insertion order so output is stable
+	Object(Vec<(String, Value)>),
+}
+
+impl Value {
+	// Look up a member of an object
+	pub fn get(&self, key: &str) -> Option<&Value> {
+		match *self {
+			Value::Object(ref members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match *self {
+			Value::String(ref s) => Some(s),
+			_ => None,
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match *self {
+			Value::Number(n) => Some(n),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&[Value]> {
+		match *self {
+			Value::Array(ref items) => Some(items),
+			_ => None,
+		}
+	}
+
+	// Serialize the value compactly
+	pub fn to_json(&self) -> String {
+		let mut out = String::new();
+		self.write(&mut out);
+		out
+	}
+
+	fn write(&self, out: &mut String) {
+		match *self {
+			Value::Null => out.push_str("null"),
+			Value::Bool(b) => out.push_str(if b { "true" } else { "false" }),
+			Value::Number(n) => {
+				if n.fract() == 0.0 && n.abs() < 1e15 {
+					out.push_str(&format!("{}", n as i64));
+				} else {
+					out.push_str(&format!("{}", n));
+				}
+			},
+			Value::String(ref s) => write_string(s, out),
+			Value::Array(ref items) => {
+				out.push('[');
+				for (idx, item) in items.iter().enumerate() {
+					if idx > 0 {
+						out.push(',');
+					}
+					item.write(out);
+				}
+				out.push(']');
+			},
+			Value::Object(ref members) => {
+				out.push('{');
+				for (idx, (key, value)) in members.iter().enumerate() {
+					if idx > 0 {
+						out.push(',');
+					}
+					write_string(key, out);
+					out.push(':');
+					value.write(out);
+				}
+				out.push('}');
+			},
+		}
+	}
+}
+
+fn write_string(s: &str, out: &mut String) {
+	out.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
+
+// Parse a JSON document
+pub fn parse(text: &str) -> Result<Value, String> {
+	let mut parser = Parser { chars: text.chars().collect(), idx: 0, depth: 0 };
+	let value = parser.value()?;
+	parser.skip_whitespace();
+	if parser.idx != parser.chars.len() {
+		return Err(parser.error("trailing characters"));
+	}
+
+	Ok(value)
+}
+
+// How deeply arrays and objects may nest, so malicious input can't overflow the stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+	chars: Vec<char>,
+	idx: usize,
+
+	// The number of arrays and objects currently open
+	depth: usize,
+}
+
+impl Parser {
+	fn error(&self, msg: &str) -> String {
+		format!("JSON error at character {}: {}", self.idx, msg)
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.idx < self.chars.len() && self.chars[self.idx].is_whitespace() {
+			self.idx += 1;
+		}
+	}
+
+	fn peek(&mut self) -> Option<char> {
+		self.skip_whitespace();
+		self.chars.get(self.idx).cloned()
+	}
+
+	fn expect(&mut self, c: char) -> Result<(), String> {
+		if self.peek() != Some(c) {
+			return Err(self.error(&format!("expected '{}'", c)));
+		}
+		self.idx += 1;
+		Ok(())
+	}
+
+	fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+		let end = self.idx + word.len();
+		if end > self.chars.len() || self.chars[self.idx..end].iter().cloned().ne(word.chars()) {
+			return Err(self.error("invalid literal"));
+		}
+		self.idx = end;
+		Ok(value)
+	}
+
+	fn value(&mut self) -> Result<Value, String> {
+		if self.depth >= MAX_DEPTH {
+			return Err(self.error("nested too deeply"));
+		}
+
+		self.depth += 1;
+		let value = self.unnested_value();
+		self.depth -= 1;
+		value
+	}
+
+	// Parse a value, with the depth already accounted for
+	fn unnested_value(&mut self) -> Result<Value, String> {
+		match self.peek() {
+			Some('n') => self.literal("null", Value::Null),
+			Some('t') => self.literal("true", Value::Bool(true)),
+			Some('f') => self.literal("false", Value::Bool(false)),
+			Some('"') => self.string().map(Value::String),
+			Some('[') => {
+				self.idx += 1;
+				let mut items = Vec::new();
+				if self.peek() == Some(']') {
+					self.idx += 1;
+					return Ok(Value::Array(items));
+				}
+				loop {
+					items.push(self.value()?);
+					match self.peek() {
+						Some(',') => self.idx += 1,
+						Some(']') => { self.idx += 1; return Ok(Value::Array(items)); },
+						_ => return Err(self.error("expected ',' or ']'")),
+					}
+				}
+			},
+			Some('{') => {
+				self.idx += 1;
+				let mut members = Vec::new();
+				if self.peek() == Some('}') {
+					self.idx += 1;
+					return Ok(Value::Object(members));
+				}
+				loop {
+					if self.peek() != Some('"') {
+						return Err(self.error("expected a member name"));
+					}
+					let key = self.string()?;
+					self.expect(':')?;
+					members.push((key, self.value()?));
+					match self.peek() {
+						Some(',') => self.idx += 1,
+						Some('}') => { self.idx += 1; return Ok(Value::Object(members)); },
+						_ => return Err(self.error("expected ',' or '}'")),
+					}
+				}
+			},
+			Some(c) if c == '-' || c.is_ascii_digit() => {
+				let start = self.idx;
+				while self.idx < self.chars.len() && (self.chars[self.idx].is_ascii_digit() || "+-.eE".contains(self.chars[self.idx])) {
+					self.idx += 1;
+				}
+				let text: String = self.chars[start..self.idx].iter().collect();
+				text.parse().map(Value::Number).map_err(|_| self.error("invalid number"))
+			},
+			_ => Err(self.error("expected a value")),
+		}
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.expect('"')?;
+		let mut out = String::new();
+		loop {
+			let c = *self.chars.get(self.idx).ok_or_else(|| self.error("unterminated string"))?;
+			self.idx += 1;
+			match c {
+				'"' => return Ok(out),
+				'\\' => {
+					let escape = *self.chars.get(self.idx).ok_or_else(|| self.error("unterminated string"))?;
+					self.idx += 1;
+					out.push(match escape {
+						'"' => '"',
+						'\\' => '\\',
+						'/' => '/',
+						'b' => '\u{8}',
+						'f' => '\u{c}',
+						'n' => '\n',
+						'r' => '\r',
+						't' => '\t',
+						'u' => {
+							let end = self.idx + 4;
+							if end > self.chars.len() {
+								return Err(self.error("truncated unicode escape"));
+							}
+							let hex: String = self.chars[self.idx..end].iter().collect();
+							self.idx = end;
+							u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32).ok_or_else(|| self.error("invalid unicode escape"))?
+						},
+						_ => return Err(self.error("invalid escape")),
+					});
+				},
+				c => out.push(c),
+			}
+		}
+	}
+}
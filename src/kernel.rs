@@ -0,0 +1,235 @@
+// Batched consistency checks for sampled layouts, the part of sampling that
+// runs the same few operations on every candidate and so suits a GPU. Each
+// candidate layout draws every ship independently from its remaining
+// placements; a kernel then decides, for a whole batch of candidates at once,
+// which are consistent: no two ships overlap (or touch, when they may not),
+// and every group of squares some ship must cover (see
+// GameState::cover_requirements) is covered. The sampler itself drops the
+// candidates with more than one ship where only a relocated ship can be (see
+// GameState::moved_positions). The consistent candidates are
+// exactly those forecast::sample_layout would have kept, so sampling through a
+// batch draws from the same distribution.
+//
+// The squares are bitmasks in flat buffers, ready to copy to a device. A device
+// backend (one built on wgpu, say) implements Kernel and is installed with
+// set_device; this build ships none, as it has no dependencies to build one on.
+// Without a device, or for a batch the device fails on, the CPU kernel checks
+// the batch, splitting it between threads.
+
+use super::{surroundings, GameState, PlacementId, ShipType};
+use rng::Rng;
+use std::sync::OnceLock;
+use std::thread;
+
+// How many candidates a batch holds
+pub const BATCH_SIZE: usize = 1024;
+
+// How many candidates in a row may be inconsistent before the sampler gives
+// up, as forecast::sample_layout does
+const MAX_CANDIDATES: usize = 1_000_000;
+
+// Below this many candidates per thread, the CPU kernel stays on one thread
+const MIN_PER_THREAD: usize = 256;
+
+// Candidate layouts and the constraints they're checked against. Every mask
+// is words 64-bit words long, square i being bit i % 64 of word i / 64.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Batch {
+	pub words: usize,
+
+	// The ships in each candidate
+	pub ships: usize,
+
+	// The squares of each ship of each candidate, candidate after candidate
+	pub squares: Vec<u64>,
+
+	// Laid out like squares: the squares no other ship of the candidate may
+	// occupy, which are the ship's own and, when ships may not touch, those
+	// around them
+	pub exclusion: Vec<u64>,
+
+	// The groups of squares some ship must cover, the same for every candidate
+	pub required: Vec<u64>,
+}
+
+impl Batch {
+	// The number of candidates
+	pub fn len(&self) -> usize {
+		self.squares.len() / (self.words * self.ships).max(1)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	// Whether the given candidate is consistent
+	pub fn check(&self, candidate: usize) -> bool {
+		let (words, stride) = (self.words, self.words * self.ships);
+		let squares = &self.squares[candidate * stride..(candidate + 1) * stride];
+		let exclusion = &self.exclusion[candidate * stride..(candidate + 1) * stride];
+		let intersects = |a: &[u64], b: &[u64]| a.iter().zip(b).any(|(&a, &b)| a & b != 0);
+
+		for first in 0..self.ships {
+			for second in first + 1..self.ships {
+				if intersects(&exclusion[first * words..(first + 1) * words], &squares[second * words..(second + 1) * words]) {
+					return false;
+				}
+			}
+		}
+
+		let mut occupied = vec![0; words];
+		for ship in squares.chunks(words) {
+			for (word, &bits) in occupied.iter_mut().zip(ship) {
+				*word |= bits;
+			}
+		}
+		self.required.chunks(words).all(|group| intersects(group, &occupied))
+	}
+}
+
+// Checks batches of candidates, returning whether each is consistent
+pub trait Kernel: Send + Sync {
+	// The name reported for the backend
+	fn name(&self) -> &str;
+
+	fn consistent(&self, batch: &Batch) -> Result<Vec<bool>, String>;
+}
+
+// The fallback: checks the batch on the CPU, in chunks on as many threads as
+// the machine has
+pub struct CpuKernel;
+
+impl Kernel for CpuKernel {
+	fn name(&self) -> &str {
+		"cpu"
+	}
+
+	fn consistent(&self, batch: &Batch) -> Result<Vec<bool>, String> {
+		let len = batch.len();
+		let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(len / MIN_PER_THREAD).max(1);
+		if threads == 1 {
+			return Ok((0..len).map(|candidate| batch.check(candidate)).collect());
+		}
+
+		let chunk = len.div_ceil(threads);
+		let mut results = vec![false; len];
+		thread::scope(|scope| {
+			for (idx, part) in results.chunks_mut(chunk).enumerate() {
+				scope.spawn(move || {
+					for (offset, result) in part.iter_mut().enumerate() {
+						*result = batch.check(idx * chunk + offset);
+					}
+				});
+			}
+		});
+		Ok(results)
+	}
+}
+
+static DEVICE: OnceLock<Box<dyn Kernel>> = OnceLock::new();
+
+// Install a device backend for every later batch. Only one may be installed.
+pub fn set_device(kernel: Box<dyn Kernel>) -> Result<(), String> {
+	DEVICE.set(kernel).map_err(|kernel| format!("A device backend ({}) is already installed", kernel.name()))
+}
+
+// The name of the backend batches run on
+pub fn backend_name() -> &'static str {
+	DEVICE.get().map_or("cpu", |kernel| kernel.name())
+}
+
+// Check a batch on the device, or on the CPU if there's none or it fails
+pub fn consistent(batch: &Batch) -> Vec<bool> {
+	if let Some(Ok(results)) = DEVICE.get().map(|kernel| kernel.consistent(batch)) {
+		if results.len() == batch.len() {
+			return results;
+		}
+	}
+	CpuKernel.consistent(batch).unwrap_or_default()
+}
+
+// The masks of one placement of a ship
+struct Choice {
+	id: PlacementId,
+	squares: Vec<u64>,
+	exclusion: Vec<u64>,
+
+	// Whether only the ship that was relocated can be there
+	moved: bool,
+}
+
+// Draws consistent layouts a batch of candidates at a time
+pub struct Sampler {
+	fleet: Vec<ShipType>,
+	words: usize,
+	choices: Vec<Vec<Choice>>,
+	required: Vec<u64>,
+
+	// Consistent layouts drawn but not yet handed out, last first
+	ready: Vec<Vec<(ShipType, PlacementId)>>,
+}
+
+impl Sampler {
+	// None if some ship has no placement left
+	pub fn new(state: &GameState) -> Option<Sampler> {
+		let board = state.board();
+		let words = board.num_squares().div_ceil(64);
+		let mask = |squares: &mut dyn Iterator<Item = usize>| {
+			let mut mask = vec![0; words];
+			for square in squares {
+				mask[square / 64] |= 1 << (square % 64);
+			}
+			mask
+		};
+
+		let moved = state.moved_positions();
+		let choices: Vec<Vec<Choice>> = board.fleet().iter().zip(moved.iter()).map(|(&stype, moved)| state.positions(stype).iter().map(|id| {
+			let id = PlacementId::new(id);
+			let placement = board.placement(stype, id);
+			let exclusion = if state.allows_touching() {
+				mask(&mut placement.cells().map(|pos| pos.index()))
+			} else {
+				mask(&mut placement.cells().flat_map(surroundings).map(|pos| pos.index()))
+			};
+			Choice { id, squares: mask(&mut placement.cells().map(|pos| pos.index())), exclusion, moved: moved.contains(id.index()) }
+		}).collect()).collect();
+		if choices.iter().any(Vec::is_empty) {
+			return None;
+		}
+
+		let required = state.cover_requirements().iter().flat_map(|group| mask(&mut group.iter().map(|pos| pos.index()))).collect();
+		Some(Sampler { fleet: board.fleet().to_vec(), words, choices, required, ready: Vec::new() })
+	}
+
+	// The next consistent layout, or None once MAX_CANDIDATES candidates in a
+	// row have been inconsistent
+	pub fn next(&mut self, rng: &mut Rng) -> Option<Vec<(ShipType, PlacementId)>> {
+		let mut drawn = 0;
+		while self.ready.is_empty() {
+			if drawn >= MAX_CANDIDATES {
+				return None;
+			}
+			self.fill(rng);
+			drawn += BATCH_SIZE;
+		}
+		self.ready.pop()
+	}
+
+	// Draw and check a batch, keeping the consistent candidates in the order drawn
+	fn fill(&mut self, rng: &mut Rng) {
+		let mut batch = Batch { words: self.words, ships: self.fleet.len(), required: self.required.clone(), ..Batch::default() };
+		let mut candidates = Vec::with_capacity(BATCH_SIZE);
+		for _ in 0..BATCH_SIZE {
+			let ships: Vec<&Choice> = self.choices.iter().map(|choices| &choices[rng.gen_range(choices.len())]).collect();
+			for choice in &ships {
+				batch.squares.extend_from_slice(&choice.squares);
+				batch.exclusion.extend_from_slice(&choice.exclusion);
+			}
+			let one_mover = ships.iter().filter(|choice| choice.moved).count() <= 1;
+			candidates.push((self.fleet.iter().zip(ships).map(|(&stype, choice)| (stype, choice.id)).collect(), one_mover));
+		}
+
+		let results = consistent(&batch);
+		self.ready = candidates.into_iter().zip(results).filter(|&((_, one_mover), ok)| ok && one_mover).map(|((ships, _), _)| ships).rev().collect();
+	}
+}
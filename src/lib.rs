@@ -0,0 +1,1488 @@
+// Temporary warning bypass to let me develop the building blocks in peace
+#![allow(dead_code)]
+#![allow(unused_variables)]
+
+pub mod adapt;
+pub mod analysis;
+pub mod arena;
+pub mod attackers;
+pub mod audit;
+pub mod belief;
+pub mod binlog;
+pub mod bitset;
+pub mod board;
+pub mod budget;
+pub mod cache;
+pub mod campaign;
+pub mod cancel;
+pub mod cheat;
+pub mod clusters;
+pub mod combine;
+pub mod commit;
+pub mod compare;
+pub mod completions;
+pub mod context;
+pub mod convert;
+pub mod coords;
+pub mod diagnostic;
+#[cfg(feature = "network")]
+pub mod distribute;
+pub mod durable;
+pub mod events;
+pub mod explain;
+pub mod finish;
+pub mod forecast;
+pub mod forced;
+pub mod gamelog;
+pub mod generate;
+pub mod geometry;
+pub mod harness;
+pub mod hint;
+pub mod history;
+pub mod import;
+pub mod json;
+#[cfg(feature = "batch-kernel")]
+pub mod kernel;
+pub mod opponent;
+pub mod memory;
+pub mod messages;
+#[cfg(feature = "montecarlo")]
+pub mod montecarlo;
+pub mod phase;
+pub mod placement;
+pub mod ponder;
+pub mod profile;
+pub mod progress;
+pub mod puzzle;
+pub mod radar;
+pub mod record;
+pub mod ratings;
+pub mod relocation;
+pub mod reference;
+#[cfg(feature = "network")]
+pub mod remote;
+pub mod rng;
+pub mod rules;
+pub mod save;
+pub mod schema;
+#[cfg(feature = "network")]
+pub mod server;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "selfplay")]
+pub mod selfplay;
+pub mod sim;
+pub mod solver;
+pub mod spoken;
+pub mod strategy;
+pub mod svg;
+pub mod team;
+pub mod text;
+pub mod timing;
+#[cfg(feature = "network")]
+pub mod transport;
+
+use bitset::{PlacementSet, SharedSet};
+pub use board::{Board, Placement};
+use cancel::CancelToken;
+use coords::Coordinates;
+pub use geometry::PlacementId;
+use memory::{Component, Guard, Tracked};
+use radar::Sweep;
+use record::ShotRecord;
+use std::sync::Arc;
+use timing::Phase;
+
+// This represents a board position.
+// The positions are numbered in a row major manner,
+// so position 0 is A1, position 9 is A10, and
+// position 99 is J10. Each position knows the size of
+// its board, and only a Board makes them, rejecting
+// anything off it, so a BoardPos is always valid.
+// This is different from the conventions for the different
+// ship types. A position also knows its board's coordinate
+// convention, to format itself in, though that isn't part
+// of which square it is (see the comparisons below).
+#[derive(Clone,Copy,Debug)]
+pub struct BoardPos {
+	index: u8,
+	size: u8,
+	coordinates: Coordinates,
+}
+
+impl PartialEq for BoardPos {
+	fn eq(&self, other: &BoardPos) -> bool {
+		(self.index, self.size) == (other.index, other.size)
+	}
+}
+
+impl Eq for BoardPos {}
+
+impl std::hash::Hash for BoardPos {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		(self.index, self.size).hash(state);
+	}
+}
+
+impl PartialOrd for BoardPos {
+	fn partial_cmp(&self, other: &BoardPos) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for BoardPos {
+	fn cmp(&self, other: &BoardPos) -> std::cmp::Ordering {
+		(self.index, self.size).cmp(&(other.index, other.size))
+	}
+}
+
+impl BoardPos {
+	// The position with the given row-major index on a board of the given
+	// size, which the index must be on
+	fn on_board(size: u8, index: u8, coordinates: Coordinates) -> BoardPos {
+		BoardPos { index, size, coordinates }
+	}
+
+	pub fn row(self) -> u8 {
+		self.index / self.size
+	}
+
+	pub fn col(self) -> u8 {
+		self.index % self.size
+	}
+
+	// The row-major index, for indexing per-square tables
+	pub fn index(self) -> usize {
+		self.index as usize
+	}
+
+	// The width and height of the board the position is on
+	pub fn board_size(self) -> u8 {
+		self.size
+	}
+
+	// The position the given number of rows down and columns right, if that
+	// is on the board
+	pub fn offset(self, drow: i32, dcol: i32) -> Option<BoardPos> {
+		let (row, col, size) = (self.row() as i32 + drow, self.col() as i32 + dcol, self.size as i32);
+		if row < 0 || col < 0 || row >= size || col >= size {
+			return None;
+		}
+
+		Some(BoardPos::on_board(self.size, (row * size + col) as u8, self.coordinates))
+	}
+}
+
+// Formats in the same notation as the moves file (e.g. "B3"), in the
+// coordinate convention of the position's board
+impl std::fmt::Display for BoardPos {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let coordinates = self.coordinates;
+		write!(f, "{}{}", coordinates.row_label(self.size, self.row()), coordinates.column_label(self.col()))
+	}
+}
+
+// Ship type
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ShipType {
+	Patrol,
+	Destroyer,
+	Submarine,
+	Battleship,
+	Carrier
+}
+
+// A list of all ship types, smallest first
+pub const NUM_SHIP_TYPES: usize = 5;
+pub const SHIP_TYPES: [ShipType; NUM_SHIP_TYPES] = [ShipType::Patrol, ShipType::Destroyer, ShipType::Submarine, ShipType::Battleship, ShipType::Carrier];
+
+// The result of a single shot
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ShotResult {
+	Miss,
+
+	// A hit, labeled with the ship type if the rules reveal it
+	Hit(Option<ShipType>),
+
+	// A hit that sank the named ship: every square of it has now been hit
+	Sunk(ShipType),
+}
+
+impl ShotResult {
+	// The ship type the result names, if any
+	pub fn label(self) -> Option<ShipType> {
+		match self {
+			ShotResult::Miss | ShotResult::Hit(None) => None,
+			ShotResult::Hit(Some(stype)) | ShotResult::Sunk(stype) => Some(stype),
+		}
+	}
+}
+
+// Decode a ship type from a character describing it
+pub fn decode_shiptype(desc: u8) -> ShipType {
+	parse_shiptype(desc as char).unwrap_or_else(|| panic!("Invalid ship type {}", desc))
+}
+
+// The ship type a character describes, if any
+pub fn parse_shiptype(desc: char) -> Option<ShipType> {
+	match desc {
+		'P' => Some(ShipType::Patrol),
+		'D' => Some(ShipType::Destroyer),
+		'S' => Some(ShipType::Submarine),
+		'B' => Some(ShipType::Battleship),
+		'C' => Some(ShipType::Carrier),
+		_ => None,
+	}
+}
+
+// Encode a ship type as the character used to describe it (inverse of decode_shiptype)
+pub fn encode_shiptype(stype: ShipType) -> char {
+	match stype {
+		ShipType::Patrol => 'P',
+		ShipType::Destroyer => 'D',
+		ShipType::Submarine => 'S',
+		ShipType::Battleship => 'B',
+		ShipType::Carrier => 'C',
+	}
+}
+
+// Compute the size of the given ship type
+pub const fn ship_size(shiptype: ShipType) -> u8 {
+	use ShipType::*;
+
+	match shiptype {
+		Patrol => 2,
+		Destroyer => 3,
+		Submarine => 3,
+		Battleship => 4,
+		Carrier => 5,
+	}
+}
+
+// The ways a ship type may be placed under the rules
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub enum Orientation {
+	#[default]
+	Any,
+	Horizontal,
+	Vertical,
+}
+
+impl Orientation {
+	pub fn allows(self, horizontal: bool) -> bool {
+		match self {
+			Orientation::Any => true,
+			Orientation::Horizontal => horizontal,
+			Orientation::Vertical => !horizontal,
+		}
+	}
+}
+
+// Check if the given ship placements overlap, or when ships may not touch,
+// come within a square of each other (including diagonally)
+fn calc_has_overlap(ship1: Placement, ship2: Placement, allow_touching: bool) -> bool {
+	if allow_touching {
+		ship1.cells().any(|p| ship2.covers(p))
+	} else {
+		ship1.cells().any(|p| surroundings(p).any(|q| ship2.covers(q)))
+	}
+}
+
+// The square and the (up to eight) squares around it
+fn surroundings(pos: BoardPos) -> impl Iterator<Item = BoardPos> {
+	(-1..=1).flat_map(move |drow| (-1..=1).filter_map(move |dcol| pos.offset(drow, dcol)))
+}
+
+// The overlap cache: for each pair of ship types (in fleet order) and each
+// position of the first, the set of positions of the second that overlap it
+// (or touch it, when ships may not touch)
+pub type OverlapCache = Vec<Vec<Vec<PlacementSet>>>;
+
+// Generate the overlap cache
+fn gen_overlap_cache(board: &Board, allow_touching: bool) -> OverlapCache {
+	// Go through each ship type combination and fill out the overlap vector
+	board.fleet().iter().map(|&stype1| {
+		board.fleet().iter().map(|&stype2| {
+			// Iterate through the first ship positions and push back sets of overlapping positions
+			board.placements(stype1).map(|ship1| {
+				let mut overlapping = PlacementSet::new_empty(board.num_positions(stype2));
+				for ship2 in board.placements(stype2) {
+					if calc_has_overlap(ship1, ship2, allow_touching) {
+						overlapping.insert(ship2.id.index());
+					}
+				}
+				overlapping
+			}).collect()
+		}).collect()
+	}).collect()
+}
+
+// The overlap cache for the board, unless it doesn't fit in the memory cap
+fn overlap_cache(board: &Board, allow_touching: bool) -> Option<Arc<Tracked<OverlapCache>>> {
+	let meter = &board.context().memory;
+	if meter.fits(overlap_cache_bytes(board)) {
+		Some(Arc::new(Tracked::new(meter, Component::OverlapCache, gen_overlap_cache(board, allow_touching), overlap_cache_bytes(board))))
+	} else {
+		None
+	}
+}
+
+// The cover masks: for each ship type and square, the set of that ship's positions covering the square
+pub type CoverMasks = Vec<Vec<PlacementSet>>;
+
+// The bytes the overlap cache takes
+fn overlap_cache_bytes(board: &Board) -> usize {
+	let per_type: usize = board.fleet().iter().map(|&stype2| std::mem::size_of::<Vec<PlacementSet>>() + PlacementSet::bytes_for(board.num_positions(stype2))).sum();
+	board.fleet().iter().map(|&stype1| std::mem::size_of::<Vec<Vec<PlacementSet>>>() + board.num_positions(stype1) * per_type).sum::<usize>()
+}
+
+// The bytes the cover masks take
+fn cover_masks_bytes(board: &Board) -> usize {
+	board.fleet().iter().map(|&stype| std::mem::size_of::<Vec<PlacementSet>>() + board.num_squares() * PlacementSet::bytes_for(board.num_positions(stype))).sum()
+}
+
+// The bytes one game state's sets of remaining positions take
+fn placements_bytes(board: &Board) -> usize {
+	board.fleet().iter().map(|&stype| PlacementSet::bytes_for(board.num_positions(stype))).sum()
+}
+
+// The bytes of the per-position counts in a heatmap computation
+fn counts_bytes(board: &Board) -> usize {
+	board.fleet().iter().map(|&stype| std::mem::size_of::<Vec<u64>>() + board.num_positions(stype) * std::mem::size_of::<u64>()).sum()
+}
+
+// Generate the cover masks
+fn gen_cover_masks(board: &Board) -> CoverMasks {
+	board.fleet().iter().map(|&stype| {
+		let mut masks = vec![PlacementSet::new_empty(board.num_positions(stype)); board.num_squares()];
+		for ship in board.placements(stype) {
+			for square in ship.cells() {
+				masks[square.index()].insert(ship.id.index());
+			}
+		}
+		masks
+	}).collect()
+}
+
+// Parse a square such as "B3" or "A10" in the board's coordinate convention,
+// returning None if it is malformed or off the board
+pub fn parse_square(board: &Board, desc: &str) -> Option<BoardPos> {
+	let coordinates = board.coordinates();
+	let mut chars = desc.chars();
+	let row = coordinates.row_of_label(board.size(), chars.next()?)?;
+	let col = coordinates.column_of_number(board.size(), chars.as_str().parse::<u32>().ok()?)?;
+
+	board.square(row, col).ok()
+}
+
+// Render a board as a grid with row letters and column numbers,
+// given the character to display in each square
+pub fn render_board(board: &Board, cells: &[char]) -> String {
+	let coordinates = board.coordinates();
+	let mut out = String::from(" ");
+	for col in 0..board.size() {
+		out += &format!(" {:>2}", coordinates.column_label(col));
+	}
+	out.push('\n');
+
+	for row in 0..board.size() {
+		out.push(coordinates.row_label(board.size(), row));
+		for col in 0..board.size() {
+			out += &format!("  {}", cells[board.square_at(row, col).index()]);
+		}
+		out.push('\n');
+	}
+
+	out
+}
+
+// Parse a single move line, such as "B3" (miss), "A10D" (hit on the destroyer),
+// "C2H" (hit on an unknown ship), or "D4S!" (hit that sank the submarine).
+// Whitespace anywhere in the line is ignored, so "C2 H" is "C2H".
+pub fn parse_move(board: &Board, line: &str) -> Result<(BoardPos, ShotResult), String> {
+	let line: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+	let line = line.as_str();
+	if let Some(hit) = line.strip_suffix('!') {
+		return match parse_move(board, hit)? {
+			(pos, ShotResult::Hit(Some(stype))) => Ok((pos, ShotResult::Sunk(stype))),
+			_ => Err(format!("Invalid move {}: a sunk ship must be named", line)),
+		};
+	}
+
+	// The square is followed by an optional result letter
+	let (square, result) = match line.chars().last() {
+		Some(c) if c.is_ascii_alphabetic() && line.len() > 1 => (&line[..line.len() - 1], match c {
+			'H' => ShotResult::Hit(None),
+			'P' | 'D' | 'S' | 'B' | 'C' => ShotResult::Hit(Some(decode_shiptype(c as u8))),
+			_ => return Err(format!("Invalid move {}: unknown result {}", line, c)),
+		}),
+		_ => (line, ShotResult::Miss),
+	};
+
+	if let Some(stype) = result.label().filter(|&stype| board.ship_index(stype).is_none()) {
+		return Err(format!("Invalid move {}: there is no {:?} in the fleet", line, stype));
+	}
+	let pos = parse_square(board, square).ok_or_else(|| format!("Invalid move {}: {} is not a square on the board", line, square))?;
+	Ok((pos, result))
+}
+
+// Format a move in the moves file format (the inverse of parse_move)
+pub fn format_move(move_val: (BoardPos, ShotResult)) -> String {
+	let mut out = move_val.0.to_string();
+	match move_val.1 {
+		ShotResult::Miss => {},
+		ShotResult::Hit(None) => out.push('H'),
+		ShotResult::Hit(Some(stype)) => out.push(encode_shiptype(stype)),
+		ShotResult::Sunk(stype) => {
+			out.push(encode_shiptype(stype));
+			out.push('!');
+		},
+	}
+
+	out
+}
+
+// Read in the moves list from the input file
+pub fn read_moves(board: &Board) -> Result<Vec<(BoardPos, ShotResult)>, String> {
+	read_moves_file(board, "moves.txt")
+}
+
+// Read in a moves list from the given file. The error says which file and,
+// for a malformed move, which line and what's wrong with it.
+pub fn read_moves_file(board: &Board, path: &str) -> Result<Vec<(BoardPos, ShotResult)>, String> {
+	// Read the whole file, in whatever encoding and line endings it was saved with
+	let contents = text::read(std::path::Path::new(path)).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+
+	// Generate the output vector by processing the file line-by-line, skipping blank lines
+	text::lines(&contents).enumerate().filter(|(_, line)| !line.trim().is_empty()).map(|(line_num, line)| {
+		parse_move(board, line).map_err(|e| format!("{} {}", path, diagnostic::diagnose_move(board, line_num + 1, line, &e)))
+	}).collect()
+}
+
+// Apply the effect of a miss on the position sets
+fn process_miss(pos_positions: &mut [SharedSet], pos: BoardPos, cover: &CoverMasks) {
+	for (stype_idx, plist) in pos_positions.iter_mut().enumerate() {
+		// Remove every position that overlaps the miss
+		plist.subtract(&cover[stype_idx][pos.index()]);
+	}
+}
+
+// Apply the effect of a hit on the ship type with the given fleet index
+fn process_hit(poslist: &mut SharedSet, stype_idx: usize, pos: BoardPos, cover: &CoverMasks) {
+	// Keep only the positions that overlap the hit
+	poslist.intersect_with(&cover[stype_idx][pos.index()]);
+}
+
+// Apply the effect of a known move result on the sets of possible positions
+fn apply_move(board: &Board, pos_positions: &mut [SharedSet], move_val: (BoardPos, ShotResult), cover: &CoverMasks) {
+	// We operate completely differently depending on whether it was a hit or miss
+	match move_val.1 {
+		ShotResult::Miss => {
+			// It was a miss. Remove BoardPos from all position sets
+			process_miss(pos_positions, move_val.0, cover);
+		},
+		ShotResult::Hit(Some(stype)) | ShotResult::Sunk(stype) => {
+			// It was a hit. Make sure that the relevant ship type
+			// overlaps the hit position. (A sunk ship is further confined
+			// to hit squares by GameState, which knows every hit.)
+			let stype_idx = board.ship_index(stype).expect("A hit ship isn't in the fleet");
+			process_hit(&mut pos_positions[stype_idx], stype_idx, move_val.0, cover);
+		},
+		ShotResult::Hit(None) => {
+			// An unlabeled hit only constrains combinations of ships,
+			// which is handled by GameState's coverage propagation
+		},
+	}
+}
+
+// Propagate the constraint that some ship must cover a square of every group
+// (see GameState::cover_requirements): if only one ship type can still cover
+// any square of a group, that ship must cover one. The unlabeled hits must
+// also be explainable by the remaining ships together (see the clusters
+// module). Repeats until no more positions are eliminated.
+fn propagate_coverage(pos_positions: &mut [SharedSet], required: &[Vec<BoardPos>], hits: &[BoardPos], cover: &CoverMasks) {
+	let mut changed = true;
+	while changed {
+		changed = false;
+
+		for group in required {
+			let mut coverers = (0..pos_positions.len()).filter(|&stype_idx| {
+				group.iter().any(|square| pos_positions[stype_idx].intersects(&cover[stype_idx][square.index()]))
+			});
+
+			let first = coverers.next();
+			if let (Some(stype_idx), None) = (first, coverers.next()) {
+				let plist = &mut pos_positions[stype_idx];
+				let old_len = plist.count();
+				let mut covering = PlacementSet::new_empty(plist.capacity());
+				for square in group {
+					covering.union_with(&cover[stype_idx][square.index()]);
+				}
+				plist.intersect_with(&covering);
+				changed |= plist.count() != old_len;
+			}
+		}
+
+		changed |= clusters::prune(pos_positions, hits, cover);
+	}
+}
+
+// The AI's knowledge about the opponent's board: the list of still-possible
+// positions for every ship type, plus the caches needed to reason about them.
+// All queries take &self, so the state can be shared behind an Arc<RwLock<_>>;
+// long computations can be cancelled (see CancelToken) or run on a clone
+// so the lock is not held while they run.
+pub struct GameState {
+	// The board the opponent's fleet is on
+	board: Board,
+
+	// The set of possible positions per ship type (in fleet order), each
+	// shared with clones until a move prunes it
+	pos_positions: Vec<SharedSet>,
+
+	// Counts pos_positions against the memory accounting
+	placements_guard: Guard,
+
+	// The ship position overlap cache (shared between clones), unless the
+	// memory cap left it out
+	olap_cache: Option<Arc<Tracked<OverlapCache>>>,
+
+	// The positions of each ship type covering each square (shared between clones)
+	cover_masks: Arc<Tracked<CoverMasks>>,
+
+	// Every move applied so far, in order
+	shots: Vec<(BoardPos, ShotResult)>,
+
+	// The record of each move in shots (see the record module)
+	records: Vec<ShotRecord>,
+
+	// Squares that have been shot but whose results haven't been revealed yet
+	pending: Vec<BoardPos>,
+
+	// Shots repeated at squares that already had a result, which cost a turn
+	// (under rules::Reshot::Turn) but tell the inference nothing
+	reshots: usize,
+
+	// Every radar sweep so far, in order
+	sweeps: Vec<Sweep>,
+
+	// How each ship type (in fleet order) may be oriented
+	orientations: Vec<Orientation>,
+
+	// Whether ships may touch each other (including diagonally)
+	allow_touching: bool,
+
+	// When the opponent relocated a ship, if they have (see apply_relocation)
+	relocation: Option<Relocation>,
+
+	// The heatmap before any moves, if it was precomputed
+	opening: Option<Heatmap>,
+
+	// The heatmap after the moves so far, if it was restored from a save file
+	latest: Option<Heatmap>,
+}
+
+// The point in the game at which the opponent relocated a ship: the number of
+// moves and of sweeps applied before it
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Relocation {
+	pub shots: usize,
+	pub sweeps: usize,
+}
+
+// Progress through a heatmap computation, which explores each remaining
+// position of the first ship type in turn
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct SearchProgress {
+	// The positions explored so far, out of how many
+	pub explored: usize,
+	pub branches: usize,
+
+	// The consistent configurations counted so far
+	pub configs: u64,
+}
+
+// Spread one ship type's per-position counts over the squares each position covers
+fn spread_counts(board: &Board, stype: ShipType, pos_counts: &[u64], counts: &mut [u64]) {
+	for (pos, &count) in pos_counts.iter().enumerate() {
+		if count == 0 {
+			continue;
+		}
+
+		for square in board.placement(stype, PlacementId::new(pos)).cells() {
+			counts[square.index()] += count;
+		}
+	}
+}
+
+// The scale a heatmap's per-square values are given on
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Normalization {
+	// The number of consistent configurations covering the square
+	Counts,
+
+	// The probability that the square is occupied
+	Probability,
+
+	// The square's share of the occupancy among unshot squares, so the unshot
+	// squares add up to 1 and shot ones are 0: where the rest of the fleet is
+	Unshot,
+
+	// The natural log of the odds that the square is occupied, with half a
+	// configuration added either way so certain squares stay finite
+	LogOdds,
+}
+
+impl Normalization {
+	// Parse a normalization from its command-line name: "counts",
+	// "probability", "unshot", or "log-odds"
+	pub fn parse(desc: &str) -> Option<Normalization> {
+		match desc {
+			"counts" => Some(Normalization::Counts),
+			"probability" => Some(Normalization::Probability),
+			"unshot" => Some(Normalization::Unshot),
+			"log-odds" => Some(Normalization::LogOdds),
+			_ => None,
+		}
+	}
+}
+
+// Per-square occupancy counts over all ship configurations consistent with the moves so far
+#[derive(Clone,Debug,PartialEq)]
+pub struct Heatmap {
+	// The number of consistent configurations in which each square is occupied
+	pub counts: Vec<u64>,
+
+	// The total number of consistent configurations
+	pub total: u64,
+}
+
+impl Heatmap {
+	// The probability that the given square is occupied
+	pub fn probability(&self, pos: BoardPos) -> f64 {
+		if self.total == 0 {
+			return 0.0;
+		}
+
+		self.counts[pos.index()] as f64 / self.total as f64
+	}
+
+	// Every square's value on the given scale, in row-major order. shots are
+	// the moves so far, which Unshot leaves out.
+	pub fn normalized(&self, board: &Board, normalization: Normalization, shots: &[(BoardPos, ShotResult)]) -> Vec<f64> {
+		match normalization {
+			Normalization::Counts => self.counts.iter().map(|&count| count as f64).collect(),
+			Normalization::Probability => board.squares().map(|pos| self.probability(pos)).collect(),
+			Normalization::Unshot => {
+				let unshot = |pos: BoardPos| !shots.iter().any(|&(shot, _)| shot == pos);
+				let sum: u64 = board.squares().filter(|&pos| unshot(pos)).map(|pos| self.counts[pos.index()]).sum();
+				board.squares().map(|pos| if unshot(pos) && sum > 0 { self.counts[pos.index()] as f64 / sum as f64 } else { 0.0 }).collect()
+			},
+			Normalization::LogOdds => self.counts.iter().map(|&count| {
+				((count as f64 + 0.5) / ((self.total - count) as f64 + 0.5)).ln()
+			}).collect(),
+		}
+	}
+
+	// Check the invariants every heatmap over the given moves on the board
+	// satisfies: each configuration covers exactly the fleet's squares, so the
+	// counts sum to that many times the total; no square is covered by more
+	// configurations than there are; and no configuration covers a miss
+	pub fn check_invariants(&self, board: &Board, shots: &[(BoardPos, ShotResult)]) -> Result<(), String> {
+		let sum: u64 = self.counts.iter().sum();
+		if sum != self.total * board.fleet_squares() {
+			return Err(format!("the counts sum to {}, not {} configurations times {} squares", sum, self.total, board.fleet_squares()));
+		}
+		if let Some(pos) = board.squares().find(|&pos| self.counts[pos.index()] > self.total) {
+			return Err(format!("{} has count {}, more than the {} configurations", pos, self.counts[pos.index()], self.total));
+		}
+		if let Some(&(pos, _)) = shots.iter().find(|&&(pos, result)| result == ShotResult::Miss && self.counts[pos.index()] != 0) {
+			return Err(format!("the miss at {} has count {}", pos, self.counts[pos.index()]));
+		}
+
+		Ok(())
+	}
+
+	// Render the heatmap, over the given board, as a grid of occupancy percentages
+	pub fn render(&self, board: &Board) -> String {
+		self.render_normalized(board, Normalization::Probability, &[])
+	}
+
+	// Render the heatmap as a grid of values on the given scale: counts as they
+	// are, probabilities and shares as percentages, and log-odds to a decimal
+	// place. shots are as for normalized.
+	pub fn render_normalized(&self, board: &Board, normalization: Normalization, shots: &[(BoardPos, ShotResult)]) -> String {
+		self.render_marked(board, normalization, shots, &[])
+	}
+
+	// Render like render_normalized, with a '!' after the value of each marked
+	// square (such as the kill shots, see GameState::kill_shots)
+	pub fn render_marked(&self, board: &Board, normalization: Normalization, shots: &[(BoardPos, ShotResult)], marked: &[BoardPos]) -> String {
+		let values = self.normalized(board, normalization, shots);
+		let mut cells: Vec<String> = values.iter().map(|&value| match normalization {
+			Normalization::Counts => format!("{}", value),
+			Normalization::Probability | Normalization::Unshot => format!("{:.0}", 100.0 * value),
+			Normalization::LogOdds => format!("{:.1}", value),
+		}).collect();
+		for &pos in marked {
+			cells[pos.index()].push('!');
+		}
+		let width = cells.iter().map(String::len).max().unwrap_or(0).max(3);
+
+		let coordinates = board.coordinates();
+		let mut out = String::from(" ");
+		for col in 0..board.size() {
+			out += &format!(" {:>width$}", coordinates.column_label(col), width = width);
+		}
+		out.push('\n');
+
+		for row in 0..board.size() {
+			out.push(coordinates.row_label(board.size(), row));
+			for col in 0..board.size() {
+				out += &format!(" {:>width$}", cells[board.square_at(row, col).index()], width = width);
+			}
+			out.push('\n');
+		}
+
+		out
+	}
+}
+
+impl GameState {
+	// Construct the state for a fresh game on the board, where every position
+	// is still possible
+	pub fn new(board: &Board) -> GameState {
+		let cover_masks = Arc::new(Tracked::new(&board.context().memory, Component::CoverMasks, gen_cover_masks(board), cover_masks_bytes(board)));
+		GameState {
+			board: board.clone(),
+			pos_positions: board.fleet().iter().map(|&stype| SharedSet::new(PlacementSet::new_full(board.num_positions(stype)))).collect(),
+			placements_guard: Guard::new(&board.context().memory, Component::Placements, placements_bytes(board)),
+			olap_cache: overlap_cache(board, true),
+			cover_masks,
+			shots: Vec::new(),
+			records: Vec::new(),
+			pending: Vec::new(),
+			reshots: 0,
+			sweeps: Vec::new(),
+			orientations: vec![Orientation::Any; board.fleet().len()],
+			allow_touching: true,
+			relocation: None,
+			opening: None,
+			latest: None,
+		}
+	}
+
+	// Forbid the ships from touching each other (including diagonally), as
+	// under rules with touching = no. Meant for a fresh game, before any
+	// heatmap is computed.
+	pub fn forbid_touching(&mut self) {
+		if self.allow_touching {
+			self.allow_touching = false;
+			self.olap_cache = overlap_cache(&self.board, false);
+			self.opening = None;
+			self.latest = None;
+		}
+	}
+
+	// Whether ships may touch each other
+	pub fn allows_touching(&self) -> bool {
+		self.allow_touching
+	}
+
+	// Load the opening heatmap from the on-disk cache, computing and saving it
+	// on the first run (unless that takes longer than cache::MAX_OPENING_TIME)
+	pub fn cache_opening(&mut self) {
+		self.opening = cache::opening_heatmap(self);
+	}
+
+	// Whether the opening heatmap is known, which after cache_opening means
+	// the board is small enough to count every layout of in good time
+	pub fn has_opening(&self) -> bool {
+		self.opening.is_some()
+	}
+
+	// Construct the state for a fresh game, loading the opening heatmap from the
+	// on-disk cache (computing and saving it on the first run)
+	pub fn new_cached(board: &Board) -> GameState {
+		GameState::new_cached_restricted(board, &vec![Orientation::Any; board.fleet().len()])
+	}
+
+	// Construct the state for a fresh game in which each ship type (in fleet
+	// order) may only be placed in the given orientation
+	pub fn new_restricted(board: &Board, orientations: &[Orientation]) -> GameState {
+		let mut state = GameState::new(board);
+		for (stype_idx, &orientation) in orientations.iter().enumerate() {
+			let stype = board.fleet()[stype_idx];
+			let forbidden: Vec<usize> = state.pos_positions[stype_idx].iter().filter(|&pos| !orientation.allows(board.placement(stype, PlacementId::new(pos)).is_horizontal())).collect();
+			for pos in forbidden {
+				state.pos_positions[stype_idx].remove(pos);
+			}
+		}
+		state.orientations = orientations.to_vec();
+
+		state
+	}
+
+	// Like new_restricted, with the opening heatmap loaded from the on-disk cache
+	pub fn new_cached_restricted(board: &Board, orientations: &[Orientation]) -> GameState {
+		let mut state = GameState::new_restricted(board, orientations);
+		state.cache_opening();
+		state
+	}
+
+	// The board the opponent's fleet is on
+	pub fn board(&self) -> &Board {
+		&self.board
+	}
+
+	// Apply the effect of a known move result. Results are applied once per
+	// square: a repeated result changes nothing, and a different one must go
+	// through a correction (see the events module) instead.
+	pub fn apply_move(&mut self, move_val: (BoardPos, ShotResult)) {
+		if self.standing_shots().any(|&(pos, _)| pos == move_val.0) {
+			return;
+		}
+
+		let before = timing::time(Phase::Pruning, || {
+			let before: Vec<usize> = self.pos_positions.iter().map(|plist| plist.count()).collect();
+			apply_move(&self.board, &mut self.pos_positions, move_val, &self.cover_masks);
+			self.shots.push(move_val);
+			self.latest = None;
+			self.pending.retain(|&pos| pos != move_val.0);
+
+			// A sunk ship lies entirely on squares hit so far
+			if let ShotResult::Sunk(stype) = move_val.1 {
+				let stype_idx = self.board.ship_index(stype).expect("A sunk ship isn't in the fleet");
+				let unhit: Vec<BoardPos> = self.board.squares().filter(|&square| !self.is_hit(square)).collect();
+				for square in unhit {
+					self.pos_positions[stype_idx].subtract(&self.cover_masks[stype_idx][square.index()]);
+				}
+			}
+
+			let (required, hits) = (self.cover_requirements(), self.unlabeled_hits());
+			propagate_coverage(&mut self.pos_positions, &required, &hits, &self.cover_masks);
+			before
+		});
+
+		// Record the move, with what the inference now knows about it
+		let (ship, inferred) = match move_val.1 {
+			ShotResult::Miss => (None, false),
+			ShotResult::Hit(Some(stype)) | ShotResult::Sunk(stype) => (Some(stype), false),
+			ShotResult::Hit(None) => (self.attribution(move_val.0), true),
+		};
+		let eliminated = self.board.fleet().iter().enumerate().map(|(stype_idx, &stype)| (stype, before[stype_idx] - self.pos_positions[stype_idx].count())).collect();
+		let turn = self.records.len() + 1;
+		self.records.push(ShotRecord { turn, pos: move_val.0, result: move_val.1, ship, inferred: inferred && ship.is_some(), eliminated });
+	}
+
+	// Apply the result of a radar sweep: a clear one rules out every position
+	// covering its area, and one that found something requires some ship to
+	// cover part of it
+	pub fn apply_sweep(&mut self, sweep: Sweep) {
+		timing::time(Phase::Pruning, || {
+			if !sweep.found {
+				for square in sweep.area(&self.board) {
+					process_miss(&mut self.pos_positions, square, &self.cover_masks);
+				}
+			}
+			self.sweeps.push(sweep);
+			self.latest = None;
+
+			let (required, hits) = (self.cover_requirements(), self.unlabeled_hits());
+			propagate_coverage(&mut self.pos_positions, &required, &hits, &self.cover_masks);
+		});
+	}
+
+	// Every radar sweep so far, in order
+	pub fn sweeps(&self) -> &[Sweep] {
+		&self.sweeps
+	}
+
+	// The groups of squares of which some ship must cover at least one: each
+	// unlabeled hit on its own, and the area of each sweep that found
+	// something
+	pub fn cover_requirements(&self) -> Vec<Vec<BoardPos>> {
+		// The ship a sweep found may have moved away since
+		let since = self.relocation.map_or(0, |relocation| relocation.sweeps);
+		let hits = self.unlabeled_hits().into_iter().map(|hit| vec![hit]);
+		hits.chain(self.sweeps[since..].iter().filter(|sweep| sweep.found).map(|sweep| sweep.area(&self.board))).collect()
+	}
+
+	// Apply the opponent relocating a ship, under moving-ships rules: once per
+	// game, a ship with no hits may move anywhere that's clear of the hits so
+	// far. Which ship moved isn't known, so each one that could have keeps its
+	// old positions and gains every new one it could have moved to. Only one
+	// ship moved, though, so no configuration has more than one ship on the
+	// misses from before the move (see moved_positions); those squares count
+	// as unshot again, and a found sweep from before no longer requires a ship.
+	pub fn apply_relocation(&mut self) {
+		if self.relocation.is_some() {
+			return;
+		}
+
+		timing::time(Phase::Pruning, || {
+			let hits: Vec<BoardPos> = self.board.squares().filter(|&square| self.is_hit(square)).collect();
+			for (stype_idx, &stype) in self.board.fleet().iter().enumerate() {
+				let hit_before = self.shots.iter().any(|&(_, result)| result == ShotResult::Hit(Some(stype)) || result == ShotResult::Sunk(stype));
+				if hit_before {
+					continue;
+				}
+
+				let mut moved = PlacementSet::new_empty(self.board.num_positions(stype));
+				for ship in self.board.placements(stype) {
+					if self.orientations[stype_idx].allows(ship.is_horizontal()) {
+						moved.insert(ship.id.index());
+					}
+				}
+				for &square in &hits {
+					moved.subtract(&self.cover_masks[stype_idx][square.index()]);
+				}
+				self.pos_positions[stype_idx].union_with(&moved);
+			}
+			self.relocation = Some(Relocation { shots: self.shots.len(), sweeps: self.sweeps.len() });
+			self.latest = None;
+
+			let (required, hits) = (self.cover_requirements(), self.unlabeled_hits());
+			propagate_coverage(&mut self.pos_positions, &required, &hits, &self.cover_masks);
+		});
+	}
+
+	// When the opponent relocated a ship, if they have
+	pub fn relocation(&self) -> Option<Relocation> {
+		self.relocation
+	}
+
+	// For each ship type (in fleet order), the remaining positions covering a
+	// miss from before the relocation, which the ship can only be in if it's
+	// the one that moved. All empty if no ship has been relocated.
+	pub fn moved_positions(&self) -> Vec<PlacementSet> {
+		let since = self.relocation.map_or(0, |relocation| relocation.shots);
+		let misses: Vec<BoardPos> = self.shots[..since].iter().filter(|&&(_, result)| result == ShotResult::Miss).map(|&(pos, _)| pos).collect();
+		self.pos_positions.iter().enumerate().map(|(stype_idx, plist)| {
+			let mut moved = PlacementSet::new_empty(plist.capacity());
+			for &square in &misses {
+				moved.union_with(&self.cover_masks[stype_idx][square.index()]);
+			}
+			moved.intersect_with(plist);
+			moved
+		}).collect()
+	}
+
+	// The moves whose results still stand: all of them, less the misses from
+	// before a relocation
+	pub fn standing_shots(&self) -> impl Iterator<Item = &(BoardPos, ShotResult)> {
+		let since = self.relocation.map_or(0, |relocation| relocation.shots);
+		self.shots.iter().enumerate().filter(move |&(idx, &(_, result))| idx >= since || result != ShotResult::Miss).map(|(_, cur_move)| cur_move)
+	}
+
+	// Render the shots so far as a grid: '.' for unshot squares (and misses
+	// from before a relocation), 'o' for misses, the ship letter (or 'X' if
+	// unknown) for hits, and '?' for pending shots
+	pub fn render(&self) -> String {
+		render_board(&self.board, &self.render_cells())
+	}
+
+	// The cells of the grid render draws, one per square
+	pub fn render_cells(&self) -> Vec<char> {
+		let mut cells = vec!['.'; self.board.num_squares()];
+		for &pos in &self.pending {
+			cells[pos.index()] = '?';
+		}
+		for &(pos, result) in self.standing_shots() {
+			cells[pos.index()] = match result {
+				ShotResult::Miss => 'o',
+				ShotResult::Hit(None) => 'X',
+				ShotResult::Hit(Some(stype)) | ShotResult::Sunk(stype) => encode_shiptype(stype),
+			};
+		}
+
+		cells
+	}
+
+	// Whether the inference has already ruled out every position of some ship,
+	// so no configuration fits the moves so far. Cheaper than finding the
+	// heatmap empty, though it can miss contradictions only the full
+	// enumeration finds.
+	pub fn is_contradictory(&self) -> bool {
+		self.pos_positions.iter().any(|plist| plist.is_empty())
+	}
+
+	// Whether the moves are known to leave no consistent configuration: either
+	// the inference has ruled out some ship (see is_contradictory), or a
+	// complete heatmap counted none. Never starts a count of its own.
+	pub fn is_known_inconsistent(&self) -> bool {
+		self.is_contradictory() || self.latest.as_ref().is_some_and(|heatmap| heatmap.total == 0)
+	}
+
+	// The squares of every hit whose ship type is unknown
+	pub fn unlabeled_hits(&self) -> Vec<BoardPos> {
+		self.shots.iter().filter(|&&(_, result)| result == ShotResult::Hit(None)).map(|&(pos, _)| pos).collect()
+	}
+
+	// Whether the square has been hit (as opposed to missed or not yet shot)
+	pub fn is_hit(&self, pos: BoardPos) -> bool {
+		self.shots.iter().any(|&(p, result)| p == pos && result != ShotResult::Miss)
+	}
+
+	// Every move applied so far, in the order their results were revealed
+	pub fn shots(&self) -> &[(BoardPos, ShotResult)] {
+		&self.shots
+	}
+
+	// Record a shot repeated at a square that already has a result, which
+	// costs a turn without changing what is known
+	pub fn record_reshot(&mut self) {
+		self.reshots += 1;
+	}
+
+	pub fn reshots(&self) -> usize {
+		self.reshots
+	}
+
+	// The single shots fired whose results are in: one per square with a
+	// result, plus every repeat
+	pub fn turns(&self) -> usize {
+		self.shots.len() + self.reshots
+	}
+
+	// Record a shot whose result is withheld for now; apply_move reveals it later
+	pub fn mark_pending(&mut self, pos: BoardPos) {
+		if !self.pending.contains(&pos) {
+			self.pending.push(pos);
+		}
+	}
+
+	// Squares shot at whose results are still withheld, in the order they were shot
+	pub fn pending(&self) -> &[BoardPos] {
+		&self.pending
+	}
+
+	// The states the pending shots could leave once their results arrive, one
+	// for each combination of a miss or a hit at every pending square (just
+	// this state when nothing is pending). Each state's share of the
+	// configurations is the probability of its outcome, so weighting by their
+	// heatmaps' totals takes the expectation over the withheld results.
+	pub fn pending_outcomes(&self) -> Vec<GameState> {
+		let mut outcomes = vec![self.clone()];
+		for &pos in &self.pending {
+			outcomes = outcomes.iter().flat_map(|state| [ShotResult::Miss, ShotResult::Hit(None)].iter().map(move |&result| {
+				let mut outcome = state.clone();
+				outcome.apply_move((pos, result));
+				outcome
+			})).collect();
+		}
+		outcomes
+	}
+
+	// Check whether the given square has already been shot at (including pending shots)
+	pub fn is_shot(&self, pos: BoardPos) -> bool {
+		self.standing_shots().any(|&(p, _)| p == pos) || self.pending.contains(&pos)
+	}
+
+	// Compute the occupancy heatmap by enumerating every non-overlapping
+	// combination of the remaining ship positions
+	pub fn heatmap(&self) -> Heatmap {
+		self.heatmap_cancellable(&CancelToken::new()).expect("Uncancelled heatmap computation was cancelled")
+	}
+
+	// Compute the occupancy heatmap, giving up (and returning None) as soon as
+	// possible after the token is cancelled
+	pub fn heatmap_cancellable(&self, cancel: &CancelToken) -> Option<Heatmap> {
+		match self.heatmap_best_effort(cancel) {
+			(heatmap, true) => Some(heatmap),
+			(_, false) => None,
+		}
+	}
+
+	// Compute the occupancy heatmap, stopping early if the token is cancelled.
+	// Returns the heatmap over the configurations counted so far, and whether the
+	// enumeration completed. A partial heatmap is biased towards the positions
+	// enumerated first, but is still a usable best-so-far answer.
+	pub fn heatmap_best_effort(&self, cancel: &CancelToken) -> (Heatmap, bool) {
+		self.heatmap_observed(cancel, &mut |_| {})
+	}
+
+	// Compute the heatmap like heatmap_best_effort, calling on_progress as the
+	// enumeration goes, so front-ends can animate the search. Heatmaps that are
+	// already known are returned without any progress events.
+	pub fn heatmap_observed(&self, cancel: &CancelToken, on_progress: &mut dyn FnMut(SearchProgress)) -> (Heatmap, bool) {
+		if let (true, Some(opening)) = (self.shots.is_empty() && self.sweeps.is_empty(), self.opening.as_ref()) {
+			return (opening.clone(), true);
+		}
+		if let Some(ref latest) = self.latest {
+			return (latest.clone(), true);
+		}
+
+		let (pos_counts, total) = self.position_counts_observed(cancel, on_progress);
+		let mut counts = vec![0; self.board.num_squares()];
+		for (&stype, stype_counts) in self.board.fleet().iter().zip(pos_counts.iter()) {
+			spread_counts(&self.board, stype, stype_counts, &mut counts);
+		}
+
+		let heatmap = Heatmap { counts, total };
+		if cfg!(debug_assertions) {
+			let standing: Vec<(BoardPos, ShotResult)> = self.standing_shots().cloned().collect();
+			if let Err(e) = heatmap.check_invariants(&self.board, &standing) {
+				panic!("Heatmap invariant violated: {}", e);
+			}
+		}
+		(heatmap, !cancel.is_cancelled())
+	}
+
+	// The heatmap split by ship type: for each ship type (in fleet order),
+	// the number of consistent configurations in which that ship occupies each
+	// square. The layers sum to heatmap().
+	pub fn ship_heatmaps(&self) -> Vec<(ShipType, Heatmap)> {
+		let (pos_counts, total) = self.position_counts(&CancelToken::new());
+		self.board.fleet().iter().zip(pos_counts.iter()).map(|(&stype, stype_counts)| {
+			let mut counts = vec![0; self.board.num_squares()];
+			spread_counts(&self.board, stype, stype_counts, &mut counts);
+			(stype, Heatmap { counts, total })
+		}).collect()
+	}
+
+	// Count how many consistent configurations use each (ship type, position) pair,
+	// along with the total number of consistent configurations. The counts are
+	// partial if the token was cancelled.
+	fn position_counts(&self, cancel: &CancelToken) -> (Vec<Vec<u64>>, u64) {
+		self.position_counts_observed(cancel, &mut |_| {})
+	}
+
+	// Count like position_counts, reporting progress after each position of the
+	// first ship type (which nothing constrains yet, so every one is explored)
+	fn position_counts_observed(&self, cancel: &CancelToken, on_progress: &mut dyn FnMut(SearchProgress)) -> (Vec<Vec<u64>>, u64) {
+		let mut pos_counts: Vec<Vec<u64>> = self.board.fleet().iter().map(|&stype| vec![0; self.board.num_positions(stype)]).collect();
+		if self.is_contradictory() {
+			return (pos_counts, 0);
+		}
+		let mut chosen = Vec::with_capacity(self.board.fleet().len());
+		let required = self.cover_requirements();
+		let moved = self.moved_positions();
+		let mut scratch: Vec<PlacementSet> = self.pos_positions.iter().map(|plist| PlacementSet::new_empty(plist.capacity())).collect();
+		let _scratch_guard = Guard::new(&self.board.context().memory, Component::HeatmapScratch, self.placements_guard.bytes() + counts_bytes(&self.board));
+
+		let branches = self.pos_positions[0].count();
+		let total = timing::time(Phase::Counting, || {
+			let mut total = 0;
+			for (explored, pos) in self.pos_positions[0].iter().enumerate() {
+				if cancel.is_cancelled() {
+					break;
+				}
+
+				chosen.push(PlacementId::new(pos));
+				total += self.count_configs(&mut chosen, &required, &moved, &mut pos_counts, &mut scratch[1..], cancel);
+				chosen.pop();
+				on_progress(SearchProgress { explored: explored + 1, branches, configs: total });
+			}
+			total
+		});
+
+		(pos_counts, total)
+	}
+
+	// Compute the heatmap ahead of time (see heatmap_observed), keeping it so the
+	// next heatmap query, e.g. from a strategy, returns at once. A heatmap cut
+	// short by the token isn't kept.
+	pub fn precompute_heatmap(&mut self, cancel: &CancelToken, on_progress: &mut dyn FnMut(SearchProgress)) {
+		if let (heatmap, true) = self.heatmap_observed(cancel, on_progress) {
+			self.latest = Some(heatmap);
+		}
+	}
+
+	// Keep the heatmap another copy of the game has already worked out, e.g. on
+	// a background thread (see the ponder module), if it's of the same position
+	pub fn reuse_heatmap(&mut self, other: &GameState) {
+		let same = self.board == other.board && self.shots == other.shots && self.pending == other.pending && self.sweeps == other.sweeps
+			&& self.orientations == other.orientations && self.allow_touching == other.allow_touching && self.relocation == other.relocation && self.pos_positions == other.pos_positions;
+		if same && other.latest.is_some() {
+			self.latest.clone_from(&other.latest);
+		}
+	}
+
+	// The probability, given the moves so far, that the given move would have the claimed result
+	pub fn result_probability(&self, move_val: (BoardPos, ShotResult)) -> f64 {
+		let (pos_counts, total) = self.position_counts(&CancelToken::new());
+		if total == 0 {
+			return 0.0;
+		}
+
+		// Number of configurations in which the given ship type occupies the square
+		let occupying = |stype_idx: usize| -> u64 {
+			self.cover_masks[stype_idx][move_val.0.index()].iter().map(|pos| pos_counts[stype_idx][pos]).sum()
+		};
+		let occupied: u64 = (0..self.board.fleet().len()).map(occupying).sum();
+
+		let matching = match move_val.1 {
+			ShotResult::Miss => total - occupied,
+			ShotResult::Hit(None) => occupied,
+			ShotResult::Hit(Some(stype)) => self.board.ship_index(stype).map_or(0, occupying),
+
+			// The ship must occupy the square, with every other square of it already hit
+			ShotResult::Sunk(stype) => match self.board.ship_index(stype) {
+				Some(stype_idx) => self.cover_masks[stype_idx][move_val.0.index()].iter().filter(|&pos| {
+					self.board.placement(stype, PlacementId::new(pos)).cells().all(|square| square == move_val.0 || self.is_hit(square))
+				}).map(|pos| pos_counts[stype_idx][pos]).sum(),
+				None => 0,
+			},
+		};
+
+		matching as f64 / total as f64
+	}
+
+	// For each square, the probability that a hit there would sink a ship, given
+	// that it's a hit: the share of the configurations with a ship there in which
+	// every other square of that ship is already hit. None for squares already
+	// shot, and those no configuration has a ship on.
+	pub fn sink_probabilities(&self) -> Vec<Option<f64>> {
+		let (pos_counts, _) = self.position_counts(&CancelToken::new());
+		let (mut occupied, mut sinking) = (vec![0u64; self.board.num_squares()], vec![0u64; self.board.num_squares()]);
+		for (&stype, (plist, counts)) in self.board.fleet().iter().zip(self.pos_positions.iter().zip(pos_counts.iter())) {
+			for id in plist.iter().filter(|&id| counts[id] > 0) {
+				let unhit: Vec<BoardPos> = self.board.placement(stype, PlacementId::new(id)).cells().filter(|&square| !self.is_hit(square)).collect();
+				for &square in &unhit {
+					occupied[square.index()] += counts[id];
+				}
+				if let [last] = unhit[..] {
+					sinking[last.index()] += counts[id];
+				}
+			}
+		}
+
+		self.board.squares().map(|pos| match occupied[pos.index()] {
+			0 => None,
+			_ if self.is_shot(pos) => None,
+			occupying => Some(sinking[pos.index()] as f64 / occupying as f64),
+		}).collect()
+	}
+
+	// The kill shots: squares where a hit would sink a ship with at least the
+	// given probability (see sink_probabilities), so 1 for a hit that sinks a
+	// ship in every configuration
+	pub fn kill_shots(&self, threshold: f64) -> Vec<BoardPos> {
+		let probabilities = self.sink_probabilities();
+		self.board.squares().filter(|pos| probabilities[pos.index()].is_some_and(|p| p > 0.0 && p >= threshold)).collect()
+	}
+
+	// Count the consistent configurations extending the already-chosen positions
+	// (one per ship type, in fleet order), tallying how often each position is used.
+	// At most one ship may take a position in moved (see moved_positions).
+	// scratch holds one preallocated set per remaining ship type, so the
+	// enumeration never allocates. Stops early, returning a partial count,
+	// once the token is cancelled.
+	fn count_configs(&self, chosen: &mut Vec<PlacementId>, required: &[Vec<BoardPos>], moved: &[PlacementSet], pos_counts: &mut [Vec<u64>],
+	                 scratch: &mut [PlacementSet], cancel: &CancelToken) -> u64 {
+		let stype_idx = chosen.len();
+		if stype_idx == self.board.fleet().len() {
+			// The configuration is only consistent if its ships cover every
+			// unlabeled hit and part of every sweep that found something
+			let covered = required.iter().all(|group| group.iter().any(|square| {
+				chosen.iter().enumerate().any(|(idx, &pos)| self.cover_masks[idx][square.index()].contains(pos.index()))
+			}));
+			if !covered {
+				return 0;
+			}
+
+			for (idx, &pos) in chosen.iter().enumerate() {
+				pos_counts[idx][pos.index()] += 1;
+			}
+			return 1;
+		}
+
+		if cancel.is_cancelled() {
+			return 0;
+		}
+
+		// The positions of this ship type that don't overlap any already-chosen ship
+		let (allowed, scratch) = scratch.split_first_mut().expect("Too little scratch space");
+		allowed.clone_from(&self.pos_positions[stype_idx]);
+		for (idx, &other) in chosen.iter().enumerate() {
+			match self.olap_cache {
+				Some(ref olap_cache) => allowed.subtract(&olap_cache[idx][stype_idx][other.index()]),
+
+				// Without the cache, take out the positions covering each of the
+				// other ship's squares (and the squares around them, if ships may
+				// not touch)
+				None => for square in self.board.placement(self.board.fleet()[idx], other).cells() {
+					if self.allow_touching {
+						allowed.subtract(&self.cover_masks[stype_idx][square.index()]);
+					} else {
+						for near in surroundings(square) {
+							allowed.subtract(&self.cover_masks[stype_idx][near.index()]);
+						}
+					}
+				},
+			}
+		}
+
+		// Once a ship is where only the moved ship can be, the rest stayed put
+		if self.relocation.is_some() && chosen.iter().enumerate().any(|(idx, &pos)| moved[idx].contains(pos.index())) {
+			allowed.subtract(&moved[stype_idx]);
+		}
+
+		let mut total = 0;
+		for pos in allowed.iter() {
+			chosen.push(PlacementId::new(pos));
+			total += self.count_configs(chosen, required, moved, pos_counts, scratch, cancel);
+			chosen.pop();
+		}
+
+		total
+	}
+
+	// The expected number of ships covering each square if every ship type's
+	// remaining positions were independent and equally likely. Ignores overlaps
+	// between ships, but only costs a popcount per (ship type, square), so it
+	// stays cheap on boards too large for exact enumeration.
+	pub fn independent_heatmap(&self) -> Vec<f64> {
+		(0..self.board.num_squares()).map(|square| {
+			self.pos_positions.iter().zip(self.cover_masks.iter()).map(|(plist, masks)| {
+				if plist.is_empty() {
+					return 0.0;
+				}
+				plist.intersection_count(&masks[square]) as f64 / plist.count() as f64
+			}).sum()
+		}).collect()
+	}
+
+	// The candidate placements remaining for the given ship type, which must be
+	// in the fleet
+	pub fn positions(&self, stype: ShipType) -> &PlacementSet {
+		&self.pos_positions[self.board.ship_index(stype).expect("The ship isn't in the fleet")]
+	}
+
+	// The orientations the rules allow the given ship type, which must be in
+	// the fleet
+	pub fn orientation(&self, stype: ShipType) -> Orientation {
+		self.orientations[self.board.ship_index(stype).expect("The ship isn't in the fleet")]
+	}
+
+	// The number of candidate placements remaining for each ship type
+	pub fn placement_counts(&self) -> Vec<(ShipType, usize)> {
+		self.board.fleet().iter().zip(self.pos_positions.iter()).map(|(&stype, plist)| (stype, plist.count())).collect()
+	}
+}
+
+// Implemented by hand so clone_from reuses the existing buffers, which makes
+// recycling search nodes (see arena::Arena::alloc_from) allocation-free
+impl Clone for GameState {
+	fn clone(&self) -> GameState {
+		GameState {
+			board: self.board.clone(),
+			pos_positions: self.pos_positions.clone(),
+			placements_guard: self.placements_guard.clone(),
+			olap_cache: self.olap_cache.clone(),
+			cover_masks: self.cover_masks.clone(),
+			shots: self.shots.clone(),
+			records: self.records.clone(),
+			pending: self.pending.clone(),
+			reshots: self.reshots,
+			sweeps: self.sweeps.clone(),
+			orientations: self.orientations.clone(),
+			allow_touching: self.allow_touching,
+			relocation: self.relocation,
+			opening: self.opening.clone(),
+			latest: self.latest.clone(),
+		}
+	}
+
+	fn clone_from(&mut self, source: &GameState) {
+		self.board.clone_from(&source.board);
+		self.pos_positions.clone_from(&source.pos_positions);
+		self.placements_guard.clone_from(&source.placements_guard);
+		self.olap_cache.clone_from(&source.olap_cache);
+		self.cover_masks.clone_from(&source.cover_masks);
+		self.shots.clone_from(&source.shots);
+		self.records.clone_from(&source.records);
+		self.pending.clone_from(&source.pending);
+		self.reshots = source.reshots;
+		self.sweeps.clone_from(&source.sweeps);
+		self.orientations.clone_from(&source.orientations);
+		self.allow_touching = source.allow_touching;
+		self.relocation = source.relocation;
+		self.opening.clone_from(&source.opening);
+		self.latest.clone_from(&source.latest);
+	}
+}
+
+// GameState must stay shareable between threads (e.g. a UI thread querying it while a search runs)
+fn assert_send_sync<T: Send + Sync>() {}
+const _: fn() = assert_send_sync::<GameState>;
+
+// Format placement counts for display, e.g. "P: 12, D: 7, S: 7, B: 4, C: 1"
+pub fn format_placement_counts(counts: &[(ShipType, usize)]) -> String {
+	counts.iter().map(|&(stype, count)| format!("{}: {}", encode_shiptype(stype), count)).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// A 2x2 board with a patrol boat, whose four placements cover each square
+	// twice
+	fn tiny() -> (Board, Heatmap) {
+		(Board::new(2, &[ShipType::Patrol]).unwrap(), Heatmap { counts: vec![2; 4], total: 4 })
+	}
+
+	#[test]
+	fn invariants_hold_over_a_game() {
+		let board = Board::new(5, &[ShipType::Patrol, ShipType::Destroyer]).unwrap();
+		let mut state = GameState::new(&board);
+		for &(row, col, result) in &[(0, 0, ShotResult::Miss), (2, 2, ShotResult::Hit(None)), (2, 3, ShotResult::Miss), (1, 2, ShotResult::Hit(None))] {
+			state.apply_move((board.square(row, col).unwrap(), result));
+			let standing: Vec<(BoardPos, ShotResult)> = state.standing_shots().cloned().collect();
+			assert_eq!(state.heatmap().check_invariants(&board, &standing), Ok(()));
+		}
+	}
+
+	#[test]
+	fn invariants_catch_a_wrong_sum() {
+		let (board, mut heatmap) = tiny();
+		heatmap.counts[0] += 1;
+		assert!(heatmap.check_invariants(&board, &[]).unwrap_err().contains("sum to 9"));
+	}
+
+	#[test]
+	fn invariants_catch_an_overcounted_square() {
+		let (board, _) = tiny();
+		let heatmap = Heatmap { counts: vec![5, 1, 1, 1], total: 4 };
+		assert!(heatmap.check_invariants(&board, &[]).unwrap_err().contains("more than the 4 configurations"));
+	}
+
+	#[test]
+	fn invariants_catch_a_covered_miss() {
+		let (board, heatmap) = tiny();
+		let miss = board.square(1, 1).unwrap();
+		assert!(heatmap.check_invariants(&board, &[(miss, ShotResult::Miss)]).unwrap_err().contains("the miss at"));
+	}
+
+	// The pending shots' outcomes split the configurations between them
+	#[test]
+	fn pending_outcomes_partition_the_configurations() {
+		let board = Board::new(5, &[ShipType::Patrol, ShipType::Destroyer]).unwrap();
+		let mut state = GameState::new(&board);
+		state.apply_move((board.square(2, 2).unwrap(), ShotResult::Hit(None)));
+		state.mark_pending(board.square(2, 3).unwrap());
+		state.mark_pending(board.square(0, 0).unwrap());
+
+		let outcomes: Vec<Heatmap> = state.pending_outcomes().iter().map(GameState::heatmap).collect();
+		assert_eq!(outcomes.len(), 4);
+		let heatmap = state.heatmap();
+		assert_eq!(outcomes.iter().map(|outcome| outcome.total).sum::<u64>(), heatmap.total);
+		for pos in board.squares() {
+			assert_eq!(outcomes.iter().map(|outcome| outcome.counts[pos.index()]).sum::<u64>(), heatmap.counts[pos.index()]);
+		}
+	}
+
+	// A state whose placements weren't pruned by its miss yields a heatmap
+	// covering the miss, which debug builds refuse
+	#[test]
+	#[cfg(debug_assertions)]
+	#[should_panic(expected = "Heatmap invariant violated")]
+	fn heatmap_panics_on_a_violation() {
+		let board = Board::new(5, &[ShipType::Patrol, ShipType::Destroyer]).unwrap();
+		let mut state = GameState::new(&board);
+		state.apply_move((board.square(0, 0).unwrap(), ShotResult::Miss));
+		state.pos_positions = board.fleet().iter().map(|&stype| SharedSet::new(PlacementSet::new_full(board.num_positions(stype)))).collect();
+		state.heatmap_best_effort(&CancelToken::new());
+	}
+}
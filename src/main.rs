@@ -1,245 +1,2324 @@
-// Temporary warning bypass to let me develop the building blocks in peace
-#![allow(dead_code)]
-#![allow(unused_variables)]
+extern crate battleship_ai;
 
-// Board size (width and height)
-//const BOARD_SIZE: u8 = 10;
-const BOARD_SIZE: u8 = 5;
+use battleship_ai::*;
+use battleship_ai::adapt::{self, Adaptive};
+use battleship_ai::binlog::{LogReader, LogWriter};
+use battleship_ai::analysis;
+use battleship_ai::attackers::{self, Attacker};
+use battleship_ai::audit::{self, Fixture};
+use battleship_ai::budget::BudgetPlanner;
+use battleship_ai::campaign::{self, Campaign};
+use battleship_ai::cancel::CancelToken;
+use battleship_ai::cheat::CheatDetector;
+use battleship_ai::combine::Memoized;
+use battleship_ai::commit::{Commitment, Reveal};
+use battleship_ai::compare::{self, Model};
+use battleship_ai::completions::{self, Command, Interface};
+use battleship_ai::context::Context;
+use battleship_ai::convert::{self, Input, Output};
+use battleship_ai::coords::{self, Coordinates};
+#[cfg(feature = "network")]
+use battleship_ai::distribute;
+use battleship_ai::durable;
+use battleship_ai::events::{Event, EventLog};
+use battleship_ai::explain;
+use battleship_ai::finish;
+use battleship_ai::forecast;
+use battleship_ai::forced::{self, Forcing};
+use battleship_ai::gamelog::GameLog;
+use battleship_ai::generate::{self, Length};
+use battleship_ai::harness;
+use battleship_ai::hint::{self, MAX_HINT_STRENGTH};
+use battleship_ai::history::{self, History};
+use battleship_ai::import;
+use battleship_ai::memory::{self, Meter};
+#[cfg(feature = "montecarlo")]
+use battleship_ai::montecarlo;
+use battleship_ai::opponent::{self, Opponent, Opponents};
+use battleship_ai::messages::{self, Cell, Locale, Message};
+use battleship_ai::spoken;
+use battleship_ai::placement::Layout;
+use battleship_ai::phase::TimeLimits;
+use battleship_ai::ponder::{self, Ponder};
+use battleship_ai::profile::Profile;
+use battleship_ai::progress::{self, Progress, ProgressBar, Tracker};
+use battleship_ai::puzzle;
+use battleship_ai::radar::Sweep;
+use battleship_ai::ratings::{self, Ratings};
+use battleship_ai::reference;
+use battleship_ai::relocation;
+#[cfg(feature = "network")]
+use battleship_ai::remote::{RemoteConfig, RemoteGame};
+use battleship_ai::rng::Rng;
+use battleship_ai::rules::{Rules, RulesRegistry, DEFAULT_PRESET};
+use battleship_ai::schema;
+#[cfg(feature = "scripting")]
+use battleship_ai::script::{Script, Scripted};
+#[cfg(feature = "selfplay")]
+use battleship_ai::selfplay::{self, Exploration};
+#[cfg(feature = "network")]
+use battleship_ai::server::Server;
+use battleship_ai::sim;
+use battleship_ai::solver::{Objective, Solver};
+use battleship_ai::strategy::{self, Difficulty, GameResult, Greedy, Maximin, Strategy, TieBreak};
+use battleship_ai::svg;
+use battleship_ai::team::{Entry, SharedBoard};
+use battleship_ai::timing::{self, Phase};
+#[cfg(feature = "network")]
+use battleship_ai::transport::Listener;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-// This represents a board position.
-// The positions are numbered in a row major manner,
-// so position 0 is A1, position 9 is A10, and
-// position 99 is J10. Larger values are invalid.
-// This is different from the conventions for the different
-// ship types
-pub type BoardPos = u8;
+// The strategy defined by the script file
+#[cfg(feature = "scripting")]
+fn script_strategy(path: &str, tie_break: TieBreak) -> Box<dyn Strategy> {
+	let script = Script::load(path).unwrap_or_else(|e| fail(&e));
+	Box::new(Scripted::new(script, tie_break))
+}
 
-// Construct a board position from its row and column parts
-pub fn pos_from_parts(row: u8, col: u8) -> BoardPos {
-	BOARD_SIZE * row + col
+#[cfg(not(feature = "scripting"))]
+fn script_strategy(_path: &str, _tie_break: TieBreak) -> Box<dyn Strategy> {
+	fail("This build has no scripting support (build with the \"scripting\" feature)");
 }
 
-// Ship type
-#[derive(Clone,Copy,Debug,PartialEq)]
-enum ShipType {
-	Patrol,
-	Destroyer,
-	Submarine,
-	Battleship,
-	Carrier
+// Parse the value of a --tie-break option
+fn tie_break_arg<I: Iterator<Item = String>>(args: &mut I, command: Option<&str>) -> TieBreak {
+	let desc = args.next().or_usage(command, "--tie-break requires a policy");
+	TieBreak::parse(&desc).unwrap_or_else(|| usage_error(command, &format!("Unknown tie-break policy {}", desc)))
 }
 
-// A list of all ship types
-const NUM_SHIP_TYPES: usize = 5;
-const SHIP_TYPES: [ShipType; NUM_SHIP_TYPES] = [ShipType::Patrol, ShipType::Destroyer, ShipType::Submarine, ShipType::Battleship, ShipType::Carrier];
+// Print the per-ship placement counts and the recommended next shot (unless
+// playing with hints), cutting the search short after the time limit for the
+// game's phase (if any), and thinking for the given time first (if any).
+// Returns the shot.
+fn report(state: &mut GameState, strategy: &mut dyn Strategy, time_limits: &TimeLimits, thinking: Option<Duration>,
+          hints: bool, stats: Option<&History>, locale: &dyn Locale) -> Option<BoardPos> {
+	let time_limit = time_limits.get(state.phase());
+	println!("{}", locale.text(&Message::PlacementCounts(&state.placement_counts())));
+	if let Some(delay) = thinking {
+		think(state, &locale.text(&Message::Thinking), delay, time_limit);
+	}
+	let cancel = time_limit.map_or_else(CancelToken::new, CancelToken::with_timeout);
+	if thinking.is_none() && strategy.needs_heatmap() {
+		let mut bar = ProgressBar::new("Counting");
+		let tracker = Tracker::new(0);
+		state.precompute_heatmap(&cancel, &mut |progress| {
+			let tracker = tracker.with_total(progress.branches as u64);
+			bar.update(tracker.at(progress.explored as u64), &format!("{} configurations", progress.configs));
+		});
+	}
+	let state = &*state;
+	if state.is_known_inconsistent() {
+		println!("{}", locale.text(&Message::NoConsistentLayout));
+		timing::end_move();
+		return None;
+	}
+
+	let shot = timing::time(Phase::Selection, || strategy.choose_shot_cancellable(state, &cancel));
+	if let Some(win) = strategy.forced_win().filter(|_| !hints) {
+		println!("{}", locale.text(&Message::ForcedWin(win)));
+	}
+	match shot {
+		Some(_) if hints => {},
+		Some(pos) => println!("{}", locale.text(&Message::BestShot { pos, cut_short: cancel.is_cancelled() })),
+		None => println!("{}", locale.text(&Message::NoSquaresLeft)),
+	}
+
+	if let Some(history) = stats {
+		let shots = state.shots();
+		println!("{}", locale.text(&Message::StatsPanel {
+			shots: state.turns(),
+			hits: shots.iter().filter(|&&(_, result)| result != ShotResult::Miss).count(),
+			sunk: shots.iter().filter(|&&(_, result)| matches!(result, ShotResult::Sunk(_))).count(),
+			ships: state.board().fleet().len(),
+			to_go: history::estimate_remaining(state, history),
+			history: history.stage(shots.len()),
+		}));
+	}
+	timing::end_move();
+	shot
+}
 
-// Compute the ID (index into SHIP_TYPES) of the given ship type
-fn stype_id(stype: ShipType) -> u8 {
-	SHIP_TYPES.iter().position(|&t| t == stype).expect("Unknown ship type!!!") as u8
+// With --ponder, start working out the results of the recommended shot while
+// waiting for the next input
+fn start_pondering(enabled: bool, shot: Option<BoardPos>, state: &GameState, rules: &Rules) -> Option<Ponder> {
+	shot.filter(|_| enabled).map(|pos| Ponder::start(state, ponder::results(state, rules, pos)))
 }
 
-// Decode a ship type from a character describing it
-fn decode_shiptype(desc: u8) -> ShipType {
-	match desc as char {
-		'P' => ShipType::Patrol,
-		'D' => ShipType::Destroyer,
-		'S' => ShipType::Submarine,
-		'B' => ShipType::Battleship,
-		'C' => ShipType::Carrier,
-		_ => panic!("Invalid ship type {}", desc)
+// The number of dots think prints
+const THINKING_DOTS: u32 = 10;
+
+// How long the interactive mode searches on a board too large to count every
+// layout of (see cache::MAX_OPENING_TIME), unless given a time limit
+const LARGE_BOARD_TIME_LIMIT: Duration = Duration::from_secs(5);
+
+// Search ahead of a recommendation at a human pace, for streamed play: print
+// the label, then a dot for each tenth of the search (but no faster than the
+// delay allows), finishing no sooner than the delay after starting. The
+// heatmap is kept for the recommendation that follows.
+fn think(state: &mut GameState, label: &str, delay: Duration, time_limit: Option<Duration>) {
+	use std::io::Write;
+
+	let start = Instant::now();
+	let dot = || {
+		print!(".");
+		std::io::stdout().flush().unwrap_or_else(|e| fail(&format!("Unable to write to stdout: {}", e)));
+	};
+	print!("{}", label);
+	std::io::stdout().flush().unwrap_or_else(|e| fail(&format!("Unable to write to stdout: {}", e)));
+
+	let cancel = time_limit.map_or_else(CancelToken::new, CancelToken::with_timeout);
+	let mut dots = 0;
+	state.precompute_heatmap(&cancel, &mut |progress| {
+		let searched = (progress.explored as u64 * THINKING_DOTS as u64 / progress.branches.max(1) as u64) as u32;
+		let paced = if delay.is_zero() { THINKING_DOTS } else { (start.elapsed().as_secs_f64() / delay.as_secs_f64() * THINKING_DOTS as f64) as u32 };
+		while dots < searched.min(paced) {
+			dot();
+			dots += 1;
+		}
+	});
+
+	// Spread the rest of the dots over the rest of the delay
+	while dots < THINKING_DOTS {
+		if let Some(wait) = (start + delay * (dots + 1) / THINKING_DOTS).checked_duration_since(Instant::now()) {
+			std::thread::sleep(wait);
+		}
+		dot();
+		dots += 1;
 	}
+	println!();
 }
 
-// Compute the size of the given ship type
-fn ship_size(shiptype: ShipType) -> u8 {
-	use ShipType::*;
+// Apply a move, first warning about it if the cheat detector finds it
+// suspicious. Once no layout fits the moves, every result is as unlikely as
+// the next, so there's nothing more to warn about.
+fn apply_checked(log: &mut EventLog, journal: &mut Option<std::fs::File>, detector: &Option<CheatDetector>, cur_move: (BoardPos, ShotResult), locale: &dyn Locale) -> bool {
+	let detector = detector.as_ref().filter(|_| !log.state().is_known_inconsistent());
+	if let Some(probability) = detector.and_then(|detector| detector.check(log.state(), cur_move)) {
+		println!("{}", locale.text(&Message::CheatWarning { pos: cur_move.0, probability }));
+	}
 
-	match shiptype {
-		Patrol => 2,
-		Destroyer => 3,
-		Submarine => 3,
-		Battleship => 4,
-		Carrier => 5,
+	record_event(log, journal, Event::Result(cur_move), locale)
+}
+
+// Append an event to the interactive game, reporting it if it doesn't apply
+fn record_event(log: &mut EventLog, journal: &mut Option<std::fs::File>, event: Event, locale: &dyn Locale) -> bool {
+	match log.append(event) {
+		Ok(()) => {
+			journal_latest(log, journal);
+			true
+		},
+		Err(e) => {
+			println!("{}", locale.text(&Message::EventRejected(e)));
+			false
+		},
+	}
+}
+
+// Write the latest event to the journal, if there is one, syncing it to disk
+// so it survives a crash
+fn journal_latest(log: &EventLog, journal: &mut Option<std::fs::File>) {
+	use std::io::Write;
+
+	if let (Some(file), Some(event)) = (journal.as_mut(), log.events().last()) {
+		writeln!(file, "{}", event).and_then(|()| file.sync_data()).unwrap_or_else(|e| fail(&format!("Unable to write to the journal: {}", e)));
 	}
 }
 
-// The number of columns the ship can be in oriented horizontally
-// or the number of rows it can be in oriented vertically
-fn reduced_poscount(shiptype: ShipType) -> u8 {
-	BOARD_SIZE - ship_size(shiptype) + 1
+// Describe a board one row per sentence, for screen readers
+fn describe_board(board: &Board, cells: &[Cell], locale: &dyn Locale) {
+	for (row, row_cells) in cells.chunks(board.size() as usize).enumerate() {
+		println!("{}", locale.text(&Message::BoardRow { row: row as u8, cells: row_cells, coordinates: board.coordinates() }));
+	}
 }
 
-// Computes the number of valid positions for the given ship type
-fn num_positions(shiptype: ShipType) -> u8 {
-	// Consider rotations and the rectangular pattern of horizontal vs.
-	// vertical positioning
-	2 * reduced_poscount(shiptype) * BOARD_SIZE
+// Interactively help the user place their own fleet, then save it to a placement file
+fn run_placement_assistant<I: Iterator<Item = String>>(mut args: I, rules: &Rules, locale: &dyn Locale) {
+	use std::io::BufRead;
+
+	let mut constraints = rules.constraints();
+	let mut out_path = String::from("placement.txt");
+	let mut screen_reader = false;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--no-touching" => constraints.allow_touching = false,
+			"--screen-reader" => screen_reader = true,
+			"--output" => out_path = args.next().or_usage(Some("place"), "--output requires a file name"),
+			_ => unknown_argument(Some("place"), &arg),
+		}
+	}
+
+	let board = constraints.board.clone();
+	let opening = GameState::new_cached_restricted(&board, &constraints.orientations).heatmap();
+	let mut layout = Layout::new(constraints);
+	let stdin = std::io::stdin();
+	let mut lines = stdin.lock().lines();
+
+	let show_layout = |layout: &Layout| if screen_reader { describe_board(&board, &spoken::layout_cells(layout), locale) } else { print!("{}", layout.render()) };
+	while let Some(stype) = layout.next_unplaced() {
+		show_layout(&layout);
+		println!("{}", locale.text(&Message::PlacePrompt(stype)));
+
+		let line = match lines.next() {
+			Some(line) => line.unwrap_or_else(|e| fail(&format!("Unable to read placement from stdin: {}", e))),
+			None => return,
+		};
+		let fields: Vec<&str> = line.split_whitespace().collect();
+
+		if fields == ["undo"] {
+			layout.undo();
+			continue;
+		}
+
+		let start = fields.first().and_then(|&square| parse_square(&board, square));
+		let horizontal = match fields.get(1) {
+			Some(&"H") => Some(true),
+			Some(&"V") => Some(false),
+			_ => None,
+		};
+		let placement = match (fields.len(), start, horizontal) {
+			(2, Some(start), Some(horizontal)) => Some((start, horizontal)),
+			_ if screen_reader => spoken::parse_spoken_placement(&board, &line).ok(),
+			_ => None,
+		};
+		match placement {
+			Some((start, horizontal)) => {
+				if let Err(e) = layout.place(stype, start, horizontal) {
+					println!("{}", locale.text(&Message::InvalidPlacement(e)));
+				}
+			},
+			None => println!("{}", locale.text(&Message::ExpectedPlacement)),
+		}
+	}
+
+	show_layout(&layout);
+	let average = board.squares().map(|pos| opening.probability(pos)).sum::<f64>() / board.num_squares() as f64;
+	println!("{}", locale.text(&Message::PlacementScore { score: layout.score(&opening), average }));
+
+	durable::write_unsealed(Path::new(&out_path), layout.to_file_string().as_bytes()).unwrap_or_else(|e| fail(&format!("Unable to write placement file: {}", e)));
+	println!("{}", locale.text(&Message::PlacementSaved(&out_path)));
 }
 
-// Compute the occupied squares for the given ship type and position ID
-fn ship_range(shiptype: ShipType, pos: u8) -> Vec<u8> {
-	// Starting square and step size for this ship's span
-	let start_square;
-	let step_size;
+// Compare placements or shot sequences from several games pairwise
+fn run_analysis<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let kind = args.next().or_usage(Some("analyze"), "analyze requires \"placements\", \"shots\", or \"wasted\"");
+	let paths: Vec<String> = args.collect();
+	let board = rules.known_board();
 
-	// Lower-numbered positions are horizontal, higher-numbered positions
-	// are vertically-oriented.
-	if pos < num_positions(shiptype)/2 {
-		// Horizontally oriented
+	match kind.as_str() {
+		"placements" => {
+			let layouts: Vec<Layout> = paths.iter().map(|path| {
+				let contents = text::read(Path::new(path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", path, e)));
+				Layout::from_file_string(&contents, rules.constraints()).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+			}).collect();
 
-		// Compute the starting square for the ship
-		start_square = pos_from_parts(pos / reduced_poscount(shiptype), pos % reduced_poscount(shiptype));
+			for i in 0..layouts.len() {
+				for j in i + 1..layouts.len() {
+					let distance = analysis::layout_distance(&layouts[i], &layouts[j]);
+					println!("{} vs {}: similarity {:.3}, {} squares differ, {} ships moved{}", paths[i], paths[j],
+					         analysis::layout_similarity(&layouts[i], &layouts[j]), distance,
+					         analysis::ships_moved(&layouts[i], &layouts[j]), if distance == 0 { " (reused layout)" } else { "" });
+				}
+			}
+		},
+		"shots" => {
+			let sequences: Vec<Vec<BoardPos>> = paths.iter().map(|path| read_moves_file(&board, path).unwrap_or_else(|e| fail(&e)).iter().map(|&(pos, _)| pos).collect()).collect();
+
+			for i in 0..sequences.len() {
+				for j in i + 1..sequences.len() {
+					println!("{} vs {}: order-independent similarity {:.3}, sequence similarity {:.3}, edit distance {}", paths[i], paths[j],
+					         analysis::shot_set_similarity(&sequences[i], &sequences[j]),
+					         analysis::shot_sequence_similarity(&sequences[i], &sequences[j]),
+					         analysis::shot_sequence_distance(&sequences[i], &sequences[j]));
+				}
+			}
+		},
+		"wasted" => {
+			let path = paths.first().or_usage(Some("analyze"), "analyze wasted requires a moves file");
+			let log = GameLog { rules: rules.name.clone(), moves: read_moves_file(&board, path).unwrap_or_else(|e| fail(&e)), board };
+			convert::validate(&log, rules).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+
+			let wasted = analysis::wasted_shots(&log.moves, rules);
+			for shot in &wasted {
+				match shot.waste {
+					analysis::Waste::Impossible => println!("Turn {} ({}): no ship could have been there", shot.turn, shot.pos),
+					analysis::Waste::Dominated(other) => println!("Turn {} ({}): {} was a ship square whenever {} was, and more often ({:.0}% vs {:.0}%)",
+					                                              shot.turn, shot.pos, other, shot.pos, 100.0 * shot.better, 100.0 * shot.probability),
+				}
+			}
+			println!("You wasted {} of {} shots", wasted.len(), log.moves.len());
+		},
+		_ => usage_error(Some("analyze"), &format!("Unknown analysis {}", kind)),
+	}
+}
+
+// Compare the heatmaps of a position under two models, printing the per-square
+// difference (right minus left) as a grid or CSV
+fn run_diff<I: Iterator<Item = String>>(mut args: I, board: &Board) {
+	let mut path = None;
+	let mut left = Model::Joint;
+	let mut right = Model::Independent;
+	let mut csv = false;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--left" => left = args.next().and_then(|m| Model::parse(&m)).or_usage(Some("diff"), "--left requires joint or independent"),
+			"--right" => right = args.next().and_then(|m| Model::parse(&m)).or_usage(Some("diff"), "--right requires joint or independent"),
+			"--csv" => csv = true,
+			_ if arg.starts_with("--") || path.is_some() => unknown_argument(Some("diff"), &arg),
+			_ => path = Some(arg),
+		}
+	}
 
-		// Step size is just 1 for horizontal
-		step_size = 1;
+	let mut state = GameState::new(board);
+	for cur_move in read_moves_file(board, &path.or_usage(Some("diff"), "diff requires a moves file")).unwrap_or_else(|e| fail(&e)) {
+		state.apply_move(cur_move);
+	}
+
+	if csv {
+		print!("{}", compare::to_csv(&state, left, right));
 	} else {
-		// Vertically oriented
-		let pos = pos - num_positions(shiptype)/2;
+		println!("{} minus {} (percentage points):", right.name(), left.name());
+		print!("{}", compare::render_diff(board, &compare::diff(&state, left, right)));
+	}
+}
+
+// Plan the shots guaranteed to sink the ship hit at a square (by default the
+// most recent hit on a ship that may not be sunk yet)
+fn run_finish<I: Iterator<Item = String>>(mut args: I, board: &Board) {
+	let path = args.next().or_usage(Some("finish"), "finish requires a moves file");
+	let mut state = GameState::new(board);
+	for cur_move in read_moves_file(board, &path).unwrap_or_else(|e| fail(&e)) {
+		state.apply_move(cur_move);
+	}
+
+	let target = match args.next() {
+		Some(square) => parse_square(board, &square).unwrap_or_else(|| usage_error(Some("finish"), &format!("Invalid square {}", square))),
+		None => match finish::default_target(&state) {
+			Some(target) => target,
+			None => {
+				println!("No unsunk ship has been hit");
+				return;
+			},
+		},
+	};
+
+	let finish = finish::plan_finish(&state, target).unwrap_or_else(|| fail(&format!("{} is not a hit", target)));
+	match finish.plan {
+		Some(plan) => {
+			println!("The ship hit at {} is sunk after at most {} more shots:", target, finish.worst_case);
+			print!("{}", plan.render());
+		},
+		None => println!("The ship hit at {} is already sunk", target),
+	}
+}
+
+// Read independent positions from stdin (blocks of move lines separated by
+// blank lines) and print the best shot for each, one per line
+fn run_eval<I: Iterator<Item = String>>(mut args: I, board: &Board) {
+	use std::io::BufRead;
+
+	let mut tie_break = TieBreak::Lexicographic;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--tie-break" => tie_break = tie_break_arg(&mut args, Some("eval")),
+			_ => unknown_argument(Some("eval"), &arg),
+		}
+	}
+
+	// Evaluate one position and print its best shot
+	let evaluate = |moves: &[(BoardPos, ShotResult)]| {
+		let mut state = GameState::new_cached(board);
+		for &cur_move in moves {
+			state.apply_move(cur_move);
+		}
+
+		match Greedy::new(tie_break).choose_shot(&state) {
+			Some(pos) => println!("{}", pos),
+			None => println!("none"),
+		}
+	};
+
+	let stdin = std::io::stdin();
+	let mut block = Vec::new();
+	let mut in_block = false;
+	for line in stdin.lock().lines() {
+		let line = line.unwrap_or_else(|e| fail(&format!("Unable to read position from stdin: {}", e)));
+		let line = line.trim();
+
+		if line.is_empty() {
+			if in_block {
+				evaluate(&block);
+				block.clear();
+				in_block = false;
+			}
+			continue;
+		}
+
+		// Lines starting with '#' are comments, which also start a (possibly empty) position
+		in_block = true;
+		if !line.starts_with('#') {
+			block.push(parse_move(board, line).unwrap_or_else(|e| fail(&e)));
+		}
+	}
+	if in_block {
+		evaluate(&block);
+	}
+}
+
+// Generate puzzles: positions with a single certain hit
+fn run_puzzle<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut count = 1;
+	let seed = seed.unwrap_or(0);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--count" => count = args.next().and_then(|n| n.parse().ok()).or_usage(Some("puzzle"), "--count requires a number"),
+			_ => unknown_argument(Some("puzzle"), &arg),
+		}
+	}
+
+	let mut rng = Rng::new(seed);
+	let mut generated = 0;
+	for _ in 0..count * 1000 {
+		if generated == count {
+			return;
+		}
+
+		if let Some(puzzle) = puzzle::generate(&mut rng, rules) {
+			if generated > 0 {
+				println!();
+			}
+			print!("{}", puzzle.describe());
+			generated += 1;
+		}
+	}
+
+	if generated < count {
+		fail(&format!("Unable to generate {} puzzles under the {} rules", count, rules.name));
+	}
+}
+
+// Show how soon a greedy attacker hits each square of our layout
+fn run_vulnerability<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let path = args.next().or_usage(Some("vulnerability"), "vulnerability requires a placement file");
+	let mut games = 20;
+	let seed = seed.unwrap_or(0);
+	let mut attacker = Attacker::Greedy;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--attacker" => {
+				let desc = args.next().or_usage(Some("vulnerability"), "--attacker requires a model");
+				attacker = Attacker::parse(&desc).unwrap_or_else(|| usage_error(Some("vulnerability"), &format!("Unknown attacker {}; expected one of {}", desc, attackers::ATTACKER_NAMES.join(", "))));
+			},
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("vulnerability"), "--games requires a number"),
+			_ => unknown_argument(Some("vulnerability"), &arg),
+		}
+	}
+
+	let contents = text::read(Path::new(&path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", path, e)));
+	let layout = Layout::from_file_string(&contents, rules.constraints()).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+	let turns = sim::vulnerability(&layout, rules, attacker, games, seed);
+
+	println!("Average turn on which each of your squares is first hit (lower is more vulnerable):");
+	let board = layout.board();
+	let coordinates = board.coordinates();
+	print!("    ");
+	for col in 0..board.size() {
+		print!("{:>5}", coordinates.column_label(col));
+	}
+	println!();
+	for row in 0..board.size() {
+		print!("{:>4}", coordinates.row_label(board.size(), row));
+		for col in 0..board.size() {
+			match turns[board.square_at(row, col).index()] {
+				Some(turn) => print!("{:>5.1}", turn),
+				None => print!("{:>5}", '.'),
+			}
+		}
+		println!();
+	}
+}
+
+// Convert a transcript from another format into the moves file format
+fn run_import<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let format = args.next().or_usage(Some("import"), "import requires a format (csv, grid, or json)");
+	let format = import::Format::parse(&format).unwrap_or_else(|| usage_error(Some("import"), &format!("Unknown transcript format {}", format)));
+	let path = args.next().or_usage(Some("import"), "import requires a transcript file");
+	let mut board_size = None;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--board-size" => board_size = Some(args.next().and_then(|n| n.parse().ok()).or_usage(Some("import"), "--board-size requires a size")),
+			_ => unknown_argument(Some("import"), &arg),
+		}
+	}
+
+	// Without --board-size, a transcript that doesn't fit the rules' board is
+	// taken to be for the board it does fit, with a warning
+	let contents = text::read(Path::new(&path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", path, e)));
+	let board = match board_size {
+		Some(size) => Board::new(size, rules.known_board().fleet()).unwrap_or_else(|e| usage_error(Some("import"), &format!("Invalid --board-size: {}", e))),
+		None => match import::infer_board(format, &rules.known_board(), &contents).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))) {
+			Some(board) => {
+				eprintln!("Warning: {} doesn't fit the {}x{} board; assuming it's for a {}x{} board (--board-size overrides)",
+				          path, rules.known_board().size(), rules.known_board().size(), board.size(), board.size());
+				board
+			},
+			None => rules.known_board(),
+		},
+	};
+	let log = import::import(format, &rules.name, &board, &contents).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+	print!("{}", log.to_moves_string());
+}
+
+// Rewrite a record from one coordinate convention and format into another
+fn run_convert<I: Iterator<Item = String>>(mut args: I, rules: &Rules, format: Option<&str>) {
+	let path = args.next().or_usage(Some("convert"), "convert requires a moves, transcript, or layout file");
+	let coords_arg = |args: &mut I, option: &str| {
+		let origin = args.next().unwrap_or_else(|| usage_error(Some("convert"), &format!("{} requires a coordinate origin", option)));
+		Coordinates::parse(&origin).unwrap_or_else(|| usage_error(Some("convert"), &format!("Unknown coordinate origin {} (available: {}, optionally followed by -{})", origin, coords::ORIGIN_NAMES.join(", "), coords::ALPHABET_NAMES.join(" or -"))))
+	};
+
+	// The output format, from the global --format or convert's own --to, and
+	// whether it's JSON (whose schema version may come later)
+	let output_format = |desc: Option<&str>| match desc {
+		Some("json") => (Output::Moves, true),
+		desc => (desc.and_then(Output::parse).or_usage(Some("convert"), "The output format (--format or --to) must be moves, json, grid, or placement"), false),
+	};
 
-		// Compute the starting square for the ship
-		start_square = pos_from_parts(pos / BOARD_SIZE, pos % BOARD_SIZE);
+	let mut input = Input::Moves;
+	let (mut output, mut json) = match format {
+		Some(_) => output_format(format),
+		None => (Output::Moves, false),
+	};
+	let mut version = schema::SCHEMA_VERSION;
+	let (mut from, mut to) = (rules.context.coordinates, rules.context.coordinates);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--from" => {
+				let format = args.next().or_usage(Some("convert"), "--from requires a format (moves, csv, grid, json, or layout)");
+				input = Input::parse(&format).unwrap_or_else(|| usage_error(Some("convert"), &format!("Unknown input format {}", format)));
+			},
+			"--to" => (output, json) = output_format(args.next().as_deref()),
+			"--from-coords" => from = coords_arg(&mut args, "--from-coords"),
+			"--to-coords" => to = coords_arg(&mut args, "--to-coords"),
+			"--schema-version" => version = args.next().and_then(|n| n.parse().ok()).or_usage(Some("convert"), "--schema-version requires a version number"),
+			_ => unknown_argument(Some("convert"), &arg),
+		}
+	}
+
+	let output = if json { Output::Json(version) } else { output };
+	let contents = text::read(Path::new(&path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", path, e)));
+	print!("{}", convert::convert(&contents, input, from, output, to, rules).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))));
+}
 
-		// 1 row per step
-		step_size = BOARD_SIZE;
+// Export a moves file as a JSON game state document
+fn run_export<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let path = args.next().or_usage(Some("export"), "export requires a moves file");
+
+	let board = rules.known_board();
+	let mut state = GameState::new(&board);
+	for cur_move in read_moves_file(&board, &path).unwrap_or_else(|e| fail(&e)) {
+		state.apply_move(cur_move);
 	}
+	println!("{}", schema::export_state(&state, &rules.name));
+}
+
+// Draw the heatmap after every move of a game, as an animated SVG on stdout or
+// one SVG file per move
+fn run_animate<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let mut path = None;
+	let mut frames_dir = None;
+	let mut seconds = 1.0;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--frames" => frames_dir = Some(std::path::PathBuf::from(args.next().or_usage(Some("animate"), "--frames requires a directory"))),
+			"--seconds" => seconds = args.next().and_then(|n| n.parse().ok()).or_usage(Some("animate"), "--seconds requires a number of seconds per frame"),
+			_ if arg.starts_with("--") || path.is_some() => unknown_argument(Some("animate"), &arg),
+			_ => path = Some(arg),
+		}
+	}
+
+	let moves = read_moves_file(&rules.known_board(), &path.or_usage(Some("animate"), "animate requires a moves file")).unwrap_or_else(|e| fail(&e));
+	match frames_dir {
+		Some(dir) => {
+			std::fs::create_dir_all(&dir).unwrap_or_else(|e| fail(&format!("Unable to create {}: {}", dir.display(), e)));
+			let frames = svg::frame_svgs(rules, &moves);
+			for (idx, frame) in frames.iter().enumerate() {
+				let frame_path = dir.join(format!("frame-{:03}.svg", idx));
+				durable::write_unsealed(&frame_path, frame.as_bytes()).unwrap_or_else(|e| fail(&format!("Unable to write {}: {}", frame_path.display(), e)));
+			}
+			println!("Wrote {} frames to {}", frames.len(), dir.display());
+		},
+		None => print!("{}", svg::animated_svg(rules, &moves, seconds)),
+	}
+}
+
+// Play the AI against many random layouts and report how it did
+fn run_tournament<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut games = 100;
+	let seed = seed.unwrap_or(0);
+	let mut verbosity = sim::Verbosity::Silent;
+	let mut log_dir = None;
+	let mut checkpoint: Option<std::path::PathBuf> = None;
+	let mut checkpoint_every = 10;
+	let mut resume = None;
+	let mut profile_path = None;
+	let mut history_path = None;
+	let mut binary_log_path = None;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--profile" => profile_path = Some(args.next().or_usage(Some("tournament"), "--profile requires a file name")),
+			"--history" => history_path = Some(args.next().or_usage(Some("tournament"), "--history requires a file name")),
+			"--binary-log" => binary_log_path = Some(args.next().or_usage(Some("tournament"), "--binary-log requires a file name")),
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("tournament"), "--games requires a number"),
+			"--checkpoint" => checkpoint = Some(args.next().or_usage(Some("tournament"), "--checkpoint requires a file").into()),
+			"--checkpoint-every" => {
+				checkpoint_every = args.next().and_then(|n| n.parse().ok()).or_usage(Some("tournament"), "--checkpoint-every requires a number of games");
+			},
+			"--resume" => resume = Some(std::path::PathBuf::from(args.next().or_usage(Some("tournament"), "--resume requires a checkpoint file"))),
+			"--verbosity" => {
+				verbosity = args.next().and_then(|v| sim::Verbosity::parse(&v)).or_usage(Some("tournament"), "--verbosity requires silent, games, moves, or beliefs");
+			},
+			"--log-dir" => log_dir = Some(std::path::PathBuf::from(args.next().or_usage(Some("tournament"), "--log-dir requires a directory"))),
+			_ => unknown_argument(Some("tournament"), &arg),
+		}
+	}
+
+	// A resumed tournament keeps its original size and seed, and keeps checkpointing
+	// to the file it was resumed from unless told otherwise
+	let start = match resume {
+		Some(path) => {
+			let contents = durable::read(&path, rules.context.allow_unsealed).unwrap_or_else(|e| fail(&format!("Unable to read checkpoint file {}: {}", path.display(), e)));
+			if checkpoint.is_none() {
+				checkpoint = Some(path);
+			}
+			sim::Checkpoint::from_file_string(&contents).unwrap_or_else(|e| fail(&format!("Invalid checkpoint: {}", e)))
+		},
+		None => sim::Checkpoint::new(games, seed, rules),
+	};
 
-	// Compute the ship span from the given starting square and step size
-	(0..ship_size(shiptype)).map(|v| start_square + step_size * v).collect()
+	let mut profile = profile_path.as_ref().map(|path| Profile::load(&rules.known_board(), path).unwrap_or_else(|e| fail(&e)));
+	let mut binary_log = binary_log_path.map(|path| LogWriter::create(&path, rules).unwrap_or_else(|e| fail(&e)));
+	let mut history = history_path.as_ref().map(|path| History::load(path, rules.context.allow_unsealed).unwrap_or_else(|e| fail(&e)));
+	let mut bar = ProgressBar::new("Playing");
+	let mut on_progress = |progress: Progress| bar.update(progress, &format!("{} of {} games", progress.done, progress.total));
+	let records = sim::Records { profile: profile.as_mut(), binary_log: binary_log.as_mut(), history: history.as_mut(), on_progress: Some(&mut on_progress) };
+	let stdout = std::io::stdout();
+	sim::run_tournament(start, rules, verbosity, &mut stdout.lock(), log_dir.as_deref(),
+	                    checkpoint.as_deref().map(|path| (path, checkpoint_every)), records).unwrap_or_else(|e| fail(&format!("Tournament failed: {}", e)));
+	drop(bar);
+	if let Some(log) = binary_log {
+		log.finish().unwrap_or_else(|e| fail(&format!("Unable to write the binary log: {}", e)));
+	}
+	if let (Some(path), Some(profile)) = (profile_path, profile) {
+		profile.save(&path).unwrap_or_else(|e| fail(&e));
+	}
+	if let (Some(path), Some(history)) = (history_path, history) {
+		history.save(&path).unwrap_or_else(|e| fail(&e));
+	}
 }
 
-// Check if the given ship positions overlap
-fn calc_has_overlap(ship1: ShipType, pos1: u8, ship2: ShipType, pos2: u8) -> bool {
-	let range1 = ship_range(ship1, pos1);
-	let range2 = ship_range(ship2, pos2);
+// Run a tournament by handing batches of games to workers that connect over TCP
+#[cfg(feature = "network")]
+fn run_coordinate<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut games = 100;
+	let seed = seed.unwrap_or(0);
+	let mut batch_size = 10;
+	let mut listen = String::from("127.0.0.1:7878");
+	let mut local_workers = 0;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("coordinate"), "--games requires a number"),
+			"--batch" => batch_size = args.next().and_then(|n| n.parse().ok()).or_usage(Some("coordinate"), "--batch requires a number of games"),
+			"--listen" => listen = args.next().or_usage(Some("coordinate"), "--listen requires an address"),
+			"--local-workers" => local_workers = args.next().and_then(|n| n.parse().ok()).or_usage(Some("coordinate"), "--local-workers requires a number"),
+			_ => unknown_argument(Some("coordinate"), &arg),
+		}
+	}
+
+	let batches = distribute::plan_batches(games, seed, rules, batch_size).unwrap_or_else(|e| fail(&e));
+	let listener = Listener::bind(&listen).unwrap_or_else(|e| fail(&e));
+	let addr = listener.local_addr().unwrap_or_else(|e| fail(&format!("Listener has no address: {}", e)));
+	println!("Waiting for workers on {} ({} batches)", addr, batches.len());
+
+	// Local workers run in this process, but talk to the coordinator like any other
+	for _ in 0..local_workers {
+		let (addr, rules) = (addr.clone(), rules.clone());
+		std::thread::spawn(move || {
+			if let Err(e) = distribute::work(&addr, &rules) {
+				eprintln!("local worker: {}", e);
+			}
+		});
+	}
 
-	range1.iter().any(|p| range2.contains(p))
+	let mut on_batch = |batch: &distribute::Batch, shots: &[usize]| {
+		println!("games {}-{}: average {:.2} shots", batch.first + 1, batch.first + batch.count,
+		         shots.iter().sum::<usize>() as f64 / shots.len().max(1) as f64);
+	};
+	let summary = distribute::coordinate(listener, batches, rules, &mut on_batch, &|e| eprintln!("{}", e)).unwrap_or_else(|e| fail(&e));
+	println!("{} games, average {:.2} shots, best {}, worst {}", summary.shots.len(), summary.average(),
+	         summary.shots.iter().min().unwrap_or(&0), summary.shots.iter().max().unwrap_or(&0));
 }
 
-// Generate the overlap cache
-fn gen_overlap_cache() -> [[Vec<Vec<bool>>; NUM_SHIP_TYPES]; NUM_SHIP_TYPES] {
-	// The variable we will be outputting
-	let mut out = [[Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()],
-	               [Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()],
-	               [Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()],
-	               [Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()],
-	               [Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()]];
+// Play batches of a coordinated tournament until it is done
+#[cfg(feature = "network")]
+fn run_worker<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let addr = args.next().or_usage(Some("worker"), "worker requires the coordinator's address");
+	let played = distribute::work(&addr, rules).unwrap_or_else(|e| fail(&e));
+	println!("Played {} games", played);
+}
 
-	// Go through each ship type combination and fill out the overlap vector
-	for stype1_idx in 0..NUM_SHIP_TYPES {
-		let stype1 = SHIP_TYPES[stype1_idx];
+// Serve one game's recommendations over TCP or a Unix domain socket
+#[cfg(feature = "network")]
+fn run_serve<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let mut listen = String::from("127.0.0.1:7878");
+	let mut strategy_name = String::from("greedy");
+	let mut time_limit = None;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--listen" => listen = args.next().or_usage(Some("serve"), "--listen requires an address"),
+			"--strategy" => strategy_name = args.next().or_usage(Some("serve"), "--strategy requires a strategy name"),
+			"--time-limit" => {
+				let millis = args.next().and_then(|n| n.parse().ok()).or_usage(Some("serve"), "--time-limit requires a number of milliseconds");
+				time_limit = Some(Duration::from_millis(millis));
+			},
+			_ => unknown_argument(Some("serve"), &arg),
+		}
+	}
 
-		for stype2_idx in 0..NUM_SHIP_TYPES {
-			let stype2 = SHIP_TYPES[stype2_idx];
+	let strategy = strategy::load(&strategy_name, 0).unwrap_or_else(|e| fail(&e));
+	let mut server = Server::new(rules, strategy, time_limit);
+	let listener = Listener::bind(&listen).unwrap_or_else(|e| fail(&e));
+	println!("Serving recommendations on {}", listener.local_addr().unwrap_or_else(|e| fail(&format!("Listener has no address: {}", e))));
+	server.serve(&listener, &mut |e| eprintln!("{}", e)).unwrap_or_else(|e| fail(&e));
+}
 
-			// Resize the vector to be as minimal as possible
-			out[stype1_idx][stype2_idx] = Vec::with_capacity(num_positions(stype1) as usize);
+// Summarize a binary game log, or print one of its games as a placement file
+// and a moves file
+fn run_binlog<I: Iterator<Item = String>>(mut args: I, context: &Context) {
+	let mut path = None;
+	let mut game = None;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--game" => game = Some(args.next().and_then(|n| n.parse::<usize>().ok()).filter(|&n| n > 0).or_usage(Some("binlog"), "--game requires a game number")),
+			_ if arg.starts_with("--") || path.is_some() => unknown_argument(Some("binlog"), &arg),
+			_ => path = Some(arg),
+		}
+	}
 
-			// Iterate through the first ship positions and push back vectors of overlap solutions
-			for pos1 in 0..num_positions(stype1) {
-				out[stype1_idx][stype2_idx].push((0..num_positions(stype2)).map(|pos2| {
-					calc_has_overlap(stype1, pos1, stype2, pos2)
-				}).collect());
+	let path = path.or_usage(Some("binlog"), "binlog requires a log file");
+	let file = std::fs::File::open(&path).unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", path, e)));
+	let reader = LogReader::new(std::io::BufReader::new(file), context).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+	let mut shots = Vec::new();
+	for (idx, record) in reader.enumerate() {
+		let record = record.unwrap_or_else(|e| fail(&format!("{}: game {}: {}", path, idx + 1, e)));
+		if game == Some(idx + 1) {
+			print!("{}", record.layout.to_file_string());
+			println!();
+			for &cur_move in &record.shots {
+				println!("{}", format_move(cur_move));
 			}
+			return;
 		}
+		shots.push(record.shots.len());
 	}
 
-	out
+	match game {
+		Some(game) => usage_error(Some("binlog"), &format!("{} has only {} games, not {}", path, shots.len(), game)),
+		None => println!("{} games, average {:.2} shots, best {}, worst {}", shots.len(),
+		                 shots.iter().sum::<usize>() as f64 / shots.len().max(1) as f64, shots.iter().min().unwrap_or(&0), shots.iter().max().unwrap_or(&0)),
+	}
 }
 
-// Fast overlap checker (uses the provided cache)
-#[inline]
-fn has_overlap(ship1: ShipType, pos1: u8, ship2: ShipType, pos2: u8, cache: &[[Vec<Vec<bool>>; NUM_SHIP_TYPES]; NUM_SHIP_TYPES]) -> bool {
-	cache[stype_id(ship1) as usize][stype_id(ship2) as usize][pos1 as usize][pos2 as usize]
+// Show the per-square profile, or record a finished game's moves file in it
+fn run_profile<I: Iterator<Item = String>>(mut args: I, board: &Board) {
+	let mut profile_path = String::from("profile.txt");
+	let mut record = None;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--profile" => profile_path = args.next().or_usage(Some("profile"), "--profile requires a file name"),
+			"record" => record = Some(args.next().or_usage(Some("profile"), "profile record requires a moves file")),
+			_ => unknown_argument(Some("profile"), &arg),
+		}
+	}
+
+	let mut profile = Profile::load(board, &profile_path).unwrap_or_else(|e| fail(&e));
+	if let Some(moves_path) = record {
+		profile.record_moves(&read_moves_file(board, &moves_path).unwrap_or_else(|e| fail(&e)));
+		profile.save(&profile_path).unwrap_or_else(|e| fail(&e));
+	}
+	print!("{}", profile.render());
 }
 
-// Read in the moves list from the input file
-fn read_moves() -> Vec<(BoardPos, Option<ShipType>)> {
+// Manage the named opponent profiles: add, list, show, delete, or record a
+// finished game against one
+fn run_opponent<I: Iterator<Item = String>>(mut args: I, board: &Board) {
+	let mut dir = String::from(opponent::DEFAULT_DIR);
+	let mut notes = Vec::new();
+	let mut positional = Vec::new();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--dir" => dir = args.next().or_usage(Some("opponent"), "--dir requires a directory"),
+			"--note" => notes.push(args.next().or_usage(Some("opponent"), "--note requires some text")),
+			_ if arg.starts_with("--") => unknown_argument(Some("opponent"), &arg),
+			_ => positional.push(arg),
+		}
+	}
+
+	let opponents = Opponents::new(Path::new(&dir), board);
+	let name = |idx: usize| positional.get(idx).map(String::as_str).or_usage(Some("opponent"), "opponent requires an opponent name");
+	match positional.first().map(String::as_str) {
+		Some("add") => {
+			let mut opponent = Opponent::new(name(1), board);
+			opponent.notes = notes;
+			opponents.add(&opponent).unwrap_or_else(|e| fail(&e));
+			println!("Added {}", opponent.name);
+		},
+		Some("list") => {
+			for name in opponents.list().unwrap_or_else(|e| fail(&e)) {
+				let opponent = opponents.load(&name).unwrap_or_else(|e| fail(&e));
+				println!("{}: {} games", name, opponent.history.games());
+			}
+		},
+		Some("show") => print!("{}", opponents.load(name(1)).unwrap_or_else(|e| fail(&e)).render()),
+		Some("delete") => {
+			opponents.delete(name(1)).unwrap_or_else(|e| fail(&e));
+			println!("Deleted {}", name(1));
+		},
+		Some("record") => {
+			let mut opponent = opponents.load(name(1)).unwrap_or_else(|e| fail(&e));
+			let moves = read_moves_file(opponent.profile.board(), positional.get(2).or_usage(Some("opponent"), "opponent record requires a moves file")).unwrap_or_else(|e| fail(&e));
+			opponent.record_game(&moves);
+			opponent.notes.extend(notes);
+			opponents.save(&opponent).unwrap_or_else(|e| fail(&e));
+			println!("Recorded a game of {} shots against {}", moves.len(), opponent.name);
+		},
+		_ => fail("opponent requires add, list, show, delete, or record"),
+	}
+}
+
+// Commit to a layout before a game: print the commitment to publish, and save
+// the reveal to publish once the game is over
+fn run_commit<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let mut layout_path = None;
+	let mut reveal_path = None;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--reveal" => reveal_path = Some(args.next().or_usage(Some("commit"), "--reveal requires a file name")),
+			_ if arg.starts_with("--") || layout_path.is_some() => unknown_argument(Some("commit"), &arg),
+			_ => layout_path = Some(arg),
+		}
+	}
+
+	let layout_path = layout_path.or_usage(Some("commit"), "commit requires a placement file");
+	let contents = text::read(Path::new(&layout_path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", layout_path, e)));
+	let layout = Layout::from_file_string(&contents, rules.constraints()).unwrap_or_else(|e| fail(&format!("{}: {}", layout_path, e)));
+	if !layout.is_complete() {
+		fail(&format!("{} is missing ships", layout_path));
+	}
+
+	let reveal = Reveal::new(layout).unwrap_or_else(|e| fail(&e));
+	let reveal_path = reveal_path.unwrap_or_else(|| format!("{}.reveal", layout_path));
+	durable::write_unsealed(Path::new(&reveal_path), reveal.to_file_string().as_bytes()).unwrap_or_else(|e| fail(&format!("Unable to write {}: {}", reveal_path, e)));
+	println!("Commitment: {}", reveal.commitment());
+	println!("Keep {} secret until the game is over, then publish it", reveal_path);
+}
+
+// Check an opponent's reveal against their commitment and the results they
+// reported for our shots
+fn run_verify<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let commitment = Commitment::parse(&args.next().or_usage(Some("verify"), "verify requires a commitment")).unwrap_or_else(|e| fail(&e));
+	let reveal_path = args.next().or_usage(Some("verify"), "verify requires a reveal file");
+	let moves_path = args.next().or_usage(Some("verify"), "verify requires a moves file");
+
+	let contents = text::read(Path::new(&reveal_path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", reveal_path, e)));
+	let reveal = Reveal::from_file_string(&contents, rules.constraints()).unwrap_or_else(|e| fail(&format!("{}: {}", reveal_path, e)));
+	match reveal.verify(&commitment, rules, &read_moves_file(&rules.known_board(), &moves_path).unwrap_or_else(|e| fail(&e))) {
+		Ok(()) => println!("Verified: the layout matches the commitment and every reported result"),
+		Err(e) => {
+			println!("Verification failed: {}", e);
+			std::process::exit(1);
+		},
+	}
+}
+
+// Measure the cheat detector against scripted opponents that lie at random
+fn run_harness<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut games = 20;
+	let seed = seed.unwrap_or(0);
+	let mut lie_rate = 0.05;
+	let mut threshold = 0.01;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("harness"), "--games requires a number"),
+			"--lie-rate" => lie_rate = args.next().and_then(|n| n.parse().ok()).or_usage(Some("harness"), "--lie-rate requires a probability"),
+			"--threshold" => threshold = args.next().and_then(|n| n.parse().ok()).or_usage(Some("harness"), "--threshold requires a probability"),
+			_ => unknown_argument(Some("harness"), &arg),
+		}
+	}
+
+	let reports = harness::run_trials(games, seed, rules, lie_rate, &CheatDetector::new(threshold)).unwrap_or_else(|e| fail(&e));
+	for (game, report) in reports.iter().enumerate() {
+		let contradiction = report.contradiction.map_or_else(String::new, |turn| format!(", contradiction after shot {}", turn + 1));
+		println!("game {}: {} shots, {} lies, {} caught, {} false alarms{}", game + 1, report.shots, report.lies.len(),
+		         report.caught(), report.false_alarms(), contradiction);
+	}
+
+	let lies: usize = reports.iter().map(|report| report.lies.len()).sum();
+	let caught: usize = reports.iter().map(|report| report.caught()).sum();
+	let false_alarms: usize = reports.iter().map(|report| report.false_alarms()).sum();
+	let contradictions = reports.iter().filter(|report| report.contradiction.is_some()).count();
+	println!("{} games: {} of {} lies caught, {} false alarms, {} games contradictory", games, caught, lies, false_alarms, contradictions);
+}
+
+// Play against an HTTP Battleship service, saving the session after every shot
+// so an interrupted game can be resumed
+#[cfg(feature = "network")]
+fn run_remote<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
+	let config_path = args.next().or_usage(Some("remote"), "remote requires a service config file");
+	let mut session_path = String::from("remote-session.txt");
+	let mut tie_break = TieBreak::Lexicographic;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--session" => session_path = args.next().or_usage(Some("remote"), "--session requires a file name"),
+			"--tie-break" => tie_break = tie_break_arg(&mut args, Some("remote")),
+			_ => unknown_argument(Some("remote"), &arg),
+		}
+	}
+
+	let contents = text::read(Path::new(&config_path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", config_path, e)));
+	let config = RemoteConfig::from_file_string(&contents).unwrap_or_else(|e| fail(&format!("{}: {}", config_path, e)));
+	let mut game = match durable::read(Path::new(&session_path), rules.context.allow_unsealed) {
+		Ok(saved) => {
+			println!("Resuming the session saved in {}", session_path);
+			RemoteGame::resume(config, rules, &saved)
+		},
+		Err(ref e) if e.kind() == std::io::ErrorKind::InvalidData => fail(&format!("{}: {}", session_path, e)),
+		Err(_) => RemoteGame::start(config, rules),
+	}.unwrap_or_else(|e| fail(&e));
+
+	let ship_squares = rules.fleet.iter().map(|&(_, size)| size as usize).sum();
+	let mut save = |game: &RemoteGame, cur_move: (BoardPos, ShotResult)| {
+		println!("{}", format_move(cur_move));
+		durable::write(Path::new(&session_path), &game.to_session_string()).unwrap_or_else(|e| fail(&format!("Unable to save the session: {}", e)));
+	};
+	match game.play(&mut Greedy::new(tie_break), ship_squares, &mut save) {
+		Ok(shots) => println!("Game finished after {} shots", shots),
+		Err(e) => fail(&format!("{} (the session is saved; run again to resume)", e)),
+	}
+}
+
+// Play a best-of-N session against a simulated opponent with placement
+// habits, with and without adapting to them between games
+fn run_match<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut games = 9;
+	let seed = seed.unwrap_or(0);
+	let mut decay = 0.8;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("match"), "--games requires a number"),
+			"--decay" => decay = args.next().and_then(|n| n.parse().ok()).or_usage(Some("match"), "--decay requires a number"),
+			_ => unknown_argument(Some("match"), &arg),
+		}
+	}
+
+	for &adapt in &[false, true] {
+		let label = if adapt { "adaptive" } else { "greedy" };
+		let mut on_game = |game: usize, ours: usize, theirs: usize| println!("{} game {}: {} shots against their {}", label, game + 1, ours, theirs);
+		let report = adapt::play_match(games, seed, rules, adapt, decay, &mut on_game)
+			.unwrap_or_else(|| fail(&format!("No valid layout exists under the {} rules", rules.name)));
+		println!("{}: {} wins, {} losses, {} ties, average {:.2} shots", label, report.wins, report.losses, report.ties,
+		         report.shots as f64 / games.max(1) as f64);
+	}
+}
+
+// Take turns with other attackers against one opponent, sharing the board
+// through a file. The AI fires at the given layout if there is one, and
+// otherwise asks for each result on stdin; with --human, the moves themselves
+// come from stdin.
+fn run_team<I: Iterator<Item = String>>(mut args: I, rules: &Rules) {
 	use std::io::BufRead;
 
-	// Open the moves file and create a read buffer for it (needed for line-by-line reading)
-	let filereader = std::io::BufReader::new(std::fs::File::open("moves.txt").expect("Unable to open moves.txt"));
+	let shared_path = args.next().or_usage(Some("team"), "team requires a shared board file");
+	let mut name = None;
+	let mut seat = 0;
+	let mut seats = 2;
+	let mut human = false;
+	let mut layout = None;
+	let mut tie_break = TieBreak::Lexicographic;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--name" => name = Some(args.next().or_usage(Some("team"), "--name requires a party name")),
+			"--seat" => seat = args.next().and_then(|n| n.parse().ok()).or_usage(Some("team"), "--seat requires a number"),
+			"--seats" => seats = args.next().and_then(|n| n.parse().ok()).or_usage(Some("team"), "--seats requires a number"),
+			"--human" => human = true,
+			"--layout" => {
+				let path = args.next().or_usage(Some("team"), "--layout requires a placement file");
+				let contents = text::read(Path::new(&path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", path, e)));
+				layout = Some(Layout::from_file_string(&contents, rules.constraints()).unwrap_or_else(|e| fail(&format!("{}: {}", path, e))));
+			},
+			"--tie-break" => tie_break = tie_break_arg(&mut args, Some("team")),
+			_ => unknown_argument(Some("team"), &arg),
+		}
+	}
+	if seat >= seats {
+		usage_error(Some("team"), "--seat must be less than --seats");
+	}
+	let name = name.unwrap_or_else(|| format!("seat{}", seat));
 
-	// Generate the output vector by processing moves.txt line-by-line
-	filereader.lines().map(|line| {
-		let line = line.expect("Unable to read line in moves.txt");
-		let line_bytes = line.as_bytes();
+	let shared_path = std::path::Path::new(&shared_path);
+	let grid = rules.known_board();
+	let ship_squares = grid.fleet_squares() as usize;
+	let mut board = SharedBoard::new(&grid);
+	let mut strategy = Greedy::new(tie_break);
+	let stdin = std::io::stdin();
+	let mut lines = stdin.lock().lines();
+	let mut waiting = false;
+	loop {
+		for conflict in board.sync(shared_path).unwrap_or_else(|e| fail(&e)) {
+			println!("Conflict: {}", conflict.describe());
+		}
+		if board.hits() >= ship_squares {
+			println!("Game finished after {} shots", board.entries().len());
+			return;
+		}
+		if board.turn(seats) != seat {
+			if !waiting {
+				println!("Waiting for the other attackers");
+				waiting = true;
+			}
+			std::thread::sleep(Duration::from_millis(200));
+			continue;
+		}
+		waiting = false;
 
-		let (pos_col, type_idx) =
-			if (line_bytes.len() >= 3) && (line_bytes[2] == '0' as u8) {
-				(9, 3)
-			} else {
-				(line_bytes[1] as u8 - '1' as u8, 2)
+		let state = board.state(&rules.orientations(&grid));
+		print!("{}", state.render());
+		let shot = match strategy.choose_shot(&state) {
+			Some(shot) => shot,
+			None => {
+				println!("Every square has been shot");
+				return;
+			},
+		};
+		let cur_move = if human {
+			println!("Best shot: {}", shot);
+			let line = match lines.next() {
+				Some(line) => line.unwrap_or_else(|e| fail(&format!("Unable to read move from stdin: {}", e))),
+				None => return,
 			};
+			match parse_move(&grid, &line) {
+				Ok(cur_move) => cur_move,
+				Err(e) => {
+					println!("{}", e);
+					continue;
+				},
+			}
+		} else if let Some(ref layout) = layout {
+			(shot, rules.reported(layout.shot_result(shot)))
+		} else {
+			println!("Fire at {}; enter the result (miss, H, or a ship letter)", shot);
+			let line = match lines.next() {
+				Some(line) => line.unwrap_or_else(|e| fail(&format!("Unable to read result from stdin: {}", e))),
+				None => return,
+			};
+			let result = line.trim();
+			let result = if result.eq_ignore_ascii_case("miss") { "" } else { result };
+			match parse_move(&grid, &format!("{}{}", shot, result)) {
+				Ok(cur_move) => cur_move,
+				Err(e) => {
+					println!("{}", e);
+					continue;
+				},
+			}
+		};
 
-		(pos_from_parts(line_bytes[0] as u8 - 'A' as u8, pos_col),
-			if line_bytes.len() > type_idx {
-				Some(decode_shiptype(line_bytes[type_idx]))
-			} else {
-				None
+		println!("{}", format_move(cur_move));
+		if let Err(conflict) = board.record(Entry { pos: cur_move.0, result: cur_move.1, party: name.clone() }) {
+			println!("Conflict: {}", conflict.describe());
+		}
+	}
+}
+
+// How a game against a human goes, besides the AI's strategy and fleet
+struct PlayOptions {
+	// The human's layout, to resolve the AI's shots against; without one, the
+	// human enters each result
+	layout: Option<Layout>,
+
+	// How long the AI thinks over each shot, for streamed games
+	thinking: Option<Duration>,
+
+	// The strongest hint the human may ask for with "hint", or 0 for none
+	hints: u8,
+}
+
+// The seed to use when none is given, different from run to run
+fn clock_seed() -> u64 {
+	std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
+
+// Read the human's layout for --layout
+fn layout_arg<I: Iterator<Item = String>>(args: &mut I, rules: &Rules, command: Option<&str>) -> Layout {
+	let path = args.next().or_usage(command, "--layout requires a placement file");
+	let contents = text::read(Path::new(&path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", path, e)));
+	let layout = Layout::from_file_string(&contents, rules.constraints()).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+	if !layout.is_complete() {
+		fail(&format!("{} is missing ships", path));
+	}
+	layout
+}
+
+// Play a game against a human, each side firing at the other's fleet in
+// turn. The AI's fleet is random; its shots are resolved against the human's
+// layout if one is given, and otherwise the human enters each result. The
+// difficulty picks how well the AI shoots.
+fn run_play<I: Iterator<Item = String>>(mut args: I, rules: &Rules, locale: &dyn Locale, seed: Option<u64>) {
+	use std::io::BufRead;
+
+	let mut difficulty = Difficulty::Inference;
+	let seed = seed.unwrap_or_else(clock_seed);
+	let mut options = PlayOptions { layout: None, thinking: None, hints: 0 };
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--difficulty" => {
+				let desc = args.next().or_usage(Some("play"), "--difficulty requires a level");
+				difficulty = Difficulty::parse(&desc)
+					.unwrap_or_else(|| usage_error(Some("play"), &format!("Unknown difficulty {}; expected one of {}", desc, strategy::DIFFICULTY_NAMES.join(", "))));
+			},
+			"--think" => options.thinking = Some(Duration::from_millis(args.next().and_then(|n| n.parse().ok()).or_usage(Some("play"), "--think requires a number of milliseconds"))),
+			"--layout" => options.layout = Some(layout_arg(&mut args, rules, Some("play"))),
+			_ => unknown_argument(Some("play"), &arg),
+		}
+	}
+
+	let mut rng = Rng::new(seed);
+	let ours = Layout::random(&mut rng, rules.constraints()).unwrap_or_else(|| fail(&format!("No valid layout exists under the {} rules", rules.name)));
+	let mut strategy = difficulty.strategy(rng.next_u64());
+	let stdin = std::io::stdin();
+	play_against(rules, &mut *strategy, ours, &options, locale, &mut stdin.lock().lines());
+}
+
+// Play one game of run_play with the strategy and fleet, reading the human's
+// input from lines. Returns whether the human won and how many shots they
+// fired, or None if the input ran out first. Under salvo rules each side
+// fires one shot per ship it has afloat each turn, and hears the results
+// once the whole salvo is in.
+fn play_against(rules: &Rules, strategy: &mut dyn Strategy, mut ours: Layout, options: &PlayOptions, locale: &dyn Locale,
+                lines: &mut dyn Iterator<Item = std::io::Result<String>>) -> Option<(bool, usize)> {
+	strategy.on_game_start(rules);
+	let board = rules.known_board();
+	let ship_squares = board.fleet_squares();
+
+	// The human's shots at our fleet, and ours at theirs. fired holds the
+	// squares shot that still count towards sinking a ship, which a
+	// relocated ship's new squares don't.
+	let mut theirs = rules.new_game();
+	let mut fired = Vec::new();
+	let mut their_shots = 0;
+	let mut state = rules.new_game();
+	let (mut their_hits, mut our_hits) = (0, 0);
+
+	loop {
+		// The human's salvo: one shot per ship of theirs we haven't sunk, as far
+		// as the results tell
+		let salvo = rules.shots_per_turn(board.fleet().len() - sunk_ships(&state));
+		let mut aimed = Vec::new();
+		while aimed.len() < salvo {
+			print!("{}", theirs.render());
+			match salvo {
+				1 => println!("Your shot:"),
+				_ => println!("Your shot ({} of {}):", aimed.len() + 1, salvo),
+			}
+			let line = match lines.next() {
+				Some(line) => line.unwrap_or_else(|e| fail(&format!("Unable to read shot from stdin: {}", e))),
+				None => return None,
+			};
+
+			// "hint" gives a hint towards a good shot, when hints are allowed
+			if options.hints > 0 && line.trim().eq_ignore_ascii_case("hint") {
+				match Greedy::new(TieBreak::Lexicographic).choose_shot(&theirs) {
+					Some(best) => println!("{}", locale.text(&Message::Hint(&hint::hint(&theirs, best, options.hints)))),
+					None => println!("{}", locale.text(&Message::NoSquaresLeft)),
+				}
+				continue;
+			}
+
+			// Under moving-ships rules, "move" spends the turn relocating one of
+			// the human's ships
+			if rules.moving_ships && line.trim().eq_ignore_ascii_case("move") {
+				if options.layout.is_some() {
+					println!("The ships in a --layout file can't move");
+					continue;
+				}
+				if state.relocation().is_some() {
+					println!("You have already moved a ship");
+					continue;
+				}
+				if !aimed.is_empty() {
+					println!("A ship can only move in place of a whole salvo");
+					continue;
+				}
+				state.apply_relocation();
+				println!("You relocate a ship");
+				break;
+			}
+
+			match parse_square(&board, line.trim()) {
+				Some(pos) if !theirs.is_shot(pos) => {
+					theirs.mark_pending(pos);
+					aimed.push(pos);
+				},
+				Some(pos) => println!("{} was already shot", pos),
+				None => println!("Invalid square {}", line.trim()),
+			}
+		}
+
+		for pos in aimed {
+			let result = rules.reported(ours.shot_result_after(pos, &fired));
+			fired.push(pos);
+			their_shots += 1;
+			theirs.apply_move((pos, result));
+			strategy.on_opponent_shot((pos, result));
+			println!("{}", format_move((pos, result)));
+			if result != ShotResult::Miss {
+				their_hits += 1;
+			}
+			if their_hits >= ship_squares {
+				println!("You sank the fleet in {} shots", their_shots);
+				strategy.on_game_end(&GameResult { moves: state.shots().to_vec(), sank_fleet: false, won: Some(false) });
+				return Some((true, their_shots));
+			}
+		}
+
+		// Under moving-ships rules we may spend the turn moving a ship in
+		// danger instead of firing
+		if rules.moving_ships {
+			if let Some(plan) = relocation::plan(&ours, &theirs) {
+				ours.relocate(plan.stype, plan.to).expect("A planned relocation doesn't fit");
+				let squares = board.ship_range(plan.stype, plan.to);
+				fired.retain(|square| !squares.contains(square));
+				theirs.apply_relocation();
+				println!("I relocate a ship instead of firing");
+				continue;
+			}
+		}
+
+		// Our salvo, every shot of it chosen before any result comes in
+		let afloat = ours.ships().iter().filter(|&&(stype, pos)| !board.ship_range(stype, pos).iter().all(|square| fired.contains(square))).count();
+		let salvo = rules.shots_per_turn(afloat);
+		let mut aimed = Vec::new();
+		for _ in 0..salvo {
+			if let Some(delay) = options.thinking {
+				think(&mut state, "Thinking", delay, None);
 			}
-		)
-	}).collect()
+			match strategy.choose_shot(&state) {
+				Some(shot) => {
+					state.mark_pending(shot);
+					aimed.push(shot);
+				},
+				None => break,
+			}
+		}
+		if aimed.is_empty() {
+			println!("Every square has been shot");
+			return None;
+		}
+		if aimed.len() > 1 {
+			println!("I fire a salvo at {}", aimed.iter().map(|pos| pos.to_string()).collect::<Vec<String>>().join(", "));
+		}
+
+		for shot in aimed {
+			let cur_move = match options.layout {
+				Some(ref layout) => (shot, rules.reported(layout.shot_result_after(shot, &state.shots().iter().map(|&(pos, _)| pos).collect::<Vec<_>>()))),
+				None => loop {
+					println!("I fire at {}; enter the result (miss, H, or a ship letter)", shot);
+					let line = match lines.next() {
+						Some(line) => line.unwrap_or_else(|e| fail(&format!("Unable to read result from stdin: {}", e))),
+						None => return None,
+					};
+					let result = line.trim();
+					let result = if result.eq_ignore_ascii_case("miss") { "" } else { result };
+					match parse_move(&board, &format!("{}{}", shot, result)) {
+						Ok(cur_move) if state.result_probability(cur_move) > 0.0 => break cur_move,
+						Ok(_) => println!("That result contradicts the earlier ones"),
+						Err(e) => println!("{}", e),
+					}
+				},
+			};
+			state.apply_move(cur_move);
+			timing::end_move();
+			println!("I fire: {}", format_move(cur_move));
+			if cur_move.1 != ShotResult::Miss {
+				our_hits += 1;
+			}
+			if our_hits >= ship_squares {
+				println!("I sank your fleet in {} shots", state.shots().len());
+				strategy.on_game_end(&GameResult { moves: state.shots().to_vec(), sank_fleet: true, won: Some(true) });
+				return Some((false, their_shots));
+			}
+		}
+	}
 }
 
-// Apply the effect of a miss on the position lists
-fn process_miss(pos_positions: &mut Vec<Vec<u8>>, pos: BoardPos) {
-	for stype_idx in 0..pos_positions.len() {
-		let plist = &mut pos_positions[stype_idx];
+// The number of ships the moves so far have sunk
+fn sunk_ships(state: &GameState) -> usize {
+	state.shots().iter().filter(|&&(_, result)| matches!(result, ShotResult::Sunk(_))).count()
+}
 
-		let mut idx = 0;
+// Play the campaign: games against each difficulty in turn, easiest first,
+// moving on after each win, with the progress kept in a file between games
+// and sessions
+fn run_campaign<I: Iterator<Item = String>>(mut args: I, rules: &Rules, locale: &dyn Locale, seed: Option<u64>) {
+	use std::io::BufRead;
 
-		while idx < plist.len() {
-			// Check if the given position overlaps the miss
-			if ship_range(SHIP_TYPES[stype_idx], plist[idx]).contains(&pos) {
-				plist.swap_remove(idx);
-			} else {
-				idx += 1;
+	let mut path = String::from(campaign::DEFAULT_PATH);
+	let seed = seed.unwrap_or_else(clock_seed);
+	let mut options = PlayOptions { layout: None, thinking: None, hints: 0 };
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--progress" => path = args.next().or_usage(Some("campaign"), "--progress requires a file name"),
+			"--think" => options.thinking = Some(Duration::from_millis(args.next().and_then(|n| n.parse().ok()).or_usage(Some("campaign"), "--think requires a number of milliseconds"))),
+			"--layout" => options.layout = Some(layout_arg(&mut args, rules, Some("campaign"))),
+			_ => unknown_argument(Some("campaign"), &arg),
+		}
+	}
+
+	let mut campaign = Campaign::load(&path, rules.context.allow_unsealed).unwrap_or_else(|e| fail(&e));
+	let mut rng = Rng::new(seed);
+	let stdin = std::io::stdin();
+	let mut lines = stdin.lock().lines();
+	print!("{}", campaign.render());
+	while let Some(difficulty) = campaign.difficulty() {
+		println!("Level {} of {}: {}", campaign.level() + 1, campaign.levels(), strategy::DIFFICULTY_NAMES[campaign.level()]);
+		options.hints = campaign.hint_strength();
+		if options.hints > 0 {
+			println!("Hints up to strength {} are unlocked; enter \"hint\" for one", options.hints);
+		}
+
+		let ours = Layout::random(&mut rng, rules.constraints()).unwrap_or_else(|| fail(&format!("No valid layout exists under the {} rules", rules.name)));
+		let mut strategy = difficulty.strategy(rng.next_u64());
+		let (won, shots) = match play_against(rules, &mut *strategy, ours, &options, locale, &mut lines) {
+			Some(outcome) => outcome,
+			None => return,
+		};
+		if campaign.record(won, shots) {
+			println!("Level {} beaten", campaign.level());
+		}
+		campaign.save(&path).unwrap_or_else(|e| fail(&e));
+		print!("{}", campaign.render());
+	}
+	println!("You have beaten every level");
+}
+
+// Cross-check the optimized inference against the slow reference on random
+// games, reporting the first move where they disagree
+fn run_crosscheck<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut games = 10;
+	let seed = seed.unwrap_or(0);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("crosscheck"), "--games requires a number"),
+			_ => unknown_argument(Some("crosscheck"), &arg),
+		}
+	}
+
+	let mut on_game = |game: usize, shots: usize| println!("game {}: {} moves agree", game + 1, shots);
+	match reference::cross_check(games, seed, rules, &mut on_game).unwrap_or_else(|e| fail(&e)) {
+		None => println!("{} games: the optimized inference matches the reference", games),
+		Some(divergence) => {
+			let last = *divergence.moves.last().expect("Divergence without moves");
+			println!("game {}: divergence after move {} ({})", divergence.game + 1, divergence.moves.len(), format_move(last));
+			println!("Moves:");
+			for &cur_move in &divergence.moves {
+				println!("{}", format_move(cur_move));
 			}
+			println!("Reference ({} configurations):\n{}", divergence.expected.total, divergence.expected.render(&rules.known_board()));
+			println!("Optimized ({} configurations):\n{}", divergence.actual.total, divergence.actual.render(&rules.known_board()));
+			std::process::exit(1);
+		},
+	}
+}
+
+// Solve a tiny game exactly, and compare the greedy policy with the optimum
+fn run_solve<I: Iterator<Item = String>>(mut args: I, meter: &Meter) {
+	let mut board_size = 5;
+	let mut fleet = vec![4, 5];
+	let mut labeled = false;
+	let mut objective = Objective::Expected;
+	let mut show_policy = false;
+	let mut cancel = CancelToken::new();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--time-limit" => {
+				let millis = args.next().and_then(|n| n.parse().ok()).or_usage(Some("solve"), "--time-limit requires a number of milliseconds");
+				cancel = CancelToken::with_timeout(Duration::from_millis(millis));
+			},
+			"--board" => board_size = args.next().and_then(|n| n.parse().ok()).or_usage(Some("solve"), "--board requires a board size"),
+			"--fleet" => {
+				let sizes = args.next().or_usage(Some("solve"), "--fleet requires ship sizes, e.g. 4,5");
+				fleet = sizes.split(',').map(|size| size.trim().parse().unwrap_or_else(|_| usage_error(Some("solve"), &format!("Invalid ship size {}", size)))).collect();
+			},
+			"--labeled" => labeled = true,
+			"--objective" => objective = args.next().and_then(|o| Objective::parse(&o)).or_usage(Some("solve"), "--objective requires expected or worst"),
+			"--policy" => show_policy = true,
+			_ => unknown_argument(Some("solve"), &arg),
 		}
 	}
+
+	let mut solver = Solver::new(board_size, &fleet, labeled, objective, meter).unwrap_or_else(|e| fail(&e));
+	let optimal = solver.optimal_value(&cancel).unwrap_or_else(|e| fail(&e));
+	let greedy = solver.greedy_value();
+	let measure = match objective {
+		Objective::Expected => "expected",
+		Objective::Worst => "worst-case",
+	};
+	println!("{} layouts of {:?} on a {}x{} board", solver.layouts(), fleet, board_size, board_size);
+	println!("Optimal policy: {:.4} {} shots", optimal, measure);
+	println!("Greedy policy:  {:.4} {} shots ({:+.4})", greedy, measure, greedy - optimal);
+	if show_policy {
+		print!("{}", solver.policy(&cancel).unwrap_or_else(|e| fail(&e)).render(labeled));
+	}
 }
 
-// Apply the effect of a hit on the given ship type
-fn process_hit(poslist: &mut Vec<u8>, stype: ShipType, pos: BoardPos) {
-	let mut idx = 0;
+// Generate training data from self-play, one JSON line per position, with
+// optional exploration noise in the shot selection
+#[cfg(feature = "selfplay")]
+fn run_selfplay<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut games = 100;
+	let seed = seed.unwrap_or(0);
+	let mut exploration = selfplay::NO_EXPLORATION;
+	let mut out_path = String::from("selfplay.jsonl");
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("selfplay"), "--games requires a number"),
+			"--temperature" => exploration.temperature = args.next().and_then(|n| n.parse().ok()).filter(|&t: &f64| t >= 0.0)
+				.or_usage(Some("selfplay"), "--temperature requires a nonnegative number"),
+			"--dirichlet" => {
+				let (alpha, epsilon) = args.next().as_deref().and_then(selfplay::parse_noise)
+					.or_usage(Some("selfplay"), "--dirichlet requires <alpha>:<epsilon>, with alpha positive and epsilon in [0, 1]");
+				exploration = Exploration { alpha, epsilon, ..exploration };
+			},
+			"--out" => out_path = args.next().or_usage(Some("selfplay"), "--out requires a file name"),
+			_ => unknown_argument(Some("selfplay"), &arg),
+		}
+	}
 
-	while idx < poslist.len() {
-		// Check if the given position overlaps the hit
-		if ship_range(stype, poslist[idx]).contains(&pos) {
-			// It overlaps, so this position is acceptable
-			idx += 1;
-		} else {
-			// No overlap; remove this position
-			poslist.swap_remove(idx);
+	let mut positions = 0;
+	let tracker = Tracker::new(games as u64);
+	let mut bar = ProgressBar::new("Generating");
+	let mut on_game = |game: usize, shots: usize| {
+		positions += shots;
+		bar.update(tracker.at(game as u64 + 1), &format!("{} positions", positions));
+	};
+	let data = selfplay::generate(games, seed, rules, exploration, &mut on_game).unwrap_or_else(|e| fail(&e));
+	durable::write_unsealed(Path::new(&out_path), data.as_bytes()).unwrap_or_else(|e| fail(&format!("Unable to write {}: {}", out_path, e)));
+	println!("Wrote {} positions from {} games to {}", positions, games, out_path);
+}
+
+// Generate random, consistent transcripts, printing them or writing one file
+// per transcript, and optionally seeding the fuzz corpora with them
+fn run_generate<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>, format: Option<&str>) {
+	let mut count = 10;
+	let seed = seed.unwrap_or(0);
+	let mut length = None;
+	let format = match format {
+		Some(desc) => generate::Format::parse(desc).or_usage(Some("generate"), "--format requires moves, csv, grid, or json"),
+		None => generate::Format::Moves,
+	};
+	let mut out_dir = None;
+	let mut corpus_dir = None;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--count" => count = args.next().and_then(|n| n.parse().ok()).or_usage(Some("generate"), "--count requires a number"),
+			"--length" => length = Some(args.next().as_deref().and_then(Length::parse).or_usage(Some("generate"), "--length requires <shots> or <min>-<max>")),
+			"--out-dir" => out_dir = Some(std::path::PathBuf::from(args.next().or_usage(Some("generate"), "--out-dir requires a directory"))),
+			"--fuzz-corpus" => corpus_dir = Some(std::path::PathBuf::from(args.next().or_usage(Some("generate"), "--fuzz-corpus requires a directory"))),
+			_ => unknown_argument(Some("generate"), &arg),
 		}
 	}
+
+	let transcripts = generate::random_transcripts(count, seed, rules, length).unwrap_or_else(|e| fail(&e));
+	let write = |path: std::path::PathBuf, contents: &[u8]| {
+		if let Some(dir) = path.parent() {
+			std::fs::create_dir_all(dir).unwrap_or_else(|e| fail(&format!("Unable to create {}: {}", dir.display(), e)));
+		}
+		durable::write_unsealed(&path, contents).unwrap_or_else(|e| fail(&format!("Unable to write {}: {}", path.display(), e)));
+	};
+	for (idx, transcript) in transcripts.iter().enumerate() {
+		let contents = transcript.to_format_string(format);
+		match out_dir {
+			Some(ref dir) => write(dir.join(format!("transcript-{:04}.{}", idx + 1, format.extension())), contents.as_bytes()),
+			None if idx > 0 => print!("\n{}", contents),
+			None => print!("{}", contents),
+		}
+
+		if let Some(ref dir) = corpus_dir {
+			for (input_idx, (target, input)) in generate::fuzz_inputs(transcript).into_iter().enumerate() {
+				write(dir.join(target).join(format!("generated-{:04}-{}", idx + 1, input_idx)), &input);
+			}
+		}
+	}
+	if out_dir.is_some() || corpus_dir.is_some() {
+		eprintln!("Generated {} transcripts", transcripts.len());
+	}
 }
 
-// Apply the effect of a known move result on the list of possible positions
-fn apply_move(pos_positions: &mut Vec<Vec<u8>>, move_val: (BoardPos, Option<ShipType>)) {
-	// We operate completely differently depending on whether it was a hit or miss
-	match move_val.1 {
+// Compare the greedy strategy with the budget planner in the limited-ammunition
+// variant: the average number of hits each scores with the given number of shots
+fn run_budget<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let budget = args.next().and_then(|n| n.parse().ok()).or_usage(Some("budget"), "budget requires a number of shots");
+	let mut games = 20;
+	let seed = seed.unwrap_or(0);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("budget"), "--games requires a number"),
+			_ => unknown_argument(Some("budget"), &arg),
+		}
+	}
+
+	let mut rng = Rng::new(seed);
+	let (mut greedy_hits, mut planner_hits) = (0, 0);
+	for game in 0..games {
+		let layout = Layout::random(&mut rng, rules.constraints()).unwrap_or_else(|| fail(&format!("No valid layout exists under the {} rules", rules.name)));
+		let greedy = sim::play_budget_game(&mut Greedy::new(TieBreak::Lexicographic), &layout, rules, budget);
+		let planner = sim::play_budget_game(&mut BudgetPlanner::new(budget, rules.labeled_hits), &layout, rules, budget);
+		println!("game {}: greedy {} hits, planner {} hits", game + 1, greedy, planner);
+		greedy_hits += greedy;
+		planner_hits += planner;
+	}
+	println!("{} games of {} shots: greedy averages {:.2} hits, planner {:.2}", games, budget,
+	         greedy_hits as f64 / games.max(1) as f64, planner_hits as f64 / games.max(1) as f64);
+}
+
+// Play strategies against each other on shared random layouts, updating their
+// persistent ratings
+fn run_rate<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut names = Vec::new();
+	let mut games = 20;
+	let seed = seed.unwrap_or(0);
+	let mut ratings_path = String::from("ratings.txt");
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("rate"), "--games requires a number"),
+			"--ratings-file" => ratings_path = args.next().or_usage(Some("rate"), "--ratings-file requires a file name"),
+			_ if arg.starts_with("--") => unknown_argument(Some("rate"), &arg),
+			_ => names.push(arg),
+		}
+	}
+	if names.is_empty() {
+		names = strategy::STRATEGY_NAMES.iter().map(|name| name.to_string()).collect();
+	}
+
+	let mut ratings = match durable::read(Path::new(&ratings_path), rules.context.allow_unsealed) {
+		Ok(contents) => Ratings::from_file_string(&contents).unwrap_or_else(|e| fail(&format!("Invalid ratings file: {}", e))),
+		Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ratings::new(),
+		Err(e) => fail(&format!("Unable to read ratings file: {}", e)),
+	};
+	ratings::rate(&mut ratings, &names, games, seed, rules).unwrap_or_else(|e| fail(&e));
+	durable::write(Path::new(&ratings_path), &ratings.to_file_string()).unwrap_or_else(|e| fail(&format!("Unable to write ratings file: {}", e)));
+	print!("{}", ratings.render());
+}
+
+// Play seeded games and hash their decisions: compared against --fixture's
+// hashes if it exists (replaying its strategy and seed), or saved there with
+// --record (or if it doesn't exist yet)
+fn run_audit<I: Iterator<Item = String>>(mut args: I, rules: &Rules, seed: Option<u64>) {
+	let mut games = 10;
+	let seed = seed.unwrap_or(0);
+	let mut strategy_name = String::from("greedy-random");
+	let mut fixture_path = String::from(audit::DEFAULT_FIXTURE);
+	let mut record = false;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--games" => games = args.next().and_then(|n| n.parse().ok()).or_usage(Some("audit"), "--games requires a number"),
+			"--strategy" => strategy_name = args.next().or_usage(Some("audit"), "--strategy requires a strategy name"),
+			"--fixture" => fixture_path = args.next().or_usage(Some("audit"), "--fixture requires a file name"),
+			"--record" => record = true,
+			_ => unknown_argument(Some("audit"), &arg),
+		}
+	}
+
+	let expected = if record {
+		None
+	} else if Path::new(&fixture_path).exists() {
+		Some(Fixture::load(&fixture_path, rules.context.allow_unsealed).unwrap_or_else(|e| fail(&e)))
+	} else {
+		eprintln!("There is no fixture at {}; record one with --record", fixture_path);
+		std::process::exit(1);
+	};
+	let replay = match expected {
+		Some(ref fixture) => {
+			assert!(fixture.rules == rules.name, "The fixture was recorded under the {} rules, not {}", fixture.rules, rules.name);
+			Fixture::record(rules, &fixture.strategy, fixture.hashes.len(), fixture.seed)
+		},
+		None => Fixture::record(rules, &strategy_name, games, seed),
+	}.unwrap_or_else(|e| fail(&e));
+	println!("{} games of {} from seed {}: decision hash {:016x}", replay.hashes.len(), replay.strategy, replay.seed, replay.digest());
+
+	match expected {
+		Some(fixture) => {
+			fixture.check(&replay).unwrap_or_else(|e| fail(&format!("Determinism audit failed: {}", e)));
+			println!("Matches the fixture");
+		},
 		None => {
-			// It was a miss. Remove BoardPos from all position lists
-			process_miss(pos_positions, move_val.0);
+			replay.save(&fixture_path).unwrap_or_else(|e| fail(&e));
+			println!("Recorded the fixture in {}", fixture_path);
+		},
+	}
+}
+
+// A game for the interactive mode to pick up instead of starting a new one
+enum Resume {
+	// The path of a save file
+	Save(String),
+
+	// The path of a belief dump (see the belief module)
+	Belief(String),
+}
+
+// Track the opponent's board, reporting the remaining placements and best shot
+// after each move. The game starts from moves.txt, or from the given save file
+// or belief dump.
+fn run_repl<I: Iterator<Item = String>>(mut args: I, rules: &Rules, locale: &dyn Locale, saved: Option<Resume>) {
+	use std::io::BufRead;
+
+	// Parse the command-line options
+	let mut tie_break = TieBreak::Lexicographic;
+	let mut detector = None;
+	let mut time_limits = TimeLimits::default();
+	let mut maximin = false;
+	let mut near_tie = None;
+	let mut budget = None;
+	let mut screen_reader = false;
+	let mut empirical = None;
+	let mut show_stats = false;
+	let mut history_path = String::from("history.txt");
+	let mut thinking = None;
+	let mut hint_strength = None;
+	let mut script = None;
+	let mut strategy_name = None;
+	let mut forced_shots = forced::DEFAULT_MAX_SHOTS;
+	let mut journal_path = None;
+	let mut opponent_name = None;
+	let mut opponent_dir = String::from(opponent::DEFAULT_DIR);
+	let mut normalization = Normalization::Probability;
+	let mut ponder_enabled = false;
+	let mut kill_threshold = 1.0;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--tie-break" => tie_break = tie_break_arg(&mut args, None),
+			"--ponder" => ponder_enabled = true,
+			"--kill-shots" => kill_threshold = args.next().and_then(|p| p.parse().ok()).filter(|p| (0.0..=1.0).contains(p))
+				.or_usage(None, "--kill-shots requires a probability from 0 to 1"),
+			"--heatmap-scale" => {
+				let desc = args.next().or_usage(None, "--heatmap-scale requires a scale");
+				normalization = Normalization::parse(&desc).unwrap_or_else(|| usage_error(None, &format!("Unknown heatmap scale {} (available: counts, probability, unshot, log-odds)", desc)));
+			},
+			"--opponent" => opponent_name = Some(args.next().or_usage(None, "--opponent requires an opponent name")),
+			"--opponent-dir" => opponent_dir = args.next().or_usage(None, "--opponent-dir requires a directory"),
+			"--journal" => journal_path = Some(args.next().or_usage(None, "--journal requires a file name")),
+			"--hints" => hint_strength = Some(args.next().and_then(|n| n.parse().ok()).filter(|n| (1..=MAX_HINT_STRENGTH).contains(n))
+				.unwrap_or_else(|| usage_error(None, &format!("--hints requires a strength from 1 to {}", MAX_HINT_STRENGTH)))),
+			"--stats" => show_stats = true,
+			"--think" => thinking = Some(Duration::from_millis(args.next().and_then(|n| n.parse().ok()).or_usage(None, "--think requires a number of milliseconds"))),
+			"--history" => history_path = args.next().or_usage(None, "--history requires a file name"),
+			"--maximin" => maximin = true,
+			"--script" => script = Some(args.next().or_usage(None, "--script requires a script file")),
+			"--strategy" => strategy_name = Some(args.next().or_usage(None, "--strategy requires a strategy name")),
+			"--forced" => forced_shots = args.next().and_then(|n| n.parse().ok()).or_usage(None, "--forced requires a number of shots"),
+			"--information" => near_tie = Some(args.next().and_then(|n| n.parse().ok()).or_usage(None, "--information requires a near-tie tolerance")),
+			"--empirical" => {
+				let path = args.next().or_usage(None, "--empirical requires a profile file");
+				empirical = Some(Profile::load(&rules.known_board(), &path).unwrap_or_else(|e| fail(&e)));
+			},
+			"--budget" => budget = Some(args.next().and_then(|n| n.parse().ok()).or_usage(None, "--budget requires a number of shots")),
+			"--screen-reader" => screen_reader = true,
+			"--paranoid" => {
+				let threshold = args.next().or_usage(None, "--paranoid requires a probability threshold");
+				detector = Some(CheatDetector::new(threshold.parse().ok().or_usage(None, "Invalid --paranoid threshold")));
+			},
+			"--time-limit" => {
+				let millis = args.next().and_then(|n| n.parse().ok()).or_usage(None, "--time-limit requires a number of milliseconds");
+				time_limits.set_default(Duration::from_millis(millis));
+			},
+			"--phase-time-limit" => {
+				let desc = args.next().or_usage(None, "--phase-time-limit requires <phase>:<milliseconds>");
+				let (phase, limit) = TimeLimits::parse_phase_limit(&desc).unwrap_or_else(|e| fail(&e));
+				time_limits.set(phase, limit);
+			},
+			_ => unknown_argument(None, &arg),
+		}
+	}
+
+	// A named opponent's profile is the prior for shooting at them, and their
+	// history the statistics panel's comparison
+	let opponent = opponent_name.map(|name| Opponents::new(Path::new(&opponent_dir), &rules.known_board()).load(&name).unwrap_or_else(|e| fail(&e)));
+	let stats = match opponent {
+		Some(ref opponent) if show_stats => Some(opponent.history.clone()),
+		_ if show_stats => Some(History::load(&history_path, rules.context.allow_unsealed).unwrap_or_else(|e| fail(&e))),
+		_ => None,
+	};
+	if let Some(opponent) = opponent {
+		empirical = Some(opponent.profile);
+	}
+	let base = match saved {
+		Some(Resume::Save(ref path)) => {
+			let contents = durable::read(Path::new(path), rules.context.allow_unsealed).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", path, e)));
+			GameState::from_save_string(&contents, &rules.known_board()).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+		},
+		Some(Resume::Belief(ref path)) => {
+			let contents = text::read(Path::new(path)).unwrap_or_else(|e| fail(&format!("Unable to read {}: {}", path, e)));
+			GameState::from_belief_string(&contents, &rules.known_board()).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+		},
+		None => rules.new_cached_game(),
+	};
+	let board = base.board().clone();
+	if let Some(ref profile) = empirical {
+		assert!(*profile.board() == board, "The profile is for the board {}, but the game is on {}", profile.board().describe(), board.describe());
+	}
+
+	// Counting every layout of a large board (such as hasbro-2002's) would take
+	// hours, which shows in the opening not being counted in time. Unless told
+	// otherwise, searches there stop after LARGE_BOARD_TIME_LIMIT, and the
+	// default recommendation samples layouts instead of counting them.
+	let countable = match saved {
+		None => base.has_opening(),
+		Some(_) => GameState::new_cached(&board).has_opening(),
+	};
+	let default_strategy = budget.is_none() && script.is_none() && strategy_name.is_none() && !maximin && empirical.is_none() && near_tie.is_none();
+	let unlimited = time_limits == TimeLimits::default();
+	let sampled = !countable && unlimited && default_strategy && cfg!(feature = "montecarlo");
+	if !countable && unlimited {
+		time_limits.set_default(LARGE_BOARD_TIME_LIMIT);
+		println!("{}", locale.text(&Message::LargeBoard { sampled, time_limit: LARGE_BOARD_TIME_LIMIT }));
+	}
+	// The recommendation is remembered until the next result, so a hint about
+	// it (or asking again) doesn't repeat the search
+	let chosen: Box<dyn Strategy> = match budget {
+		Some(budget) => Box::new(BudgetPlanner::new(budget, rules.labeled_hits)),
+		None if script.is_some() => script_strategy(script.as_deref().expect("No script given"), tie_break),
+		None if strategy_name.is_some() => strategy::load(strategy_name.as_deref().expect("No strategy given"), 0).unwrap_or_else(|e| fail(&e)),
+		None if sampled => strategy::load("montecarlo", 0).unwrap_or_else(|e| fail(&e)),
+		None if maximin => Box::new(Maximin::new(tie_break)),
+		None if empirical.is_some() => {
+			let profile = empirical.expect("No profile loaded");
+			Box::new(Adaptive::with_prior(&board, |pos, uninformed| profile.occupancy(pos, uninformed), tie_break))
 		},
-		Some(stype) => {
-			// It was a hit. Make sure that the relevant ship type
-			// overlaps the hit position
-			process_hit(&mut pos_positions[stype_id(stype) as usize], stype, move_val.0);
+		None => match near_tie {
+			Some(near_tie) => Box::new(Greedy::with_information_gain(tie_break, near_tie)),
+			None => Box::new(Greedy::new(tie_break)),
 		},
+	};
+	let mut strategy = Memoized::new(Box::new(Forcing::new(chosen, rules, forced_shots)));
+
+	// Pick the game up from its journal if there is one, or else load in the
+	// moves file and process the moves. A journal's last line is dropped if a
+	// crash cut it off.
+	let recovered = journal_path.as_ref().and_then(|path| match text::read(Path::new(path)) {
+		Ok(contents) => Some(contents),
+		Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => None,
+		Err(e) => fail(&format!("Unable to read the journal {}: {}", path, e)),
+	});
+	let mut log = match recovered {
+		Some(mut contents) => {
+			if !contents.ends_with('\n') {
+				contents.truncate(contents.rfind('\n').map_or(0, |idx| idx + 1));
+			}
+			EventLog::from_file_string(&contents, base, rules.reshot).unwrap_or_else(|e| fail(&format!("{}: {}", journal_path.as_deref().unwrap_or_default(), e)))
+		},
+		None => EventLog::new(base, rules.reshot),
+	};
+	let mut journal = journal_path.as_ref().map(|path| {
+		std::fs::OpenOptions::new().create(true).append(true).open(path).unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", path, e)))
+	});
+	if saved.is_none() && log.events().is_empty() {
+		for cur_move in read_moves(&board).unwrap_or_else(|e| fail(&e)) {
+			apply_checked(&mut log, &mut journal, &detector, cur_move, locale);
+		}
 	}
+	let mut pondering = start_pondering(ponder_enabled, report(log.state_mut(), &mut strategy, &time_limits, thinking, hint_strength.is_some(), stats.as_ref(), locale), log.state(), rules);
+
+	// Read further moves interactively, reporting the remaining placements after
+	// each one. Input is read on a thread of its own, so pondering goes on
+	// while waiting for it.
+	let (sender, lines) = std::sync::mpsc::channel();
+	std::thread::spawn(move || {
+		for line in std::io::stdin().lock().lines() {
+			if sender.send(line).is_err() {
+				break;
+			}
+		}
+	});
+	for line in lines {
+		let line = line.unwrap_or_else(|e| fail(&format!("Unable to read move from stdin: {}", e)));
+		let line = line.trim();
+		if line.is_empty() {
+			continue;
+		}
+
+		let state = log.state();
+
+		// "board" shows the board so far
+		if line.eq_ignore_ascii_case("board") {
+			if screen_reader {
+				describe_board(&board, &spoken::state_cells(state), locale);
+			} else {
+				print!("{}", state.render());
+			}
+			continue;
+		}
+
+		// "shots" lists the record of every move so far
+		if line.eq_ignore_ascii_case("shots") {
+			for record in state.records() {
+				println!("{}", locale.text(&Message::ShotLog(record)));
+			}
+			continue;
+		}
+
+		// "explain <ship letter>" shows which moves ruled out which of the
+		// ship's placements
+		if let Some(letter) = line.strip_prefix("explain ") {
+			let letter = letter.trim().to_ascii_uppercase();
+			let mut chars = letter.chars();
+			match (chars.next().and_then(parse_shiptype), chars.next()) {
+				(Some(stype), None) if board.ship_index(stype).is_some() => print!("{}", explain::explain(state, stype).render(&board)),
+				_ => println!("{}", locale.text(&Message::InvalidMove(line))),
+			}
+			continue;
+		}
+
+		// "heatmap" shows every square's probability of holding a ship, marking
+		// the kill shots with '!', and "heatmap <ship letter>" the probability
+		// of holding the given ship
+		if line.eq_ignore_ascii_case("heatmap") || line.starts_with("heatmap ") {
+			let letter = line[7..].trim().to_ascii_uppercase();
+			let mut chars = letter.chars();
+			match (chars.next(), chars.next()) {
+				(None, _) => print!("{}", state.heatmap().render_marked(&board, normalization, state.shots(), &state.kill_shots(kill_threshold))),
+				(Some(c), None) if parse_shiptype(c).is_some_and(|stype| board.ship_index(stype).is_some()) => {
+					let stype = parse_shiptype(c).expect("Not a ship type");
+					let (_, layer) = state.ship_heatmaps().into_iter().find(|&(layer_stype, _)| layer_stype == stype).expect("No layer for the ship type");
+					print!("{}", layer.render_normalized(&board, normalization, state.shots()));
+				},
+				_ => println!("{}", locale.text(&Message::InvalidMove(line))),
+			}
+			continue;
+		}
+
+		// "forecast" estimates, for each ship afloat, the chance it's sunk next
+		// and the shots it will take, from games played out from here
+		if line.eq_ignore_ascii_case("forecast") {
+			match forecast::forecast(state, rules, forecast::DEFAULT_SAMPLES, state.shots().len() as u64) {
+				Ok(forecasts) => println!("{}", locale.text(&Message::SinkForecasts(&forecasts))),
+				Err(e) => println!("{}", e),
+			}
+			continue;
+		}
+
+		// "sample" estimates the heatmap from sampled layouts, with how far the
+		// estimates may be off
+		#[cfg(feature = "montecarlo")]
+		if line.eq_ignore_ascii_case("sample") {
+			let cancel = time_limits.get(state.phase()).map_or_else(CancelToken::new, CancelToken::with_timeout);
+			let mut rng = Rng::new(state.shots().len() as u64);
+			match montecarlo::sample(state, &mut rng, montecarlo::DEFAULT_MAX_SAMPLES, false, &cancel) {
+				Ok(sampled) => {
+					print!("{}", sampled.to_heatmap().render_normalized(&board, normalization, state.shots()));
+					println!("{} samples, largest standard error {:.1}%, best shot {}", sampled.samples, 100.0 * sampled.max_standard_error(state),
+					         if sampled.separated(state) { "clearly ahead" } else { "not yet separated" });
+				},
+				Err(e) => println!("{}", e),
+			}
+			continue;
+		}
+
+		// "hint" or "hint <strength>" gives a hint towards the recommended shot,
+		// at the --hints strength (or the weakest) by default
+		if line.eq_ignore_ascii_case("hint") || line.starts_with("hint ") {
+			let strength = match line[4..].trim() {
+				"" => Some(hint_strength.unwrap_or(1)),
+				desc => desc.parse().ok().filter(|n| (1..=MAX_HINT_STRENGTH).contains(n)),
+			};
+			if state.is_known_inconsistent() {
+				println!("{}", locale.text(&Message::NoConsistentLayout));
+				continue;
+			}
+			let cancel = time_limits.get(state.phase()).map_or_else(CancelToken::new, CancelToken::with_timeout);
+			match (strength, timing::time(Phase::Selection, || strategy.choose_shot_cancellable(state, &cancel))) {
+				(None, _) => println!("{}", locale.text(&Message::InvalidHintStrength(MAX_HINT_STRENGTH))),
+				(Some(strength), Some(best)) => println!("{}", locale.text(&Message::Hint(&hint::hint(state, best, strength)))),
+				(Some(_), None) => println!("{}", locale.text(&Message::NoSquaresLeft)),
+			}
+			continue;
+		}
+
+		// "cache" shows how often the recommendation was reused rather than
+		// searched for again
+		if line.eq_ignore_ascii_case("cache") {
+			let stats = strategy.stats();
+			println!("Recommendations: {} reused, {} searched, {} outdated", stats.hits, stats.misses, stats.invalidations);
+			continue;
+		}
+
+		// "save <file>" snapshots the game for resuming later
+		if let Some(path) = line.strip_prefix("save ") {
+			let path = path.trim();
+			match durable::write(Path::new(path), &state.to_save_string()) {
+				Ok(()) => println!("Saved to {}", path),
+				Err(e) => println!("Unable to write {}: {}", path, e),
+			}
+			continue;
+		}
+
+		// "belief" dumps what the inference believes, to paste into a bug report
+		if line.eq_ignore_ascii_case("belief") {
+			print!("{}", state.to_belief_string());
+			continue;
+		}
+
+		// "phase" names the phase of the game (see the phase module)
+		if line.eq_ignore_ascii_case("phase") {
+			println!("{}", state.phase());
+			continue;
+		}
+
+		// "undo" takes back the latest shot
+		if line.eq_ignore_ascii_case("undo") {
+			match log.undo() {
+				Ok(pos) => {
+					journal_latest(&log, &mut journal);
+					match (log.events().last(), pos) {
+						(Some(&Event::Unsweep(_)), Some(pos)) => println!("{}", locale.text(&Message::SweepUndone(pos))),
+						(_, Some(pos)) => println!("{}", locale.text(&Message::Undone(pos))),
+						(_, None) => println!("{}", locale.text(&Message::RelocationUndone)),
+					}
+				},
+				Err(e) => {
+					println!("{}", locale.text(&Message::EventRejected(e)));
+					continue;
+				},
+			}
+			pondering = start_pondering(ponder_enabled, report(log.state_mut(), &mut strategy, &time_limits, thinking, hint_strength.is_some(), stats.as_ref(), locale), log.state(), rules);
+			continue;
+		}
+
+		// "correct <move>" fixes a misreported result
+		if let Some(desc) = line.strip_prefix("correct ") {
+			match parse_move(&board, desc) {
+				Ok(cur_move) if record_event(&mut log, &mut journal, Event::Correct(cur_move), locale) => {
+					pondering = start_pondering(ponder_enabled, report(log.state_mut(), &mut strategy, &time_limits, thinking, hint_strength.is_some(), stats.as_ref(), locale), log.state(), rules);
+				},
+				Ok(_) => {},
+				Err(_) => println!("{}", locale.text(&Message::InvalidMove(line))),
+			}
+			continue;
+		}
+
+		// "sweep C3 found" or "sweep C3 clear" records a radar sweep of the
+		// 3x3 area around C3
+		if let Some(desc) = line.strip_prefix("sweep ") {
+			match Sweep::parse(&board, desc) {
+				Ok(sweep) if record_event(&mut log, &mut journal, Event::Sweep(sweep), locale) => {
+					pondering = start_pondering(ponder_enabled, report(log.state_mut(), &mut strategy, &time_limits, thinking, hint_strength.is_some(), stats.as_ref(), locale), log.state(), rules);
+				},
+				Ok(_) => {},
+				Err(e) => println!("{}", e),
+			}
+			continue;
+		}
+
+		// Under moving-ships rules, "relocated" records the opponent moving a
+		// ship with no hits
+		if line.eq_ignore_ascii_case("relocated") && rules.moving_ships {
+			if record_event(&mut log, &mut journal, Event::Relocated, locale) {
+				println!("{}", locale.text(&Message::Relocated));
+				pondering = start_pondering(ponder_enabled, report(log.state_mut(), &mut strategy, &time_limits, thinking, hint_strength.is_some(), stats.as_ref(), locale), log.state(), rules);
+			}
+			continue;
+		}
+
+		// Under fog-of-war rules, "B3?" records a shot whose result is withheld;
+		// its result is entered as a normal move once revealed
+		if let Some(pos) = line.strip_suffix('?').and_then(|square| parse_square(&board, square)) {
+			if record_event(&mut log, &mut journal, Event::Fired(pos), locale) {
+				pondering = start_pondering(ponder_enabled, report(log.state_mut(), &mut strategy, &time_limits, thinking, hint_strength.is_some(), stats.as_ref(), locale), log.state(), rules);
+			}
+			continue;
+		}
+
+		let parsed = match parse_move(&board, line) {
+			Err(_) if screen_reader => spoken::parse_spoken_move(&board, line),
+			parsed => parsed,
+		};
+		match parsed {
+			Ok(cur_move) => {
+				if !apply_checked(&mut log, &mut journal, &detector, cur_move, locale) {
+					continue;
+				}
+				let deadline = time_limits.get(log.state().phase()).map_or_else(CancelToken::new, CancelToken::with_timeout);
+				if let Some(after) = pondering.take().and_then(|pondering| pondering.finish(cur_move, &deadline)) {
+					log.state_mut().reuse_heatmap(&after);
+				}
+			},
+			Err(_) => {
+				println!("{}", locale.text(&Message::InvalidMove(line)));
+				continue;
+			},
+		}
+		pondering = start_pondering(ponder_enabled, report(log.state_mut(), &mut strategy, &time_limits, thinking, hint_strength.is_some(), stats.as_ref(), locale), log.state(), rules);
+	}
+}
+
+// The options taken anywhere on the command line, whatever the subcommand
+const GLOBAL_OPTIONS: &[&str] = &["--rules", "--rules-file", "--seed", "--format", "--lang", "--coords", "--max-memory", "--memory-report", "--quiet", "--legacy-files", "--help"];
+
+// The options of the interactive mode, run without a subcommand (or by resume)
+const REPL_OPTIONS: &[&str] = &[
+	"--budget", "--empirical", "--forced", "--heatmap-scale", "--hints", "--history", "--information", "--journal", "--kill-shots", "--maximin", "--opponent",
+	"--opponent-dir", "--paranoid", "--phase-time-limit", "--ponder", "--screen-reader", "--script", "--stats", "--strategy", "--think", "--tie-break", "--time-limit",
+];
+
+// Every subcommand, for help and shell completions. main dispatches on the
+// same names.
+const COMMANDS: &[Command] = &[
+	Command { name: "place", summary: "Place your own fleet, with advice", options: &["--no-touching", "--output", "--screen-reader"] },
+	Command { name: "eval", summary: "Recommend shots for positions read from stdin", options: &["--tie-break"] },
+	Command { name: "puzzle", summary: "Generate mid-game puzzles", options: &["--count"] },
+	Command { name: "vulnerability", summary: "Measure how fast an attacker finds a placement", options: &["--attacker", "--games"] },
+	Command { name: "analyze", summary: "Analyze placements, shot patterns, or wasted shots", options: &[] },
+	Command { name: "diff", summary: "Compare two models over a game", options: &["--csv", "--left", "--right"] },
+	Command { name: "finish", summary: "Plan how to sink a hit ship", options: &[] },
+	Command { name: "import", summary: "Import a transcript in another format", options: &["--board-size"] },
+	Command { name: "export", summary: "Export a game state as JSON", options: &[] },
+	Command { name: "convert", summary: "Convert a game record between formats", options: &["--from", "--from-coords", "--schema-version", "--to", "--to-coords"] },
+	Command { name: "animate", summary: "Animate a game as SVG", options: &["--frames", "--seconds"] },
+	Command { name: "tournament", summary: "Play the AI against random fleets", options: &["--binary-log", "--checkpoint", "--checkpoint-every", "--games", "--history", "--log-dir", "--profile", "--resume", "--verbosity"] },
+	Command { name: "rate", summary: "Rate the strategies against each other", options: &["--games", "--ratings-file"] },
+	Command { name: "audit", summary: "Check seeded games against a determinism fixture", options: &["--fixture", "--games", "--record", "--strategy"] },
+	Command { name: "harness", summary: "Test cheat detection against lying opponents", options: &["--games", "--lie-rate", "--threshold"] },
+	Command { name: "budget", summary: "Compare strategies under limited ammunition", options: &["--games"] },
+	Command { name: "selfplay", summary: "Generate self-play training data", options: &["--dirichlet", "--games", "--out", "--temperature"] },
+	Command { name: "generate", summary: "Generate random game transcripts", options: &["--count", "--fuzz-corpus", "--length", "--out-dir"] },
+	Command { name: "crosscheck", summary: "Cross-check the inference against the reference", options: &["--games"] },
+	Command { name: "coordinate", summary: "Run a distributed tournament", options: &["--batch", "--games", "--listen", "--local-workers"] },
+	Command { name: "worker", summary: "Play games for a coordinator", options: &[] },
+	Command { name: "serve", summary: "Serve shot recommendations over the network", options: &["--listen", "--strategy", "--time-limit"] },
+	Command { name: "solve", summary: "Solve a tiny game exactly", options: &["--board", "--fleet", "--labeled", "--objective", "--policy", "--time-limit"] },
+	Command { name: "remote", summary: "Play a game against an HTTP service", options: &["--session", "--tie-break"] },
+	Command { name: "team", summary: "Take turns with other attackers against one opponent", options: &["--human", "--layout", "--name", "--seat", "--seats", "--tie-break"] },
+	Command { name: "play", summary: "Play a game against the AI", options: &["--difficulty", "--layout", "--think"] },
+	Command { name: "campaign", summary: "Play through the difficulties, easiest first", options: &["--layout", "--progress", "--think"] },
+	Command { name: "match", summary: "Play a session against an adapting opponent", options: &["--decay", "--games"] },
+	Command { name: "profile", summary: "Show or record the per-square profile", options: &["--profile"] },
+	Command { name: "opponent", summary: "Manage opponent profiles", options: &["--dir", "--note"] },
+	Command { name: "binlog", summary: "Summarize a binary game log", options: &["--game"] },
+	Command { name: "commit", summary: "Commit to a layout, or reveal it", options: &["--reveal"] },
+	Command { name: "verify", summary: "Verify an opponent's reveal against their commitment", options: &[] },
+	Command { name: "resume", summary: "Resume a saved game", options: REPL_OPTIONS },
+	Command { name: "load-belief", summary: "Resume from a belief dump", options: REPL_OPTIONS },
+	Command { name: "help", summary: "List the subcommands and global options", options: &[] },
+	Command { name: "completions", summary: "Print a shell completion script", options: &[] },
+];
+
+// Print the subcommands and global options
+fn run_help<I: Iterator<Item = String>>(mut args: I) {
+	if let Some(arg) = args.next() {
+		unknown_argument(Some("help"), &arg);
+	}
+
+	println!("Usage: battleship_ai [global options] [command] [options]");
+	println!("Without a command, reads moves and recommends shots interactively.\n");
+	println!("Commands:");
+	let width = COMMANDS.iter().map(|command| command.name.len()).max().unwrap_or(0);
+	for command in COMMANDS {
+		println!("  {:width$}  {}", command.name, command.summary, width = width);
+	}
+	println!("\nGlobal options: {}", GLOBAL_OPTIONS.join(" "));
+}
+
+// The usage of a command, or of the interactive mode without one
+fn usage(command: Option<&str>) -> String {
+	let (line, summary, options) = match command.and_then(|name| COMMANDS.iter().find(|command| command.name == name)) {
+		Some(command) => (format!("Usage: battleship_ai [global options] {} [options]", command.name), command.summary, command.options),
+		None => (String::from("Usage: battleship_ai [global options] [command] [options]"), "Without a command, reads moves and recommends shots interactively (see battleship_ai help for the commands)", REPL_OPTIONS),
+	};
+	let mut out = format!("{}\n{}\n", line, summary);
+	if !options.is_empty() {
+		out += &format!("Options: {}\n", options.join(" "));
+	}
+	out + &format!("Global options: {}\n", GLOBAL_OPTIONS.join(" "))
+}
+
+// Report a mistake on the command line with the usage, and exit
+fn usage_error(command: Option<&str>, message: &str) -> ! {
+	eprintln!("{}\n", message);
+	eprint!("{}", usage(command));
+	std::process::exit(2);
+}
+
+fn unknown_argument(command: Option<&str>, arg: &str) -> ! {
+	usage_error(command, &format!("Unknown argument {}", arg))
+}
+
+// Turns a missing or unparsable option value into a usage error
+trait OrUsage<T> {
+	fn or_usage(self, command: Option<&str>, message: &str) -> T;
+}
+
+impl<T> OrUsage<T> for Option<T> {
+	fn or_usage(self, command: Option<&str>, message: &str) -> T {
+		self.unwrap_or_else(|| usage_error(command, message))
+	}
+}
+
+// Report an error that isn't a mistake in the usage, such as an input that
+// can't be read, and exit
+fn fail(message: &str) -> ! {
+	eprintln!("{}", message);
+	std::process::exit(1);
+}
+
+// Print the completion script for a shell
+fn run_completions<I: Iterator<Item = String>>(mut args: I) {
+	let shell = args.next().unwrap_or_else(|| usage_error(Some("completions"), &format!("completions requires a shell ({})", completions::SHELL_NAMES.join(", "))));
+	if let Some(arg) = args.next() {
+		unknown_argument(Some("completions"), &arg);
+	}
+
+	let interface = Interface { program: "battleship_ai", commands: COMMANDS, global_options: GLOBAL_OPTIONS, default_options: REPL_OPTIONS };
+	print!("{}", completions::script(&shell, &interface).unwrap_or_else(|e| fail(&e)));
 }
 
 fn main() {
-	// The list of possible moves per ship type
-	let mut pos_positions = SHIP_TYPES.iter().map(|&stype| (0..num_positions(stype)).collect::<Vec<_>>()).collect::<Vec<_>>();
+	// Pull out the global options, which may appear anywhere on the command line
+	let mut registry = RulesRegistry::new();
+	let mut rules_name = String::from(DEFAULT_PRESET);
+	let mut locale = messages::from_env();
+	let mut context = Context::new();
+	let mut seed = None;
+	let mut format = None;
+	let mut memory_report = false;
+	let mut help = false;
+	let mut args = Vec::new();
+	let mut all_args = std::env::args().skip(1);
+	while let Some(arg) = all_args.next() {
+		match arg.as_str() {
+			"--rules" => rules_name = all_args.next().or_usage(None, "--rules requires a preset name"),
+			"--lang" => {
+				let code = all_args.next().or_usage(None, "--lang requires a language code");
+				locale = messages::by_code(&code).unwrap_or_else(|| {
+					usage_error(None, &format!("Unknown language {} (available: {})", code, messages::LOCALE_CODES.join(", ")))
+				});
+			},
+			"--rules-file" => {
+				let path = all_args.next().or_usage(None, "--rules-file requires a file name");
+				registry.load_file(&path).unwrap_or_else(|e| fail(&format!("Unable to load rules: {}", e)));
+			},
+			"--coords" => {
+				let origin = all_args.next().or_usage(None, "--coords requires a coordinate origin");
+				context.coordinates = Coordinates::parse(&origin).unwrap_or_else(|| {
+					usage_error(None, &format!("Unknown coordinate origin {} (available: {}, optionally followed by -{})", origin, coords::ORIGIN_NAMES.join(", "), coords::ALPHABET_NAMES.join(" or -")))
+				});
+			},
+			"--max-memory" => {
+				let cap = all_args.next().and_then(|size| memory::parse_size(&size)).or_usage(None, "--max-memory requires a size, e.g. 64M");
+				context.memory = Meter::with_cap(cap);
+			},
+			"--seed" => seed = Some(all_args.next().and_then(|n| n.parse().ok()).or_usage(None, "--seed requires a number")),
+			"--format" => format = Some(all_args.next().or_usage(None, "--format requires an output format")),
+			"--memory-report" => memory_report = true,
+			"--quiet" => progress::set_quiet(true),
+			"--legacy-files" => context.allow_unsealed = true,
+			"-h" | "--help" => help = true,
+			_ => args.push(arg),
+		}
+	}
 
-	// Load in the moves file and process the moves
-	for cur_move in read_moves() {
-		apply_move(&mut pos_positions, cur_move);
+	let mut rules = registry.get(&rules_name).unwrap_or_else(|| {
+		usage_error(None, &format!("Unknown rules preset {} (available: {})", rules_name, registry.names().join(", ")))
+	}).clone();
+	rules.context = context;
+	let board = rules.board().unwrap_or_else(|e| fail(&format!("Unsupported rules: {}", e)));
+
+	// The subcommand, if any, comes first; otherwise run the REPL
+	let mut args = args.into_iter().peekable();
+	let command = match args.peek() {
+		Some(arg) if !arg.starts_with("--") => args.next(),
+		_ => None,
+	};
+	if help {
+		match command.as_deref() {
+			None | Some("help") => run_help(std::iter::empty()),
+			Some(name) if COMMANDS.iter().any(|command| command.name == name) => print!("{}", usage(Some(name))),
+			Some(name) => usage_error(None, &format!("Unknown command {}", name)),
+		}
+		return;
 	}
 
-	// Generate the ship position overlap cache
-	let olap_cache = gen_overlap_cache();
+	match command.as_deref() {
+		Some("place") => run_placement_assistant(args, &rules, &*locale),
+		Some("eval") => run_eval(args, &board),
+		Some("puzzle") => run_puzzle(args, &rules, seed),
+		Some("vulnerability") => run_vulnerability(args, &rules, seed),
+		Some("analyze") => run_analysis(args, &rules),
+		Some("diff") => run_diff(args, &board),
+		Some("finish") => run_finish(args, &board),
+		Some("import") => run_import(args, &rules),
+		Some("export") => run_export(args, &rules),
+		Some("convert") => run_convert(args, &rules, format.as_deref()),
+		Some("animate") => run_animate(args, &rules),
+		Some("tournament") => run_tournament(args, &rules, seed),
+		Some("rate") => run_rate(args, &rules, seed),
+		Some("audit") => run_audit(args, &rules, seed),
+		Some("harness") => run_harness(args, &rules, seed),
+		Some("budget") => run_budget(args, &rules, seed),
+		#[cfg(feature = "selfplay")]
+		Some("selfplay") => run_selfplay(args, &rules, seed),
+		Some("generate") => run_generate(args, &rules, seed, format.as_deref()),
+		Some("crosscheck") => run_crosscheck(args, &rules, seed),
+		#[cfg(feature = "network")]
+		Some("coordinate") => run_coordinate(args, &rules, seed),
+		#[cfg(feature = "network")]
+		Some("worker") => run_worker(args, &rules),
+		#[cfg(feature = "network")]
+		Some("serve") => run_serve(args, &rules),
+		Some("solve") => run_solve(args, &rules.context.memory),
+		#[cfg(feature = "network")]
+		Some("remote") => run_remote(args, &rules),
+		Some("team") => run_team(args, &rules),
+		Some("play") => run_play(args, &rules, &*locale, seed),
+		Some("campaign") => run_campaign(args, &rules, &*locale, seed),
+		Some("match") => run_match(args, &rules, seed),
+		Some("profile") => run_profile(args, &board),
+		Some("opponent") => run_opponent(args, &board),
+		Some("binlog") => run_binlog(args, &rules.context),
+		Some("commit") => run_commit(args, &rules),
+		Some("verify") => run_verify(args, &rules),
+		Some("resume") => {
+			let path = args.next().or_usage(Some("resume"), "resume requires a save file");
+			run_repl(args, &rules, &*locale, Some(Resume::Save(path)))
+		},
+		Some("load-belief") => {
+			let path = args.next().or_usage(Some("load-belief"), "load-belief requires a belief dump");
+			run_repl(args, &rules, &*locale, Some(Resume::Belief(path)))
+		},
+		Some("help") => run_help(args),
+		Some("completions") => run_completions(args),
+		#[cfg(not(feature = "network"))]
+		Some(command @ ("coordinate" | "worker" | "remote" | "serve")) => fail(&format!("This build has no {} command (build with the \"network\" feature)", command)),
+		#[cfg(not(feature = "selfplay"))]
+		Some("selfplay") => fail("This build has no selfplay command (build with the \"selfplay\" feature)"),
+		Some(command) => usage_error(None, &format!("Unknown command {}", command)),
+		None => run_repl(args, &rules, &*locale, None),
+	}
+
+	if memory_report {
+		print!("Peak memory:\n{}", rules.context.memory.report());
+	}
+
+	// Timings go to stderr, out of the way of the command's own output
+	if timing::ENABLED {
+		eprint!("{}", timing::report());
+	}
 }
@@ -0,0 +1,212 @@
+// Accounting for the memory held by the inference's large data structures,
+// and an optional cap on it. Each structure registers its size with a Guard
+// for as long as it lives, so the peak of each component can be reported
+// afterwards. With a cap set, GameState skips building the overlap cache when
+// it would push the total past the cap, falling back on the cover masks
+// (slower, but far smaller), and the exact solver stops before its memo
+// outgrows the cap.
+//
+// The accounts live in a Meter, which boards carry (see context::Context), so
+// games counted against different meters, or capped differently, can run
+// side by side in one process.
+//
+// Sizes count the heap buffers and the structures themselves, not allocator
+// overhead, so they are estimates on the low side.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Component {
+	// The overlap cache shared by game states (see OverlapCache)
+	OverlapCache,
+
+	// The square-to-positions masks shared by game states (see CoverMasks)
+	CoverMasks,
+
+	// Each game state's sets of remaining positions
+	Placements,
+
+	// The counts and bitsets a heatmap computation works in
+	HeatmapScratch,
+
+	// The exact solver's table of solved states
+	SolverMemo,
+}
+
+pub const COMPONENTS: [Component; 5] = [Component::OverlapCache, Component::CoverMasks, Component::Placements,
+                                        Component::HeatmapScratch, Component::SolverMemo];
+
+impl Component {
+	pub fn name(self) -> &'static str {
+		match self {
+			Component::OverlapCache => "overlap cache",
+			Component::CoverMasks => "cover masks",
+			Component::Placements => "placement sets",
+			Component::HeatmapScratch => "heatmap scratch",
+			Component::SolverMemo => "solver memo",
+		}
+	}
+
+	fn index(self) -> usize {
+		COMPONENTS.iter().position(|&component| component == self).expect("Unlisted component")
+	}
+}
+
+#[derive(Debug,Default)]
+struct Accounts {
+	// The bytes each component holds now, and the most it has held
+	live: [AtomicUsize; 5],
+	peak: [AtomicUsize; 5],
+
+	cap: Option<usize>,
+}
+
+// A set of accounts, shared by its clones
+#[derive(Clone,Debug,Default)]
+pub struct Meter(Arc<Accounts>);
+
+impl Meter {
+	// A meter with no cap
+	pub fn new() -> Meter {
+		Meter::default()
+	}
+
+	// A meter that caps the total at the given number of bytes
+	pub fn with_cap(bytes: usize) -> Meter {
+		Meter(Arc::new(Accounts { cap: Some(bytes), ..Accounts::default() }))
+	}
+
+	fn add(&self, component: Component, bytes: usize) {
+		let idx = component.index();
+		let live = self.0.live[idx].fetch_add(bytes, Ordering::Relaxed) + bytes;
+		self.0.peak[idx].fetch_max(live, Ordering::Relaxed);
+	}
+
+	fn sub(&self, component: Component, bytes: usize) {
+		self.0.live[component.index()].fetch_sub(bytes, Ordering::Relaxed);
+	}
+
+	// The bytes held now, over every component
+	pub fn live_total(&self) -> usize {
+		self.0.live.iter().map(|live| live.load(Ordering::Relaxed)).sum()
+	}
+
+	// The most bytes the component has held at once
+	pub fn peak(&self, component: Component) -> usize {
+		self.0.peak[component.index()].load(Ordering::Relaxed)
+	}
+
+	// Whether holding the given number of further bytes stays within the cap
+	pub fn fits(&self, bytes: usize) -> bool {
+		self.0.cap.is_none_or(|cap| self.live_total() + bytes <= cap)
+	}
+
+	// The peak of each component, one per line
+	pub fn report(&self) -> String {
+		COMPONENTS.iter().map(|&component| format!("{:<16} {}\n", component.name(), format_size(self.peak(component)))).collect()
+	}
+}
+
+// Meters are the same if they share their accounts
+impl PartialEq for Meter {
+	fn eq(&self, other: &Meter) -> bool {
+		Arc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+// Counts bytes against a component of a meter for as long as it lives.
+// Cloning counts the bytes again, as the clone's buffers are separate.
+#[derive(Debug)]
+pub struct Guard {
+	meter: Meter,
+	component: Component,
+	bytes: usize,
+}
+
+impl Guard {
+	pub fn new(meter: &Meter, component: Component, bytes: usize) -> Guard {
+		meter.add(component, bytes);
+		Guard { meter: meter.clone(), component, bytes }
+	}
+
+	// Count further bytes, for a structure that has grown
+	pub fn grow(&mut self, bytes: usize) {
+		self.meter.add(self.component, bytes);
+		self.bytes += bytes;
+	}
+
+	pub fn bytes(&self) -> usize {
+		self.bytes
+	}
+
+	// Whether growing by the given number of bytes stays within the meter's cap
+	pub fn fits(&self, bytes: usize) -> bool {
+		self.meter.fits(bytes)
+	}
+}
+
+impl Clone for Guard {
+	fn clone(&self) -> Guard {
+		Guard::new(&self.meter, self.component, self.bytes)
+	}
+
+	fn clone_from(&mut self, source: &Guard) {
+		self.meter.sub(self.component, self.bytes);
+		source.meter.add(source.component, source.bytes);
+		self.meter.clone_from(&source.meter);
+		self.component = source.component;
+		self.bytes = source.bytes;
+	}
+}
+
+impl Drop for Guard {
+	fn drop(&mut self) {
+		self.meter.sub(self.component, self.bytes);
+	}
+}
+
+// A value counted against a component for as long as it lives
+#[derive(Debug)]
+pub struct Tracked<T> {
+	value: T,
+	guard: Guard,
+}
+
+impl<T> Tracked<T> {
+	pub fn new(meter: &Meter, component: Component, value: T, bytes: usize) -> Tracked<T> {
+		Tracked { value, guard: Guard::new(meter, component, bytes) }
+	}
+
+	pub fn bytes(&self) -> usize {
+		self.guard.bytes()
+	}
+}
+
+impl<T> std::ops::Deref for Tracked<T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		&self.value
+	}
+}
+
+// Parse a size such as "4096", "512K", "64M", or "1G" (powers of 1024)
+pub fn parse_size(desc: &str) -> Option<usize> {
+	let (digits, scale) = match desc.chars().last()?.to_ascii_uppercase() {
+		'K' => (&desc[..desc.len() - 1], 1 << 10),
+		'M' => (&desc[..desc.len() - 1], 1 << 20),
+		'G' => (&desc[..desc.len() - 1], 1 << 30),
+		_ => (desc, 1),
+	};
+	digits.parse::<usize>().ok()?.checked_mul(scale)
+}
+
+// Format a size in bytes for people, e.g. "1.5 MiB"
+pub fn format_size(bytes: usize) -> String {
+	match bytes {
+		0..=1023 => format!("{} B", bytes),
+		1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+		_ => format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+	}
+}
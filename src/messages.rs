@@ -0,0 +1,366 @@
+// User-facing text for the interactive modes. Game logic produces Messages,
+// and a Locale turns them into text, so offering the assistant in another
+// language means adding a Locale rather than touching the game logic.
+
+use super::{format_move, format_placement_counts, BoardPos, ShipType, ShotResult};
+use coords::Coordinates;
+use events::EventError;
+use forecast::SinkForecast;
+use forced::ForcedWin;
+use hint::Hint;
+use history::Stage;
+use placement::PlacementError;
+use record::ShotRecord;
+use std::time::Duration;
+
+// What a board description says about one square
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Cell {
+	// Unshot (or, on our own board, empty)
+	Unknown,
+
+	Miss,
+
+	// A hit, on the given ship if known
+	Hit(Option<ShipType>),
+
+	// The hit that sank the given ship
+	Sunk(ShipType),
+
+	// One of our own ships
+	Ship(ShipType),
+}
+
+// Everything the interactive modes say to the user
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Message<'a> {
+	// The number of candidate placements remaining for each ship type
+	PlacementCounts(&'a [(ShipType, usize)]),
+
+	// The recommended next shot, and whether the search was cut short
+	BestShot { pos: BoardPos, cut_short: bool },
+
+	// A line sinking the rest of the fleet within a number of shots whatever the layout
+	ForcedWin(ForcedWin),
+
+	NoSquaresLeft,
+
+	// The moves leave no layout of the fleet, so there is nothing to
+	// recommend until one of them is taken back or corrected
+	NoConsistentLayout,
+
+	// Counting every layout of the board would take too long, so searches
+	// stop after the time limit, and shots come from sampled estimates if
+	// sampled
+	LargeBoard { sampled: bool, time_limit: Duration },
+
+	// Shown while the AI searches, before the dots marking its progress
+	Thinking,
+
+	// A move result that was suspiciously unlikely
+	CheatWarning { pos: BoardPos, probability: f64 },
+
+	// A line that isn't a valid move
+	InvalidMove(&'a str),
+
+	// Ask for the placement of the given ship
+	PlacePrompt(ShipType),
+
+	InvalidPlacement(PlacementError),
+
+	// A placement line that isn't a square and orientation
+	ExpectedPlacement,
+
+	// The finished layout's score against the opening heatmap, and the board average
+	PlacementScore { score: f64, average: f64 },
+
+	// The layout was saved to the given file
+	PlacementSaved(&'a str),
+
+	// A sentence describing one row of a board (for screen readers), one cell
+	// per column, labeled in the board's coordinates
+	BoardRow { row: u8, cells: &'a [Cell], coordinates: Coordinates },
+
+	// The statistics panel: the game so far, the estimated shots to go, and the
+	// AI's historical average after as many shots, if known
+	StatsPanel { shots: usize, hits: usize, sunk: usize, ships: usize, to_go: f64, history: Option<Stage> },
+
+	// One move's record: what it hit and what it ruled out
+	ShotLog(&'a ShotRecord),
+
+	// A hint towards the recommended shot
+	Hint(&'a Hint),
+
+	// A hint was asked for with a strength outside 1 to the given maximum
+	InvalidHintStrength(u8),
+
+	// An event (a move, correction, or undo) didn't apply to the game
+	EventRejected(EventError),
+
+	// A shot was undone
+	Undone(BoardPos),
+
+	// A radar sweep around the square was undone
+	SweepUndone(BoardPos),
+
+	// The opponent relocated a ship, or that was undone
+	Relocated,
+	RelocationUndone,
+
+	// The forecast for each ship still afloat
+	SinkForecasts(&'a [SinkForecast]),
+}
+
+// The positions a move ruled out, e.g. "P: 4, D: 12", or None if it ruled out nothing
+fn eliminated_counts(record: &ShotRecord) -> Option<String> {
+	let counts: Vec<(ShipType, usize)> = record.eliminated.iter().cloned().filter(|&(_, count)| count > 0).collect();
+	if counts.is_empty() { None } else { Some(format_placement_counts(&counts)) }
+}
+
+// The hit rate of the given shots, as a percentage
+fn accuracy(shots: usize, hits: usize) -> f64 {
+	if shots == 0 { 0.0 } else { hits as f64 * 100.0 / shots as f64 }
+}
+
+// The notable squares of a row as (column number, cell) pairs
+fn notable_cells(cells: &[Cell], coordinates: Coordinates) -> Vec<(u32, Cell)> {
+	cells.iter().enumerate().filter(|&(_, &cell)| cell != Cell::Unknown).map(|(col, &cell)| (coordinates.column_label(col as u8), cell)).collect()
+}
+
+// A list of squares, e.g. "A1, B2, C3"
+fn squares_list(squares: &[BoardPos]) -> String {
+	squares.iter().map(|pos| pos.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+// A language for the interactive modes
+pub trait Locale {
+	// The language code selecting this locale, e.g. "en"
+	fn code(&self) -> &'static str;
+
+	fn ship_name(&self, stype: ShipType) -> &'static str;
+
+	fn text(&self, message: &Message) -> String;
+}
+
+pub struct English;
+
+impl Locale for English {
+	fn code(&self) -> &'static str {
+		"en"
+	}
+
+	fn ship_name(&self, stype: ShipType) -> &'static str {
+		match stype {
+			ShipType::Patrol => "Patrol",
+			ShipType::Destroyer => "Destroyer",
+			ShipType::Submarine => "Submarine",
+			ShipType::Battleship => "Battleship",
+			ShipType::Carrier => "Carrier",
+		}
+	}
+
+	fn text(&self, message: &Message) -> String {
+		match *message {
+			Message::PlacementCounts(counts) => format_placement_counts(counts),
+			Message::BestShot { pos, cut_short } => format!("Best shot: {}{}", pos, if cut_short { " (search cut short)" } else { "" }),
+			Message::ForcedWin(win) => format!("Mate in {}: every remaining ship sinks within {} shots whatever the layout, starting at {}", win.shots, win.shots, win.first),
+			Message::NoSquaresLeft => "No squares left to shoot".to_string(),
+			Message::NoConsistentLayout => "No layout is consistent with the moves so far; undo or correct a move to go on".to_string(),
+			Message::LargeBoard { sampled: true, time_limit } => format!("Counting every layout of this board would take too long, so shots come from Monte Carlo estimates and other searches stop after {} s (see --strategy and --time-limit)", time_limit.as_secs()),
+			Message::LargeBoard { sampled: false, time_limit } => format!("Counting every layout of this board would take too long, so searches stop after {} s (see --time-limit)", time_limit.as_secs()),
+			Message::Thinking => "Thinking".to_string(),
+			Message::CheatWarning { pos, probability } => {
+				format!("Warning: the result of {} had probability {:.4}; possible cheating or data-entry error", pos, probability)
+			},
+			Message::InvalidMove(line) => format!("Invalid move {}", line),
+			Message::PlacePrompt(stype) => {
+				format!("Place the {} (size {}) as <square> <H|V>, or \"undo\":", self.ship_name(stype), super::ship_size(stype))
+			},
+			Message::InvalidPlacement(e) => format!("Invalid placement: {}", match e {
+				PlacementError::OutOfBounds => "the ship would run off the board".to_string(),
+				PlacementError::Overlap(stype) => format!("the ship would overlap the {}", self.ship_name(stype)),
+				PlacementError::Touching(stype) => format!("the ship would touch the {}", self.ship_name(stype)),
+				PlacementError::AlreadyPlaced => "the ship has already been placed".to_string(),
+				PlacementError::WrongOrientation(stype) => format!("the rules don't allow the {} that way round", self.ship_name(stype).to_lowercase()),
+				PlacementError::NotInFleet(stype) => format!("there is no {} in the fleet", self.ship_name(stype).to_lowercase()),
+			}),
+			Message::ExpectedPlacement => "Expected a square and H or V, e.g. \"B2 H\"".to_string(),
+			Message::PlacementScore { score, average } => {
+				format!("Average attacker probability on your ships: {:.3} (board average {:.3})", score, average)
+			},
+			Message::PlacementSaved(path) => format!("Placement saved to {}", path),
+			Message::BoardRow { row, cells, coordinates } => {
+				let notable: Vec<String> = notable_cells(cells, coordinates).iter().map(|&(col, cell)| match cell {
+					Cell::Unknown | Cell::Miss => format!("miss at {}", col),
+					Cell::Hit(None) => format!("hit at {}", col),
+					Cell::Hit(Some(stype)) => format!("hit on the {} at {}", self.ship_name(stype).to_lowercase(), col),
+					Cell::Sunk(stype) => format!("sank the {} at {}", self.ship_name(stype).to_lowercase(), col),
+					Cell::Ship(stype) => format!("{} at {}", self.ship_name(stype).to_lowercase(), col),
+				}).collect();
+				let row = coordinates.row_label(cells.len() as u8, row);
+				if notable.is_empty() { format!("Row {}: empty.", row) } else { format!("Row {}: {}.", row, notable.join(", ")) }
+			},
+			Message::StatsPanel { shots, hits, sunk, ships, to_go, history } => {
+				let mut out = format!("Shots {}, hits {} ({:.0}% accuracy), sunk {} of {}, about {:.1} shots to go",
+				                      shots, hits, accuracy(shots, hits), sunk, ships, to_go);
+				out += &match history {
+					Some(stage) => format!("\nAI average after {} shots ({} games): {:.1} hits, {:.1} sunk, {:.1} shots to go",
+					                       shots, stage.games, stage.hits, stage.sunk, stage.to_go),
+					None => format!("\nNo AI history after {} shots", shots),
+				};
+				out
+			},
+			Message::ShotLog(record) => {
+				let what = match (record.result, record.ship) {
+					(ShotResult::Miss, _) => String::from("miss"),
+					(ShotResult::Sunk(stype), _) => format!("sank the {}", self.ship_name(stype).to_lowercase()),
+					(_, Some(stype)) => format!("hit the {}{}", self.ship_name(stype).to_lowercase(), if record.inferred { " (inferred)" } else { "" }),
+					(_, None) => String::from("hit an unknown ship"),
+				};
+				let ruled_out = eliminated_counts(record).map_or(String::from("ruled nothing out"), |counts| format!("ruled out {}", counts));
+				format!("{}. {}: {}; {}", record.turn, format_move((record.pos, record.result)), what, ruled_out)
+			},
+			Message::Hint(hint) => match *hint {
+				Hint::Quadrant { top, left } => format!("Hint: the {}-{} quadrant is the most promising",
+				                                        if top { "top" } else { "bottom" }, if left { "left" } else { "right" }),
+				Hint::Candidates(ref squares) => format!("Hint: try one of {}", squares_list(squares)),
+				Hint::Shot(pos) => format!("Hint: shoot {}", pos),
+			},
+			Message::InvalidHintStrength(max) => format!("The hint strength must be from 1 to {}", max),
+			Message::EventRejected(EventError::AlreadyShot(pos)) => format!("{} already has a result; use \"correct\" to change it", pos),
+			Message::EventRejected(EventError::NotShot(pos)) => format!("{} hasn't been shot this game", pos),
+			Message::EventRejected(EventError::NotSwept(pos)) => format!("{} hasn't been swept this game", pos),
+			Message::EventRejected(EventError::AlreadyRelocated) => String::from("A ship has already been relocated this game"),
+			Message::EventRejected(EventError::NotRelocated) => String::from("No ship has been relocated this game"),
+			Message::EventRejected(EventError::NothingToUndo) => String::from("There is nothing to undo"),
+			Message::Undone(pos) => format!("Undid the shot at {}", pos),
+			Message::SweepUndone(pos) => format!("Undid the sweep around {}", pos),
+			Message::Relocated => String::from("The opponent relocated a ship; misses from before the move only hold for the ships that stayed"),
+			Message::RelocationUndone => String::from("Undid the relocation"),
+			Message::SinkForecasts(forecasts) => forecasts.iter().map(|forecast| {
+				format!("{}: {:.0}% likely to be sunk next, about {:.1} shots to sink", self.ship_name(forecast.stype), 100.0 * forecast.next, forecast.shots)
+			}).collect::<Vec<_>>().join("\n"),
+		}
+	}
+}
+
+pub struct Spanish;
+
+impl Locale for Spanish {
+	fn code(&self) -> &'static str {
+		"es"
+	}
+
+	fn ship_name(&self, stype: ShipType) -> &'static str {
+		match stype {
+			ShipType::Patrol => "patrullero",
+			ShipType::Destroyer => "destructor",
+			ShipType::Submarine => "submarino",
+			ShipType::Battleship => "acorazado",
+			ShipType::Carrier => "portaaviones",
+		}
+	}
+
+	fn text(&self, message: &Message) -> String {
+		match *message {
+			Message::PlacementCounts(counts) => format_placement_counts(counts),
+			Message::BestShot { pos, cut_short } => format!("Mejor disparo: {}{}", pos, if cut_short { " (búsqueda interrumpida)" } else { "" }),
+			Message::ForcedWin(win) => format!("Mate en {}: todos los barcos restantes se hunden en {} disparos sea cual sea la colocación, empezando por {}", win.shots, win.shots, win.first),
+			Message::NoSquaresLeft => "No quedan casillas por disparar".to_string(),
+			Message::NoConsistentLayout => "Ninguna colocación es coherente con las jugadas hasta ahora; deshaz o corrige una jugada para seguir".to_string(),
+			Message::LargeBoard { sampled: true, time_limit } => format!("Contar todas las colocaciones de este tablero llevaría demasiado tiempo, así que los disparos salen de estimaciones de Monte Carlo y las demás búsquedas se detienen a los {} s (véanse --strategy y --time-limit)", time_limit.as_secs()),
+			Message::LargeBoard { sampled: false, time_limit } => format!("Contar todas las colocaciones de este tablero llevaría demasiado tiempo, así que las búsquedas se detienen a los {} s (véase --time-limit)", time_limit.as_secs()),
+			Message::Thinking => "Pensando".to_string(),
+			Message::CheatWarning { pos, probability } => {
+				format!("Aviso: el resultado de {} tenía probabilidad {:.4}; posible trampa o error al introducir los datos", pos, probability)
+			},
+			Message::InvalidMove(line) => format!("Jugada no válida: {}", line),
+			Message::PlacePrompt(stype) => {
+				format!("Coloca el {} (tamaño {}) como <casilla> <H|V>, o \"undo\":", self.ship_name(stype), super::ship_size(stype))
+			},
+			Message::InvalidPlacement(e) => format!("Colocación no válida: {}", match e {
+				PlacementError::OutOfBounds => "el barco se saldría del tablero".to_string(),
+				PlacementError::Overlap(stype) => format!("el barco se superpondría al {}", self.ship_name(stype)),
+				PlacementError::Touching(stype) => format!("el barco tocaría al {}", self.ship_name(stype)),
+				PlacementError::AlreadyPlaced => "el barco ya está colocado".to_string(),
+				PlacementError::WrongOrientation(stype) => format!("las reglas no permiten el {} en esa orientación", self.ship_name(stype)),
+				PlacementError::NotInFleet(stype) => format!("no hay ningún {} en la flota", self.ship_name(stype)),
+			}),
+			Message::ExpectedPlacement => "Se esperaba una casilla y H o V, p. ej. \"B2 H\"".to_string(),
+			Message::PlacementScore { score, average } => {
+				format!("Probabilidad media del atacante sobre tus barcos: {:.3} (media del tablero {:.3})", score, average)
+			},
+			Message::PlacementSaved(path) => format!("Colocación guardada en {}", path),
+			Message::BoardRow { row, cells, coordinates } => {
+				let notable: Vec<String> = notable_cells(cells, coordinates).iter().map(|&(col, cell)| match cell {
+					Cell::Unknown | Cell::Miss => format!("agua en {}", col),
+					Cell::Hit(None) => format!("tocado en {}", col),
+					Cell::Hit(Some(stype)) => format!("tocado al {} en {}", self.ship_name(stype), col),
+					Cell::Sunk(stype) => format!("hundido el {} en {}", self.ship_name(stype), col),
+					Cell::Ship(stype) => format!("{} en {}", self.ship_name(stype), col),
+				}).collect();
+				let row = coordinates.row_label(cells.len() as u8, row);
+				if notable.is_empty() { format!("Fila {}: vacía.", row) } else { format!("Fila {}: {}.", row, notable.join(", ")) }
+			},
+			Message::StatsPanel { shots, hits, sunk, ships, to_go, history } => {
+				let mut out = format!("Disparos {}, tocados {} ({:.0}% de acierto), hundidos {} de {}, faltan unos {:.1} disparos",
+				                      shots, hits, accuracy(shots, hits), sunk, ships, to_go);
+				out += &match history {
+					Some(stage) => format!("\nMedia de la IA tras {} disparos ({} partidas): {:.1} tocados, {:.1} hundidos, faltaban {:.1} disparos",
+					                       shots, stage.games, stage.hits, stage.sunk, stage.to_go),
+					None => format!("\nSin historial de la IA tras {} disparos", shots),
+				};
+				out
+			},
+			Message::ShotLog(record) => {
+				let what = match (record.result, record.ship) {
+					(ShotResult::Miss, _) => String::from("agua"),
+					(ShotResult::Sunk(stype), _) => format!("hundido el {}", self.ship_name(stype)),
+					(_, Some(stype)) => format!("tocado el {}{}", self.ship_name(stype), if record.inferred { " (deducido)" } else { "" }),
+					(_, None) => String::from("tocado un barco desconocido"),
+				};
+				let ruled_out = eliminated_counts(record).map_or(String::from("no descartó nada"), |counts| format!("descartó {}", counts));
+				format!("{}. {}: {}; {}", record.turn, format_move((record.pos, record.result)), what, ruled_out)
+			},
+			Message::Hint(hint) => match *hint {
+				Hint::Quadrant { top, left } => format!("Pista: el cuadrante {} {} es el más prometedor",
+				                                        if top { "superior" } else { "inferior" }, if left { "izquierdo" } else { "derecho" }),
+				Hint::Candidates(ref squares) => format!("Pista: prueba una de {}", squares_list(squares)),
+				Hint::Shot(pos) => format!("Pista: dispara a {}", pos),
+			},
+			Message::InvalidHintStrength(max) => format!("La fuerza de la pista debe ir de 1 a {}", max),
+			Message::EventRejected(EventError::AlreadyShot(pos)) => format!("{} ya tiene resultado; usa \"correct\" para cambiarlo", pos),
+			Message::EventRejected(EventError::NotShot(pos)) => format!("No se ha disparado a {} en esta partida", pos),
+			Message::EventRejected(EventError::NotSwept(pos)) => format!("No se ha usado el radar en {} en esta partida", pos),
+			Message::EventRejected(EventError::AlreadyRelocated) => String::from("Ya se ha movido un barco en esta partida"),
+			Message::EventRejected(EventError::NotRelocated) => String::from("No se ha movido ningún barco en esta partida"),
+			Message::EventRejected(EventError::NothingToUndo) => String::from("No hay nada que deshacer"),
+			Message::Undone(pos) => format!("Se deshizo el disparo a {}", pos),
+			Message::SweepUndone(pos) => format!("Se deshizo el barrido de radar alrededor de {}", pos),
+			Message::Relocated => String::from("El rival movió un barco; los fallos de antes del movimiento solo valen para los barcos que no se movieron"),
+			Message::RelocationUndone => String::from("Se deshizo el movimiento del barco"),
+			Message::SinkForecasts(forecasts) => forecasts.iter().map(|forecast| {
+				format!("{}: {:.0}% de probabilidad de hundirse el próximo, unos {:.1} disparos para hundirlo", self.ship_name(forecast.stype), 100.0 * forecast.next, forecast.shots)
+			}).collect::<Vec<_>>().join("\n"),
+		}
+	}
+}
+
+// The codes of every built-in locale, for by_code
+pub const LOCALE_CODES: &[&str] = &["en", "es"];
+
+pub fn by_code(code: &str) -> Option<Box<dyn Locale>> {
+	match code {
+		"en" => Some(Box::new(English)),
+		"es" => Some(Box::new(Spanish)),
+		_ => None,
+	}
+}
+
+// The locale chosen by $BATTLESHIP_AI_LANG, falling back to the language part
+// of $LANG (e.g. "es" from "es_ES.UTF-8") and then to English
+pub fn from_env() -> Box<dyn Locale> {
+	let lang = std::env::var("BATTLESHIP_AI_LANG").or_else(|_| std::env::var("LANG")).unwrap_or_default();
+	let code = lang.split(['_', '.', '-']).next().unwrap_or("");
+	by_code(code).unwrap_or_else(|| Box::new(English))
+}
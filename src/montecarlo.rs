@@ -0,0 +1,179 @@
+// Heatmaps estimated by sampling layouts rather than counting every one, for
+// positions where the exact count takes too long. Each estimate comes with its
+// standard error, so a search can tell when it has sampled enough: the
+// strategy here stops as soon as its best square is clearly better than the
+// runner-up (or the two are known to be within a sliver of each other), or
+// when its time runs out.
+
+use super::{BoardPos, GameState, Heatmap};
+use cancel::CancelToken;
+#[cfg(not(feature = "batch-kernel"))]
+use forecast;
+#[cfg(feature = "batch-kernel")]
+use kernel;
+use rng::Rng;
+use strategy::Strategy;
+
+// How many layouts the strategy samples at most
+pub const DEFAULT_MAX_SAMPLES: u64 = 10_000;
+
+// How many layouts are sampled before separation is first checked, and
+// between later checks
+const BATCH: u64 = 200;
+
+// How many standard errors the best square's estimate must lead the
+// runner-up's by to count as separated
+pub const SEPARATION_Z: f64 = 3.0;
+
+// How close the best and runner-up squares' probabilities must be known to be
+// for the choice between them not to matter
+pub const INDIFFERENCE: f64 = 0.05;
+
+// Per-square hit counts over layouts drawn uniformly from those consistent
+// with the moves so far
+#[derive(Clone,Debug,PartialEq)]
+pub struct SampledHeatmap {
+	// The number of sampled layouts in which each square is occupied
+	pub hits: Vec<u64>,
+
+	// The number of layouts sampled
+	pub samples: u64,
+}
+
+impl SampledHeatmap {
+	// The estimated probability that the given square is occupied
+	pub fn probability(&self, pos: BoardPos) -> f64 {
+		if self.samples == 0 {
+			return 0.0;
+		}
+
+		self.hits[pos.index()] as f64 / self.samples as f64
+	}
+
+	// The standard error of the square's estimate
+	pub fn standard_error(&self, pos: BoardPos) -> f64 {
+		if self.samples == 0 {
+			return 0.5;
+		}
+
+		let p = self.probability(pos);
+		(p * (1.0 - p) / self.samples as f64).sqrt()
+	}
+
+	// The convergence diagnostic: the largest standard error of any
+	// square's estimate
+	pub fn max_standard_error(&self, state: &GameState) -> f64 {
+		state.board().squares().map(|pos| self.standard_error(pos)).fold(0.0, f64::max)
+	}
+
+	// The unshot squares with the highest and second-highest estimates
+	fn leaders(&self, state: &GameState) -> (Option<BoardPos>, Option<BoardPos>) {
+		let mut best: Option<BoardPos> = None;
+		let mut second: Option<BoardPos> = None;
+		for pos in state.board().squares().filter(|&pos| !state.is_shot(pos)) {
+			if best.is_none_or(|best| self.hits[pos.index()] > self.hits[best.index()]) {
+				second = best;
+				best = Some(pos);
+			} else if second.is_none_or(|second| self.hits[pos.index()] > self.hits[second.index()]) {
+				second = Some(pos);
+			}
+		}
+		(best, second)
+	}
+
+	// The unshot square with the highest estimate, first in row-major order
+	// among equals
+	pub fn best(&self, state: &GameState) -> Option<BoardPos> {
+		self.leaders(state).0
+	}
+
+	// Whether the best unshot square's estimate leads the runner-up's by at
+	// least SEPARATION_Z standard errors of their difference (true when
+	// there's no runner-up)
+	pub fn separated(&self, state: &GameState) -> bool {
+		match self.leaders(state) {
+			(Some(best), Some(second)) => {
+				let spread = (self.standard_error(best).powi(2) + self.standard_error(second).powi(2)).sqrt();
+				self.probability(best) - self.probability(second) > SEPARATION_Z * spread
+			},
+			_ => true,
+		}
+	}
+
+	// Whether sampling more would be wasted on choosing the shot: the best
+	// square is separated from the runner-up, or the gap between them is known
+	// to within INDIFFERENCE either way
+	pub fn settled(&self, state: &GameState) -> bool {
+		match self.leaders(state) {
+			(Some(best), Some(second)) => {
+				let spread = (self.standard_error(best).powi(2) + self.standard_error(second).powi(2)).sqrt();
+				self.separated(state) || SEPARATION_Z * spread < INDIFFERENCE
+			},
+			_ => true,
+		}
+	}
+
+	// The estimates as a heatmap over the samples, for rendering or for
+	// strategies built on exact heatmaps
+	pub fn to_heatmap(&self) -> Heatmap {
+		Heatmap { counts: self.hits.clone(), total: self.samples }
+	}
+}
+
+// Sample up to the given number of layouts, stopping early once the choice
+// of shot is settled (if stop_when_settled) or the token is cancelled. Fails
+// if no layout fits the moves. With the "batch-kernel" feature, the layouts are drawn a
+// batch at a time and checked by the kernel module's backend.
+pub fn sample(state: &GameState, rng: &mut Rng, max_samples: u64, stop_when_settled: bool, cancel: &CancelToken) -> Result<SampledHeatmap, String> {
+	let board = state.board();
+	let mut heatmap = SampledHeatmap { hits: vec![0; board.num_squares()], samples: 0 };
+	#[cfg(feature = "batch-kernel")]
+	let mut sampler = kernel::Sampler::new(state);
+	while heatmap.samples < max_samples && !cancel.is_cancelled() {
+		#[cfg(feature = "batch-kernel")]
+		let ships = sampler.as_mut().and_then(|sampler| sampler.next(rng)).ok_or("No layout is consistent with the moves so far")?;
+		#[cfg(not(feature = "batch-kernel"))]
+		let ships = forecast::sample_layout(state, rng).ok_or("No layout is consistent with the moves so far")?.ships().to_vec();
+		for &(stype, pos) in &ships {
+			for square in board.placement(stype, pos).cells() {
+				heatmap.hits[square.index()] += 1;
+			}
+		}
+		heatmap.samples += 1;
+
+		if stop_when_settled && heatmap.samples.is_multiple_of(BATCH) && heatmap.settled(state) {
+			break;
+		}
+	}
+
+	Ok(heatmap)
+}
+
+// Shoots at the square with the highest sampled estimate, sampling until
+// the choice is settled, the sample limit is reached, or the search is
+// cancelled
+pub struct MonteCarlo {
+	rng: Rng,
+	max_samples: u64,
+}
+
+impl MonteCarlo {
+	pub fn new(max_samples: u64, seed: u64) -> MonteCarlo {
+		MonteCarlo { rng: Rng::new(seed), max_samples }
+	}
+}
+
+impl Strategy for MonteCarlo {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		match sample(state, &mut self.rng, self.max_samples, true, cancel) {
+			Ok(ref heatmap) if heatmap.samples > 0 => heatmap.best(state),
+
+			// With nothing sampled, any unshot square will do
+			_ => state.board().squares().find(|&pos| !state.is_shot(pos)),
+		}
+	}
+
+	fn needs_heatmap(&self) -> bool {
+		false
+	}
+}
@@ -0,0 +1,170 @@
+// Named profiles of the people we play against, kept in a directory with one
+// file per opponent: where they tend to place their fleet (a per-square
+// profile, which doubles as a prior for shooting at them), how our games
+// against them have gone (a history, for the statistics panel), and free-form
+// notes.
+//
+// An opponent file has a "name <name>" line and one "note <text>" line per
+// note, then a "[profile]" line followed by the profile file, and a
+// "[history]" line followed by the history file.
+
+use super::{Board, BoardPos, ShotResult};
+use durable;
+use history::History;
+use profile::Profile;
+use std::path::{Path, PathBuf};
+
+// Where the opponent profiles are kept unless told otherwise
+pub const DEFAULT_DIR: &str = "opponents";
+
+const FILE_EXTENSION: &str = "txt";
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Opponent {
+	pub name: String,
+	pub notes: Vec<String>,
+
+	// Where they placed their ships, over the games recorded against them
+	pub profile: Profile,
+
+	// How our shooting went in those games
+	pub history: History,
+}
+
+impl Opponent {
+	// A new opponent whose games are played on the given board
+	pub fn new(name: &str, board: &Board) -> Opponent {
+		Opponent { name: String::from(name), notes: Vec::new(), profile: Profile::new(board), history: History::new() }
+	}
+
+	// Record a finished game against them, from our moves at their board
+	pub fn record_game(&mut self, shots: &[(BoardPos, ShotResult)]) {
+		self.profile.record_moves(shots);
+		self.history.record(shots);
+	}
+
+	// A summary of the profile for showing: the name, record, and notes,
+	// followed by where their ships tend to be
+	pub fn render(&self) -> String {
+		let mut out = format!("{}: {} games recorded\n", self.name, self.history.games());
+		for note in &self.notes {
+			out += &format!("  note: {}\n", note);
+		}
+		out + &self.profile.render()
+	}
+
+	pub fn to_file_string(&self) -> String {
+		let mut out = format!("name {}\n", self.name);
+		for note in &self.notes {
+			out += &format!("note {}\n", note);
+		}
+		out += "[profile]\n";
+		out += &self.profile.to_file_string();
+		out += "[history]\n";
+		out + &self.history.to_file_string()
+	}
+
+	// Parse an opponent whose profile is for base's board, unless it names
+	// another (see Profile::from_file_string)
+	pub fn from_file_string(contents: &str, base: &Board) -> Result<Opponent, String> {
+		let (header, rest) = contents.split_once("[profile]\n").ok_or("Missing the [profile] section")?;
+		let (profile, history) = rest.split_once("[history]\n").ok_or("Missing the [history] section")?;
+
+		let mut name = None;
+		let mut notes = Vec::new();
+		for (line_num, line) in header.lines().enumerate().filter(|&(_, line)| !line.trim().is_empty()) {
+			match line.split_once(' ') {
+				Some(("name", value)) => name = Some(String::from(value.trim())),
+				Some(("note", value)) => notes.push(String::from(value.trim())),
+				_ => return Err(format!("Line {}: expected \"name <name>\" or \"note <text>\"", line_num + 1)),
+			}
+		}
+
+		Ok(Opponent {
+			name: name.ok_or("Missing the name line")?,
+			notes,
+			profile: Profile::from_file_string(profile, base).map_err(|e| format!("[profile]: {}", e))?,
+			history: History::from_file_string(history).map_err(|e| format!("[history]: {}", e))?,
+		})
+	}
+}
+
+// Check that a name is usable as a file name: letters, digits, '-', and '_'
+pub fn check_name(name: &str) -> Result<(), String> {
+	if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+		return Err(format!("Invalid opponent name {} (use letters, digits, '-', and '_')", name));
+	}
+	Ok(())
+}
+
+// The directory of opponent profiles
+pub struct Opponents {
+	dir: PathBuf,
+
+	// The board opponents are read against, whose context applies to them all
+	board: Board,
+}
+
+impl Opponents {
+	pub fn new(dir: &Path, board: &Board) -> Opponents {
+		Opponents { dir: dir.to_path_buf(), board: board.clone() }
+	}
+
+	fn path(&self, name: &str) -> Result<PathBuf, String> {
+		check_name(name)?;
+		Ok(self.dir.join(format!("{}.{}", name, FILE_EXTENSION)))
+	}
+
+	// The names of every opponent, sorted
+	pub fn list(&self) -> Result<Vec<String>, String> {
+		let entries = match std::fs::read_dir(&self.dir) {
+			Ok(entries) => entries,
+			Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+			Err(e) => return Err(format!("Unable to read {}: {}", self.dir.display(), e)),
+		};
+
+		let mut names = Vec::new();
+		for entry in entries {
+			let path = entry.map_err(|e| format!("Unable to read {}: {}", self.dir.display(), e))?.path();
+			if path.extension().is_some_and(|ext| ext == FILE_EXTENSION) {
+				if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()).filter(|name| check_name(name).is_ok()) {
+					names.push(String::from(name));
+				}
+			}
+		}
+		names.sort();
+		Ok(names)
+	}
+
+	pub fn load(&self, name: &str) -> Result<Opponent, String> {
+		let path = self.path(name)?;
+		match durable::read(&path, self.board.context().allow_unsealed) {
+			Ok(contents) => Opponent::from_file_string(&contents, &self.board).map_err(|e| format!("{}: {}", path.display(), e)),
+			Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Err(format!("No opponent named {}", name)),
+			Err(e) => Err(format!("Unable to read {}: {}", path.display(), e)),
+		}
+	}
+
+	pub fn save(&self, opponent: &Opponent) -> Result<(), String> {
+		let path = self.path(&opponent.name)?;
+		std::fs::create_dir_all(&self.dir).map_err(|e| format!("Unable to create {}: {}", self.dir.display(), e))?;
+		durable::write(&path, &opponent.to_file_string()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+	}
+
+	// Save a new opponent, refusing to replace an existing one
+	pub fn add(&self, opponent: &Opponent) -> Result<(), String> {
+		if self.path(&opponent.name)?.exists() {
+			return Err(format!("There is already an opponent named {}", opponent.name));
+		}
+		self.save(opponent)
+	}
+
+	pub fn delete(&self, name: &str) -> Result<(), String> {
+		let path = self.path(name)?;
+		match std::fs::remove_file(&path) {
+			Ok(()) => Ok(()),
+			Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Err(format!("No opponent named {}", name)),
+			Err(e) => Err(format!("Unable to delete {}: {}", path.display(), e)),
+		}
+	}
+}
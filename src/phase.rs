@@ -0,0 +1,122 @@
+// Where a game stands, from what the inference believes:
+//   opening  nothing hit yet, and most of the fleet's placements remain
+//   hunt     searching for the next ship
+//   target   a hit may belong to a ship afloat, which is waiting to be sunk
+//   endgame  one ship afloat, or so few placements left for the ships afloat
+//            that the search is nearly over
+// A hit to finish off takes precedence over the rest, so the endgame is the
+// hunt for the last ships rather than their sinking.
+//
+// Compound strategies pick a policy per phase (see combine::PhaseSwitch), and
+// the interactive mode can give each phase its own time limit (see TimeLimits).
+
+use super::{GameState, ShipType, ShotResult};
+use std::time::Duration;
+use strategy::open_hits;
+
+// The opening lasts while at least this fraction of the placements remain
+const OPENING_PLACEMENTS: f64 = 0.75;
+
+// The endgame starts when the ships afloat have no more placements than this
+const ENDGAME_PLACEMENTS: usize = 12;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum GamePhase {
+	Opening,
+	Hunt,
+	Target,
+	Endgame,
+}
+
+// The names accepted by GamePhase::parse, in the order of a game
+pub const PHASE_NAMES: &[&str] = &["opening", "hunt", "target", "endgame"];
+
+impl GamePhase {
+	pub fn parse(name: &str) -> Option<GamePhase> {
+		match name {
+			"opening" => Some(GamePhase::Opening),
+			"hunt" => Some(GamePhase::Hunt),
+			"target" => Some(GamePhase::Target),
+			"endgame" => Some(GamePhase::Endgame),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		PHASE_NAMES[self.index()]
+	}
+
+	// The phase's position in PHASE_NAMES
+	pub fn index(self) -> usize {
+		match self {
+			GamePhase::Opening => 0,
+			GamePhase::Hunt => 1,
+			GamePhase::Target => 2,
+			GamePhase::Endgame => 3,
+		}
+	}
+}
+
+impl std::fmt::Display for GamePhase {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+impl GameState {
+	// Classify the position
+	pub fn phase(&self) -> GamePhase {
+		if !open_hits(self).is_empty() {
+			return GamePhase::Target;
+		}
+
+		let sunk: Vec<ShipType> = self.shots.iter().filter_map(|&(_, result)| match result {
+			ShotResult::Sunk(stype) => Some(stype),
+			_ => None,
+		}).collect();
+		let (mut afloat, mut remaining, mut initial) = (0, 0, 0);
+		for (&stype, plist) in self.board.fleet().iter().zip(self.pos_positions.iter()).filter(|&(stype, _)| !sunk.contains(stype)) {
+			afloat += 1;
+			remaining += plist.count();
+			initial += self.board.num_positions(stype);
+		}
+
+		if afloat <= 1 || remaining <= ENDGAME_PLACEMENTS {
+			GamePhase::Endgame
+		} else if sunk.is_empty() && self.shots.iter().all(|&(_, result)| result == ShotResult::Miss) && remaining as f64 >= OPENING_PLACEMENTS * initial as f64 {
+			GamePhase::Opening
+		} else {
+			GamePhase::Hunt
+		}
+	}
+}
+
+// A time limit for each phase, falling back to one for all of them
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct TimeLimits {
+	default: Option<Duration>,
+	per_phase: [Option<Duration>; 4],
+}
+
+impl TimeLimits {
+	pub fn set_default(&mut self, limit: Duration) {
+		self.default = Some(limit);
+	}
+
+	pub fn set(&mut self, phase: GamePhase, limit: Duration) {
+		self.per_phase[phase.index()] = Some(limit);
+	}
+
+	// The limit in the given phase, if any
+	pub fn get(&self, phase: GamePhase) -> Option<Duration> {
+		self.per_phase[phase.index()].or(self.default)
+	}
+
+	// Parse a "<phase>:<milliseconds>" limit, as --phase-time-limit takes it
+	pub fn parse_phase_limit(desc: &str) -> Result<(GamePhase, Duration), String> {
+		let (name, millis) = desc.split_once(':').ok_or_else(|| format!("expected <phase>:<milliseconds>, not {}", desc))?;
+		let phase = GamePhase::parse(name).ok_or_else(|| format!("Unknown phase {} (available: {})", name, PHASE_NAMES.join(", ")))?;
+		let millis = millis.parse().map_err(|_| format!("Invalid number of milliseconds {}", millis))?;
+		Ok((phase, Duration::from_millis(millis)))
+	}
+}
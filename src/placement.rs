@@ -0,0 +1,374 @@
+// Placement of our own fleet: validation, scoring, and the placement file format.
+//
+// A placement file has one line per ship: its type letter, the square of its
+// top-left end, and its orientation (H or V), e.g. "C B2 H". Layouts can also
+// be read and written as grids: one line per board row, top row first, with
+// '.' for water and the ship type letter for each square of a ship, e.g.
+// ".PP..".
+
+use super::{decode_shiptype, encode_shiptype, parse_square, render_board, ship_size, Board, BoardPos, Heatmap, Orientation,
+            PlacementId, ShipType, ShotResult};
+use context::Context;
+use diagnostic::{self, Diagnostic};
+use rng::Rng;
+use text;
+
+// How many times Layout::random starts over before giving up
+const MAX_RANDOM_ATTEMPTS: usize = 100_000;
+
+// Why a ship could not be placed
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum PlacementError {
+	// The ship would run off the board
+	OutOfBounds,
+
+	// The ship would overlap the given, already placed ship
+	Overlap(ShipType),
+
+	// The ship would touch the given, already placed ship (when touching is forbidden)
+	Touching(ShipType),
+
+	// The ship has already been placed
+	AlreadyPlaced,
+
+	// The rules don't allow the ship to be placed in that orientation
+	WrongOrientation(ShipType),
+
+	// The ship isn't in the board's fleet
+	NotInFleet(ShipType),
+}
+
+impl std::fmt::Display for PlacementError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match *self {
+			PlacementError::OutOfBounds => write!(f, "the ship would run off the board"),
+			PlacementError::Overlap(stype) => write!(f, "the ship would overlap the {:?}", stype),
+			PlacementError::Touching(stype) => write!(f, "the ship would touch the {:?}", stype),
+			PlacementError::AlreadyPlaced => write!(f, "the ship has already been placed"),
+			PlacementError::WrongOrientation(stype) => write!(f, "the {:?} may not be placed in that orientation", stype),
+			PlacementError::NotInFleet(stype) => write!(f, "there is no {:?} in the fleet", stype),
+		}
+	}
+}
+
+// Check whether two squares are orthogonally or diagonally adjacent (or equal)
+fn adjacent(pos1: BoardPos, pos2: BoardPos) -> bool {
+	let (row1, col1) = (pos1.row(), pos1.col());
+	let (row2, col2) = (pos2.row(), pos2.col());
+
+	(row1 as i32 - row2 as i32).abs() <= 1 && (col1 as i32 - col2 as i32).abs() <= 1
+}
+
+// The rules a layout must follow
+#[derive(Clone,Debug,PartialEq)]
+pub struct Constraints {
+	// The board the fleet is placed on
+	pub board: Board,
+
+	// Whether ships are allowed to touch each other (including diagonally)
+	pub allow_touching: bool,
+
+	// How each ship type (in fleet order) may be oriented
+	pub orientations: Vec<Orientation>,
+}
+
+impl Constraints {
+	// Constraints on the board allowing any orientation
+	pub fn new(board: &Board, allow_touching: bool) -> Constraints {
+		Constraints { board: board.clone(), allow_touching, orientations: vec![Orientation::Any; board.fleet().len()] }
+	}
+}
+
+// A (possibly partial) placement of our fleet
+#[derive(Clone,Debug,PartialEq)]
+pub struct Layout {
+	// The ships placed so far: type and position ID
+	ships: Vec<(ShipType, PlacementId)>,
+
+	constraints: Constraints,
+}
+
+impl Layout {
+	pub fn new(constraints: Constraints) -> Layout {
+		Layout { ships: Vec::new(), constraints }
+	}
+
+	// The ships placed so far: type and position ID
+	pub fn ships(&self) -> &[(ShipType, PlacementId)] {
+		&self.ships
+	}
+
+	// The board the layout is on
+	pub fn board(&self) -> &Board {
+		&self.constraints.board
+	}
+
+	pub fn constraints(&self) -> &Constraints {
+		&self.constraints
+	}
+
+	// The same layout in another context, e.g. to write it in other
+	// coordinates
+	pub fn with_context(&self, context: Context) -> Layout {
+		let mut layout = self.clone();
+		layout.constraints.board = layout.constraints.board.with_context(context);
+		layout
+	}
+
+	// Whether every ship type has been placed
+	pub fn is_complete(&self) -> bool {
+		self.ships.len() == self.board().fleet().len()
+	}
+
+	// The first ship type that has not yet been placed
+	pub fn next_unplaced(&self) -> Option<ShipType> {
+		self.board().fleet().iter().cloned().find(|&stype| self.ships.iter().all(|&(placed, _)| placed != stype))
+	}
+
+	// The squares the ship at the given position covers
+	fn ship_range(&self, stype: ShipType, pos: PlacementId) -> Vec<BoardPos> {
+		self.board().ship_range(stype, pos)
+	}
+
+	// Check whether the ship can be placed at the given position ID
+	pub fn check(&self, stype: ShipType, pos: PlacementId) -> Result<(), PlacementError> {
+		if self.ships.iter().any(|&(placed, _)| placed == stype) {
+			return Err(PlacementError::AlreadyPlaced);
+		}
+
+		let stype_idx = self.board().ship_index(stype).ok_or(PlacementError::NotInFleet(stype))?;
+		if !self.constraints.orientations[stype_idx].allows(self.board().is_horizontal(stype, pos)) {
+			return Err(PlacementError::WrongOrientation(stype));
+		}
+
+		let range = self.ship_range(stype, pos);
+		for &(other, other_pos) in &self.ships {
+			let other_range = self.ship_range(other, other_pos);
+			if range.iter().any(|square| other_range.contains(square)) {
+				return Err(PlacementError::Overlap(other));
+			}
+			if !self.constraints.allow_touching && range.iter().any(|&square| other_range.iter().any(|&o| adjacent(square, o))) {
+				return Err(PlacementError::Touching(other));
+			}
+		}
+
+		Ok(())
+	}
+
+	// Place a ship with its top-left end on the given square
+	pub fn place(&mut self, stype: ShipType, start: BoardPos, horizontal: bool) -> Result<(), PlacementError> {
+		self.board().ship_index(stype).ok_or(PlacementError::NotInFleet(stype))?;
+		let pos = self.board().position_id(stype, start, horizontal).ok_or(PlacementError::OutOfBounds)?;
+		self.check(stype, pos)?;
+		self.ships.push((stype, pos));
+		Ok(())
+	}
+
+	// Generate a uniformly random complete layout, or None if none was found
+	// (e.g. because the fleet cannot fit on the board without touching)
+	pub fn random(rng: &mut Rng, constraints: Constraints) -> Option<Layout> {
+		// Place the ships one at a time, starting over whenever a ship has nowhere to go.
+		// Restarting (rather than backtracking) keeps every complete layout equally likely.
+		'restart: for _ in 0..MAX_RANDOM_ATTEMPTS {
+			let mut layout = Layout::new(constraints.clone());
+
+			for (stype_idx, &stype) in constraints.board.fleet().iter().enumerate() {
+				// Horizontal positions come first, so a restricted ship picks from one half
+				let half = constraints.board.num_positions(stype) / 2;
+				let pos = match constraints.orientations[stype_idx] {
+					Orientation::Any => PlacementId::new(rng.gen_range(constraints.board.num_positions(stype))),
+					Orientation::Horizontal => PlacementId::new(rng.gen_range(half)),
+					Orientation::Vertical => PlacementId::new(half + rng.gen_range(half)),
+				};
+				if layout.check(stype, pos).is_err() {
+					continue 'restart;
+				}
+				layout.ships.push((stype, pos));
+			}
+
+			return Some(layout);
+		}
+
+		None
+	}
+
+	// The result of a shot at the given square against this layout
+	pub fn shot_result(&self, pos: BoardPos) -> ShotResult {
+		match self.ships.iter().find(|&&(stype, ship_pos)| self.ship_range(stype, ship_pos).contains(&pos)) {
+			Some(&(stype, _)) => ShotResult::Hit(Some(stype)),
+			None => ShotResult::Miss,
+		}
+	}
+
+	// The result of a shot at the given square after shots at the earlier squares:
+	// like shot_result, but Sunk if the shot hits the last unhit square of a ship
+	pub fn shot_result_after(&self, pos: BoardPos, earlier: &[BoardPos]) -> ShotResult {
+		match self.ships.iter().find(|&&(stype, ship_pos)| self.ship_range(stype, ship_pos).contains(&pos)) {
+			Some(&(stype, ship_pos)) if self.ship_range(stype, ship_pos).iter().all(|square| *square == pos || earlier.contains(square)) => {
+				ShotResult::Sunk(stype)
+			},
+			Some(&(stype, _)) => ShotResult::Hit(Some(stype)),
+			None => ShotResult::Miss,
+		}
+	}
+
+	// Remove the most recently placed ship
+	pub fn undo(&mut self) -> Option<(ShipType, PlacementId)> {
+		self.ships.pop()
+	}
+
+	// Remove the ship of the given type, returning where it was
+	pub fn remove(&mut self, stype: ShipType) -> Option<PlacementId> {
+		let idx = self.ships.iter().position(|&(placed, _)| placed == stype)?;
+		Some(self.ships.remove(idx).1)
+	}
+
+	// Move a placed ship to the given position ID, as under moving-ships
+	// rules. The layout is unchanged if the ship can't go there.
+	pub fn relocate(&mut self, stype: ShipType, to: PlacementId) -> Result<(), PlacementError> {
+		let idx = self.ships.iter().position(|&(placed, _)| placed == stype).ok_or(PlacementError::NotInFleet(stype))?;
+		let (_, from) = self.ships.remove(idx);
+		if let Err(e) = self.check(stype, to) {
+			self.ships.insert(idx, (stype, from));
+			return Err(e);
+		}
+		self.ships.insert(idx, (stype, to));
+		Ok(())
+	}
+
+	// The ship occupying each square, if any
+	pub fn occupancy(&self) -> Vec<Option<ShipType>> {
+		let mut cells = vec![None; self.board().num_squares()];
+		for &(stype, pos) in &self.ships {
+			for square in self.ship_range(stype, pos) {
+				cells[square.index()] = Some(stype);
+			}
+		}
+
+		cells
+	}
+
+	// Render the layout as a grid of ship letters
+	pub fn render(&self) -> String {
+		let cells: Vec<char> = self.occupancy().iter().map(|cell| cell.map_or('.', encode_shiptype)).collect();
+		render_board(self.board(), &cells)
+	}
+
+	// Score the layout against an attack heatmap: the average probability an
+	// attacker assigns to our occupied squares (lower is better)
+	pub fn score(&self, heatmap: &Heatmap) -> f64 {
+		let squares: Vec<BoardPos> = self.ships.iter().flat_map(|&(stype, pos)| self.ship_range(stype, pos)).collect();
+		if squares.is_empty() {
+			return 0.0;
+		}
+
+		squares.iter().map(|&square| heatmap.probability(square)).sum::<f64>() / squares.len() as f64
+	}
+
+	// Serialize the layout in the placement file format
+	pub fn to_file_string(&self) -> String {
+		self.ships.iter().map(|&(stype, pos)| {
+			let range = self.ship_range(stype, pos);
+			let horizontal = range.len() < 2 || range[1].index() == range[0].index() + 1;
+			format!("{} {} {}\n", encode_shiptype(stype), range[0], if horizontal { 'H' } else { 'V' })
+		}).collect()
+	}
+
+	// Write the layout as a grid
+	pub fn to_grid_string(&self) -> String {
+		self.occupancy().chunks(self.board().size() as usize).map(|row| row.iter().map(|cell| cell.map_or('.', encode_shiptype)).collect::<String>() + "\n").collect()
+	}
+
+	// Whether the contents are a grid for the board rather than a placement
+	// file: the first nonblank line is a row of cells
+	fn is_grid(board: &Board, contents: &str) -> bool {
+		text::lines(contents).map(str::trim).find(|line| !line.is_empty())
+			.is_some_and(|line| line.len() == board.size() as usize && line.chars().all(|c| ".PDSBC".contains(c)))
+	}
+
+	// Parse a layout from a grid, validating every ship
+	pub fn from_grid_string(contents: &str, constraints: Constraints) -> Result<Layout, String> {
+		let board = constraints.board.clone();
+		let rows: Vec<&str> = text::lines(contents).map(str::trim).filter(|line| !line.is_empty()).collect();
+		if rows.len() != board.size() as usize {
+			return Err(format!("expected {} rows, found {}", board.size(), rows.len()));
+		}
+
+		// Gather the squares of each ship type, in row-major order
+		let mut squares: Vec<Vec<BoardPos>> = vec![Vec::new(); board.fleet().len()];
+		for (row, line) in rows.iter().enumerate() {
+			let cells: Vec<char> = line.chars().collect();
+			if cells.len() != board.size() as usize {
+				return Err(format!("row {}: expected {} cells", row + 1, board.size()));
+			}
+			for (col, &cell) in cells.iter().enumerate() {
+				match cell {
+					'.' => {},
+					'P' | 'D' | 'S' | 'B' | 'C' => {
+						let stype = decode_shiptype(cell as u8);
+						let stype_idx = board.ship_index(stype).ok_or_else(|| format!("row {}: {}", row + 1, PlacementError::NotInFleet(stype)))?;
+						squares[stype_idx].push(board.square(row as u8, col as u8)?);
+					},
+					_ => return Err(format!("row {}: unknown cell '{}'", row + 1, cell)),
+				}
+			}
+		}
+
+		// Each ship's squares must be exactly one position of it: the one
+		// starting at its first square
+		let mut layout = Layout::new(constraints);
+		for (&stype, ship) in board.fleet().iter().zip(squares.iter()) {
+			let start = match ship.first() {
+				Some(&start) => start,
+				None => continue,
+			};
+			let horizontal = ship.get(1).is_none_or(|second| second.row() == start.row());
+			let matches = board.position_id(stype, start, horizontal).is_some_and(|pos| board.ship_range(stype, pos) == *ship);
+			if !matches {
+				return Err(format!("the {} squares aren't a straight line of {}", encode_shiptype(stype), ship_size(stype)));
+			}
+			layout.place(stype, start, horizontal).map_err(|e| e.to_string())?;
+		}
+
+		Ok(layout)
+	}
+
+	// Parse a layout from the placement file format or a grid, validating every ship
+	pub fn from_file_string(contents: &str, constraints: Constraints) -> Result<Layout, String> {
+		if Layout::is_grid(&constraints.board, contents) {
+			return Layout::from_grid_string(contents, constraints);
+		}
+
+		let board = constraints.board.clone();
+		let mut layout = Layout::new(constraints);
+
+		for (line_num, line) in text::lines(contents).enumerate().filter(|&(_, line)| !line.trim().is_empty()) {
+			let fields: Vec<&str> = line.split_whitespace().collect();
+			let err = |msg: &str| Diagnostic::new(line_num + 1, line, msg);
+			if fields.len() != 3 || fields[0].len() != 1 {
+				return Err(err("expected a ship letter, a square, and H or V").to_string());
+			}
+
+			let stype = match fields[0].as_bytes()[0] {
+				desc @ b'P' | desc @ b'D' | desc @ b'S' | desc @ b'B' | desc @ b'C' => decode_shiptype(desc),
+				_ => {
+					let letters: Vec<String> = board.fleet().iter().map(|&stype| encode_shiptype(stype).to_string()).collect();
+					let letters: Vec<&str> = letters.iter().map(String::as_str).collect();
+					return Err(err("unknown ship type").at_field(0).suggest(diagnostic::closest(fields[0], &letters)).to_string());
+				},
+			};
+			let start = parse_square(&board, fields[1]).ok_or_else(|| {
+				err("invalid square").at_field(1).suggest(diagnostic::nearest_square(&board, fields[1]).map(|pos| pos.to_string())).to_string()
+			})?;
+			let horizontal = match fields[2] {
+				"H" => true,
+				"V" => false,
+				_ => return Err(err("orientation must be H or V").at_field(2).suggest(diagnostic::closest(fields[2], &["H", "V"])).to_string()),
+			};
+
+			layout.place(stype, start, horizontal).map_err(|e| err(&e.to_string()).to_string())?;
+		}
+
+		Ok(layout)
+	}
+}
@@ -0,0 +1,88 @@
+// Thinking on the opponent's time. While the interactive mode waits for the
+// result of the shot it recommended, background threads apply each result it
+// could get to a copy of the game and count its heatmap, so once the real
+// result comes in, the next recommendation is ready at once (see
+// GameState::reuse_heatmap). When the result arrives, the other threads are
+// stopped and the one for that result is given until the deadline to finish;
+// a result that wasn't pondered is counted as usual.
+
+use super::{BoardPos, GameState, PlacementId, ShotResult};
+use cancel::CancelToken;
+use rules::Rules;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// How often finish checks on the thread it's waiting for
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+struct Task {
+	cur_move: (BoardPos, ShotResult),
+	cancel: CancelToken,
+	thread: JoinHandle<GameState>,
+}
+
+pub struct Ponder {
+	tasks: Vec<Task>,
+}
+
+// The results a shot at the square could be reported with under the rules: a
+// miss, and a hit on or the sinking of each ship with a remaining placement
+// there (whether the rest of the fleet still fits is left to the counting)
+pub fn results(state: &GameState, rules: &Rules, pos: BoardPos) -> Vec<(BoardPos, ShotResult)> {
+	let board = state.board();
+	let mut results = vec![ShotResult::Miss];
+	for &stype in board.fleet() {
+		let covering = state.positions(stype).iter().map(|id| board.placement(stype, PlacementId::new(id))).filter(|ship| ship.covers(pos));
+		for ship in covering {
+			let sinks = ship.cells().all(|square| square == pos || state.is_hit(square));
+			let result = rules.reported(if sinks { ShotResult::Sunk(stype) } else { ShotResult::Hit(Some(stype)) });
+			if !results.contains(&result) {
+				results.push(result);
+			}
+		}
+	}
+	results.into_iter().map(|result| (pos, result)).collect()
+}
+
+impl Ponder {
+	// Start working out the game after each of the moves
+	pub fn start(state: &GameState, moves: Vec<(BoardPos, ShotResult)>) -> Ponder {
+		let tasks = moves.into_iter().map(|cur_move| {
+			let cancel = CancelToken::new();
+			let (mut after, stop) = (state.clone(), cancel.clone());
+			let thread = thread::spawn(move || {
+				after.apply_move(cur_move);
+				after.precompute_heatmap(&stop, &mut |_| {});
+				after
+			});
+			Task { cur_move, cancel, thread }
+		}).collect();
+		Ponder { tasks }
+	}
+
+	// The result came in: stop pondering the others, and return the game after
+	// the move if it was pondered, with its heatmap if that was finished before the
+	// deadline
+	pub fn finish(mut self, cur_move: (BoardPos, ShotResult), deadline: &CancelToken) -> Option<GameState> {
+		let idx = self.tasks.iter().position(|task| task.cur_move == cur_move)?;
+		let task = self.tasks.swap_remove(idx);
+		for other in &self.tasks {
+			other.cancel.cancel();
+		}
+
+		while !task.thread.is_finished() && !deadline.is_cancelled() {
+			thread::sleep(POLL_INTERVAL);
+		}
+		task.cancel.cancel();
+		task.thread.join().ok()
+	}
+}
+
+impl Drop for Ponder {
+	// The threads left running notice the cancellation and stop on their own
+	fn drop(&mut self) {
+		for task in &self.tasks {
+			task.cancel.cancel();
+		}
+	}
+}
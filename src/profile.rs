@@ -0,0 +1,186 @@
+// A persistent profile of per-square statistics over every game played or
+// simulated locally: how often each square held a ship, and on which turn it
+// was hit. The occupancy statistics double as an empirical prior for shooting
+// (see adapt::Adaptive::with_prior).
+//
+// The profile file starts with a "games <count>" line and a "board <size>
+// <ship>..." line (the standard board if there is none), followed by one line
+// per square: "<square> <games occupied> <times hit> <sum of the turns it was
+// hit>", with turns counted from 1.
+
+use super::{parse_square, Board, BoardPos, ShotResult};
+use durable;
+use std::path::Path;
+
+// How many games' worth of weight the uninformed prior gets when blending it
+// with the profile
+const PRIOR_GAMES: f64 = 10.0;
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Profile {
+	board: Board,
+	games: u64,
+	occupied: Vec<u64>,
+	hits: Vec<u64>,
+	hit_turns: Vec<u64>,
+}
+
+impl Profile {
+	pub fn new(board: &Board) -> Profile {
+		let squares = board.num_squares();
+		Profile { board: board.clone(), games: 0, occupied: vec![0; squares], hits: vec![0; squares], hit_turns: vec![0; squares] }
+	}
+
+	pub fn board(&self) -> &Board {
+		&self.board
+	}
+
+	pub fn games(&self) -> u64 {
+		self.games
+	}
+
+	// Record a finished game: the squares that held a ship, and the shots in order
+	pub fn record(&mut self, occupied: &[BoardPos], shots: &[(BoardPos, ShotResult)]) {
+		self.games += 1;
+		for pos in occupied {
+			self.occupied[pos.index()] += 1;
+		}
+		for (turn, &(pos, result)) in shots.iter().enumerate() {
+			if result != ShotResult::Miss {
+				self.hits[pos.index()] += 1;
+				self.hit_turns[pos.index()] += turn as u64 + 1;
+			}
+		}
+	}
+
+	// Record a finished game known only from its moves, where the hits are the
+	// only occupied squares known
+	pub fn record_moves(&mut self, shots: &[(BoardPos, ShotResult)]) {
+		let occupied: Vec<BoardPos> = shots.iter().filter(|&&(_, result)| result != ShotResult::Miss).map(|&(pos, _)| pos).collect();
+		self.record(&occupied, shots);
+	}
+
+	// The fraction of games in which the square held a ship, or None before any games
+	pub fn occupancy_rate(&self, pos: BoardPos) -> Option<f64> {
+		if self.games == 0 {
+			return None;
+		}
+
+		Some(self.occupied[pos.index()] as f64 / self.games as f64)
+	}
+
+	// The average turn on which the square was hit, or None if it never was
+	pub fn average_hit_turn(&self, pos: BoardPos) -> Option<f64> {
+		if self.hits[pos.index()] == 0 {
+			return None;
+		}
+
+		Some(self.hit_turns[pos.index()] as f64 / self.hits[pos.index()] as f64)
+	}
+
+	// The empirical probability that the square holds a ship, blended with the
+	// given uninformed probability so that a short profile can't rule squares out
+	pub fn occupancy(&self, pos: BoardPos, uninformed: f64) -> f64 {
+		(self.occupied[pos.index()] as f64 + PRIOR_GAMES * uninformed) / (self.games as f64 + PRIOR_GAMES)
+	}
+
+	// Render the occupancy rates and average hit turns as two grids
+	pub fn render(&self) -> String {
+		let coordinates = self.board.coordinates();
+		let size = self.board.size();
+		let grid = |cell: &dyn Fn(BoardPos) -> String| {
+			let mut out = String::from("  ");
+			for col in 0..size {
+				out += &format!("{:>6}", coordinates.column_label(col));
+			}
+			out.push('\n');
+			for row in 0..size {
+				out.push(coordinates.row_label(size, row));
+				out.push(' ');
+				for col in 0..size {
+					out += &format!("{:>6}", cell(self.board.square_at(row, col)));
+				}
+				out.push('\n');
+			}
+			out
+		};
+
+		let mut out = format!("{} games\nHeld a ship (% of games):\n", self.games);
+		out += &grid(&|pos| self.occupancy_rate(pos).map_or(String::from("-"), |rate| format!("{:.1}", rate * 100.0)));
+		out += "Average turn hit:\n";
+		out += &grid(&|pos| self.average_hit_turn(pos).map_or(String::from("-"), |turn| format!("{:.1}", turn)));
+		out
+	}
+
+	pub fn to_file_string(&self) -> String {
+		let mut out = format!("games {}\nboard {}\n", self.games, self.board.describe());
+		for pos in self.board.squares() {
+			let idx = pos.index();
+			out += &format!("{} {} {} {}\n", pos, self.occupied[idx], self.hits[idx], self.hit_turns[idx]);
+		}
+		out
+	}
+
+	// Parse a profile, on the board its board line names (in base's context)
+	// or else on base
+	pub fn from_file_string(contents: &str, base: &Board) -> Result<Profile, String> {
+		let lines: Vec<(usize, &str)> = contents.lines().map(str::trim).enumerate()
+			.filter(|&(_, line)| !line.is_empty() && !line.starts_with('#')).collect();
+
+		// The board comes first, as the squares are read against it
+		let board = match lines.iter().find(|&&(_, line)| line.starts_with("board ")) {
+			Some(&(line_num, line)) => Board::parse(&line["board ".len()..], base.context()).map_err(|e| format!("Line {}: {}", line_num + 1, e))?,
+			None => base.clone(),
+		};
+
+		let mut profile = Profile::new(&board);
+		let mut seen_games = false;
+		for &(line_num, line) in &lines {
+			let parts: Vec<&str> = line.split_whitespace().collect();
+			match parts.as_slice() {
+				["board", ..] => {},
+				["games", games] => {
+					profile.games = games.parse().map_err(|_| format!("Line {}: invalid game count", line_num + 1))?;
+					seen_games = true;
+				},
+				[square, occupied, hits, hit_turns] => {
+					let pos = parse_square(&board, square).ok_or_else(|| format!("Line {}: invalid square {}", line_num + 1, square))?;
+					match (occupied.parse(), hits.parse(), hit_turns.parse()) {
+						(Ok(occupied), Ok(hits), Ok(hit_turns)) => {
+							profile.occupied[pos.index()] = occupied;
+							profile.hits[pos.index()] = hits;
+							profile.hit_turns[pos.index()] = hit_turns;
+						},
+						_ => return Err(format!("Line {}: invalid counts", line_num + 1)),
+					}
+				},
+				_ => return Err(format!("Line {}: expected \"<square> <occupied> <hits> <hit turns>\"", line_num + 1)),
+			}
+		}
+
+		if !seen_games {
+			return Err(String::from("Missing the games line"));
+		}
+		Ok(profile)
+	}
+
+	// Load the profile at path, or start a new one for the given board if the
+	// file doesn't exist. Fails if the profile is for a different board.
+	pub fn load(board: &Board, path: &str) -> Result<Profile, String> {
+		match durable::read(Path::new(path), board.context().allow_unsealed) {
+			Ok(contents) => {
+				let profile = Profile::from_file_string(&contents, board).map_err(|e| format!("{}: {}", path, e))?;
+				if profile.board != *board {
+					return Err(format!("{} is a profile for the board {}, not {}", path, profile.board.describe(), board.describe()));
+				}
+				Ok(profile)
+			},
+			Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Profile::new(board)),
+			Err(e) => Err(format!("Unable to read {}: {}", path, e)),
+		}
+	}
+
+	pub fn save(&self, path: &str) -> Result<(), String> {
+		durable::write(Path::new(path), &self.to_file_string()).map_err(|e| format!("Unable to write {}: {}", path, e))
+	}
+}
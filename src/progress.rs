@@ -0,0 +1,144 @@
+// Progress reporting for long computations, such as exact heatmap counts and
+// tournaments: the library reports Progress through callbacks, and
+// ProgressBar draws it on stderr for the command line. A bar only appears
+// once its computation has run for a moment, and never in quiet mode or when
+// stderr isn't a terminal, so scripted use sees clean output.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// How long a computation runs before its bar appears, and how often the bar
+// is redrawn
+const SHOW_AFTER: Duration = Duration::from_millis(500);
+const REDRAW_EVERY: Duration = Duration::from_millis(100);
+
+// The width of the bar itself, in characters
+const BAR_WIDTH: usize = 30;
+
+// Turn progress bars off (or back on) for the rest of the process
+pub fn set_quiet(quiet: bool) {
+	QUIET.store(quiet, Ordering::Relaxed);
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Progress {
+	// The steps done so far, out of how many
+	pub done: u64,
+	pub total: u64,
+
+	// The estimated time left, from the pace so far, if any steps have been
+	// timed yet
+	pub eta: Option<Duration>,
+}
+
+impl Progress {
+	pub fn fraction(&self) -> f64 {
+		if self.total == 0 { 1.0 } else { self.done as f64 / self.total as f64 }
+	}
+}
+
+// Times a computation, turning its step counts into Progress
+#[derive(Clone,Copy,Debug)]
+pub struct Tracker {
+	start: Instant,
+
+	// The steps already done when timing started (e.g. in a resumed
+	// tournament), which don't count towards the pace
+	first: u64,
+	total: u64,
+}
+
+impl Tracker {
+	pub fn new(total: u64) -> Tracker {
+		Tracker::resumed(0, total)
+	}
+
+	pub fn resumed(done: u64, total: u64) -> Tracker {
+		Tracker { start: Instant::now(), first: done, total }
+	}
+
+	// The tracker with the total changed, for computations that only learn
+	// their total once started
+	pub fn with_total(self, total: u64) -> Tracker {
+		Tracker { total, ..self }
+	}
+
+	pub fn at(&self, done: u64) -> Progress {
+		let timed = done.saturating_sub(self.first);
+		let eta = if timed == 0 {
+			None
+		} else {
+			Some(self.start.elapsed().mul_f64(self.total.saturating_sub(done) as f64 / timed as f64))
+		};
+		Progress { done, total: self.total, eta }
+	}
+}
+
+// A duration as a short human-readable string, e.g. "42s" or "3m05s"
+fn format_duration(duration: Duration) -> String {
+	let secs = duration.as_secs();
+	match secs {
+		0..=59 => format!("{}s", secs),
+		60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+		_ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+	}
+}
+
+// A progress bar on stderr, cleared again when finished or dropped
+pub struct ProgressBar {
+	label: String,
+	start: Instant,
+	last_drawn: Option<Instant>,
+
+	// The length of the line last drawn, for clearing it
+	drawn_len: usize,
+	enabled: bool,
+}
+
+impl ProgressBar {
+	pub fn new(label: &str) -> ProgressBar {
+		ProgressBar {
+			label: String::from(label),
+			start: Instant::now(),
+			last_drawn: None,
+			drawn_len: 0,
+			enabled: !QUIET.load(Ordering::Relaxed) && std::io::stderr().is_terminal(),
+		}
+	}
+
+	// Redraw the bar, with a note on what has been counted so far (e.g.
+	// "1200 configurations"), if it's due
+	pub fn update(&mut self, progress: Progress, note: &str) {
+		let now = Instant::now();
+		if !self.enabled || now.duration_since(self.start) < SHOW_AFTER || self.last_drawn.is_some_and(|last| now.duration_since(last) < REDRAW_EVERY) {
+			return;
+		}
+		self.last_drawn = Some(now);
+
+		let filled = ((progress.fraction() * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+		let mut line = format!("{} [{}{}] {:3.0}% {}", self.label, "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), 100.0 * progress.fraction(), note);
+		if let Some(eta) = progress.eta {
+			line += &format!(", ETA {}", format_duration(eta));
+		}
+		let padding = self.drawn_len.saturating_sub(line.len());
+		self.drawn_len = line.len();
+		let _ = write!(std::io::stderr(), "\r{}{}", line, " ".repeat(padding));
+	}
+
+	// Clear the bar, if it was drawn
+	pub fn finish(&mut self) {
+		if self.drawn_len > 0 {
+			let _ = write!(std::io::stderr(), "\r{}\r", " ".repeat(self.drawn_len));
+			self.drawn_len = 0;
+		}
+	}
+}
+
+impl Drop for ProgressBar {
+	fn drop(&mut self) {
+		self.finish();
+	}
+}
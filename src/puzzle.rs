@@ -0,0 +1,78 @@
+// Generation of mid-game puzzles: positions with exactly one unshot square that
+// every consistent configuration occupies, so there is a single certain hit.
+
+use super::{format_move, BoardPos, GameState, ShotResult};
+use placement::Layout;
+use rng::Rng;
+use rules::Rules;
+
+pub struct Puzzle {
+	// The moves leading to the puzzle position
+	pub moves: Vec<(BoardPos, ShotResult)>,
+
+	// The puzzle position itself
+	pub state: GameState,
+
+	// The only unshot square that is certainly occupied
+	pub solution: BoardPos,
+}
+
+// The only unshot square occupied in every consistent configuration, if there
+// is exactly one and at least min_uncertain other unshot squares are still in doubt
+pub fn unique_forced_square(state: &GameState, min_uncertain: usize) -> Option<BoardPos> {
+	let heatmap = state.heatmap();
+	if heatmap.total == 0 {
+		return None;
+	}
+
+	let unshot: Vec<BoardPos> = state.board().squares().filter(|&pos| !state.is_shot(pos)).collect();
+	let uncertain = unshot.iter().filter(|&&pos| heatmap.counts[pos.index()] != 0 && heatmap.counts[pos.index()] != heatmap.total).count();
+	if uncertain < min_uncertain {
+		return None;
+	}
+
+	let mut forced = unshot.into_iter().filter(|&pos| heatmap.counts[pos.index()] == heatmap.total);
+	match (forced.next(), forced.next()) {
+		(Some(pos), None) => Some(pos),
+		_ => None,
+	}
+}
+
+// Try to generate a puzzle by shooting randomly at a random layout until a
+// mid-game position with a unique forced square (and at least one hit) comes up.
+// Returns None if the game gets too far along first, or no layout could be generated.
+pub fn generate(rng: &mut Rng, rules: &Rules) -> Option<Puzzle> {
+	let layout = Layout::random(rng, rules.constraints())?;
+	let mut state = rules.new_game();
+	let mut moves = Vec::new();
+	let mut unshot: Vec<BoardPos> = state.board().squares().collect();
+
+	while !unshot.is_empty() {
+		let pos = unshot.swap_remove(rng.gen_range(unshot.len()));
+		let cur_move = (pos, rules.reported(layout.shot_result(pos)));
+		state.apply_move(cur_move);
+		moves.push(cur_move);
+
+		if !moves.iter().any(|&(_, result)| result != ShotResult::Miss) {
+			continue;
+		}
+		if let Some(solution) = unique_forced_square(&state, state.board().size() as usize) {
+			return Some(Puzzle { moves, state, solution });
+		}
+	}
+
+	None
+}
+
+impl Puzzle {
+	// Format the puzzle as its moves (in the moves file format), the rendered board, and the solution
+	pub fn describe(&self) -> String {
+		let mut out = String::new();
+		for &cur_move in &self.moves {
+			out += &format_move(cur_move);
+			out.push('\n');
+		}
+
+		out + &self.state.render() + &format!("Solution: {}\n", self.solution)
+	}
+}
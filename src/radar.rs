@@ -0,0 +1,50 @@
+// Radar sweeps, from variants where a player can spend a turn asking whether
+// any ship lies in the 3x3 area around a square (cut off at the board's edge)
+// instead of shooting. A clear sweep rules out every square in the area, like
+// misses that aren't shots; a sweep that finds something only says some ship
+// covers at least one of them, which the inference checks against whole
+// configurations, like an unlabeled hit spread over the area.
+//
+// A sweep is written "<square> found" or "<square> clear", e.g. "C3 found".
+
+use super::{parse_square, Board, BoardPos};
+
+// How far the swept area reaches from its center, in rows and columns
+pub const SWEEP_RADIUS: u8 = 1;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Sweep {
+	pub center: BoardPos,
+
+	// Whether any ship was in the area
+	pub found: bool,
+}
+
+impl Sweep {
+	// Parse a sweep on the given board
+	pub fn parse(board: &Board, desc: &str) -> Result<Sweep, String> {
+		let (square, outcome) = desc.trim().split_once(' ').ok_or_else(|| format!("expected \"<square> found\" or \"<square> clear\", not {}", desc.trim()))?;
+		let center = parse_square(board, square).ok_or_else(|| format!("invalid square {}", square))?;
+		match outcome.trim() {
+			"found" => Ok(Sweep { center, found: true }),
+			"clear" => Ok(Sweep { center, found: false }),
+			outcome => Err(format!("a sweep is found or clear, not {}", outcome)),
+		}
+	}
+
+	// The squares swept, in row-major order
+	pub fn area(&self, board: &Board) -> Vec<BoardPos> {
+		let (row, col) = (self.center.row(), self.center.col());
+		let rows = row.saturating_sub(SWEEP_RADIUS)..=(row + SWEEP_RADIUS).min(board.size() - 1);
+		rows.flat_map(|row| {
+			let cols = col.saturating_sub(SWEEP_RADIUS)..=(col + SWEEP_RADIUS).min(board.size() - 1);
+			cols.map(move |col| board.square_at(row, col))
+		}).collect()
+	}
+}
+
+impl std::fmt::Display for Sweep {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{} {}", self.center, if self.found { "found" } else { "clear" })
+	}
+}
@@ -0,0 +1,153 @@
+// Elo ratings for strategies, so long-term development has a single comparable
+// strength number. Strategies are compared by playing each of them against the
+// same random layout: a strategy beats another if it sinks the fleet in fewer
+// shots, and ties draw. Each pair of strategies counts as one rated game.
+
+use placement::Layout;
+use rng::Rng;
+use rules::Rules;
+use sim::play_game;
+use strategy;
+
+// The rating a strategy starts with
+pub const INITIAL_RATING: f64 = 1500.0;
+
+// How far a single game moves the ratings
+pub const K_FACTOR: f64 = 16.0;
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Rating {
+	pub name: String,
+	pub rating: f64,
+
+	// The number of rated games played
+	pub games: u64,
+}
+
+// The ratings file contents: one "name rating games" line per strategy
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Ratings {
+	entries: Vec<Rating>,
+}
+
+impl Ratings {
+	pub fn new() -> Ratings {
+		Ratings { entries: Vec::new() }
+	}
+
+	pub fn get(&self, name: &str) -> Option<&Rating> {
+		self.entries.iter().find(|entry| entry.name == name)
+	}
+
+	// The index of the strategy's entry, adding it at the initial rating if needed
+	fn index_of(&mut self, name: &str) -> usize {
+		if let Some(idx) = self.entries.iter().position(|entry| entry.name == name) {
+			return idx;
+		}
+
+		self.entries.push(Rating { name: name.to_string(), rating: INITIAL_RATING, games: 0 });
+		self.entries.len() - 1
+	}
+
+	// The expected score of a rating against another
+	pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+		1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+	}
+
+	// Record a game between two strategies. score is first's result: 1 for a
+	// win, 0.5 for a draw, or 0 for a loss.
+	pub fn record(&mut self, first: &str, second: &str, score: f64) {
+		let first = self.index_of(first);
+		let second = self.index_of(second);
+		let expected = Ratings::expected_score(self.entries[first].rating, self.entries[second].rating);
+		let delta = K_FACTOR * (score - expected);
+
+		self.entries[first].rating += delta;
+		self.entries[first].games += 1;
+		self.entries[second].rating -= delta;
+		self.entries[second].games += 1;
+	}
+
+	// Every rated strategy, best first
+	pub fn standings(&self) -> Vec<&Rating> {
+		let mut standings: Vec<&Rating> = self.entries.iter().collect();
+		standings.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap_or(std::cmp::Ordering::Equal));
+		standings
+	}
+
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		for (rank, entry) in self.standings().iter().enumerate() {
+			out += &format!("{:>2}. {:<20} {:>7.1} ({} games)\n", rank + 1, entry.name, entry.rating, entry.games);
+		}
+		out
+	}
+
+	pub fn to_file_string(&self) -> String {
+		let mut out = String::new();
+		for entry in &self.entries {
+			out += &format!("{} {} {}\n", entry.name, entry.rating, entry.games);
+		}
+		out
+	}
+
+	pub fn from_file_string(contents: &str) -> Result<Ratings, String> {
+		let mut ratings = Ratings::new();
+		for (line_num, line) in contents.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			// Names may hold spaces (e.g. combinations of strategies), so the
+			// numbers are split off the end
+			let parts: Vec<&str> = line.rsplitn(3, ' ').collect();
+			let entry = match parts.as_slice() {
+				[games, rating, name] => match (rating.parse(), games.parse()) {
+					(Ok(rating), Ok(games)) => Rating { name: name.trim().to_string(), rating, games },
+					_ => return Err(format!("Line {}: invalid rating or game count", line_num + 1)),
+				},
+				_ => return Err(format!("Line {}: expected \"name rating games\"", line_num + 1)),
+			};
+			if ratings.get(&entry.name).is_some() {
+				return Err(format!("Line {}: {} is rated twice", line_num + 1, entry.name));
+			}
+			ratings.entries.push(entry);
+		}
+
+		Ok(ratings)
+	}
+}
+
+// Play the named strategies against the given number of random layouts,
+// updating the ratings after every layout
+pub fn rate(ratings: &mut Ratings, names: &[String], games: usize, seed: u64, rules: &Rules) -> Result<(), String> {
+	if names.len() < 2 {
+		return Err("At least two strategies are needed for a rated game".to_string());
+	}
+
+	let mut rng = Rng::new(seed);
+	for _ in 0..games {
+		let layout = Layout::random(&mut rng, rules.constraints())
+			.ok_or_else(|| format!("No valid layout exists under the {} rules", rules.name))?;
+
+		let mut shots = Vec::with_capacity(names.len());
+		for name in names {
+			let mut player = strategy::load(name, rng.next_u64())?;
+			shots.push(play_game(&mut *player, &layout, rules).len());
+		}
+
+		for first in 0..names.len() {
+			for second in first + 1..names.len() {
+				let score = match shots[first].cmp(&shots[second]) {
+					std::cmp::Ordering::Less => 1.0,
+					std::cmp::Ordering::Equal => 0.5,
+					std::cmp::Ordering::Greater => 0.0,
+				};
+				ratings.record(&names[first], &names[second], score);
+			}
+		}
+	}
+
+	Ok(())
+}
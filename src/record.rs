@@ -0,0 +1,48 @@
+// Rich per-shot records: for every move applied to a GameState, the turn it
+// was revealed on, which ship it hit (as far as was known at the time), and
+// how many positions of each ship type it ruled out. Explanations, replays,
+// and analyses read these instead of rederiving them from the moves.
+
+use super::{BoardPos, GameState, ShipType, ShotResult};
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct ShotRecord {
+	// The (1-based) turn on which the result was revealed
+	pub turn: usize,
+
+	pub pos: BoardPos,
+	pub result: ShotResult,
+
+	// The ship hit, if the result named it or the inference pinned it down
+	// right after the move; None for misses
+	pub ship: Option<ShipType>,
+
+	// Whether ship was inferred rather than reported
+	pub inferred: bool,
+
+	// The number of positions of each ship type (in fleet order) the move
+	// ruled out, counting what the inference then deduced from it
+	pub eliminated: Vec<(ShipType, usize)>,
+}
+
+impl GameState {
+	// The record of every move applied so far, in the order of shots()
+	pub fn records(&self) -> &[ShotRecord] {
+		&self.records
+	}
+
+	// The record of the move at the given square, if it has been revealed
+	pub fn record_at(&self, pos: BoardPos) -> Option<&ShotRecord> {
+		self.records.iter().find(|record| record.pos == pos)
+	}
+
+	// The ship that must occupy the square given everything known now: the
+	// only ship type with a remaining position covering it, if there is one
+	pub fn attribution(&self, pos: BoardPos) -> Option<ShipType> {
+		let mut coverers = (0..self.pos_positions.len()).filter(|&stype_idx| self.pos_positions[stype_idx].intersects(&self.cover_masks[stype_idx][pos.index()]));
+		match (coverers.next(), coverers.next()) {
+			(Some(stype_idx), None) => Some(self.board.fleet()[stype_idx]),
+			_ => None,
+		}
+	}
+}
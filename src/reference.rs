@@ -0,0 +1,212 @@
+// A slow but obviously correct reference for the inference, for differential
+// testing of the optimized code. It enumerates every fleet configuration
+// directly from the ship geometry, without the overlap cache, cover masks,
+// bitsets, or incremental pruning, checking each complete configuration
+// against every move.
+
+use super::{Board, BoardPos, Heatmap, Orientation, ShotResult};
+use placement::Layout;
+use rng::Rng;
+use rules::Rules;
+use strategy::{Greedy, Strategy, TieBreak};
+
+// Check a complete configuration (the squares of each ship, in fleet order) against the moves
+fn consistent(board: &Board, ships: &[Vec<BoardPos>], moves: &[(BoardPos, ShotResult)]) -> bool {
+	moves.iter().enumerate().all(|(turn, &(pos, result))| {
+		let mut covering = (0..ships.len()).filter(|&idx| ships[idx].contains(&pos));
+		match result {
+			ShotResult::Miss => covering.next().is_none(),
+			ShotResult::Hit(None) => covering.next().is_some(),
+			ShotResult::Hit(Some(stype)) => covering.any(|idx| board.fleet()[idx] == stype),
+
+			// Every square of the sunk ship must have been hit by this turn
+			ShotResult::Sunk(stype) => covering.any(|idx| board.fleet()[idx] == stype) && {
+				let ship = &ships[board.ship_index(stype).expect("A sunk ship isn't in the fleet")];
+				ship.iter().all(|square| moves[..=turn].iter().any(|&(shot, result)| shot == *square && result != ShotResult::Miss))
+			},
+		}
+	})
+}
+
+// Check a complete configuration against the moves, the first since of which
+// came before the opponent relocated a ship. The misses from before no longer
+// stand, but at most one ship, which must avoid the hits from before, can be
+// on any of them.
+fn consistent_relocated(board: &Board, ships: &[Vec<BoardPos>], moves: &[(BoardPos, ShotResult)], since: usize) -> bool {
+	let is_miss = |idx: usize| idx < since && moves[idx].1 == ShotResult::Miss;
+	let standing: Vec<(BoardPos, ShotResult)> = (0..moves.len()).filter(|&idx| !is_miss(idx)).map(|idx| moves[idx]).collect();
+	let (misses, hits): (Vec<_>, Vec<_>) = moves[..since].iter().partition(|&&(_, result)| result == ShotResult::Miss);
+
+	let mut movers = ships.iter().filter(|ship| misses.iter().any(|&&(pos, _)| ship.contains(&pos)));
+	let mover_fits = match (movers.next(), movers.next()) {
+		(None, _) => true,
+		(Some(ship), None) => !hits.iter().any(|&&(pos, _)| ship.contains(&pos)),
+		(Some(_), Some(_)) => false,
+	};
+	mover_fits && consistent(board, ships, &standing)
+}
+
+// Whether the squares are the same or next to each other, including diagonally
+fn near(a: BoardPos, b: BoardPos) -> bool {
+	(a.row() as i32 - b.row() as i32).abs() <= 1 && (a.col() as i32 - b.col() as i32).abs() <= 1
+}
+
+fn enumerate(board: &Board, ships: &mut Vec<Vec<BoardPos>>, orientations: &[Orientation], allow_touching: bool, moves: &[(BoardPos, ShotResult)],
+             relocation: Option<usize>, heatmap: &mut Heatmap) {
+	if ships.len() == board.fleet().len() {
+		let fits = match relocation {
+			Some(since) => consistent_relocated(board, ships, moves, since),
+			None => consistent(board, ships, moves),
+		};
+		if fits {
+			heatmap.total += 1;
+			for square in ships.iter().flatten() {
+				heatmap.counts[square.index()] += 1;
+			}
+		}
+		return;
+	}
+
+	let stype = board.fleet()[ships.len()];
+	for ship in board.placements(stype) {
+		let squares: Vec<BoardPos> = ship.cells().collect();
+		if !orientations[ships.len()].allows(squares[0].row() == squares[1].row()) {
+			continue;
+		}
+
+		// Skipping overlapping ships early is needed to finish in reasonable time
+		if ships.iter().any(|other| other.iter().any(|square| squares.contains(square))) {
+			continue;
+		}
+		if !allow_touching && ships.iter().any(|other| other.iter().any(|&square| squares.iter().any(|&own| near(square, own)))) {
+			continue;
+		}
+
+		ships.push(squares);
+		enumerate(board, ships, orientations, allow_touching, moves, relocation, heatmap);
+		ships.pop();
+	}
+}
+
+// The heatmap of the given moves on the board, computed by direct enumeration
+// of the fleets with the given orientations (one per ship, in fleet order),
+// whose ships touch only if allow_touching
+pub fn reference_heatmap(board: &Board, orientations: &[Orientation], allow_touching: bool, moves: &[(BoardPos, ShotResult)]) -> Heatmap {
+	reference_heatmap_relocated(board, orientations, allow_touching, moves, None)
+}
+
+// Like reference_heatmap, after the opponent relocated a ship once the given
+// number of the moves had been made, if they have
+pub fn reference_heatmap_relocated(board: &Board, orientations: &[Orientation], allow_touching: bool, moves: &[(BoardPos, ShotResult)],
+                                   relocation: Option<usize>) -> Heatmap {
+	let mut heatmap = Heatmap { counts: vec![0; board.num_squares()], total: 0 };
+	enumerate(board, &mut Vec::with_capacity(board.fleet().len()), orientations, allow_touching, moves, relocation, &mut heatmap);
+	heatmap
+}
+
+// The first disagreement between the optimized inference and the reference
+#[derive(Clone,Debug,PartialEq)]
+pub struct Divergence {
+	// The (0-based) game it happened in
+	pub game: usize,
+
+	// The moves up to and including the first diverging one
+	pub moves: Vec<(BoardPos, ShotResult)>,
+
+	pub expected: Heatmap,
+	pub actual: Heatmap,
+}
+
+// Play randomized games, checking the optimized heatmap against the reference
+// after every move. Returns the first divergence, if any. on_game is called
+// with the game number and its length once a game has checked out.
+pub fn cross_check(games: usize, seed: u64, rules: &Rules, on_game: &mut dyn FnMut(usize, usize)) -> Result<Option<Divergence>, String> {
+	let board = rules.board()?;
+	let orientations = rules.orientations(&board);
+	let mut rng = Rng::new(seed);
+	for game in 0..games {
+		let layout = Layout::random(&mut rng, rules.constraints())
+			.ok_or_else(|| format!("No valid layout exists under the {} rules", rules.name))?;
+		let mut attacker = Greedy::new(TieBreak::Random(rng.next_u64()));
+		let mut state = rules.new_game();
+		let ship_squares = layout.occupancy().iter().filter(|cell| cell.is_some()).count();
+
+		while state.shots().iter().filter(|&&(_, result)| result != ShotResult::Miss).count() < ship_squares {
+			let pos = match attacker.choose_shot(&state) {
+				Some(pos) => pos,
+				None => break,
+			};
+			let fired: Vec<BoardPos> = state.shots().iter().map(|&(pos, _)| pos).collect();
+			state.apply_move((pos, rules.reported(layout.shot_result_after(pos, &fired))));
+
+			let expected = reference_heatmap(&board, &orientations, rules.allow_touching, state.shots());
+			let actual = state.heatmap();
+			if actual != expected {
+				return Ok(Some(Divergence { game, moves: state.shots().to_vec(), expected, actual }));
+			}
+		}
+		on_game(game, state.shots().len());
+	}
+
+	Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rules::parse_profiles;
+	use PlacementId;
+
+	// Play a game under the rules, relocating an unhit ship of the layout
+	// after the given number of shots, and check the inference against the
+	// reference after every move from then on
+	fn check_relocation(rules: &Rules, seed: u64, relocate_after: usize) {
+		let board = rules.board().unwrap();
+		let orientations = rules.orientations(&board);
+		let mut rng = Rng::new(seed);
+		let mut layout = Layout::random(&mut rng, rules.constraints()).unwrap();
+		let mut attacker = Greedy::new(TieBreak::Random(rng.next_u64()));
+		let mut state = rules.new_game();
+		let mut fired: Vec<BoardPos> = Vec::new();
+
+		while state.shots().iter().filter(|&&(_, result)| result != ShotResult::Miss).count() < board.fleet_squares() as usize {
+			if state.shots().len() == relocate_after {
+				let hits: Vec<BoardPos> = state.shots().iter().filter(|&&(_, result)| result != ShotResult::Miss).map(|&(pos, _)| pos).collect();
+				let unhit: Vec<_> = layout.ships().iter().cloned().filter(|&(stype, pos)| board.ship_range(stype, pos).iter().all(|square| !hits.contains(square))).collect();
+				let &(stype, _) = match unhit.first() {
+					Some(ship) => ship,
+					None => return,
+				};
+				let targets: Vec<PlacementId> = board.placements(stype).filter(|ship| ship.cells().all(|square| !hits.contains(&square))).map(|ship| ship.id).collect();
+				if layout.relocate(stype, targets[rng.gen_range(targets.len())]).is_err() {
+					return;
+				}
+				let squares = board.ship_range(stype, layout.ships().iter().find(|&&(placed, _)| placed == stype).unwrap().1);
+				fired.retain(|square| !squares.contains(square));
+				state.apply_relocation();
+			}
+
+			let pos = match attacker.choose_shot(&state) {
+				Some(pos) => pos,
+				None => break,
+			};
+			state.apply_move((pos, rules.reported(layout.shot_result_after(pos, &fired))));
+			fired.push(pos);
+
+			if let Some(relocation) = state.relocation() {
+				let expected = reference_heatmap_relocated(&board, &orientations, rules.allow_touching, state.shots(), Some(relocation.shots));
+				assert_eq!(state.heatmap(), expected, "seed {} after {:?}", seed, state.shots());
+			}
+		}
+	}
+
+	#[test]
+	fn relocations_match_the_reference() {
+		let profiles = parse_profiles("[labeled]\nboard = 5\nfleet = P2 D3 S3\nmoving = yes\n[unlabeled]\nboard = 5\nfleet = P2 D3 S3\nlabeled_hits = no\nmoving = yes\n").unwrap();
+		for rules in &profiles {
+			for seed in 0..6 {
+				check_relocation(rules, seed, 2 + seed as usize);
+			}
+		}
+	}
+}
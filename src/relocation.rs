@@ -0,0 +1,72 @@
+// Relocating one of our own ships under moving-ships rules (see
+// Rules::moving_ships). The opponent's view of our fleet is a GameState like
+// the one we keep of theirs, so a ship's risk is the average probability the
+// opponent's heatmap gives the squares it covers. The move is saved for a
+// ship in real danger, and only made when it leaves the ship clearly safer
+// in the opponent's view after the move.
+
+use super::{GameState, Heatmap, PlacementId, ShipType};
+use placement::Layout;
+
+// How high a ship's risk must be for moving it to be worth the once-a-game move
+pub const RISK_THRESHOLD: f64 = 0.75;
+
+// How much a ship's risk must drop for the move to be made
+const MIN_IMPROVEMENT: f64 = 0.2;
+
+// A relocation we could make: the ship, where it goes, and its risk
+// before and after
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Plan {
+	pub stype: ShipType,
+	pub to: PlacementId,
+	pub risk_before: f64,
+	pub risk_after: f64,
+}
+
+// Decide whether to relocate a ship of the layout, given the opponent's view
+// of it so far. Returns None if the move has been used, no ship with no hits
+// is at risk enough, or no destination would leave it clearly safer.
+pub fn plan(layout: &Layout, their_view: &GameState) -> Option<Plan> {
+	if their_view.relocation().is_some() {
+		return None;
+	}
+
+	let board = layout.board();
+	let heatmap = their_view.heatmap();
+	let risk = |heatmap: &Heatmap, stype: ShipType, pos: PlacementId| {
+		let squares = board.ship_range(stype, pos);
+		squares.iter().map(|&square| heatmap.probability(square)).sum::<f64>() / squares.len() as f64
+	};
+
+	// The unhit ship at most risk
+	let (stype, pos, risk_before) = layout.ships().iter()
+		.filter(|&&(stype, pos)| board.ship_range(stype, pos).iter().all(|&square| !their_view.is_hit(square)))
+		.map(|&(stype, pos)| (stype, pos, risk(&heatmap, stype, pos)))
+		.fold(None, |best: Option<(ShipType, PlacementId, f64)>, ship| match best {
+			Some(best) if best.2 >= ship.2 => Some(best),
+			_ => Some(ship),
+		})?;
+	if risk_before < RISK_THRESHOLD {
+		return None;
+	}
+
+	// Where the view after the move makes it least likely
+	let mut after = their_view.clone();
+	after.apply_relocation();
+	let heatmap = after.heatmap();
+	let mut rest = layout.clone();
+	rest.remove(stype);
+	let (to, risk_after) = board.positions(stype)
+		.filter(|&to| to != pos && rest.check(stype, to).is_ok())
+		.map(|to| (to, risk(&heatmap, stype, to)))
+		.fold(None, |best: Option<(PlacementId, f64)>, dest| match best {
+			Some(best) if best.1 <= dest.1 => Some(best),
+			_ => Some(dest),
+		})?;
+	if risk_after > risk_before - MIN_IMPROVEMENT {
+		return None;
+	}
+
+	Some(Plan { stype, to, risk_before, risk_after })
+}
@@ -0,0 +1,324 @@
+// Auto-play against an HTTP Battleship service.
+//
+// The service is described by a config file of "key = value" lines:
+//
+//   host = localhost:8080                  (plain HTTP only)
+//   new_game = POST /games                 (optional; starts a session)
+//   session_field = id                     (field of the new_game response holding the session ID)
+//   fire = POST /games/{session}/fire      (request made for each shot)
+//   fire_body = {"square": "{square}"}     (optional request body)
+//   result_field = result                  (field of the fire response holding the result)
+//   hit_values = hit                       (result values meaning a hit)
+//   sunk_values = sunk                     (result values meaning a hit that sank the ship)
+//   miss_values = miss                     (result values meaning a miss)
+//   ship_field = ship                      (optional field naming the ship hit)
+//   done_field = game_over                 (optional field that is true once the game is won)
+//   min_interval_ms = 1000                 (politeness delay between requests)
+//   retries = 3                            (attempts beyond the first, for requests safe to repeat)
+//
+// Templates may use {session}, {square} (e.g. "B3"), {row} and {col} (both 0-based).
+// Responses must be JSON objects.
+
+use super::{decode_shiptype, format_move, parse_move, BoardPos, GameState, ShotResult};
+use json::{self, Value};
+use rules::Rules;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use strategy::Strategy;
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct RemoteConfig {
+	pub host: String,
+
+	// (method, path template)
+	pub new_game: Option<(String, String)>,
+	pub session_field: String,
+	pub fire: (String, String),
+	pub fire_body: Option<String>,
+	pub result_field: String,
+	pub hit_values: Vec<String>,
+	pub sunk_values: Vec<String>,
+	pub miss_values: Vec<String>,
+	pub ship_field: Option<String>,
+	pub done_field: Option<String>,
+	pub min_interval: Duration,
+	pub retries: u32,
+}
+
+// Parse a "METHOD /path" endpoint
+fn parse_endpoint(value: &str) -> Option<(String, String)> {
+	let mut parts = value.split_whitespace();
+	let method = parts.next()?.to_ascii_uppercase();
+	let path = parts.next()?;
+	if parts.next().is_some() || !path.starts_with('/') {
+		return None;
+	}
+
+	Some((method, path.to_string()))
+}
+
+// Split a comma-separated list of values
+fn parse_list(value: &str) -> Vec<String> {
+	value.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect()
+}
+
+impl RemoteConfig {
+	pub fn from_file_string(contents: &str) -> Result<RemoteConfig, String> {
+		let mut config = RemoteConfig {
+			host: String::new(),
+			new_game: None,
+			session_field: String::from("id"),
+			fire: (String::new(), String::new()),
+			fire_body: None,
+			result_field: String::from("result"),
+			hit_values: vec![String::from("hit")],
+			sunk_values: vec![String::from("sunk")],
+			miss_values: vec![String::from("miss")],
+			ship_field: None,
+			done_field: None,
+			min_interval: Duration::from_millis(1000),
+			retries: 3,
+		};
+
+		for (line_num, line) in contents.lines().enumerate() {
+			let line = line.trim();
+			let err = |msg: &str| format!("line {}: {}", line_num + 1, msg);
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let mut parts = line.splitn(2, '=');
+			let key = parts.next().unwrap_or("").trim();
+			let value = parts.next().ok_or_else(|| err("expected key = value"))?.trim();
+			match key {
+				"host" => config.host = value.to_string(),
+				"new_game" => config.new_game = Some(parse_endpoint(value).ok_or_else(|| err("expected METHOD /path"))?),
+				"session_field" => config.session_field = value.to_string(),
+				"fire" => config.fire = parse_endpoint(value).ok_or_else(|| err("expected METHOD /path"))?,
+				"fire_body" => config.fire_body = Some(value.to_string()),
+				"result_field" => config.result_field = value.to_string(),
+				"hit_values" => config.hit_values = parse_list(value),
+				"sunk_values" => config.sunk_values = parse_list(value),
+				"miss_values" => config.miss_values = parse_list(value),
+				"ship_field" => config.ship_field = Some(value.to_string()),
+				"done_field" => config.done_field = Some(value.to_string()),
+				"min_interval_ms" => config.min_interval = Duration::from_millis(value.parse().map_err(|_| err("invalid interval"))?),
+				"retries" => config.retries = value.parse().map_err(|_| err("invalid retry count"))?,
+				_ => return Err(err("unknown option")),
+			}
+		}
+
+		if config.host.is_empty() {
+			return Err("the config must give a host".to_string());
+		}
+		if config.fire.1.is_empty() {
+			return Err("the config must give a fire endpoint".to_string());
+		}
+
+		Ok(config)
+	}
+}
+
+// Fill in a template's {placeholders}
+fn fill_template(template: &str, session: &str, pos: Option<BoardPos>) -> String {
+	let mut out = template.replace("{session}", session);
+	if let Some(pos) = pos {
+		out = out.replace("{square}", &pos.to_string()).replace("{row}", &pos.row().to_string())
+		         .replace("{col}", &pos.col().to_string());
+	}
+	out
+}
+
+// Decode a chunked transfer-encoded body
+fn decode_chunked(mut body: &str) -> Result<String, String> {
+	let mut out = String::new();
+	loop {
+		let line_end = body.find("\r\n").ok_or("truncated chunked body")?;
+		let size_text = body[..line_end].split(';').next().unwrap_or("").trim();
+		let size = usize::from_str_radix(size_text, 16).map_err(|_| "invalid chunk size")?;
+		body = &body[line_end + 2..];
+		if size == 0 {
+			return Ok(out);
+		}
+		let chunk = body.get(..size).ok_or("truncated chunk")?;
+		out += chunk;
+		body = body.get(size + 2..).unwrap_or("");
+	}
+}
+
+// Make one HTTP/1.1 request, returning the status code and body
+pub fn http_request(host: &str, method: &str, path: &str, body: Option<&str>) -> Result<(u16, String), String> {
+	let mut stream = TcpStream::connect(host).map_err(|e| format!("unable to connect to {}: {}", host, e))?;
+	stream.set_read_timeout(Some(Duration::from_secs(30))).map_err(|e| e.to_string())?;
+
+	let body = body.unwrap_or("");
+	let request = format!("{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: battleship_ai\r\nAccept: application/json\r\n\
+	                       Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+	                      method, path, host, body.len(), body);
+	stream.write_all(request.as_bytes()).map_err(|e| format!("unable to send request: {}", e))?;
+
+	let mut response = String::new();
+	stream.read_to_string(&mut response).map_err(|e| format!("unable to read response: {}", e))?;
+
+	let header_end = response.find("\r\n\r\n").ok_or("malformed HTTP response")?;
+	let (head, body) = (&response[..header_end], &response[header_end + 4..]);
+	let status = head.split_whitespace().nth(1).and_then(|code| code.parse().ok()).ok_or("malformed HTTP status line")?;
+	let chunked = head.lines().any(|line| {
+		let line = line.to_ascii_lowercase();
+		line.starts_with("transfer-encoding:") && line.contains("chunked")
+	});
+
+	Ok((status, if chunked { decode_chunked(body)? } else { body.to_string() }))
+}
+
+// Spaces requests at least a minimum interval apart
+pub struct RateLimiter {
+	min_interval: Duration,
+	last: Option<Instant>,
+}
+
+impl RateLimiter {
+	pub fn new(min_interval: Duration) -> RateLimiter {
+		RateLimiter { min_interval, last: None }
+	}
+
+	// Sleep until the next request is allowed
+	pub fn wait(&mut self) {
+		if let Some(last) = self.last {
+			let elapsed = last.elapsed();
+			if elapsed < self.min_interval {
+				std::thread::sleep(self.min_interval - elapsed);
+			}
+		}
+		self.last = Some(Instant::now());
+	}
+}
+
+// A session with the service, persisted so an interrupted game can continue
+pub struct RemoteGame {
+	config: RemoteConfig,
+	limiter: RateLimiter,
+	pub session: String,
+	pub state: GameState,
+}
+
+impl RemoteGame {
+	// Start a new session (if the service has a new_game endpoint) of a game under the rules
+	pub fn start(config: RemoteConfig, rules: &Rules) -> Result<RemoteGame, String> {
+		let mut game = RemoteGame { limiter: RateLimiter::new(config.min_interval), config, session: String::new(), state: rules.new_game() };
+		if let Some((method, path)) = game.config.new_game.clone() {
+			let response = game.request(&method, &fill_template(&path, "", None), None)?;
+			game.session = match response.get(&game.config.session_field) {
+				Some(Value::String(id)) => id.clone(),
+				Some(&Value::Number(id)) => id.to_string(),
+				_ => return Err(format!("new_game response has no {} field", game.config.session_field)),
+			};
+		}
+		Ok(game)
+	}
+
+	// Continue a session saved by to_session_string, of a game under the rules
+	pub fn resume(config: RemoteConfig, rules: &Rules, saved: &str) -> Result<RemoteGame, String> {
+		let mut lines = saved.lines();
+		let session = lines.next().and_then(|line| line.strip_prefix("session ")).ok_or("not a saved session")?;
+		let mut state = rules.new_game();
+		let board = state.board().clone();
+		for (line_num, line) in lines.enumerate().filter(|&(_, line)| !line.trim().is_empty()) {
+			state.apply_move(parse_move(&board, line).map_err(|e| format!("line {}: {}", line_num + 2, e))?);
+		}
+
+		Ok(RemoteGame { limiter: RateLimiter::new(config.min_interval), config, session: session.to_string(), state })
+	}
+
+	pub fn to_session_string(&self) -> String {
+		let moves: String = self.state.shots().iter().map(|&cur_move| format_move(cur_move) + "\n").collect();
+		format!("session {}\n{}", self.session, moves)
+	}
+
+	// Make a rate-limited request, retrying failures with exponential backoff,
+	// and parse the JSON response. A request that may have taken effect before
+	// failing, such as a shot, is only retried if its method is idempotent
+	// (GET, HEAD, PUT, DELETE or OPTIONS) or the service turned it away with
+	// 429 Too Many Requests.
+	fn request(&mut self, method: &str, path: &str, body: Option<&str>) -> Result<Value, String> {
+		let idempotent = ["GET", "HEAD", "PUT", "DELETE", "OPTIONS"].contains(&method);
+		let mut backoff = self.config.min_interval.max(Duration::from_millis(100));
+		let mut attempt = 0;
+		loop {
+			self.limiter.wait();
+			let error = match http_request(&self.config.host, method, path, body) {
+				Ok((status, body)) if (200..300).contains(&status) => return json::parse(&body),
+				// Client errors won't go away on a retry
+				Ok((status, body)) if (400..500).contains(&status) && status != 429 => {
+					return Err(format!("{} {} failed with status {}: {}", method, path, status, body.trim()));
+				},
+				Ok((status, _)) if idempotent || status == 429 => format!("{} {} failed with status {}", method, path, status),
+				Ok((status, _)) => return Err(format!("{} {} failed with status {}, and isn't retried as it may have taken effect", method, path, status)),
+				Err(e) if idempotent => e,
+				Err(e) => return Err(format!("{} (not retried, as {} requests may have taken effect)", e, method)),
+			};
+			if attempt >= self.config.retries {
+				return Err(error);
+			}
+			attempt += 1;
+			std::thread::sleep(backoff);
+			backoff *= 2;
+		}
+	}
+
+	// Fire at a square, returning the result and whether the game is now over
+	pub fn fire(&mut self, pos: BoardPos) -> Result<(ShotResult, bool), String> {
+		let (method, path) = self.config.fire.clone();
+		let path = fill_template(&path, &self.session, Some(pos));
+		let body = self.config.fire_body.as_ref().map(|body| fill_template(body, &self.session, Some(pos)));
+		let response = self.request(&method, &path, body.as_deref())?;
+
+		let result = response.get(&self.config.result_field).and_then(|result| result.as_str())
+			.ok_or_else(|| format!("fire response has no {} field", self.config.result_field))?.to_ascii_lowercase();
+		let ship = self.config.ship_field.as_ref().and_then(|field| response.get(field)).and_then(|ship| ship.as_str())
+			.and_then(|ship| match ship.to_ascii_uppercase().chars().next() {
+				Some(letter) if "PDSBC".contains(letter) => Some(decode_shiptype(letter as u8)),
+				_ => None,
+			});
+		let shot_result = if self.config.hit_values.iter().any(|value| value.eq_ignore_ascii_case(&result)) {
+			ShotResult::Hit(ship)
+		} else if self.config.sunk_values.iter().any(|value| value.eq_ignore_ascii_case(&result)) {
+			// A sinking only tells the inference more if the ship is named
+			ship.map_or(ShotResult::Hit(None), ShotResult::Sunk)
+		} else if self.config.miss_values.iter().any(|value| value.eq_ignore_ascii_case(&result)) {
+			ShotResult::Miss
+		} else {
+			return Err(format!("unrecognized result {}", result));
+		};
+		let done = self.config.done_field.as_ref().and_then(|field| response.get(field)) == Some(&Value::Bool(true));
+
+		self.state.apply_move((pos, shot_result));
+		Ok((shot_result, done))
+	}
+
+	// Play until the game is won, the strategy runs out of shots, or the given
+	// number of ship squares has been hit. after_shot is called after every shot
+	// (e.g. to save the session).
+	pub fn play(&mut self, strategy: &mut dyn Strategy, ship_squares: usize,
+	            after_shot: &mut dyn FnMut(&RemoteGame, (BoardPos, ShotResult))) -> Result<usize, String> {
+		loop {
+			let hits = self.state.shots().iter().filter(|&&(_, result)| result != ShotResult::Miss).count();
+			if hits >= ship_squares {
+				break;
+			}
+			let pos = match strategy.choose_shot(&self.state) {
+				Some(pos) => pos,
+				None => break,
+			};
+
+			let (result, done) = self.fire(pos)?;
+			after_shot(self, (pos, result));
+			if done {
+				break;
+			}
+		}
+
+		Ok(self.state.shots().len())
+	}
+}
@@ -0,0 +1,79 @@
+// A small, seedable pseudo-random number generator (SplitMix64).
+// Results only need to be reproducible, not cryptographically secure,
+// so this avoids pulling in an external dependency.
+#[derive(Clone,Debug)]
+pub struct Rng {
+	state: u64,
+}
+
+impl Rng {
+	// Construct a generator from the given seed
+	pub fn new(seed: u64) -> Rng {
+		Rng { state: seed }
+	}
+
+	// The generator's internal state; Rng::new(state) resumes the sequence from here
+	pub fn state(&self) -> u64 {
+		self.state
+	}
+
+	// Generate the next 64 random bits
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	// Generate a uniformly-distributed value in 0..bound (bound must be nonzero)
+	pub fn gen_range(&mut self, bound: usize) -> usize {
+		(self.next_u64() % bound as u64) as usize
+	}
+
+	// Generate a uniformly-distributed value in [0, 1)
+	pub fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	// Generate a standard normal value (Box-Muller)
+	pub fn next_normal(&mut self) -> f64 {
+		let u1 = 1.0 - self.next_f64();
+		let u2 = self.next_f64();
+		(-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+	}
+
+	// Generate a Gamma(shape, 1) value (Marsaglia-Tsang), for positive shape
+	pub fn next_gamma(&mut self, shape: f64) -> f64 {
+		// Boost small shapes, then scale back down by U^(1/shape)
+		if shape < 1.0 {
+			let u = 1.0 - self.next_f64();
+			return self.next_gamma(shape + 1.0) * u.powf(1.0 / shape);
+		}
+
+		let d = shape - 1.0 / 3.0;
+		let c = 1.0 / (9.0 * d).sqrt();
+		loop {
+			let x = self.next_normal();
+			let v = (1.0 + c * x).powi(3);
+			if v <= 0.0 {
+				continue;
+			}
+			let u = 1.0 - self.next_f64();
+			if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+				return d * v;
+			}
+		}
+	}
+
+	// Generate a sample of the symmetric Dirichlet distribution with the given
+	// concentration over n outcomes
+	pub fn next_dirichlet(&mut self, alpha: f64, n: usize) -> Vec<f64> {
+		let draws: Vec<f64> = (0..n).map(|_| self.next_gamma(alpha)).collect();
+		let total: f64 = draws.iter().sum();
+		if total <= 0.0 {
+			return vec![1.0 / n as f64; n];
+		}
+		draws.into_iter().map(|draw| draw / total).collect()
+	}
+}
@@ -0,0 +1,345 @@
+// Named rules profiles: board size, fleet, and the rule options that vary between
+// editions of the game. The built-in presets live in data/presets.ini; more can
+// be registered at runtime or loaded from a file in the same format.
+
+use super::{encode_shiptype, parse_shiptype, ship_size, Board, GameState, Orientation, ShotResult};
+use context::Context;
+use geometry::{self, MAX_CHECKED_BOARD_SIZE};
+use diagnostic::{self, Diagnostic};
+use placement::Constraints;
+use std::path::Path;
+use text;
+
+// The built-in preset definitions
+const PRESETS: &str = include_str!("../data/presets.ini");
+
+// The preset matching the standard board and fleet
+pub const DEFAULT_PRESET: &str = "demo";
+
+// What happens when a square that already has a result is shot again
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Reshot {
+	// The shot is refused
+	Error,
+
+	// The shot is let go, as if it were never fired
+	Ignore,
+
+	// The shot stands and costs its turn, though it tells us nothing new
+	Turn,
+}
+
+impl Reshot {
+	pub fn parse(desc: &str) -> Option<Reshot> {
+		match desc {
+			"error" => Some(Reshot::Error),
+			"ignore" => Some(Reshot::Ignore),
+			"turn" => Some(Reshot::Turn),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Rules {
+	pub name: String,
+
+	// Board width and height
+	pub board_size: u8,
+
+	// Every ship in the fleet, as its type letter and size
+	pub fleet: Vec<(char, u8)>,
+
+	// How ships may be oriented, unless overridden for their type letter
+	pub orientation: Orientation,
+	pub ship_orientations: Vec<(char, Orientation)>,
+
+	// Whether ships may touch each other (including diagonally)
+	pub allow_touching: bool,
+
+	// Whether the opponent reveals which ship was hit
+	pub labeled_hits: bool,
+
+	// Whether each turn is a salvo of one shot per ship the shooter has
+	// afloat, every shot of it fired before any of its results come in (see
+	// sim::play_game_observed)
+	pub salvo: bool,
+
+	// How many turns each shot's result is withheld for (fog of war). Until
+	// then the square counts as shot but tells the inference nothing.
+	pub result_delay: usize,
+
+	// Whether the opponent announces (and names) each ship as it is sunk
+	pub announce_sunk: bool,
+
+	// What happens when a square is shot again. The repeat must report the
+	// same result as before, whatever the rule.
+	pub reshot: Reshot,
+
+	// Whether each player may relocate a ship with no hits once per game,
+	// in place of a shot (see GameState::apply_relocation)
+	pub moving_ships: bool,
+
+	// The run's settings, handed to every board made from these rules
+	pub context: Context,
+}
+
+impl Rules {
+	// The board and fleet these rules play on. Fails if a ship's letter doesn't
+	// name one of the ship types at its usual size, or the board can't hold
+	// the fleet (see Board::new). Fleets with several ships of a type or
+	// ships of size 1, such as the russian preset's, are refused: hits are
+	// told apart by the ship type's letter, so a Board holds each type once.
+	pub fn board(&self) -> Result<Board, String> {
+		for (idx, &(letter, _)) in self.fleet.iter().enumerate() {
+			if self.fleet[..idx].iter().any(|&(other, _)| other == letter) {
+				let count = self.fleet.iter().filter(|&&(other, _)| other == letter).count();
+				return Err(format!("the {} rules have {} ships of type {}, but a fleet can hold each of the P2 D3 S3 B4 C5 ship types only once, as hits are told apart by the type's letter", self.name, count, letter));
+			}
+		}
+		if let Some(&(letter, _)) = self.fleet.iter().find(|&&(_, size)| size == 1) {
+			return Err(format!("the {} rules have a ship {}1, but ships of size 1 aren't supported; the ship types are P2 D3 S3 B4 C5", self.name, letter));
+		}
+
+		let fleet = self.fleet.iter().map(|&(letter, size)| match parse_shiptype(letter) {
+			Some(stype) if ship_size(stype) == size => Ok(stype),
+			_ => Err(format!("the {} rules need a ship {}{}, which isn't one of the P2 D3 S3 B4 C5 ship types", self.name, letter, size)),
+		}).collect::<Result<Vec<_>, String>>()?;
+
+		Board::new(self.board_size, &fleet).map(|board| board.with_context(self.context.clone())).map_err(|e| format!("the {} rules: {}", self.name, e))
+	}
+
+	// The board, for rules already known to have one (see board)
+	pub fn known_board(&self) -> Board {
+		self.board().unwrap_or_else(|e| panic!("Unsupported rules: {}", e))
+	}
+
+	// Check that the board can hold the fleet: the board is one a BoardPos can
+	// address, every ship fits on it, and the ships don't need more squares
+	// than the board has. Whether they can all be placed at once (e.g. without
+	// touching) isn't checked.
+	pub fn check_feasible(&self) -> Result<(), String> {
+		if self.board_size == 0 || self.board_size > MAX_CHECKED_BOARD_SIZE {
+			return Err(format!("the board size must be from 1 to {}, not {}", MAX_CHECKED_BOARD_SIZE, self.board_size));
+		}
+		for &(letter, size) in &self.fleet {
+			geometry::check_fits(self.board_size, size).map_err(|e| format!("ship {}: {}", letter, e))?;
+		}
+
+		let squares: usize = self.fleet.iter().map(|&(_, size)| size as usize).sum();
+		let area = self.board_size as usize * self.board_size as usize;
+		if squares > area {
+			return Err(format!("the fleet covers {} squares, more than the {} on a {}x{} board", squares, area, self.board_size, self.board_size));
+		}
+
+		Ok(())
+	}
+
+	// The result the opponent reports for a shot with the given true result
+	pub fn reported(&self, result: ShotResult) -> ShotResult {
+		match result {
+			ShotResult::Sunk(stype) if !self.announce_sunk => self.reported(ShotResult::Hit(Some(stype))),
+			ShotResult::Hit(Some(_)) if !self.labeled_hits => ShotResult::Hit(None),
+			_ => result,
+		}
+	}
+
+	// How each ship type of the board's fleet (in fleet order) may be oriented
+	pub fn orientations(&self, board: &Board) -> Vec<Orientation> {
+		board.fleet().iter().map(|&stype| {
+			self.ship_orientations.iter().find(|&&(letter, _)| letter == encode_shiptype(stype)).map_or(self.orientation, |&(_, orientation)| orientation)
+		}).collect()
+	}
+
+	// The constraints layouts must follow under these rules, which must have a
+	// board (see board)
+	pub fn constraints(&self) -> Constraints {
+		let board = self.known_board();
+		Constraints { orientations: self.orientations(&board), board, allow_touching: self.allow_touching }
+	}
+
+	// The state for a fresh game under these rules, which must have a board
+	// (see board)
+	pub fn new_game(&self) -> GameState {
+		let board = self.known_board();
+		let mut state = GameState::new_restricted(&board, &self.orientations(&board));
+		if !self.allow_touching {
+			state.forbid_touching();
+		}
+		state
+	}
+
+	// Like new_game, with the opening heatmap loaded from the on-disk cache
+	pub fn new_cached_game(&self) -> GameState {
+		let mut state = self.new_game();
+		state.cache_opening();
+		state
+	}
+
+	// The number of shots fired in a turn with the given number of ships afloat
+	pub fn shots_per_turn(&self, ships_afloat: usize) -> usize {
+		if self.salvo { ships_afloat } else { 1 }
+	}
+}
+
+// Parse a yes/no option value
+fn parse_flag(value: &str) -> Option<bool> {
+	match value {
+		"yes" | "true" => Some(true),
+		"no" | "false" => Some(false),
+		_ => None,
+	}
+}
+
+// The options a profile section may set
+const OPTIONS: &[&str] = &["board", "fleet", "touching", "labeled_hits", "salvo", "delay", "sunk", "reshot", "orientation", "moving"];
+
+const FLAGS: &[&str] = &["yes", "no"];
+
+// Parse rules profiles in the presets file format
+pub fn parse_profiles(contents: &str) -> Result<Vec<Rules>, String> {
+	let mut profiles: Vec<Rules> = Vec::new();
+
+	for (line_num, line) in text::lines(contents).enumerate() {
+		let line = line.trim();
+		let err = |msg: &str| Diagnostic::new(line_num + 1, line, msg).to_string();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		if line.starts_with('[') && line.ends_with(']') {
+			profiles.push(Rules {
+				name: line[1..line.len() - 1].to_string(),
+				board_size: 0,
+				fleet: Vec::new(),
+				orientation: Orientation::Any,
+				ship_orientations: Vec::new(),
+				allow_touching: true,
+				labeled_hits: true,
+				salvo: false,
+				result_delay: 0,
+				announce_sunk: false,
+				reshot: Reshot::Error,
+				moving_ships: false,
+				context: Context::new(),
+			});
+			continue;
+		}
+
+		let profile = profiles.last_mut().ok_or_else(|| err("option outside of a [profile] section"))?;
+		let mut parts = line.splitn(2, '=');
+		let key = parts.next().unwrap_or("").trim();
+		let value = parts.next().ok_or_else(|| err("expected key = value"))?.trim();
+
+		// An error in the value, suggesting the closest of the values allowed
+		let value_span = line.find('=').and_then(|eq| {
+			let offset = line[..=eq].chars().count();
+			diagnostic::find_span(&line[eq + 1..], value).map(|(start, end)| (start + offset, end + offset))
+		});
+		let bad_value = |msg: &str, allowed: &[&str]| Diagnostic::new(line_num + 1, line, msg).at(value_span).suggest(diagnostic::closest(value, allowed)).to_string();
+
+		match key {
+			"board" => profile.board_size = value.parse().map_err(|_| bad_value("invalid board size", &[]))?,
+			"fleet" => {
+				let ships = value.split_whitespace().map(|ship| {
+					let mut chars = ship.chars();
+					let letter = chars.next()?;
+					let (size, orientation) = match chars.as_str().strip_suffix('h') {
+						Some(size) => (size, Some(Orientation::Horizontal)),
+						None => match chars.as_str().strip_suffix('v') {
+							Some(size) => (size, Some(Orientation::Vertical)),
+							None => (chars.as_str(), None),
+						},
+					};
+					Some((letter, size.parse().ok()?, orientation))
+				}).collect::<Option<Vec<_>>>().ok_or_else(|| bad_value("fleet entries must be a letter followed by a size, and optionally h or v", &[]))?;
+				profile.fleet = ships.iter().map(|&(letter, size, _)| (letter, size)).collect();
+				profile.ship_orientations = ships.iter().filter_map(|&(letter, _, orientation)| Some((letter, orientation?))).collect();
+			},
+			"orientation" => profile.orientation = match value {
+				"any" => Orientation::Any,
+				"horizontal" => Orientation::Horizontal,
+				"vertical" => Orientation::Vertical,
+				_ => return Err(bad_value("expected any, horizontal, or vertical", &["any", "horizontal", "vertical"])),
+			},
+			"touching" => profile.allow_touching = parse_flag(value).ok_or_else(|| bad_value("expected yes or no", FLAGS))?,
+			"labeled_hits" => profile.labeled_hits = parse_flag(value).ok_or_else(|| bad_value("expected yes or no", FLAGS))?,
+			"salvo" => profile.salvo = parse_flag(value).ok_or_else(|| bad_value("expected yes or no", FLAGS))?,
+			"delay" => profile.result_delay = value.parse().map_err(|_| bad_value("invalid delay", &[]))?,
+			"sunk" => profile.announce_sunk = parse_flag(value).ok_or_else(|| bad_value("expected yes or no", FLAGS))?,
+			"reshot" => profile.reshot = Reshot::parse(value).ok_or_else(|| bad_value("expected error, ignore, or turn", &["error", "ignore", "turn"]))?,
+			"moving" => profile.moving_ships = parse_flag(value).ok_or_else(|| bad_value("expected yes or no", FLAGS))?,
+			_ => return Err(Diagnostic::new(line_num + 1, line, "unknown option").at(diagnostic::find_span(line, key)).suggest(diagnostic::closest(key, OPTIONS)).to_string()),
+		}
+	}
+
+	for profile in &profiles {
+		if profile.board_size == 0 {
+			return Err(format!("[{}]: no board size given (board = <size>)", profile.name));
+		}
+		profile.check_feasible().map_err(|e| format!("[{}]: {}", profile.name, e))?;
+	}
+
+	Ok(profiles)
+}
+
+// A set of named rules profiles
+pub struct RulesRegistry {
+	profiles: Vec<Rules>,
+}
+
+impl RulesRegistry {
+	// A registry holding the built-in presets
+	pub fn new() -> RulesRegistry {
+		RulesRegistry { profiles: parse_profiles(PRESETS).expect("Invalid built-in rules presets") }
+	}
+
+	// Add a profile, replacing any existing profile with the same name
+	pub fn register(&mut self, rules: Rules) {
+		self.profiles.retain(|profile| profile.name != rules.name);
+		self.profiles.push(rules);
+	}
+
+	// Register every profile in a file in the presets format
+	pub fn load_file(&mut self, path: &str) -> Result<(), String> {
+		let contents = text::read(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?;
+		for rules in parse_profiles(&contents).map_err(|e| format!("{}: {}", path, e))? {
+			self.register(rules);
+		}
+
+		Ok(())
+	}
+
+	// Look up a profile by name
+	pub fn get(&self, name: &str) -> Option<&Rules> {
+		self.profiles.iter().find(|profile| profile.name == name)
+	}
+
+	// The names of every registered profile
+	pub fn names(&self) -> Vec<&str> {
+		self.profiles.iter().map(|profile| profile.name.as_str()).collect()
+	}
+}
+
+impl Default for RulesRegistry {
+	fn default() -> RulesRegistry {
+		RulesRegistry::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn built_in_presets_have_boards() {
+		let registry = RulesRegistry::new();
+		for name in registry.names() {
+			let rules = registry.get(name).unwrap();
+			match name {
+				"russian" => assert!(rules.board().unwrap_err().contains("2 ships of type C")),
+				_ => assert!(rules.board().is_ok(), "{}: {:?}", name, rules.board()),
+			}
+		}
+	}
+}
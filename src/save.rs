@@ -0,0 +1,220 @@
+// Save files: a snapshot of a game in progress that can be resumed without
+// replaying its moves or recomputing its heatmap.
+//
+// The file starts with a "battleship_ai save <version>" line, followed by
+// "key values..." lines:
+//   board <size> <ship>...   the board size and fleet (the standard board if
+//                            there is no board line)
+//   shots <move>...          every move so far, in the moves file format
+//   record <ship> <inferred> <eliminated>... the record of each move in turn
+//                            (see the record module): the ship letter or "-",
+//                            1 if it was inferred, and the positions ruled out
+//                            of each ship type
+//   pending <square>...      shots whose results are still withheld
+//   reshots <count>          shots repeated at squares with a result, if any
+//   sweep <sweep>            a radar sweep (see the radar module), one line
+//                            per sweep in order
+//   orientations <orientation>... how each ship type may be oriented (any,
+//                            horizontal or vertical), if any is restricted
+//   touching no              if ships may not touch each other
+//   relocated <moves> <sweeps> the moves and sweeps applied before the
+//                            opponent relocated a ship, if they have
+//   positions <ship> <id>... the still-possible position IDs of one ship type
+//   opening <total> <count>... the opening heatmap, if it was precomputed
+//   heatmap <total> <count>... the heatmap after the moves so far
+// with the heatmap counts in row-major square order. The overlap cache and
+// cover masks only depend on the board and fleet, so they are rebuilt rather
+// than saved.
+
+use super::{decode_shiptype, encode_shiptype, format_move, parse_move, parse_square, Board, BoardPos, GameState, Heatmap, Orientation, Relocation, ShotResult};
+use bitset::{PlacementSet, SharedSet};
+use radar::Sweep;
+use record::ShotRecord;
+
+const HEADER: &str = "battleship_ai save 2";
+
+fn line<T: ToString>(key: &str, values: &[T]) -> String {
+	let mut out = String::from(key);
+	for value in values {
+		out.push(' ');
+		out += &value.to_string();
+	}
+	out.push('\n');
+	out
+}
+
+fn heatmap_line(key: &str, heatmap: &Heatmap) -> String {
+	let mut values = vec![heatmap.total];
+	values.extend_from_slice(&heatmap.counts);
+	line(key, &values)
+}
+
+pub fn orientation_name(orientation: Orientation) -> &'static str {
+	match orientation {
+		Orientation::Any => "any",
+		Orientation::Horizontal => "horizontal",
+		Orientation::Vertical => "vertical",
+	}
+}
+
+pub fn parse_orientation(name: &str) -> Option<Orientation> {
+	match name {
+		"any" => Some(Orientation::Any),
+		"horizontal" => Some(Orientation::Horizontal),
+		"vertical" => Some(Orientation::Vertical),
+		_ => None,
+	}
+}
+
+fn parse_heatmap(board: &Board, values: &[&str]) -> Result<Heatmap, String> {
+	let numbers = values.iter().map(|n| n.parse().map_err(|_| format!("invalid count {}", n))).collect::<Result<Vec<u64>, String>>()?;
+	if numbers.len() != board.num_squares() + 1 {
+		return Err(format!("expected a total and {} counts", board.num_squares()));
+	}
+
+	Ok(Heatmap { total: numbers[0], counts: numbers[1..].to_vec() })
+}
+
+impl GameState {
+	// Snapshot the game, computing the current heatmap if it isn't known yet
+	pub fn to_save_string(&self) -> String {
+		let mut out = format!("{}\n", HEADER);
+		out += &line("board", &[self.board.describe()]);
+		let shots: Vec<String> = self.shots.iter().map(|&cur_move| format_move(cur_move)).collect();
+		out += &line("shots", &shots);
+		for record in &self.records {
+			let mut values = vec![record.ship.map_or(String::from("-"), |stype| encode_shiptype(stype).to_string())];
+			values.push(if record.inferred { "1" } else { "0" }.to_string());
+			values.extend(record.eliminated.iter().map(|&(_, count)| count.to_string()));
+			out += &line("record", &values);
+		}
+		out += &line("pending", &self.pending);
+		if self.reshots > 0 {
+			out += &line("reshots", &[self.reshots]);
+		}
+		for sweep in &self.sweeps {
+			out += &line("sweep", &[sweep]);
+		}
+		if self.orientations.iter().any(|&orientation| orientation != Orientation::Any) {
+			let names: Vec<&str> = self.orientations.iter().map(|&orientation| orientation_name(orientation)).collect();
+			out += &line("orientations", &names);
+		}
+		if !self.allow_touching {
+			out += &line("touching", &["no"]);
+		}
+		if let Some(relocation) = self.relocation {
+			out += &line("relocated", &[relocation.shots, relocation.sweeps]);
+		}
+		for (&stype, plist) in self.board.fleet().iter().zip(self.pos_positions.iter()) {
+			let mut values = vec![encode_shiptype(stype).to_string()];
+			values.extend(plist.iter().map(|pos| pos.to_string()));
+			out += &line("positions", &values);
+		}
+		if let Some(ref opening) = self.opening {
+			out += &heatmap_line("opening", opening);
+		}
+		out += &heatmap_line("heatmap", &self.heatmap());
+		out
+	}
+
+	// Restore a game from a save file, on the board its board line names (in
+	// base's context) or else on base
+	pub fn from_save_string(contents: &str, base: &Board) -> Result<GameState, String> {
+		let mut lines = contents.lines();
+		if lines.next().map(|line| line.trim()) != Some(HEADER) {
+			return Err(String::from("Not a battleship_ai save file (or from an incompatible version)"));
+		}
+
+		// The board comes first, as everything else is read against it
+		let lines: Vec<(usize, Vec<&str>)> = lines.enumerate().map(|(line_num, line)| (line_num + 2, line.split_whitespace().collect())).collect();
+		let board = match lines.iter().find(|(_, fields)| fields.first() == Some(&"board")) {
+			Some(&(line_num, ref fields)) => Board::parse(&fields[1..].join(" "), base.context()).map_err(|e| format!("line {}: {}", line_num, e))?,
+			None => base.clone(),
+		};
+
+		let mut state = GameState::new(&board);
+		let mut seen_positions = vec![false; board.fleet().len()];
+		let mut seen_heatmap = false;
+		let mut records = Vec::new();
+		for &(line_num, ref fields) in &lines {
+			let err = |msg: &str| format!("line {}: {}", line_num, msg);
+			match fields.split_first() {
+				None | Some((&"board", _)) => {},
+				Some((&"shots", moves)) => {
+					state.shots = moves.iter().map(|cur_move| parse_move(&board, cur_move)).collect::<Result<_, _>>().map_err(|e| err(&e))?;
+				},
+				Some((&"record", values)) if values.len() == 2 + board.fleet().len() => {
+					let ship = match values[0] {
+						"-" => None,
+						"P" | "D" | "S" | "B" | "C" => Some(decode_shiptype(values[0].as_bytes()[0])),
+						_ => return Err(err("unknown ship type")),
+					};
+					let eliminated = values[2..].iter().zip(board.fleet().iter())
+						.map(|(count, &stype)| count.parse().map(|count| (stype, count)).map_err(|_| err(&format!("invalid count {}", count))))
+						.collect::<Result<_, _>>()?;
+					records.push((ship, values[1] == "1", eliminated));
+				},
+				Some((&"pending", squares)) => {
+					state.pending = squares.iter().map(|square| parse_square(&board, square).ok_or_else(|| err(&format!("invalid square {}", square))))
+						.collect::<Result<_, _>>()?;
+				},
+				Some((&"reshots", [count])) => state.reshots = count.parse().map_err(|_| err(&format!("invalid count {}", count)))?,
+				Some((&"sweep", values)) => state.sweeps.push(Sweep::parse(&board, &values.join(" ")).map_err(|e| err(&e))?),
+				Some((&"orientations", names)) if names.len() == board.fleet().len() => {
+					state.orientations = names.iter().map(|name| parse_orientation(name).ok_or_else(|| err(&format!("invalid orientation {}", name)))).collect::<Result<_, _>>()?;
+				},
+				Some((&"touching", ["no"])) => {
+					let (opening, latest) = (state.opening.take(), state.latest.take());
+					state.forbid_touching();
+					state.opening = opening;
+					state.latest = latest;
+				},
+				Some((&"relocated", [shots, sweeps])) => {
+					let count = |value: &str| value.parse().map_err(|_| err(&format!("invalid count {}", value)));
+					state.relocation = Some(Relocation { shots: count(shots)?, sweeps: count(sweeps)? });
+				},
+				Some((&"positions", values)) if !values.is_empty() => {
+					let stype = match values[0] {
+						"P" | "D" | "S" | "B" | "C" => decode_shiptype(values[0].as_bytes()[0]),
+						_ => return Err(err("unknown ship type")),
+					};
+					let stype_idx = board.ship_index(stype).ok_or_else(|| err(&format!("there is no {:?} in the fleet", stype)))?;
+					let capacity = board.num_positions(stype);
+					let mut plist = PlacementSet::new_empty(capacity);
+					for id in &values[1..] {
+						match id.parse::<usize>() {
+							Ok(id) if id < capacity => plist.insert(id),
+							_ => return Err(err(&format!("invalid position {}", id))),
+						}
+					}
+
+					state.pos_positions[stype_idx] = SharedSet::new(plist);
+					seen_positions[stype_idx] = true;
+				},
+				Some((&"opening", values)) => state.opening = Some(parse_heatmap(&board, values).map_err(|e| err(&e))?),
+				Some((&"heatmap", values)) => {
+					let heatmap = parse_heatmap(&board, values).map_err(|e| err(&e))?;
+					let standing: Vec<(BoardPos, ShotResult)> = state.standing_shots().cloned().collect();
+					heatmap.check_invariants(&board, &standing).map_err(|e| err(&format!("inconsistent heatmap: {}", e)))?;
+					state.latest = Some(heatmap);
+					seen_heatmap = true;
+				},
+				Some((key, _)) => return Err(err(&format!("unexpected {}", key))),
+			}
+		}
+
+		if let Some(stype_idx) = seen_positions.iter().position(|&seen| !seen) {
+			return Err(format!("Missing the positions of the {:?}", board.fleet()[stype_idx]));
+		}
+		if !seen_heatmap {
+			return Err(String::from("Missing the heatmap"));
+		}
+		if records.len() != state.shots.len() {
+			return Err(format!("Expected a record for each of the {} shots, found {}", state.shots.len(), records.len()));
+		}
+		state.records = records.into_iter().zip(state.shots.iter()).enumerate().map(|(idx, ((ship, inferred, eliminated), &(pos, result)))| {
+			ShotRecord { turn: idx + 1, pos, result, ship, inferred, eliminated }
+		}).collect();
+		Ok(state)
+	}
+}
@@ -0,0 +1,169 @@
+// The versioned JSON interchange format for game logs and game states.
+//
+// This is the stable contract for third-party tools, independent of the
+// internal structs. A game log document looks like:
+//
+//   {"schema": "battleship_ai/game-log", "version": 2, "rules": "demo",
+//    "board_size": 5, "moves": [{"square": "B3", "result": "miss"},
+//                               {"square": "C4", "result": "hit", "ship": "D"}]}
+//
+// "result" may also be "sunk", for a hit that sank the (always named) ship.
+// "ship" is only present for hits on a known ship. A game state document has
+// schema "battleship_ai/game-state" and additionally records the derived
+// "placement_counts" (an object mapping ship letters to counts) and
+// "kill_shots" (the squares where a hit could sink a ship, each with the
+// probability that a hit there would, e.g.
+// [{"square": "C2", "probability": 1}]) and "phase" (one of
+// phase::PHASE_NAMES) for consumers that do not want to rerun the inference;
+// they are ignored on import.
+//
+// Documents with an older version are migrated on import (see migrate());
+// documents from a newer version are rejected.
+
+use super::{decode_shiptype, encode_shiptype, parse_square, Board, GameState, ShotResult};
+use gamelog::GameLog;
+use json::{self, Value};
+
+// The current schema version
+pub const SCHEMA_VERSION: u32 = 2;
+
+pub const GAME_LOG_SCHEMA: &str = "battleship_ai/game-log";
+pub const GAME_STATE_SCHEMA: &str = "battleship_ai/game-state";
+
+// Convert a document of any supported version into the current version.
+// When the schema changes, bump SCHEMA_VERSION and add a step here that
+// rewrites documents of the previous version.
+pub fn migrate(doc: Value) -> Result<Value, String> {
+	let version = doc.get("version").and_then(Value::as_f64).ok_or("missing schema version")? as u32;
+	if version > SCHEMA_VERSION {
+		return Err(format!("schema version {} is newer than the supported version {}", version, SCHEMA_VERSION));
+	}
+	if version == 0 {
+		return Err(String::from("invalid schema version 0"));
+	}
+
+	// Version 2 added sunk results; version 1 documents are valid as they are
+
+	Ok(doc)
+}
+
+// The JSON members shared by game logs and game states
+fn common_members(schema: &str, log: &GameLog) -> Vec<(String, Value)> {
+	let moves = log.moves.iter().map(|&(pos, result)| {
+		let mut members = vec![(String::from("square"), Value::String(pos.to_string()))];
+		match result {
+			ShotResult::Miss => members.push((String::from("result"), Value::String(String::from("miss")))),
+			ShotResult::Hit(stype) => {
+				members.push((String::from("result"), Value::String(String::from("hit"))));
+				if let Some(stype) = stype {
+					members.push((String::from("ship"), Value::String(encode_shiptype(stype).to_string())));
+				}
+			},
+			ShotResult::Sunk(stype) => {
+				members.push((String::from("result"), Value::String(String::from("sunk"))));
+				members.push((String::from("ship"), Value::String(encode_shiptype(stype).to_string())));
+			},
+		}
+		Value::Object(members)
+	}).collect();
+
+	vec![
+		(String::from("schema"), Value::String(schema.to_string())),
+		(String::from("version"), Value::Number(SCHEMA_VERSION as f64)),
+		(String::from("rules"), Value::String(log.rules.clone())),
+		(String::from("board_size"), Value::Number(log.board.size() as f64)),
+		(String::from("moves"), Value::Array(moves)),
+	]
+}
+
+// Export a game log as a JSON document
+pub fn export_log(log: &GameLog) -> String {
+	Value::Object(common_members(GAME_LOG_SCHEMA, log)).to_json()
+}
+
+// Export a game log as a JSON document of an older (or the current) version,
+// for tools that haven't caught up. Fails if the version can't express the log.
+pub fn export_log_version(log: &GameLog, version: u32) -> Result<String, String> {
+	if version == 0 || version > SCHEMA_VERSION {
+		return Err(format!("schema version {} is not one of 1 to {}", version, SCHEMA_VERSION));
+	}
+	if version < 2 && log.moves.iter().any(|&(_, result)| matches!(result, ShotResult::Sunk(_))) {
+		return Err(format!("schema version {} can't record sunk results", version));
+	}
+
+	let members = common_members(GAME_LOG_SCHEMA, log).into_iter().map(|(key, value)| match key.as_str() {
+		"version" => (key, Value::Number(version as f64)),
+		_ => (key, value),
+	}).collect();
+	Ok(Value::Object(members).to_json())
+}
+
+// Export a game state (the log of its moves plus derived data) as a JSON document
+pub fn export_state(state: &GameState, rules: &str) -> String {
+	let log = GameLog { rules: rules.to_string(), board: state.board().clone(), moves: state.shots().to_vec() };
+	let mut members = common_members(GAME_STATE_SCHEMA, &log);
+
+	let counts = state.placement_counts().iter().map(|&(stype, count)| {
+		(encode_shiptype(stype).to_string(), Value::Number(count as f64))
+	}).collect();
+	members.push((String::from("placement_counts"), Value::Object(counts)));
+
+	let probabilities = state.sink_probabilities();
+	let kill_shots = state.kill_shots(0.0).into_iter().map(|pos| Value::Object(vec![
+		(String::from("square"), Value::String(pos.to_string())),
+		(String::from("probability"), Value::Number(probabilities[pos.index()].unwrap_or(0.0))),
+	])).collect();
+	members.push((String::from("kill_shots"), Value::Array(kill_shots)));
+	members.push((String::from("phase"), Value::String(state.phase().name().to_string())));
+
+	Value::Object(members).to_json()
+}
+
+// Import a game log from a JSON document of either schema, for a game on the
+// given board
+pub fn import_log(board: &Board, text: &str) -> Result<GameLog, String> {
+	let doc = migrate(json::parse(text)?)?;
+
+	match doc.get("schema").and_then(Value::as_str) {
+		Some(GAME_LOG_SCHEMA) | Some(GAME_STATE_SCHEMA) => {},
+		_ => return Err(String::from("not a battleship_ai game log or game state")),
+	}
+	if doc.get("board_size").and_then(Value::as_f64) != Some(board.size() as f64) {
+		return Err(format!("the document is not for a {}x{} board", board.size(), board.size()));
+	}
+
+	let rules = doc.get("rules").and_then(Value::as_str).ok_or("missing rules")?;
+	let mut log = GameLog::new(rules, board);
+	for (idx, mv) in doc.get("moves").and_then(Value::as_array).ok_or("missing moves")?.iter().enumerate() {
+		let err = |msg: &str| format!("move {}: {}", idx + 1, msg);
+		let pos = mv.get("square").and_then(Value::as_str).and_then(|square| parse_square(board, square)).ok_or_else(|| err("invalid square"))?;
+		let ship = match mv.get("ship").and_then(Value::as_str) {
+			None => None,
+			Some(letter) if letter.len() == 1 && "PDSBC".contains(letter) => Some(decode_shiptype(letter.as_bytes()[0])),
+			Some(_) => return Err(err("invalid ship")),
+		};
+		if let Some(stype) = ship.filter(|&stype| board.ship_index(stype).is_none()) {
+			return Err(err(&format!("there is no {:?} in the fleet", stype)));
+		}
+		let result = match mv.get("result").and_then(Value::as_str) {
+			Some("miss") => ShotResult::Miss,
+			Some("hit") => ShotResult::Hit(ship),
+			Some("sunk") => ShotResult::Sunk(ship.ok_or_else(|| err("a sunk ship must be named"))?),
+			_ => return Err(err("result must be \"hit\", \"sunk\", or \"miss\"")),
+		};
+		log.moves.push((pos, result));
+	}
+
+	Ok(log)
+}
+
+// Import a game state from a JSON document by replaying its moves
+pub fn import_state(board: &Board, text: &str) -> Result<(GameState, String), String> {
+	let log = import_log(board, text)?;
+	let mut state = GameState::new(board);
+	for &cur_move in &log.moves {
+		state.apply_move(cur_move);
+	}
+
+	Ok((state, log.rules))
+}
@@ -0,0 +1,540 @@
+// Scripted strategies, for experimenting with shot selection without
+// recompiling: a script scores every unshot square, and the highest score is
+// shot (ties broken by the tie-break policy). Only built with the "scripting"
+// feature.
+//
+// A script is a list of lines, each "let <name> = <expression>" or, once and
+// last, "score = <expression>"; '#' starts a comment. Expressions work on
+// numbers, with + - * / %, comparisons, && || and ! (true is 1 and false 0),
+// parentheses, and the functions min(a, b), max(a, b), abs(x), and
+// if(condition, then, else). For example:
+//
+//   # The greedy strategy, with a bonus for the checkerboard while hunting
+//   let hunting = adjacent_hits == 0
+//   score = p + if(hunting && parity == 0, 0.05, 0)
+//
+// Scripts can read these variables about the square being scored:
+//   p                the heatmap probability of a ship there
+//   count            the number of consistent configurations with a ship there
+//   row, col         its row and column, counted from 0
+//   parity           (row + col) % 2
+//   edge_distance    the number of squares between it and the nearest edge
+//   adjacent_hits    orthogonal neighbors hit on ships not known to be sunk
+//   adjacent_misses  orthogonal neighbors that were misses
+//   adjacent_unshot  orthogonal neighbors not shot yet
+// and these about the game:
+//   total            the number of consistent configurations
+//   shots, hits, misses  the shots so far, and how they went
+//   ships_afloat     the ships not yet announced as sunk
+//   squares_afloat   the squares those ships cover
+//   largest_afloat, smallest_afloat  the sizes of the largest and smallest of them
+//   board_size       the board's width and height
+// and these functions look further into the game:
+//   p_at(row, col)     the heatmap probability at any square (0 off the board)
+//   shot_at(row, col)  what is known of any square: 0 unshot, 1 a miss, 2 a hit,
+//                      3 a hit on a ship since sunk, -1 off the board
+//   shot_row(n), shot_col(n), shot_result(n)  the square and result of the
+//                      nth shot, counted from 0, or back from the end when
+//                      negative (-1 is the latest); the result is 1 a miss, 2
+//                      a hit, 3 a sinking, and all three are -1 past the shots
+//   afloat(size)       the number of ships of that size not yet sunk
+//
+// The language is the crate's own rather than an embedded Lua or Rhai, since
+// the build takes no dependencies; the functions above are how a script gets
+// at the heatmap, the shot history, and the fleet instead of through an
+// engine's object bindings.
+
+use super::{ship_size, BoardPos, GameState, Heatmap, ShipType, ShotResult};
+use cancel::CancelToken;
+use std::path::Path;
+use strategy::{Strategy, TieBreak, TieBreaker};
+use text;
+
+// The variables every script can read, in the order Inputs fills them
+const BUILTINS: &[&str] = &["p", "count", "row", "col", "parity", "edge_distance", "adjacent_hits", "adjacent_misses",
+                            "adjacent_unshot", "total", "shots", "hits", "misses", "ships_afloat", "squares_afloat",
+                            "largest_afloat", "smallest_afloat", "board_size"];
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum BinaryOp {
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Rem,
+	Less,
+	LessEq,
+	Greater,
+	GreaterEq,
+	Equal,
+	NotEqual,
+	And,
+	Or,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+enum Expr {
+	Number(f64),
+
+	// A builtin (indexing BUILTINS) or, past them, an earlier let
+	Variable(usize),
+
+	Negate(Box<Expr>),
+	Not(Box<Expr>),
+	Binary(BinaryOp, Box<Expr>, Box<Expr>),
+	Min(Box<Expr>, Box<Expr>),
+	Max(Box<Expr>, Box<Expr>),
+	Abs(Box<Expr>),
+	If(Box<Expr>, Box<Expr>, Box<Expr>),
+	Query(Query, Vec<Expr>),
+}
+
+// The functions reading the game rather than their arguments
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum Query {
+	ProbabilityAt,
+	ShotAt,
+	ShotRow,
+	ShotCol,
+	ShotResult,
+	Afloat,
+}
+
+// The query functions' names and numbers of arguments
+const QUERIES: &[(&str, Query, usize)] = &[
+	("p_at", Query::ProbabilityAt, 2),
+	("shot_at", Query::ShotAt, 2),
+	("shot_row", Query::ShotRow, 1),
+	("shot_col", Query::ShotCol, 1),
+	("shot_result", Query::ShotResult, 1),
+	("afloat", Query::Afloat, 1),
+];
+
+#[derive(Clone,Debug,PartialEq)]
+enum Token {
+	Number(f64),
+	Name(String),
+
+	// An operator or punctuation symbol
+	Symbol(&'static str),
+}
+
+// Longer symbols first, so "<=" isn't read as "<" then "="
+const SYMBOLS: &[&str] = &["<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!", "(", ")", ","];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+	let mut tokens = Vec::new();
+	let mut rest = text.trim_start();
+	while let Some(c) = rest.chars().next() {
+		if c.is_ascii_digit() || c == '.' {
+			let len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+			tokens.push(Token::Number(rest[..len].parse().map_err(|_| format!("invalid number {}", &rest[..len]))?));
+			rest = &rest[len..];
+		} else if c.is_ascii_alphabetic() || c == '_' {
+			let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+			tokens.push(Token::Name(rest[..len].to_string()));
+			rest = &rest[len..];
+		} else {
+			let symbol = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)).ok_or_else(|| format!("unexpected '{}'", c))?;
+			tokens.push(Token::Symbol(symbol));
+			rest = &rest[symbol.len()..];
+		}
+		rest = rest.trim_start();
+	}
+
+	Ok(tokens)
+}
+
+// The binary operators by precedence level, loosest first
+const PRECEDENCE: &[&[(&str, BinaryOp)]] = &[
+	&[("||", BinaryOp::Or)],
+	&[("&&", BinaryOp::And)],
+	&[("==", BinaryOp::Equal), ("!=", BinaryOp::NotEqual)],
+	&[("<", BinaryOp::Less), ("<=", BinaryOp::LessEq), (">", BinaryOp::Greater), (">=", BinaryOp::GreaterEq)],
+	&[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+	&[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+];
+
+// A recursive-descent parser over one line's tokens, resolving variable names
+// against the given names in scope
+struct Parser<'a> {
+	tokens: &'a [Token],
+	next: usize,
+	names: &'a [String],
+}
+
+impl<'a> Parser<'a> {
+	fn peek_symbol(&self) -> Option<&'static str> {
+		match self.tokens.get(self.next) {
+			Some(&Token::Symbol(symbol)) => Some(symbol),
+			_ => None,
+		}
+	}
+
+	fn expect(&mut self, symbol: &str) -> Result<(), String> {
+		if self.peek_symbol() != Some(symbol) {
+			return Err(format!("expected '{}'", symbol));
+		}
+		self.next += 1;
+		Ok(())
+	}
+
+	fn expression(&mut self, level: usize) -> Result<Expr, String> {
+		if level == PRECEDENCE.len() {
+			return self.unary();
+		}
+
+		let mut lhs = self.expression(level + 1)?;
+		while let Some(&(_, op)) = self.peek_symbol().and_then(|symbol| PRECEDENCE[level].iter().find(|&&(name, _)| name == symbol)) {
+			self.next += 1;
+			let rhs = self.expression(level + 1)?;
+			lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+		}
+		Ok(lhs)
+	}
+
+	fn unary(&mut self) -> Result<Expr, String> {
+		match self.peek_symbol() {
+			Some("-") => {
+				self.next += 1;
+				Ok(Expr::Negate(Box::new(self.unary()?)))
+			},
+			Some("!") => {
+				self.next += 1;
+				Ok(Expr::Not(Box::new(self.unary()?)))
+			},
+			_ => self.primary(),
+		}
+	}
+
+	// The comma-separated arguments of a call, after its name
+	fn arguments(&mut self, name: &str, count: usize) -> Result<Vec<Expr>, String> {
+		self.expect("(")?;
+		let mut args = vec![self.expression(0)?];
+		while self.peek_symbol() == Some(",") {
+			self.next += 1;
+			args.push(self.expression(0)?);
+		}
+		self.expect(")")?;
+
+		if args.len() != count {
+			return Err(format!("{} takes {} arguments, not {}", name, count, args.len()));
+		}
+		Ok(args)
+	}
+
+	fn primary(&mut self) -> Result<Expr, String> {
+		let token = self.tokens.get(self.next).cloned().ok_or("unexpected end of line")?;
+		self.next += 1;
+		match token {
+			Token::Number(value) => Ok(Expr::Number(value)),
+			Token::Symbol("(") => {
+				let inner = self.expression(0)?;
+				self.expect(")")?;
+				Ok(inner)
+			},
+			Token::Symbol(symbol) => Err(format!("unexpected '{}'", symbol)),
+			Token::Name(name) => {
+				if self.peek_symbol() != Some("(") {
+					return self.names.iter().position(|known| *known == name).map(Expr::Variable).ok_or_else(|| format!("unknown variable {}", name));
+				}
+
+				match name.as_str() {
+					"min" | "max" => {
+						let mut args = self.arguments(&name, 2)?.into_iter().map(Box::new);
+						let (a, b) = (args.next().expect("Missing argument"), args.next().expect("Missing argument"));
+						Ok(if name == "min" { Expr::Min(a, b) } else { Expr::Max(a, b) })
+					},
+					"abs" => Ok(Expr::Abs(Box::new(self.arguments(&name, 1)?.remove(0)))),
+					"if" => {
+						let mut args = self.arguments(&name, 3)?.into_iter().map(Box::new);
+						let (cond, then, otherwise) = (args.next().expect("Missing argument"), args.next().expect("Missing argument"),
+						                               args.next().expect("Missing argument"));
+						Ok(Expr::If(cond, then, otherwise))
+					},
+					_ => match QUERIES.iter().find(|&&(known, _, _)| known == name) {
+						Some(&(_, query, count)) => Ok(Expr::Query(query, self.arguments(&name, count)?)),
+						None => Err(format!("unknown function {}", name)),
+					},
+				}
+			},
+		}
+	}
+}
+
+fn truth(value: bool) -> f64 {
+	if value { 1.0 } else { 0.0 }
+}
+
+// The ships announced as sunk
+fn sunk_ships(state: &GameState) -> Vec<ShipType> {
+	state.shots().iter().filter_map(|&(_, result)| match result {
+		ShotResult::Sunk(stype) => Some(stype),
+		_ => None,
+	}).collect()
+}
+
+// What the query functions read
+struct Game<'a> {
+	state: &'a GameState,
+	heatmap: &'a Heatmap,
+	sunk: Vec<ShipType>,
+}
+
+impl<'a> Game<'a> {
+	fn new(state: &'a GameState, heatmap: &'a Heatmap) -> Game<'a> {
+		Game { state, heatmap, sunk: sunk_ships(state) }
+	}
+
+	// The square at the given row and column, if they're whole numbers on the board
+	fn square(&self, row: f64, col: f64) -> Option<BoardPos> {
+		let size = self.state.board().size() as f64;
+		if [row, col].iter().any(|&coord| coord < 0.0 || coord >= size || coord.fract() != 0.0) {
+			return None;
+		}
+		self.state.board().square(row as u8, col as u8).ok()
+	}
+
+	// The nth shot, counting back from the end if n is negative
+	fn shot(&self, n: f64) -> Option<(BoardPos, ShotResult)> {
+		let shots = self.state.shots();
+		let idx = if n < 0.0 { shots.len() as f64 + n } else { n };
+		if idx < 0.0 || idx.fract() != 0.0 {
+			return None;
+		}
+		shots.get(idx as usize).cloned()
+	}
+
+	fn query(&self, query: Query, args: &[f64]) -> f64 {
+		match query {
+			Query::ProbabilityAt => self.square(args[0], args[1]).map_or(0.0, |pos| self.heatmap.probability(pos)),
+			Query::ShotAt => match self.square(args[0], args[1]) {
+				None => -1.0,
+				Some(pos) if !self.state.is_shot(pos) => 0.0,
+				Some(pos) if !self.state.is_hit(pos) => 1.0,
+				Some(pos) if self.state.record_at(pos).and_then(|record| record.ship).is_some_and(|stype| self.sunk.contains(&stype)) => 3.0,
+				Some(_) => 2.0,
+			},
+			Query::ShotRow => self.shot(args[0]).map_or(-1.0, |(pos, _)| pos.row() as f64),
+			Query::ShotCol => self.shot(args[0]).map_or(-1.0, |(pos, _)| pos.col() as f64),
+			Query::ShotResult => self.shot(args[0]).map_or(-1.0, |(_, result)| match result {
+				ShotResult::Miss => 1.0,
+				ShotResult::Hit(_) => 2.0,
+				ShotResult::Sunk(_) => 3.0,
+			}),
+			Query::Afloat => self.state.board().fleet().iter().filter(|&&stype| ship_size(stype) as f64 == args[0] && !self.sunk.contains(&stype)).count() as f64,
+		}
+	}
+}
+
+fn eval(expr: &Expr, vars: &[f64], game: &Game) -> f64 {
+	match *expr {
+		Expr::Number(value) => value,
+		Expr::Variable(idx) => vars[idx],
+		Expr::Negate(ref inner) => -eval(inner, vars, game),
+		Expr::Not(ref inner) => truth(eval(inner, vars, game) == 0.0),
+		Expr::Binary(op, ref lhs, ref rhs) => {
+			let lhs = eval(lhs, vars, game);
+
+			// && and || only evaluate the right side if they need it
+			match op {
+				BinaryOp::And => return truth(lhs != 0.0 && eval(rhs, vars, game) != 0.0),
+				BinaryOp::Or => return truth(lhs != 0.0 || eval(rhs, vars, game) != 0.0),
+				_ => {},
+			}
+
+			let rhs = eval(rhs, vars, game);
+			match op {
+				BinaryOp::Add => lhs + rhs,
+				BinaryOp::Sub => lhs - rhs,
+				BinaryOp::Mul => lhs * rhs,
+				BinaryOp::Div => lhs / rhs,
+				BinaryOp::Rem => lhs % rhs,
+				BinaryOp::Less => truth(lhs < rhs),
+				BinaryOp::LessEq => truth(lhs <= rhs),
+				BinaryOp::Greater => truth(lhs > rhs),
+				BinaryOp::GreaterEq => truth(lhs >= rhs),
+				BinaryOp::Equal => truth(lhs == rhs),
+				BinaryOp::NotEqual => truth(lhs != rhs),
+				BinaryOp::And | BinaryOp::Or => unreachable!("Short-circuit operators are handled above"),
+			}
+		},
+		Expr::Min(ref a, ref b) => eval(a, vars, game).min(eval(b, vars, game)),
+		Expr::Max(ref a, ref b) => eval(a, vars, game).max(eval(b, vars, game)),
+		Expr::Abs(ref inner) => eval(inner, vars, game).abs(),
+		Expr::If(ref cond, ref then, ref otherwise) => if eval(cond, vars, game) != 0.0 { eval(then, vars, game) } else { eval(otherwise, vars, game) },
+		Expr::Query(query, ref args) => {
+			let args: Vec<f64> = args.iter().map(|arg| eval(arg, vars, game)).collect();
+			game.query(query, &args)
+		},
+	}
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Script {
+	// The let expressions, in order, then the score
+	lets: Vec<Expr>,
+	score: Expr,
+}
+
+impl Script {
+	pub fn parse(contents: &str) -> Result<Script, String> {
+		let mut names: Vec<String> = BUILTINS.iter().map(|name| name.to_string()).collect();
+		let mut lets = Vec::new();
+		let mut score = None;
+		for (line_num, line) in text::lines(contents).enumerate() {
+			let err = |msg: String| format!("line {}: {}", line_num + 1, msg);
+			let line = line.split('#').next().unwrap_or("").trim();
+			if line.is_empty() {
+				continue;
+			}
+			if score.is_some() {
+				return Err(err(String::from("nothing may follow the score")));
+			}
+
+			let (target, expr) = line.split_once('=').filter(|(_, expr)| !expr.starts_with('='))
+				.ok_or_else(|| err(String::from("expected \"let <name> = <expression>\" or \"score = <expression>\"")))?;
+			let tokens = tokenize(expr).map_err(&err)?;
+			let mut parser = Parser { tokens: &tokens, next: 0, names: &names };
+			let parsed = parser.expression(0).map_err(&err)?;
+			if parser.next != tokens.len() {
+				return Err(err(String::from("unexpected text after the expression")));
+			}
+
+			let target = target.trim();
+			match target.strip_prefix("let ").map(str::trim) {
+				Some(name) if names.iter().any(|known| known == name) => return Err(err(format!("{} is already defined", name))),
+				Some(name) if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+				              && !name.starts_with(|c: char| c.is_ascii_digit()) => {
+					names.push(name.to_string());
+					lets.push(parsed);
+				},
+				Some(name) => return Err(err(format!("invalid variable name {}", name))),
+				None if target == "score" => score = Some(parsed),
+				None => return Err(err(format!("expected \"let\" or \"score\", not {}", target))),
+			}
+		}
+
+		let score = score.ok_or("The script has no score line")?;
+		Ok(Script { lets, score })
+	}
+
+	pub fn load(path: &str) -> Result<Script, String> {
+		let contents = text::read(Path::new(path)).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+		Script::parse(&contents).map_err(|e| format!("{}: {}", path, e))
+	}
+
+	// Score a square, given its builtin variables (in BUILTINS order)
+	fn score(&self, builtins: &[f64], game: &Game) -> f64 {
+		let mut vars = builtins.to_vec();
+		for expr in &self.lets {
+			let value = eval(expr, &vars, game);
+			vars.push(value);
+		}
+		eval(&self.score, &vars, game)
+	}
+}
+
+// The builtin variables for every square, filled in the order of BUILTINS
+fn builtins(state: &GameState, heatmap: &Heatmap, pos: BoardPos) -> Vec<f64> {
+	let shots = state.shots();
+	let sunk = sunk_ships(state);
+	let afloat: Vec<u8> = state.board().fleet().iter().filter(|stype| !sunk.contains(stype)).map(|&stype| ship_size(stype)).collect();
+	let open_hit = |pos: BoardPos| state.is_hit(pos) && !state.record_at(pos).and_then(|record| record.ship).is_some_and(|stype| sunk.contains(&stype));
+
+	let (row, col) = (pos.row() as i32, pos.col() as i32);
+	let neighbors: Vec<BoardPos> = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+		.filter_map(|&(drow, dcol)| pos.offset(drow, dcol))
+		.collect();
+	let hits = shots.iter().filter(|&&(_, result)| result != ShotResult::Miss).count();
+	let last = state.board().size() as i32 - 1;
+
+	vec![
+		heatmap.probability(pos),
+		heatmap.counts[pos.index()] as f64,
+		row as f64,
+		col as f64,
+		((row + col) % 2) as f64,
+		row.min(col).min(last - row).min(last - col) as f64,
+		neighbors.iter().filter(|&&next| open_hit(next)).count() as f64,
+		neighbors.iter().filter(|&&next| state.is_shot(next) && !state.is_hit(next)).count() as f64,
+		neighbors.iter().filter(|&&next| !state.is_shot(next)).count() as f64,
+		heatmap.total as f64,
+		shots.len() as f64,
+		hits as f64,
+		(shots.len() - hits) as f64,
+		afloat.len() as f64,
+		afloat.iter().map(|&size| size as f64).sum(),
+		afloat.iter().cloned().max().unwrap_or(0) as f64,
+		afloat.iter().cloned().min().unwrap_or(0) as f64,
+		state.board().size() as f64,
+	]
+}
+
+// Shoots the unshot square the script scores highest. Squares scoring NaN are
+// never chosen.
+pub struct Scripted {
+	script: Script,
+	tie_break: TieBreaker,
+}
+
+impl Scripted {
+	pub fn new(script: Script, tie_break: TieBreak) -> Scripted {
+		Scripted { script, tie_break: TieBreaker::new(tie_break) }
+	}
+}
+
+impl Strategy for Scripted {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		let (heatmap, _) = state.heatmap_best_effort(cancel);
+		let game = Game::new(state, &heatmap);
+		let scored: Vec<(BoardPos, f64)> = state.board().squares().filter(|&pos| !state.is_shot(pos))
+			.map(|pos| (pos, self.script.score(&builtins(state, &heatmap, pos), &game)))
+			.filter(|&(_, score)| !score.is_nan())
+			.collect();
+
+		let best = scored.iter().map(|&(_, score)| score).fold(f64::NEG_INFINITY, f64::max);
+		let candidates: Vec<BoardPos> = scored.iter().filter(|&&(_, score)| score == best).map(|&(pos, _)| pos).collect();
+		if candidates.is_empty() {
+			return None;
+		}
+		Some(self.tie_break.select(&candidates, state))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use board::Board;
+	use SHIP_TYPES;
+
+	// The script's score for the first square after a miss at A1 and a hit at B2
+	fn score_after_shots(text: &str) -> f64 {
+		let board = Board::new(5, &SHIP_TYPES).unwrap();
+		let mut state = GameState::new(&board);
+		state.apply_move((board.square(0, 0).unwrap(), ShotResult::Miss));
+		state.apply_move((board.square(1, 1).unwrap(), ShotResult::Hit(None)));
+		let heatmap = state.heatmap();
+		let pos = board.square(0, 0).unwrap();
+		Script::parse(text).unwrap().score(&builtins(&state, &heatmap, pos), &Game::new(&state, &heatmap))
+	}
+
+	#[test]
+	fn reads_the_shot_history() {
+		assert_eq!(score_after_shots("score = shot_row(-1) * 10 + shot_col(-1)"), 11.0);
+		assert_eq!(score_after_shots("score = shot_result(0) * 10 + shot_result(1)"), 12.0);
+		assert_eq!(score_after_shots("score = shot_result(2) + shot_row(-3)"), -2.0);
+	}
+
+	#[test]
+	fn reads_the_board_and_fleet() {
+		assert_eq!(score_after_shots("score = shot_at(0, 0) * 100 + shot_at(1, 1) * 10 + shot_at(4, 4)"), 120.0);
+		assert_eq!(score_after_shots("score = shot_at(-1, 0) + p_at(0, 0) + p_at(5, 0)"), -1.0);
+		assert_eq!(score_after_shots("score = afloat(3) * 10 + afloat(2)"), 21.0);
+	}
+
+	#[test]
+	fn checks_query_arguments() {
+		assert_eq!(Script::parse("score = p_at(row)"), Err(String::from("line 1: p_at takes 2 arguments, not 1")));
+	}
+}
@@ -0,0 +1,181 @@
+// Self-play data generation for machine learning: the AI plays random layouts
+// and every position it faces becomes one training example, written as a line
+// of JSON with the board, the heatmap probabilities, the distribution the shot
+// was drawn from, the shot, the true layout, and how many shots the game went
+// on to need.
+//
+// Playing greedily yields almost the same line every time, so shot selection
+// can be explored: the probabilities are sharpened or flattened with a
+// temperature, then mixed with Dirichlet noise, as in AlphaZero's self-play.
+
+use super::{encode_shiptype, format_move, Board, BoardPos, GameState, Heatmap, ShotResult};
+use cancel::CancelToken;
+use json::Value;
+use placement::Layout;
+use rng::Rng;
+use rules::Rules;
+use strategy::Strategy;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Exploration {
+	// Probabilities are raised to the power 1 / temperature before sampling;
+	// 0 always takes the most likely square (the first in row-major order on ties)
+	pub temperature: f64,
+
+	// The concentration of the Dirichlet noise, and the weight it gets in the
+	// mix (0 for no noise)
+	pub alpha: f64,
+	pub epsilon: f64,
+}
+
+// Plain greedy play
+pub const NO_EXPLORATION: Exploration = Exploration { temperature: 0.0, alpha: 0.3, epsilon: 0.0 };
+
+// Parse a noise setting from its command-line form, "<alpha>:<epsilon>",
+// returning the concentration and the weight
+pub fn parse_noise(desc: &str) -> Option<(f64, f64)> {
+	let (alpha, epsilon) = desc.split_once(':')?;
+	let (alpha, epsilon) = (alpha.parse::<f64>().ok()?, epsilon.parse::<f64>().ok()?);
+	if alpha <= 0.0 || !(0.0..=1.0).contains(&epsilon) {
+		return None;
+	}
+
+	Some((alpha, epsilon))
+}
+
+// Greedy shooting with exploration, drawing each shot from the distribution
+// given by policy
+pub struct Exploring {
+	exploration: Exploration,
+	rng: Rng,
+}
+
+impl Exploring {
+	pub fn new(exploration: Exploration, seed: u64) -> Exploring {
+		Exploring { exploration, rng: Rng::new(seed) }
+	}
+
+	// The probability of shooting each square (indexed by BoardPos::index),
+	// zero for shot squares. Draws fresh noise on every call.
+	pub fn policy(&mut self, state: &GameState, heatmap: &Heatmap) -> Vec<f64> {
+		let unshot: Vec<BoardPos> = state.board().squares().filter(|&pos| !state.is_shot(pos)).collect();
+		let mut policy = vec![0.0; heatmap.counts.len()];
+		if unshot.is_empty() {
+			return policy;
+		}
+
+		// Temper the probabilities, falling back on uniform if nothing is possible
+		let temperature = self.exploration.temperature;
+		let weights: Vec<f64> = if temperature <= 0.0 {
+			let best = unshot.iter().cloned().max_by_key(|pos| (heatmap.counts[pos.index()], std::cmp::Reverse(pos.index())))
+				.expect("No unshot squares");
+			unshot.iter().map(|&pos| if pos == best { 1.0 } else { 0.0 }).collect()
+		} else {
+			unshot.iter().map(|&pos| heatmap.probability(pos).powf(1.0 / temperature)).collect()
+		};
+		let total: f64 = weights.iter().sum();
+		let tempered: Vec<f64> = weights.iter().map(|&weight| if total > 0.0 { weight / total } else { 1.0 / unshot.len() as f64 }).collect();
+
+		let epsilon = self.exploration.epsilon;
+		let noise = if epsilon > 0.0 { self.rng.next_dirichlet(self.exploration.alpha, unshot.len()) } else { vec![0.0; unshot.len()] };
+		for (idx, &pos) in unshot.iter().enumerate() {
+			policy[pos.index()] = (1.0 - epsilon) * tempered[idx] + epsilon * noise[idx];
+		}
+		policy
+	}
+
+	// Draw a square of the board from the policy
+	fn sample(&mut self, board: &Board, policy: &[f64]) -> Option<BoardPos> {
+		let mut remaining = self.rng.next_f64() * policy.iter().sum::<f64>();
+		let mut last = None;
+		for pos in board.squares().filter(|pos| policy[pos.index()] > 0.0) {
+			remaining -= policy[pos.index()];
+			last = Some(pos);
+			if remaining < 0.0 {
+				break;
+			}
+		}
+		last
+	}
+}
+
+impl Strategy for Exploring {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		let (heatmap, _) = state.heatmap_best_effort(cancel);
+		let policy = self.policy(state, &heatmap);
+		self.sample(state.board(), &policy)
+	}
+}
+
+// The board as the attacker sees it, in row-major order: '.' unshot, '-' a
+// miss, 'H' an unlabeled hit, or the letter of the ship hit
+fn board_string(state: &GameState) -> String {
+	let mut board = vec!['.'; state.board().num_squares()];
+	for &(pos, result) in state.shots() {
+		board[pos.index()] = match result {
+			ShotResult::Miss => '-',
+			ShotResult::Hit(None) => 'H',
+			ShotResult::Hit(Some(stype)) | ShotResult::Sunk(stype) => encode_shiptype(stype),
+		};
+	}
+	board.into_iter().collect()
+}
+
+fn numbers(values: &[f64]) -> Value {
+	Value::Array(values.iter().map(|&value| Value::Number(value)).collect())
+}
+
+// Play the given number of games on random layouts, returning one line of JSON
+// per position. on_game is called with each game's number and length.
+pub fn generate(games: usize, seed: u64, rules: &Rules, exploration: Exploration,
+                on_game: &mut dyn FnMut(usize, usize)) -> Result<String, String> {
+	let mut rng = Rng::new(seed);
+	let mut player = Exploring::new(exploration, rng.next_u64());
+	let mut out = String::new();
+	for game in 0..games {
+		let layout = Layout::random(&mut rng, rules.constraints())
+			.ok_or_else(|| format!("No valid layout exists under the {} rules", rules.name))?;
+		let truth: String = layout.occupancy().iter().map(|cell| cell.map_or('.', encode_shiptype)).collect();
+		let ship_squares = truth.chars().filter(|&c| c != '.').count();
+
+		let mut state = rules.new_game();
+		let mut fired = Vec::new();
+		let mut hits = 0;
+		let mut positions = Vec::new();
+		while hits < ship_squares {
+			let (heatmap, _) = state.heatmap_best_effort(&CancelToken::new());
+			let policy = player.policy(&state, &heatmap);
+			let pos = player.sample(state.board(), &policy).ok_or("Every square was shot before the fleet sank")?;
+			let result = rules.reported(layout.shot_result_after(pos, &fired));
+			if result != ShotResult::Miss {
+				hits += 1;
+			}
+
+			let probabilities: Vec<f64> = state.board().squares().map(|pos| if state.is_shot(pos) { 0.0 } else { heatmap.probability(pos) }).collect();
+			positions.push(vec![
+				(String::from("board"), Value::String(board_string(&state))),
+				(String::from("probabilities"), numbers(&probabilities)),
+				(String::from("policy"), numbers(&policy)),
+				(String::from("shot"), Value::String(format_move((pos, result)))),
+			]);
+			fired.push(pos);
+			state.apply_move((pos, result));
+		}
+
+		let length = positions.len();
+		for (turn, members) in positions.into_iter().enumerate() {
+			let mut line = vec![
+				(String::from("game"), Value::Number((game + 1) as f64)),
+				(String::from("turn"), Value::Number((turn + 1) as f64)),
+			];
+			line.extend(members);
+			line.push((String::from("layout"), Value::String(truth.clone())));
+			line.push((String::from("shots_to_finish"), Value::Number((length - turn) as f64)));
+			out += &Value::Object(line).to_json();
+			out.push('\n');
+		}
+		on_game(game, length);
+	}
+
+	Ok(out)
+}
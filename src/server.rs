@@ -0,0 +1,155 @@
+// The server mode: one game, played over TCP or a Unix domain socket (see
+// transport.rs), for front ends that would rather send requests than drive
+// the REPL. Recommendations go through a Memoized strategy, so a display
+// polling for the best shot only pays for the search once per new result.
+//
+// The protocol is line-based text, one response line per request line:
+//   move <move>   record a result in the moves file format (e.g. "B3 D"), or
+//                 a shot whose result is withheld, e.g. "B3?"; answers "ok"
+//   best-shot     answers "shot <square>", or "shot none" when there's none
+//   cache         answers "hits <n> misses <n> invalidations <n>", the memo's
+//                 counts as the REPL's cache command shows them
+//   new           starts a new game; answers "ok"
+//   quit          closes the connection
+// Any request that fails answers "error: <message>". Connections are served
+// one at a time, in the order they arrive, and all of them share the game.
+
+use super::{parse_move, GameState};
+use cancel::CancelToken;
+use combine::Memoized;
+use rules::Rules;
+use std::io::{BufRead, BufReader, Write};
+use std::time::Duration;
+use strategy::Strategy;
+use transport::{Listener, Stream};
+
+pub struct Server {
+	rules: Rules,
+	state: GameState,
+	strategy: Memoized,
+
+	// How long a best-shot request may search for
+	time_limit: Option<Duration>,
+}
+
+impl Server {
+	pub fn new(rules: &Rules, strategy: Box<dyn Strategy>, time_limit: Option<Duration>) -> Server {
+		let mut strategy = Memoized::new(strategy);
+		strategy.on_game_start(rules);
+		Server { rules: rules.clone(), state: rules.new_cached_game(), strategy, time_limit }
+	}
+
+	// Answer a request line, or None for one that ends the connection
+	pub fn handle(&mut self, request: &str) -> Option<String> {
+		let (command, arg) = request.trim().split_once(' ').unwrap_or((request.trim(), ""));
+		let response = match command {
+			"move" => self.record(arg.trim()).map(|_| String::from("ok")),
+			"best-shot" => {
+				let cancel = self.time_limit.map_or_else(CancelToken::new, CancelToken::with_timeout);
+				let shot = self.strategy.choose_shot_cancellable(&self.state, &cancel);
+				Ok(shot.map_or_else(|| String::from("shot none"), |pos| format!("shot {}", pos)))
+			},
+			"cache" => {
+				let stats = self.strategy.stats();
+				Ok(format!("hits {} misses {} invalidations {}", stats.hits, stats.misses, stats.invalidations))
+			},
+			"new" => {
+				self.state = self.rules.new_cached_game();
+				self.strategy.on_game_start(&self.rules);
+				Ok(String::from("ok"))
+			},
+			"quit" => return None,
+			_ => Err(format!("unknown request {}", command)),
+		};
+		Some(response.unwrap_or_else(|e| format!("error: {}", e)))
+	}
+
+	fn record(&mut self, desc: &str) -> Result<(), String> {
+		let board = self.state.board().clone();
+		if let Some(square) = desc.strip_suffix('?') {
+			let (pos, _) = parse_move(&board, square)?;
+			if self.state.is_shot(pos) {
+				return Err(format!("{} has already been shot", pos));
+			}
+			self.state.mark_pending(pos);
+			return Ok(());
+		}
+
+		let (pos, result) = parse_move(&board, desc)?;
+		if self.state.is_shot(pos) && !self.state.pending().contains(&pos) {
+			return Err(format!("{} has already been shot", pos));
+		}
+		self.state.apply_move((pos, result));
+		Ok(())
+	}
+
+	// Serve connections until the listener fails, reporting each connection's
+	// errors to on_error
+	pub fn serve(&mut self, listener: &Listener, on_error: &mut dyn FnMut(String)) -> Result<(), String> {
+		loop {
+			let (stream, addr) = listener.accept().map_err(|e| format!("Unable to accept a connection: {}", e))?;
+			if let Err(e) = self.serve_connection(stream) {
+				on_error(format!("{}: {}", addr, e));
+			}
+		}
+	}
+
+	fn serve_connection(&mut self, stream: Stream) -> Result<(), String> {
+		let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+		let mut stream = stream;
+		let mut line = String::new();
+		loop {
+			line.clear();
+			match reader.read_line(&mut line) {
+				Ok(0) => return Ok(()),
+				Ok(_) => {},
+				Err(e) => return Err(format!("Unable to read from the connection: {}", e)),
+			}
+			if line.trim().is_empty() {
+				continue;
+			}
+
+			let response = match self.handle(&line) {
+				Some(response) => response,
+				None => return Ok(()),
+			};
+			stream.write_all(format!("{}\n", response).as_bytes()).map_err(|e| format!("Unable to write to the connection: {}", e))?;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rules::RulesRegistry;
+	use strategy::{Greedy, TieBreak};
+
+	fn server() -> Server {
+		let rules = RulesRegistry::new().get("demo").unwrap().clone();
+		Server::new(&rules, Box::new(Greedy::new(TieBreak::Lexicographic)), None)
+	}
+
+	#[test]
+	fn remembers_the_best_shot_until_the_next_result() {
+		let mut server = server();
+		let first = server.handle("best-shot").unwrap();
+		assert_eq!(server.handle("best-shot"), Some(first.clone()));
+		assert_eq!(server.handle("cache").unwrap(), "hits 1 misses 1 invalidations 0");
+
+		let square = first.strip_prefix("shot ").unwrap().to_string();
+		assert_eq!(server.handle(&format!("move {}", square)).unwrap(), "ok");
+		assert_ne!(server.handle("best-shot"), Some(first));
+		assert_eq!(server.handle("cache").unwrap(), "hits 1 misses 2 invalidations 1");
+	}
+
+	#[test]
+	fn reports_bad_requests() {
+		let mut server = server();
+		assert_eq!(server.handle("move C2 H").unwrap(), "ok");
+		assert_eq!(server.handle("move C2 H").unwrap(), "error: C2 has already been shot");
+		assert_eq!(server.handle("move C3?").unwrap(), "ok");
+		assert_eq!(server.handle("move C3 H").unwrap(), "ok");
+		assert!(server.handle("shoot C4").unwrap().starts_with("error: unknown request"));
+		assert_eq!(server.handle("quit"), None);
+	}
+}
@@ -0,0 +1,449 @@
+// Simulated games of an attacking strategy against a known layout
+
+use super::{encode_shiptype, format_move, render_board, Board, BoardPos, GameState, ShipType, ShotResult};
+use std::io::Write;
+use std::path::Path;
+use attackers::Attacker;
+use binlog::LogWriter;
+use durable;
+use history::History;
+use placement::Layout;
+use profile::Profile;
+use progress::{Progress, Tracker};
+use rng::Rng;
+use rules::Rules;
+use strategy::{GameResult, Greedy, Strategy, TieBreak};
+use timing;
+
+// Play a full game of the strategy against the layout under the given rules,
+// returning every move in order
+pub fn play_game(strategy: &mut dyn Strategy, layout: &Layout, rules: &Rules) -> Vec<(BoardPos, ShotResult)> {
+	play_game_observed(strategy, layout, rules, &mut |_, _| {})
+}
+
+// The number of shots the attacker fires each turn of a simulated game. Its
+// own fleet is never fired on, so under salvo rules every turn is a salvo of
+// one shot per ship of the fleet.
+pub fn salvo_size(rules: &Rules, board: &Board) -> usize {
+	rules.shots_per_turn(board.fleet().len())
+}
+
+// The number of turns a simulated game of the given number of shots took
+pub fn turns_taken(rules: &Rules, board: &Board, shots: usize) -> usize {
+	shots.div_ceil(salvo_size(rules, board))
+}
+
+// Play a full game like play_game, calling on_move with the state after each
+// move. Under salvo rules, every shot of a turn is chosen before the turn's
+// results are revealed. Under fog-of-war rules, each turn's results are
+// revealed (and on_move called) only after the given number of further turns.
+pub fn play_game_observed(strategy: &mut dyn Strategy, layout: &Layout, rules: &Rules,
+                          on_move: &mut dyn FnMut(&GameState, (BoardPos, ShotResult))) -> Vec<(BoardPos, ShotResult)> {
+	let mut state = rules.new_game();
+	let ship_squares = layout.occupancy().iter().filter(|cell| cell.is_some()).count();
+	let salvo = salvo_size(rules, layout.board());
+	let mut hits = 0;
+	strategy.on_game_start(rules);
+
+	// Every square fired at, and the turns whose results haven't been revealed, oldest first
+	let mut fired = Vec::new();
+	let mut withheld = std::collections::VecDeque::new();
+
+	'game: while hits < ship_squares {
+		let mut turn = Vec::with_capacity(salvo);
+		while turn.len() < salvo && hits < ship_squares {
+			let pos = match strategy.choose_shot(&state) {
+				Some(pos) => pos,
+				None if turn.is_empty() => break 'game,
+				None => break,
+			};
+
+			let result = layout.shot_result_after(pos, &fired);
+			if result != ShotResult::Miss {
+				hits += 1;
+			}
+			fired.push(pos);
+			state.mark_pending(pos);
+			turn.push((pos, rules.reported(result)));
+			timing::end_move();
+		}
+		withheld.push_back(turn);
+
+		while withheld.len() > rules.result_delay {
+			for cur_move in withheld.pop_front().expect("Withheld queue is empty") {
+				state.apply_move(cur_move);
+				on_move(&state, cur_move);
+			}
+		}
+	}
+
+	// The game is over, so every remaining result comes out
+	for cur_move in withheld.into_iter().flatten() {
+		state.apply_move(cur_move);
+		on_move(&state, cur_move);
+	}
+	timing::end_game();
+
+	let moves = state.shots().to_vec();
+	strategy.on_game_end(&GameResult { moves: moves.clone(), sank_fleet: hits >= ship_squares, won: None });
+	moves
+}
+
+// Play the strategy against the layout with a limited number of shots,
+// returning how many of them hit
+pub fn play_budget_game(strategy: &mut dyn Strategy, layout: &Layout, rules: &Rules, budget: usize) -> usize {
+	let mut state = rules.new_game();
+	let ship_squares = layout.occupancy().iter().filter(|cell| cell.is_some()).count();
+	let mut hits = 0;
+	strategy.on_game_start(rules);
+	while state.shots().len() < budget {
+		let pos = match strategy.choose_shot(&state) {
+			Some(pos) => pos,
+			None => break,
+		};
+
+		let fired: Vec<BoardPos> = state.shots().iter().map(|&(pos, _)| pos).collect();
+		let result = layout.shot_result_after(pos, &fired);
+		if result != ShotResult::Miss {
+			hits += 1;
+		}
+		state.apply_move((pos, rules.reported(result)));
+		timing::end_move();
+	}
+	timing::end_game();
+
+	strategy.on_game_end(&GameResult { moves: state.shots().to_vec(), sank_fleet: hits >= ship_squares, won: None });
+	hits
+}
+
+// For each square, the average turn (1-based) on which the attacker first
+// hits it, over the given number of games with a fresh seed for each.
+// Squares not occupied by the layout are None.
+pub fn vulnerability(layout: &Layout, rules: &Rules, attacker: Attacker, games: usize, seed: u64) -> Vec<Option<f64>> {
+	let mut rng = Rng::new(seed);
+	let mut turn_sums = vec![0usize; layout.board().num_squares()];
+
+	for _ in 0..games {
+		let mut attacker = attacker.strategy(rng.next_u64());
+		for (turn, &(pos, _)) in play_game(&mut *attacker, layout, rules).iter().enumerate() {
+			turn_sums[pos.index()] += turn + 1;
+		}
+	}
+
+	layout.occupancy().iter().zip(turn_sums.iter()).map(|(cell, &sum)| {
+		cell.map(|_| sum as f64 / games as f64)
+	}).collect()
+}
+
+// How much detail a tournament reports
+#[derive(Clone,Copy,Debug,PartialEq,PartialOrd)]
+pub enum Verbosity {
+	// Only the final aggregate results
+	Silent,
+
+	// A summary line per game
+	Games,
+
+	// Every move of every game, with the marks it changed on the board, and
+	// the board at the end
+	Moves,
+
+	// Every move plus the full heatmap after it
+	Beliefs,
+}
+
+impl Verbosity {
+	pub fn parse(desc: &str) -> Option<Verbosity> {
+		match desc {
+			"silent" => Some(Verbosity::Silent),
+			"games" => Some(Verbosity::Games),
+			"moves" => Some(Verbosity::Moves),
+			"beliefs" => Some(Verbosity::Beliefs),
+			_ => None,
+		}
+	}
+}
+
+// The squares whose marks differ between two renders of a board (see
+// GameState::render_cells), as "C3 o, D4 ?"
+pub fn mark_diff(board: &Board, before: &[char], after: &[char]) -> String {
+	let changed: Vec<String> = board.squares().filter(|pos| before[pos.index()] != after[pos.index()])
+		.map(|pos| format!("{} {}", pos, after[pos.index()])).collect();
+	changed.join(", ")
+}
+
+// The layout after the moves, for the end of a game log: hit ship squares
+// show their ship's letter and unhit ones the lowercase letter, misses 'o',
+// and untouched water '.'. Below the grid, the move on which each ship's
+// last square was hit.
+pub fn final_board(layout: &Layout, moves: &[(BoardPos, ShotResult)]) -> String {
+	let board = layout.board();
+	let occupancy = layout.occupancy();
+	let mut cells: Vec<char> = occupancy.iter().map(|cell| cell.map_or('.', |stype| encode_shiptype(stype).to_ascii_lowercase())).collect();
+	for &(pos, _) in moves {
+		cells[pos.index()] = occupancy[pos.index()].map_or('o', encode_shiptype);
+	}
+
+	let mut sunk: Vec<(usize, ShipType)> = board.fleet().iter().filter_map(|&stype| {
+		let squares: Vec<BoardPos> = board.squares().filter(|pos| occupancy[pos.index()] == Some(stype)).collect();
+		let last_hit = squares.iter().map(|&square| moves.iter().position(|&(pos, _)| pos == square)).collect::<Option<Vec<usize>>>()?;
+		Some((last_hit.into_iter().max()?, stype))
+	}).collect();
+	sunk.sort_by_key(|&(turn, _)| turn);
+	let sunk: Vec<String> = sunk.iter().map(|&(turn, stype)| format!("{} on move {}", encode_shiptype(stype), turn + 1)).collect();
+	let mut out = render_board(board, &cells);
+	if !sunk.is_empty() {
+		out += &format!("sunk: {}\n", sunk.join(", "));
+	}
+	out
+}
+
+// Aggregate results of a tournament
+#[derive(Clone,Debug,PartialEq)]
+pub struct TournamentSummary {
+	// The number of shots each game took to win, in order
+	pub shots: Vec<usize>,
+}
+
+impl TournamentSummary {
+	pub fn average(&self) -> f64 {
+		self.shots.iter().sum::<usize>() as f64 / self.shots.len().max(1) as f64
+	}
+}
+
+// The progress of a tournament: enough to continue it without replaying completed games
+#[derive(Clone,Debug)]
+pub struct Checkpoint {
+	// The name of the rules preset being played
+	pub rules: String,
+
+	// The total number of games in the tournament
+	pub games: usize,
+
+	// The seed the tournament started from
+	pub seed: u64,
+
+	// The RNG state after the last completed game
+	pub rng: Rng,
+
+	// The shots taken by each completed game
+	pub shots: Vec<usize>,
+}
+
+const CHECKPOINT_HEADER: &str = "battleship_ai tournament checkpoint";
+
+impl Checkpoint {
+	// The starting point of a fresh tournament
+	pub fn new(games: usize, seed: u64, rules: &Rules) -> Checkpoint {
+		Checkpoint { rules: rules.name.clone(), games, seed, rng: Rng::new(seed), shots: Vec::with_capacity(games) }
+	}
+
+	pub fn to_file_string(&self) -> String {
+		let shots: Vec<String> = self.shots.iter().map(|n| n.to_string()).collect();
+		format!("{}\nrules {}\ngames {}\nseed {}\nrng {}\nshots {}\n", CHECKPOINT_HEADER, self.rules,
+		        self.games, self.seed, self.rng.state(), shots.join(" "))
+	}
+
+	pub fn from_file_string(contents: &str) -> Result<Checkpoint, String> {
+		let mut lines = contents.lines();
+		if lines.next().map(|line| line.trim()) != Some(CHECKPOINT_HEADER) {
+			return Err("Not a tournament checkpoint".to_string());
+		}
+
+		let mut fields = Vec::new();
+		for line in lines {
+			let line = line.trim();
+			if line.is_empty() {
+				continue;
+			}
+			let (key, value) = match line.find(' ') {
+				Some(idx) => (&line[..idx], line[idx + 1..].trim()),
+				None => (line, ""),
+			};
+			fields.push((key, value));
+		}
+		let field = |name: &str| {
+			fields.iter().find(|&&(key, _)| key == name).map(|&(_, value)| value)
+			      .ok_or_else(|| format!("Checkpoint is missing {}", name))
+		};
+		let number = |name: &str| -> Result<u64, String> {
+			field(name)?.parse().map_err(|_| format!("Invalid checkpoint {}", name))
+		};
+
+		let shots = field("shots")?.split_whitespace().map(|n| {
+			n.parse().map_err(|_| format!("Invalid checkpoint shot count {}", n))
+		}).collect::<Result<Vec<usize>, String>>()?;
+		let games = number("games")? as usize;
+		if shots.len() > games {
+			return Err(format!("Checkpoint has {} completed games of {}", shots.len(), games));
+		}
+
+		Ok(Checkpoint {
+			rules: field("rules")?.to_string(),
+			games,
+			seed: number("seed")?,
+			rng: Rng::new(number("rng")?),
+			shots,
+		})
+	}
+
+	// Write the checkpoint to path, replacing it atomically so an interruption
+	// mid-write can't destroy the previous checkpoint (see the durable module)
+	pub fn save(&self, path: &Path) -> std::io::Result<()> {
+		durable::write(path, &self.to_file_string())
+	}
+}
+
+// Where a tournament records the games it plays, beyond its summary, and who
+// hears about its progress after each game
+#[derive(Default)]
+pub struct Records<'a> {
+	pub profile: Option<&'a mut Profile>,
+	pub binary_log: Option<&'a mut LogWriter>,
+	pub history: Option<&'a mut History>,
+	pub on_progress: Option<&'a mut dyn FnMut(Progress)>,
+}
+
+// Play the greedy strategy against random layouts until the tournament described by
+// start (a fresh Checkpoint::new or a loaded checkpoint) is complete, reporting at
+// the given verbosity. Summaries go to out; per-move streams go to one file per
+// game in log_dir if given (so huge tournaments don't flood out), or to out otherwise.
+// If checkpoint is given as (path, interval), progress is saved there every
+// interval games and once more at the end. Every game is recorded in the given records.
+pub fn run_tournament(start: Checkpoint, rules: &Rules, verbosity: Verbosity, out: &mut dyn Write,
+                      log_dir: Option<&Path>, checkpoint: Option<(&Path, usize)>,
+                      records: Records) -> std::io::Result<TournamentSummary> {
+	let Records { mut profile, mut binary_log, mut history, mut on_progress } = records;
+	if start.rules != rules.name {
+		return Err(std::io::Error::other(format!("checkpoint was made under the {} rules, not {}", start.rules, rules.name)));
+	}
+	let mut progress = start;
+	let tracker = Tracker::resumed(progress.shots.len() as u64, progress.games as u64);
+	if let Some(dir) = log_dir {
+		std::fs::create_dir_all(dir)?;
+	}
+
+	for game in progress.shots.len()..progress.games {
+		let rng = &mut progress.rng;
+		let layout = Layout::random(rng, rules.constraints()).ok_or_else(|| {
+			std::io::Error::other(format!("no valid layout exists under the {} rules", rules.name))
+		})?;
+		let mut attacker = Greedy::new(TieBreak::Random(rng.next_u64()));
+
+		// Where this game's per-move stream goes
+		let mut game_file = match (verbosity >= Verbosity::Moves, log_dir) {
+			(true, Some(dir)) => Some(std::fs::File::create(dir.join(format!("game-{:04}.log", game + 1)))?),
+			_ => None,
+		};
+
+		let mut write_err = None;
+		let mut marks = vec!['.'; layout.board().num_squares()];
+		let moves = {
+			let mut log_move = |state: &GameState, cur_move: (BoardPos, ShotResult)| {
+				if verbosity < Verbosity::Moves || write_err.is_some() {
+					return;
+				}
+
+				let stream: &mut dyn Write = match game_file {
+					Some(ref mut file) => file,
+					None => &mut *out,
+				};
+				let mut text = format!("game {} move {}: {}\n", game + 1, state.shots().len(), format_move(cur_move));
+				let cells = state.render_cells();
+				text += &format!("  marks: {}\n", mark_diff(state.board(), &marks, &cells));
+				marks = cells;
+				if verbosity >= Verbosity::Beliefs {
+					text += &state.heatmap().render(state.board());
+				}
+				if let Err(e) = stream.write_all(text.as_bytes()) {
+					write_err = Some(e);
+				}
+			};
+			play_game_observed(&mut attacker, &layout, rules, &mut log_move)
+		};
+		if let Some(e) = write_err {
+			return Err(e);
+		}
+		if verbosity >= Verbosity::Moves {
+			let stream: &mut dyn Write = match game_file {
+				Some(ref mut file) => file,
+				None => &mut *out,
+			};
+			write!(stream, "game {} final board:\n{}", game + 1, final_board(&layout, &moves))?;
+		}
+
+		if verbosity >= Verbosity::Games {
+			match salvo_size(rules, layout.board()) {
+				1 => writeln!(out, "game {}: won in {} shots", game + 1, moves.len())?,
+				_ => writeln!(out, "game {}: won in {} shots ({} turns)", game + 1, moves.len(), turns_taken(rules, layout.board(), moves.len()))?,
+			}
+		}
+		progress.shots.push(moves.len());
+		if let Some(ref mut profile) = profile {
+			let occupied: Vec<BoardPos> = layout.board().squares().filter(|&pos| layout.shot_result(pos) != ShotResult::Miss).collect();
+			profile.record(&occupied, &moves);
+		}
+		if let Some(ref mut log) = binary_log {
+			log.write_game(&layout, &moves)?;
+		}
+		if let Some(ref mut history) = history {
+			history.record(&moves);
+		}
+		if let Some(ref mut on_progress) = on_progress {
+			on_progress(tracker.at(progress.shots.len() as u64));
+		}
+
+		if let Some((path, interval)) = checkpoint {
+			if progress.shots.len().is_multiple_of(interval.max(1)) && progress.shots.len() < progress.games {
+				progress.save(path)?;
+			}
+		}
+	}
+	if let Some((path, _)) = checkpoint {
+		progress.save(path)?;
+	}
+	if let Some(log) = binary_log {
+		log.flush()?;
+	}
+
+	let summary = TournamentSummary { shots: progress.shots };
+	write!(out, "{} games, average {:.2} shots, best {}, worst {}", progress.games, summary.average(),
+	       summary.shots.iter().min().unwrap_or(&0), summary.shots.iter().max().unwrap_or(&0))?;
+	let board = rules.known_board();
+	match salvo_size(rules, &board) {
+		1 => writeln!(out)?,
+		salvo => writeln!(out, ", average {:.2} turns of {} shots", summary.shots.iter().map(|&shots| turns_taken(rules, &board, shots)).sum::<usize>() as f64 / summary.shots.len().max(1) as f64, salvo)?,
+	}
+
+	Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cancel::CancelToken;
+	use rules::RulesRegistry;
+
+	// Shoots the first unshot square, noting how many shots were pending each time
+	struct Counting(Vec<usize>);
+
+	impl Strategy for Counting {
+		fn choose_shot_cancellable(&mut self, state: &GameState, _cancel: &CancelToken) -> Option<BoardPos> {
+			self.0.push(state.pending().len());
+			state.board().squares().find(|&pos| !state.is_shot(pos))
+		}
+	}
+
+	#[test]
+	fn salvos_are_chosen_before_their_results() {
+		let mut rules = RulesRegistry::new().get("demo").unwrap().clone();
+		rules.salvo = true;
+		let layout = Layout::random(&mut Rng::new(1), rules.constraints()).unwrap();
+		let mut strategy = Counting(Vec::new());
+		let moves = play_game(&mut strategy, &layout, &rules);
+
+		let expected: Vec<usize> = (0..moves.len()).map(|shot| shot % 5).collect();
+		assert_eq!(strategy.0, expected);
+		assert_eq!(turns_taken(&rules, layout.board(), moves.len()), moves.len().div_ceil(5));
+	}
+}
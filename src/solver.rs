@@ -0,0 +1,366 @@
+// An exact solver for tiny games: the shot policy that provably minimizes the
+// number of shots needed to sink a fleet, for boards of up to 8x8 squares and
+// fleets small enough to search (in practice, a couple of ships on a 4x4
+// board, or the battleship and carrier on 5x5). It serves as a gold standard
+// for the heuristics: the greedy policy can be scored on the same game and
+// compared with the optimum.
+//
+// The defender's layout is one of every configuration of the fleet, and each
+// shot reveals whether it hit (and, with labeled hits, which ship), and
+// whether the fleet is now sunk. Two objectives are supported: the expected
+// number of shots against a layout chosen uniformly at random, and the worst
+// case against a layout chosen to make the policy do as badly as possible.
+//
+// The solver is independent of this build's BOARD_SIZE and SHIP_TYPES. It
+// works on (row, column) positions through the geometry module, with squares
+// numbered row-major as bit indices.
+
+use cancel::CancelToken;
+use geometry;
+use memory::{Component, Guard, Meter};
+use std::collections::HashMap;
+
+// The most layouts the solver will take on
+const MAX_CONFIGS: usize = 20_000;
+
+// The most states the solver will remember before giving up. Small ships on a
+// 5x5 board already need more than this: the search for them is long.
+const MAX_STATES: usize = 5_000_000;
+
+// What the policy minimizes
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Objective {
+	// The average number of shots over every layout, each equally likely
+	Expected,
+
+	// The most shots needed against any one layout
+	Worst,
+}
+
+impl Objective {
+	pub fn parse(desc: &str) -> Option<Objective> {
+		match desc {
+			"expected" => Some(Objective::Expected),
+			"worst" => Some(Objective::Worst),
+			_ => None,
+		}
+	}
+}
+
+// One layout of the fleet: the squares of each ship, as bitmasks
+struct Config {
+	ships: Vec<u64>,
+	occupied: u64,
+}
+
+impl Config {
+	// What a shot at the square reveals: the (1-based) index of the ship hit if
+	// hits are labeled, 1 for an unlabeled hit, or 0 for a miss
+	fn observe(&self, square: usize, labeled: bool) -> usize {
+		match self.ships.iter().position(|&ship| ship & (1 << square) != 0) {
+			Some(idx) if labeled => idx + 1,
+			Some(_) => 1,
+			None => 0,
+		}
+	}
+}
+
+// A set of configurations, as a bitset over their indices
+type ConfigSet = Vec<u64>;
+
+// The indices in the set, in increasing order
+fn members(set: &ConfigSet) -> impl Iterator<Item = usize> + '_ {
+	set.iter().enumerate().flat_map(|(word_idx, &word)| {
+		let mut bits = word;
+		std::iter::from_fn(move || {
+			if bits == 0 {
+				return None;
+			}
+			let bit = bits.trailing_zeros() as usize;
+			bits &= bits - 1;
+			Some(word_idx * 64 + bit)
+		})
+	})
+}
+
+// What a shot can reveal: each observation and the layouts that produce it
+type Outcomes = Vec<(usize, ConfigSet)>;
+
+fn insert(set: &mut ConfigSet, idx: usize) {
+	set[idx / 64] |= 1 << (idx % 64);
+}
+
+fn count(set: &ConfigSet) -> usize {
+	set.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+// One shot of a policy, and where the policy goes for each outcome
+#[derive(Clone,Debug,PartialEq)]
+pub struct PolicyNode {
+	// The square shot, as (row, column)
+	pub square: (u8, u8),
+
+	// Per outcome that doesn't end the game: the observation (0 for a miss, then
+	// as for Config::observe), how many layouts produce it, and the policy after it.
+	// Outcomes that sink the fleet are left out.
+	pub outcomes: Vec<(usize, usize, PolicyNode)>,
+}
+
+pub struct Solver {
+	board_size: u8,
+	labeled: bool,
+	objective: Objective,
+	configs: Vec<Config>,
+
+	// The optimal value and shot for each (squares shot, remaining layouts) state seen
+	memo: HashMap<(u64, ConfigSet), (f64, usize)>,
+
+	// Counts the memo against the meter's accounts and cap
+	memo_guard: Guard,
+}
+
+impl Solver {
+	// Enumerate every layout of ships of the given sizes on the board, counting
+	// the memo against the meter. Returns an error if the game is too big to
+	// solve.
+	pub fn new(board_size: u8, ship_sizes: &[u8], labeled: bool, objective: Objective, meter: &Meter) -> Result<Solver, String> {
+		if board_size == 0 || board_size > 8 {
+			return Err(String::from("The solver supports boards from 1x1 to 8x8"));
+		}
+		if let Some(&size) = ship_sizes.iter().find(|&&size| size < 2 || size > board_size) {
+			return Err(format!("A ship of size {} doesn't fit the solver's {}x{} board", size, board_size, board_size));
+		}
+
+		let mut configs = vec![Config { ships: Vec::new(), occupied: 0 }];
+		for &size in ship_sizes {
+			let placements: Vec<u64> = geometry::positions(board_size, size).map(|pos| {
+				geometry::ship_squares(board_size, size, pos).iter().fold(0, |mask, &(row, col)| mask | 1 << (row * board_size + col))
+			}).collect();
+
+			let mut extended = Vec::new();
+			for config in &configs {
+				for &placement in placements.iter().filter(|&&placement| placement & config.occupied == 0) {
+					let mut ships = config.ships.clone();
+					ships.push(placement);
+					extended.push(Config { ships, occupied: config.occupied | placement });
+				}
+				if extended.len() > MAX_CONFIGS {
+					return Err(format!("The fleet has over {} layouts on a {}x{} board, too many to solve", MAX_CONFIGS, board_size, board_size));
+				}
+			}
+			configs = extended;
+		}
+		if configs.is_empty() {
+			return Err(String::from("The fleet doesn't fit on the board"));
+		}
+
+		Ok(Solver { board_size, labeled, objective, configs, memo: HashMap::new(), memo_guard: Guard::new(meter, Component::SolverMemo, 0) })
+	}
+
+	// The number of possible layouts
+	pub fn layouts(&self) -> usize {
+		self.configs.len()
+	}
+
+	fn all_configs(&self) -> ConfigSet {
+		let mut set = vec![0; self.configs.len().div_ceil(64)];
+		for idx in 0..self.configs.len() {
+			insert(&mut set, idx);
+		}
+		set
+	}
+
+	fn square_pos(&self, square: usize) -> (u8, u8) {
+		(square as u8 / self.board_size, square as u8 % self.board_size)
+	}
+
+	// Split the layouts by what a shot at the square would reveal, leaving out
+	// the layouts the shot sinks
+	fn split(&self, configs: &ConfigSet, shots: u64, square: usize) -> Outcomes {
+		let mut outcomes: Outcomes = Vec::new();
+		let after = shots | 1 << square;
+		for idx in members(configs) {
+			let config = &self.configs[idx];
+			if config.occupied & !after == 0 {
+				continue;
+			}
+
+			let observation = config.observe(square, self.labeled);
+			match outcomes.iter_mut().find(|(seen, _)| *seen == observation) {
+				Some((_, set)) => insert(set, idx),
+				None => {
+					let mut set = vec![0; configs.len()];
+					insert(&mut set, idx);
+					outcomes.push((observation, set));
+				},
+			}
+		}
+
+		outcomes
+	}
+
+	// The squares worth considering: unshot squares that some remaining layout occupies.
+	// (A shot that is sure to miss reveals nothing, so never helps.)
+	fn candidates(&self, configs: &ConfigSet, shots: u64) -> Vec<usize> {
+		let occupied = members(configs).fold(0, |mask, idx| mask | self.configs[idx].occupied);
+		(0..(self.board_size as usize) * (self.board_size as usize)).filter(|&square| occupied & !shots & (1 << square) != 0).collect()
+	}
+
+	// Combine the values after each outcome into the value of a shot
+	fn shot_value(&self, total: usize, outcomes: &[(usize, f64)]) -> f64 {
+		match self.objective {
+			Objective::Expected => 1.0 + outcomes.iter().map(|&(size, value)| size as f64 * value).sum::<f64>() / total as f64,
+			Objective::Worst => 1.0 + outcomes.iter().map(|&(_, value)| value).fold(0.0, f64::max),
+		}
+	}
+
+	// A lower bound on the value of a state: every layout still needs a shot at
+	// each of its unshot squares
+	fn lower_bound(&self, configs: &ConfigSet, shots: u64) -> f64 {
+		let remaining = members(configs).map(|idx| (self.configs[idx].occupied & !shots).count_ones() as usize);
+		match self.objective {
+			Objective::Expected => remaining.sum::<usize>() as f64 / count(configs) as f64,
+			Objective::Worst => remaining.max().unwrap_or(0) as f64,
+		}
+	}
+
+	// The optimal value and shot when the given layouts remain and the given
+	// squares have been shot, or an error once cancelled. The memo only ever
+	// holds finished states, so a cancelled search can be resumed.
+	fn solve_state(&mut self, configs: &ConfigSet, shots: u64, cancel: &CancelToken) -> Result<(f64, usize), String> {
+		if cancel.is_cancelled() {
+			return Err(format!("The search was cancelled after {} states", self.memo.len()));
+		}
+
+		// Misses outside every remaining layout no longer matter, so leave them
+		// out of the key to share more states
+		let occupied = members(configs).fold(0, |mask, idx| mask | self.configs[idx].occupied);
+		let key = (shots & occupied, configs.clone());
+		if let Some(&known) = self.memo.get(&key) {
+			return Ok(known);
+		}
+		if self.memo.len() >= MAX_STATES {
+			return Err(format!("The search passed {} states; try a smaller board or larger ships", MAX_STATES));
+		}
+
+		// Try the most promising squares first, so the rest can be cut off by
+		// their lower bounds
+		let total = count(configs);
+		let mut candidates: Vec<(usize, Outcomes, f64)> = self.candidates(configs, shots).into_iter().map(|square| {
+			let outcomes = self.split(configs, shots, square);
+			let after = shots | 1 << square;
+			let bounds: Vec<(usize, f64)> = outcomes.iter().map(|(_, set)| (count(set), self.lower_bound(set, after))).collect();
+			let bound = self.shot_value(total, &bounds);
+			(square, outcomes, bound)
+		}).collect();
+		candidates.sort_by(|a, b| a.2.partial_cmp(&b.2).expect("Shot values are never NaN"));
+
+		let mut best = (f64::INFINITY, 0);
+		for (square, outcomes, bound) in candidates {
+			if bound >= best.0 {
+				break;
+			}
+
+			let after = shots | 1 << square;
+			let mut values: Vec<(usize, f64)> = outcomes.iter().map(|(_, set)| (count(set), self.lower_bound(set, after))).collect();
+			for (idx, (_, set)) in outcomes.iter().enumerate() {
+				values[idx].1 = self.solve_state(set, after, cancel)?.0;
+				if self.shot_value(total, &values) >= best.0 {
+					break;
+				}
+			}
+			let value = self.shot_value(total, &values);
+			if value < best.0 {
+				best = (value, square);
+			}
+		}
+
+		let entry_bytes = std::mem::size_of::<((u64, ConfigSet), (f64, usize))>() + key.1.len() * std::mem::size_of::<u64>();
+		if !self.memo_guard.fits(entry_bytes) {
+			return Err(format!("The search outgrew the memory cap after {} states", self.memo.len()));
+		}
+		self.memo_guard.grow(entry_bytes);
+		self.memo.insert(key, best);
+		Ok(best)
+	}
+
+	// The optimal number of shots (expected or worst-case, per the objective), or
+	// an error if the search is too large or cancelled
+	pub fn optimal_value(&mut self, cancel: &CancelToken) -> Result<f64, String> {
+		let all = self.all_configs();
+		Ok(self.solve_state(&all, 0, cancel)?.0)
+	}
+
+	// The value of the greedy policy: always shoot the candidate square occupied
+	// in the most remaining layouts, breaking ties by the lowest row, then column
+	pub fn greedy_value(&self) -> f64 {
+		self.greedy_state(&self.all_configs(), 0)
+	}
+
+	fn greedy_state(&self, configs: &ConfigSet, shots: u64) -> f64 {
+		let square = self.candidates(configs, shots).into_iter().max_by_key(|&square| {
+			let occupying = members(configs).filter(|&idx| self.configs[idx].occupied & (1 << square) != 0).count();
+			(occupying, std::cmp::Reverse(square))
+		}).expect("A game in progress has no candidate squares");
+
+		let outcomes = self.split(configs, shots, square);
+		let values: Vec<(usize, f64)> = outcomes.iter().map(|(_, set)| (count(set), self.greedy_state(set, shots | 1 << square))).collect();
+		self.shot_value(count(configs), &values)
+	}
+
+	// The optimal policy as a decision tree, or an error if the search is too
+	// large or cancelled
+	pub fn policy(&mut self, cancel: &CancelToken) -> Result<PolicyNode, String> {
+		let all = self.all_configs();
+		self.policy_state(&all, 0, cancel)
+	}
+
+	fn policy_state(&mut self, configs: &ConfigSet, shots: u64, cancel: &CancelToken) -> Result<PolicyNode, String> {
+		let (_, square) = self.solve_state(configs, shots, cancel)?;
+		let outcomes = self.split(configs, shots, square);
+		let outcomes = outcomes.iter().map(|(observation, set)| Ok((*observation, count(set), self.policy_state(set, shots | 1 << square, cancel)?)))
+			.collect::<Result<_, String>>()?;
+		Ok(PolicyNode { square: self.square_pos(square), outcomes })
+	}
+}
+
+impl PolicyNode {
+	// Render the tree with one line per shot, indented by depth. Squares are
+	// written like "B3"; outcomes as "miss", "hit", or the 1-based ship number.
+	pub fn render(&self, labeled: bool) -> String {
+		let mut out = String::new();
+		self.render_into(&mut out, 0, labeled);
+		out
+	}
+
+	fn render_into(&self, out: &mut String, depth: usize, labeled: bool) {
+		out.push_str(&format!("{}shoot {}{}\n", "  ".repeat(depth), (b'A' + self.square.0) as char, self.square.1 + 1));
+		for (observation, layouts, next) in &self.outcomes {
+			let outcome = match *observation {
+				0 => String::from("miss"),
+				ship if labeled => format!("hit ship {}", ship),
+				_ => String::from("hit"),
+			};
+			out.push_str(&format!("{}if {} ({} layouts):\n", "  ".repeat(depth + 1), outcome, layouts));
+			next.render_into(out, depth + 2, labeled);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn stops_when_cancelled() {
+		let mut solver = Solver::new(3, &[2], false, Objective::Expected, &Meter::new()).unwrap();
+		let cancel = CancelToken::new();
+		cancel.cancel();
+		assert!(solver.optimal_value(&cancel).unwrap_err().contains("cancelled"));
+		assert!(solver.policy(&cancel).is_err());
+
+		// Nothing half-searched was remembered, so the search can start over
+		let optimal = solver.optimal_value(&CancelToken::new()).unwrap();
+		assert!(optimal <= solver.greedy_value());
+	}
+}
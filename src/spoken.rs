@@ -0,0 +1,135 @@
+// Spelled-out commands and sentence descriptions for screen-reader users.
+//
+// Commands may spell out squares ("B 3", "bravo three", "row B column 10")
+// and results ("hit", "miss", "hit on the destroyer"), with filler words
+// ignored, so "B3 hit destroyer" and "shot at bravo three was a miss" both parse.
+
+use super::{parse_square, Board, BoardPos, GameState, ShipType, ShotResult};
+use messages::Cell;
+use placement::Layout;
+
+// Words ignored anywhere in a command. "a" is also ignored, but only once the
+// square has been found, since it may be row A.
+const FILLER: &[&str] = &["an", "and", "at", "boat", "column", "is", "it", "on", "row", "shot", "square", "the", "to", "was"];
+
+// Split a command into lowercase words, dropping punctuation and filler
+fn words(line: &str) -> Vec<String> {
+	line.split(|c: char| c.is_whitespace() || c == ',' || c == '.')
+		.map(|word| word.to_ascii_lowercase())
+		.filter(|word| !word.is_empty() && !FILLER.contains(&word.as_str()))
+		.collect()
+}
+
+// A row letter, spelled as itself or in the NATO alphabet
+fn row_word(board: &Board, word: &str) -> Option<u8> {
+	const NATO: &[&str] = &["alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliet"];
+	let letter = match NATO.iter().position(|&name| name == word) {
+		Some(letter) => letter as u32,
+		None if word.len() == 1 && word.as_bytes()[0].is_ascii_lowercase() => (word.as_bytes()[0] - b'a') as u32,
+		None => return None,
+	};
+	board.coordinates().row_of_letter(board.size(), letter)
+}
+
+// A column number, as digits or spelled out
+fn column_word(board: &Board, word: &str) -> Option<u8> {
+	const NUMBERS: &[&str] = &["zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten"];
+	let number = match NUMBERS.iter().position(|&name| name == word) {
+		Some(number) => number as u32,
+		None => word.parse().ok()?,
+	};
+	board.coordinates().column_of_number(board.size(), number)
+}
+
+fn ship_word(word: &str) -> Option<ShipType> {
+	match word {
+		"patrol" => Some(ShipType::Patrol),
+		"destroyer" => Some(ShipType::Destroyer),
+		"submarine" | "sub" => Some(ShipType::Submarine),
+		"battleship" => Some(ShipType::Battleship),
+		"carrier" => Some(ShipType::Carrier),
+		_ => None,
+	}
+}
+
+// Find a square in the words, either written together ("b3") or as a row
+// word followed by a column word ("bravo three"), removing its words
+fn take_square(board: &Board, words: &mut Vec<String>) -> Option<BoardPos> {
+	if let Some(idx) = words.iter().position(|word| word.len() > 1 && parse_square(board, &word.to_ascii_uppercase()).is_some()) {
+		return parse_square(board, &words.remove(idx).to_ascii_uppercase());
+	}
+
+	for idx in 0..words.len().saturating_sub(1) {
+		if let (Some(row), Some(col)) = (row_word(board, &words[idx]), column_word(board, &words[idx + 1])) {
+			words.drain(idx..idx + 2);
+			return board.square(row, col).ok();
+		}
+	}
+
+	None
+}
+
+// Parse a spelled-out move such as "B 3 hit on the destroyer" or "bravo three miss"
+pub fn parse_spoken_move(board: &Board, line: &str) -> Result<(BoardPos, ShotResult), String> {
+	let mut words = words(line);
+	let pos = take_square(board, &mut words).ok_or_else(|| format!("No square in \"{}\"", line))?;
+
+	let mut hit = None;
+	let mut sunk = false;
+	let mut ship = None;
+	for word in &words {
+		match word.as_str() {
+			"a" => {},
+			"hit" => hit = Some(true),
+			"sunk" | "sank" => {
+				hit = Some(true);
+				sunk = true;
+			},
+			"miss" | "missed" => hit = Some(false),
+			_ => match ship_word(word) {
+				Some(stype) => ship = Some(stype),
+				None => return Err(format!("Unexpected word \"{}\"", word)),
+			},
+		}
+	}
+
+	match (hit, ship) {
+		(Some(false), Some(_)) => Err("A miss can't hit a ship".to_string()),
+		(Some(false), None) => Ok((pos, ShotResult::Miss)),
+		(_, Some(stype)) if sunk => Ok((pos, ShotResult::Sunk(stype))),
+		(_, Some(stype)) => Ok((pos, ShotResult::Hit(Some(stype)))),
+		(Some(true), None) => Ok((pos, ShotResult::Hit(None))),
+		(None, None) => Err("Say whether the shot was a hit or a miss".to_string()),
+	}
+}
+
+// Parse a spelled-out placement such as "B 2 horizontal" or "charlie one down"
+pub fn parse_spoken_placement(board: &Board, line: &str) -> Result<(BoardPos, bool), String> {
+	let mut words = words(line);
+	let start = take_square(board, &mut words).ok_or_else(|| format!("No square in \"{}\"", line))?;
+
+	match words.iter().map(|word| word.as_str()).filter(|&word| word != "a").collect::<Vec<_>>().as_slice() {
+		["h"] | ["horizontal"] | ["across"] => Ok((start, true)),
+		["v"] | ["vertical"] | ["down"] => Ok((start, false)),
+		_ => Err("Say horizontal or vertical".to_string()),
+	}
+}
+
+// The cells of the opponent's board as the moves so far reveal it
+pub fn state_cells(state: &GameState) -> Vec<Cell> {
+	let mut cells = vec![Cell::Unknown; state.board().num_squares()];
+	for &(pos, result) in state.shots() {
+		cells[pos.index()] = match result {
+			ShotResult::Miss => Cell::Miss,
+			ShotResult::Hit(stype) => Cell::Hit(stype),
+			ShotResult::Sunk(stype) => Cell::Sunk(stype),
+		};
+	}
+
+	cells
+}
+
+// The cells of our own layout
+pub fn layout_cells(layout: &Layout) -> Vec<Cell> {
+	layout.occupancy().iter().map(|cell| cell.map_or(Cell::Unknown, Cell::Ship)).collect()
+}
@@ -0,0 +1,586 @@
+use super::{BoardPos, GameState, Heatmap, ShipType, ShotResult};
+use arena::Arena;
+use cancel::CancelToken;
+use combine;
+use forced::ForcedWin;
+#[cfg(feature = "montecarlo")]
+use montecarlo;
+use rng::Rng;
+use rules::Rules;
+#[cfg(feature = "scripting")]
+use script;
+use timing::{self, Phase};
+
+// How to choose between several squares that are equally good
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum TieBreak {
+	// The first square in row-major order
+	Lexicographic,
+
+	// The square closest to the center of the board
+	CenterMost,
+
+	// A random square, from a generator with the given seed
+	Random(u64),
+
+	// The square farthest from every previous shot
+	FarthestFromShots,
+}
+
+impl TieBreak {
+	// Parse a tie-breaking policy from its command-line name:
+	// "lexicographic", "center", "farthest", or "random:<seed>"
+	pub fn parse(desc: &str) -> Option<TieBreak> {
+		match desc {
+			"lexicographic" => Some(TieBreak::Lexicographic),
+			"center" => Some(TieBreak::CenterMost),
+			"farthest" => Some(TieBreak::FarthestFromShots),
+			_ => {
+				if let Some(seed) = desc.strip_prefix("random:") {
+					seed.parse().ok().map(TieBreak::Random)
+				} else {
+					None
+				}
+			}
+		}
+	}
+}
+
+// Squared Euclidean distance between two points
+fn dist2(row1: i32, col1: i32, row2: i32, col2: i32) -> i32 {
+	(row1 - row2) * (row1 - row2) + (col1 - col2) * (col1 - col2)
+}
+
+// Pick the minimum-scored candidate, keeping the first one on equal scores
+fn min_by_score<F: Fn(BoardPos) -> i32>(candidates: &[BoardPos], score: F) -> BoardPos {
+	let mut best = candidates[0];
+	let mut best_score = score(best);
+	for &pos in &candidates[1..] {
+		let cur_score = score(pos);
+		if cur_score < best_score {
+			best = pos;
+			best_score = cur_score;
+		}
+	}
+
+	best
+}
+
+// Applies a tie-breaking policy, holding the random generator state when needed
+pub struct TieBreaker {
+	policy: TieBreak,
+	rng: Rng,
+}
+
+impl TieBreaker {
+	pub fn new(policy: TieBreak) -> TieBreaker {
+		let seed = match policy {
+			TieBreak::Random(seed) => seed,
+			_ => 0,
+		};
+
+		TieBreaker { policy, rng: Rng::new(seed) }
+	}
+
+	// The policy being applied
+	pub fn policy(&self) -> TieBreak {
+		self.policy
+	}
+
+	// Choose one of the (nonempty, row-major ordered) candidate squares
+	pub fn select(&mut self, candidates: &[BoardPos], state: &GameState) -> BoardPos {
+		match self.policy {
+			TieBreak::Lexicographic => candidates[0],
+			TieBreak::CenterMost => {
+				// Work in half-square units so the center of an even-sized board is an integer
+				let center = state.board().size() as i32 - 1;
+				min_by_score(candidates, |pos| {
+					let (row, col) = (pos.row(), pos.col());
+					dist2(2 * row as i32, 2 * col as i32, center, center)
+				})
+			},
+			TieBreak::Random(_) => candidates[self.rng.gen_range(candidates.len())],
+			TieBreak::FarthestFromShots => min_by_score(candidates, |pos| {
+				let (row, col) = (pos.row(), pos.col());
+				-state.shots().iter().map(|&(shot, _)| {
+					let (shot_row, shot_col) = (shot.row(), shot.col());
+					dist2(row as i32, col as i32, shot_row as i32, shot_col as i32)
+				}).min().unwrap_or(0)
+			}),
+		}
+	}
+}
+
+// How a game went, for Strategy::on_game_end
+#[derive(Clone,Debug,PartialEq)]
+pub struct GameResult {
+	// Every shot the strategy fired, with the result reported, in order
+	pub moves: Vec<(BoardPos, ShotResult)>,
+
+	// Whether the whole fleet was sunk (games may also end when the shots run out)
+	pub sank_fleet: bool,
+
+	// Against an opponent shooting back, whether the strategy sank their fleet first
+	pub won: Option<bool>,
+}
+
+// A shot selection policy. Besides choosing shots, a strategy hears when a
+// game starts and ends, and about the opponent's shots when there is an
+// opponent shooting back, so strategies that keep state across games (an
+// opening book, adapting to an opponent) can reset and learn between them.
+pub trait Strategy {
+	// Choose the next square to shoot at. Returns None if every square has been shot.
+	fn choose_shot(&mut self, state: &GameState) -> Option<BoardPos> {
+		timing::time(Phase::Selection, || self.choose_shot_cancellable(state, &CancelToken::new()))
+	}
+
+	// Choose the next square to shoot at, returning the best choice found so far
+	// as soon as possible after the token is cancelled
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos>;
+
+	// The forced win the latest shot chosen starts, for strategies that look
+	// for them (see forced::Forcing)
+	fn forced_win(&self) -> Option<ForcedWin> {
+		None
+	}
+
+	// Whether choosing a shot uses the exact heatmap, so front-ends counting it
+	// ahead of time (e.g. to show progress) aren't wasting their time
+	fn needs_heatmap(&self) -> bool {
+		true
+	}
+
+	// A new game is starting under the rules
+	fn on_game_start(&mut self, _rules: &Rules) {}
+
+	// The opponent fired at one of our squares
+	fn on_opponent_shot(&mut self, _shot: (BoardPos, ShotResult)) {}
+
+	// The game is over
+	fn on_game_end(&mut self, _result: &GameResult) {}
+}
+
+// Shots revealing less than this many bits are information traps
+pub const TRAP_BITS: f64 = 0.1;
+
+// The default tolerance for near-ties in the information-gain greedy strategy
+pub const DEFAULT_NEAR_TIE: f64 = 0.05;
+
+// The information a shot at the square reveals, in bits: the entropy of its
+// hit-or-miss outcome
+pub fn information(heatmap: &Heatmap, pos: BoardPos) -> f64 {
+	let p = heatmap.probability(pos);
+	if p <= 0.0 || p >= 1.0 {
+		return 0.0;
+	}
+
+	-(p * p.log2() + (1.0 - p) * (1.0 - p).log2())
+}
+
+// The information a shot at the square is expected to reveal beyond what the
+// pending shots will: the entropy of its outcome once theirs are known,
+// averaged over their outcomes' heatmaps (see GameState::pending_outcomes),
+// each weighted by its share of the configurations. With nothing pending,
+// that's just information.
+pub fn expected_information(outcomes: &[Heatmap], pos: BoardPos) -> f64 {
+	let total: u64 = outcomes.iter().map(|heatmap| heatmap.total).sum();
+	if total == 0 {
+		return 0.0;
+	}
+
+	outcomes.iter().map(|heatmap| heatmap.total as f64 / total as f64 * information(heatmap, pos)).sum()
+}
+
+// Whether a shot at the unshot square would be an information trap: it reveals
+// almost nothing, because nearly every configuration or nearly none covers it
+// (in expectation over the pending shots' outcomes, given as for
+// expected_information). A certain hit is not a trap, since every way of
+// finishing the game shoots it.
+pub fn is_information_trap(heatmap: &Heatmap, outcomes: &[Heatmap], pos: BoardPos) -> bool {
+	heatmap.counts[pos.index()] < heatmap.total && expected_information(outcomes, pos) < TRAP_BITS
+}
+
+// Shoots the unshot square most likely to be occupied
+pub struct Greedy {
+	tie_break: TieBreaker,
+
+	// With information gain: the fraction below the best count within which
+	// squares count as near-ties
+	near_tie: Option<f64>,
+}
+
+impl Greedy {
+	pub fn new(tie_break: TieBreak) -> Greedy {
+		Greedy { tie_break: TieBreaker::new(tie_break), near_tie: None }
+	}
+
+	// Greedy, except that squares whose count is within the given fraction of the
+	// best are treated as tied, and the ties are broken by information gain before
+	// the tie-breaking policy. Information traps are avoided whenever another
+	// near-tie isn't one.
+	pub fn with_information_gain(tie_break: TieBreak, near_tie: f64) -> Greedy {
+		Greedy { tie_break: TieBreaker::new(tie_break), near_tie: Some(near_tie) }
+	}
+}
+
+impl Greedy {
+	// Choose the unshot square with the highest count in the given heatmap
+	fn best_in(&mut self, state: &GameState, heatmap: &Heatmap, cancel: &CancelToken) -> Option<BoardPos> {
+		// Find every unshot square sharing the maximum occupancy count
+		let mut candidates = Vec::new();
+		let mut best_count = 0;
+		for pos in state.board().squares() {
+			if state.is_shot(pos) {
+				continue;
+			}
+
+			let count = heatmap.counts[pos.index()];
+			if candidates.is_empty() || count > best_count {
+				candidates.clear();
+				best_count = count;
+			}
+			if count == best_count {
+				candidates.push(pos);
+			}
+		}
+
+		if candidates.is_empty() {
+			return None;
+		}
+		if let Some(near_tie) = self.near_tie {
+			candidates = most_informative(state, heatmap, best_count, near_tie, cancel);
+		}
+
+		Some(self.tie_break.select(&candidates, state))
+	}
+}
+
+// The near-tied unshot squares (with counts within the given fraction of the
+// best) that reveal the most information, in row-major order, leaving out
+// information traps unless every near-tie is one. The information is expected
+// over the pending shots' outcomes, as a square whose result theirs will give
+// away is worth little.
+fn most_informative(state: &GameState, heatmap: &Heatmap, best_count: u64, near_tie: f64, cancel: &CancelToken) -> Vec<BoardPos> {
+	let outcomes: Vec<Heatmap> = if state.pending().is_empty() {
+		vec![heatmap.clone()]
+	} else {
+		state.pending_outcomes().iter().map(|outcome| outcome.heatmap_best_effort(cancel).0).collect()
+	};
+
+	let threshold = best_count as f64 * (1.0 - near_tie);
+	let near: Vec<BoardPos> = state.board().squares().filter(|&pos| !state.is_shot(pos) && heatmap.counts[pos.index()] as f64 >= threshold).collect();
+	let informative: Vec<BoardPos> = near.iter().cloned().filter(|&pos| !is_information_trap(heatmap, &outcomes, pos)).collect();
+	let pool = if informative.is_empty() { near } else { informative };
+
+	let best = pool.iter().map(|&pos| expected_information(&outcomes, pos)).fold(0.0, f64::max);
+	pool.into_iter().filter(|&pos| expected_information(&outcomes, pos) >= best).collect()
+}
+
+impl Strategy for Greedy {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		let (heatmap, _) = state.heatmap_best_effort(cancel);
+		self.best_in(state, &heatmap, cancel)
+	}
+}
+
+// Pessimistic strategy for opponents who place to exploit probability-greedy
+// AIs: assumes the opponent's fleet can be moved to any configuration still
+// consistent with the observations, and shoots where the worst-case result
+// leaves the fewest consistent configurations. Exact minimax over whole games
+// is far out of reach, so the number of remaining configurations stands in for
+// the number of remaining shots. Squares hit in every configuration are shot
+// first, since every way of finishing the game needs them.
+pub struct Maximin {
+	tie_break: TieBreaker,
+}
+
+impl Maximin {
+	pub fn new(tie_break: TieBreak) -> Maximin {
+		Maximin { tie_break: TieBreaker::new(tie_break) }
+	}
+}
+
+impl Strategy for Maximin {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		let (heatmap, _) = state.heatmap_best_effort(cancel);
+
+		// Score each useful unshot square by (configurations left after the worst
+		// result, configurations left after a hit); lower is better on the first,
+		// higher on the second
+		let mut candidates = Vec::new();
+		let mut best = (0, 0);
+		for pos in state.board().squares() {
+			let count = heatmap.counts[pos.index()];
+			if state.is_shot(pos) || count == 0 {
+				continue;
+			}
+
+			let worst = if count == heatmap.total { 0 } else { count.max(heatmap.total - count) };
+			let better = candidates.is_empty() || worst < best.0 || (worst == best.0 && count > best.1);
+			if better {
+				candidates.clear();
+				best = (worst, count);
+			}
+			if (worst, count) == best {
+				candidates.push(pos);
+			}
+		}
+
+		if candidates.is_empty() {
+			return None;
+		}
+
+		Some(self.tie_break.select(&candidates, state))
+	}
+}
+
+// Shoots a random unshot square, ignoring every result
+pub struct RandomShots {
+	tie_break: TieBreaker,
+}
+
+impl RandomShots {
+	pub fn new(seed: u64) -> RandomShots {
+		RandomShots { tie_break: TieBreaker::new(TieBreak::Random(seed)) }
+	}
+}
+
+impl Strategy for RandomShots {
+	fn choose_shot_cancellable(&mut self, state: &GameState, _cancel: &CancelToken) -> Option<BoardPos> {
+		let unshot: Vec<BoardPos> = state.board().squares().filter(|&pos| !state.is_shot(pos)).collect();
+		if unshot.is_empty() {
+			return None;
+		}
+
+		Some(self.tie_break.select(&unshot, state))
+	}
+
+	fn needs_heatmap(&self) -> bool {
+		false
+	}
+}
+
+// The classic hunt-and-target player, without any inference: while no hit
+// belongs to a ship still afloat, shoots random squares of one checkerboard
+// color (every ship is at least two long, so it covers one); otherwise shoots
+// next to those hits, preferring squares that extend a line of them.
+pub struct ParityHunter {
+	tie_break: TieBreaker,
+}
+
+impl ParityHunter {
+	pub fn new(seed: u64) -> ParityHunter {
+		ParityHunter { tie_break: TieBreaker::new(TieBreak::Random(seed)) }
+	}
+}
+
+// The squares orthogonally adjacent to the given one, paired with the square
+// one step further in the same direction, if that is on the board
+fn neighbors(pos: BoardPos) -> Vec<(BoardPos, Option<BoardPos>)> {
+	[(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+		.filter_map(|&(drow, dcol)| pos.offset(drow, dcol).map(|next| (next, pos.offset(-drow, -dcol))))
+		.collect()
+}
+
+// The hits that may belong to a ship still afloat (every hit whose ship isn't
+// known to be sunk), in the order they were shot
+pub fn open_hits(state: &GameState) -> Vec<BoardPos> {
+	let sunk: Vec<ShipType> = state.shots().iter().filter_map(|&(_, result)| match result {
+		ShotResult::Sunk(stype) => Some(stype),
+		_ => None,
+	}).collect();
+	let afloat = |pos: BoardPos| match state.record_at(pos).and_then(|record| record.ship) {
+		Some(stype) => !sunk.contains(&stype),
+		None => true,
+	};
+	state.shots().iter()
+		.filter(|&&(pos, result)| result != ShotResult::Miss && afloat(pos))
+		.map(|&(pos, _)| pos).collect()
+}
+
+impl Strategy for ParityHunter {
+	fn choose_shot_cancellable(&mut self, state: &GameState, _cancel: &CancelToken) -> Option<BoardPos> {
+		let open_hits = open_hits(state);
+
+		// Target: the unshot squares next to an open hit, and among them those
+		// continuing a line through it
+		let mut targets = Vec::new();
+		let mut lines = Vec::new();
+		for &hit in &open_hits {
+			for (next, behind) in neighbors(hit) {
+				if state.is_shot(next) || targets.contains(&next) {
+					continue;
+				}
+				targets.push(next);
+				if behind.is_some_and(|behind| open_hits.contains(&behind)) {
+					lines.push(next);
+				}
+			}
+		}
+
+		let unshot: Vec<BoardPos> = state.board().squares().filter(|&pos| !state.is_shot(pos)).collect();
+		let hunt: Vec<BoardPos> = unshot.iter().cloned().filter(|pos| (pos.row() + pos.col()).is_multiple_of(2)).collect();
+		let mut candidates = vec![lines, targets, hunt, unshot].into_iter().find(|candidates| !candidates.is_empty())?;
+		candidates.sort();
+		Some(self.tie_break.select(&candidates, state))
+	}
+
+	fn needs_heatmap(&self) -> bool {
+		false
+	}
+}
+
+// The number of squares the lookahead strategy considers
+pub const DEFAULT_BEAM: usize = 5;
+
+// How far below the best square's count the lookahead strategy still considers squares
+const LOOKAHEAD_MARGIN: f64 = 0.1;
+
+// Full inference with a one-shot lookahead: of the few squares nearly as likely
+// as the best, shoots the one maximizing the expected hits from this shot and
+// the best shot after it, over whether it hits or misses. This favors squares
+// whose result sharpens the next shot, which pure greed ignores.
+pub struct Lookahead {
+	// How many of the most likely squares to consider
+	beam: usize,
+
+	// The states after each candidate's results. Their buffers are recycled
+	// from shot to shot, so the search doesn't allocate a state per node.
+	nodes: Arena<GameState>,
+}
+
+impl Lookahead {
+	pub fn new(beam: usize) -> Lookahead {
+		Lookahead { beam, nodes: Arena::new() }
+	}
+}
+
+// The highest probability of any unshot square
+fn best_probability(state: &GameState, heatmap: &Heatmap) -> f64 {
+	state.board().squares().filter(|&pos| !state.is_shot(pos)).map(|pos| heatmap.probability(pos)).fold(0.0, f64::max)
+}
+
+impl Strategy for Lookahead {
+	fn choose_shot_cancellable(&mut self, state: &GameState, cancel: &CancelToken) -> Option<BoardPos> {
+		let (heatmap, _) = state.heatmap_best_effort(cancel);
+		let mut candidates: Vec<BoardPos> = state.board().squares().filter(|&pos| !state.is_shot(pos)).collect();
+		candidates.sort_by_key(|pos| std::cmp::Reverse(heatmap.counts[pos.index()]));
+		candidates.truncate(self.beam.max(1));
+		if let Some(&first) = candidates.first() {
+			let threshold = heatmap.counts[first.index()] as f64 * (1.0 - LOOKAHEAD_MARGIN);
+			candidates.retain(|pos| heatmap.counts[pos.index()] as f64 >= threshold);
+		}
+
+		let mut best = None;
+		let mut best_value = -1.0;
+		for &pos in &candidates {
+			if cancel.is_cancelled() && best.is_some() {
+				break;
+			}
+
+			let p = heatmap.probability(pos);
+			let mut value = p;
+			for &(result, weight) in &[(ShotResult::Hit(None), p), (ShotResult::Miss, 1.0 - p)] {
+				if weight <= 0.0 {
+					continue;
+				}
+				let node = self.nodes.alloc_from(state);
+				let after = self.nodes.get_mut(node).expect("A new node is missing");
+				after.apply_move((pos, result));
+				let (after_heatmap, _) = after.heatmap_best_effort(cancel);
+				value += weight * best_probability(after, &after_heatmap);
+				self.nodes.remove(node);
+			}
+
+			if value > best_value {
+				best = Some(pos);
+				best_value = value;
+			}
+		}
+
+		best
+	}
+}
+
+// How strong an opponent the crate plays when a human is shooting back
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Difficulty {
+	Random,
+	Parity,
+	Inference,
+	Lookahead,
+}
+
+// The names accepted by Difficulty::parse, easiest first
+pub const DIFFICULTY_NAMES: &[&str] = &["random", "parity", "inference", "lookahead"];
+
+impl Difficulty {
+	pub fn parse(desc: &str) -> Option<Difficulty> {
+		match desc {
+			"random" => Some(Difficulty::Random),
+			"parity" => Some(Difficulty::Parity),
+			"inference" => Some(Difficulty::Inference),
+			"lookahead" => Some(Difficulty::Lookahead),
+			_ => None,
+		}
+	}
+
+	// The strategy playing at this level. seed drives any randomness, so the
+	// weaker levels don't repeat themselves from game to game.
+	pub fn strategy(self, seed: u64) -> Box<dyn Strategy> {
+		match self {
+			Difficulty::Random => Box::new(RandomShots::new(seed)),
+			Difficulty::Parity => Box::new(ParityHunter::new(seed)),
+			Difficulty::Inference => Box::new(Greedy::new(TieBreak::Random(seed))),
+			Difficulty::Lookahead => Box::new(Lookahead::new(DEFAULT_BEAM)),
+		}
+	}
+}
+
+// Look up a strategy by name like by_name, also accepting "script:<file>" for a
+// scripted strategy (see the script module) in builds with scripting, and
+// combinations of strategies such as "fallback(script:a.txt, greedy)" or
+// "compose:<file>" for one read from a file (see the combine module)
+pub fn load(name: &str, seed: u64) -> Result<Box<dyn Strategy>, String> {
+	if name.contains('(') {
+		return combine::parse(name, seed);
+	}
+	if let Some(path) = name.strip_prefix("compose:") {
+		return combine::load(path, seed);
+	}
+
+	#[cfg(feature = "scripting")]
+	{
+		if let Some(path) = name.strip_prefix("script:") {
+			return Ok(Box::new(script::Scripted::new(script::Script::load(path)?, TieBreak::Lexicographic)));
+		}
+	}
+
+	by_name(name, seed).ok_or_else(|| format!("Unknown strategy {}", name))
+}
+
+// The names of every registered strategy, for by_name (the sampling one only
+// in builds with the "montecarlo" feature)
+#[cfg(feature = "montecarlo")]
+pub const STRATEGY_NAMES: &[&str] = &["greedy", "greedy-center", "greedy-farthest", "greedy-random", "greedy-info", "maximin", "random", "parity", "lookahead", "montecarlo"];
+#[cfg(not(feature = "montecarlo"))]
+pub const STRATEGY_NAMES: &[&str] = &["greedy", "greedy-center", "greedy-farthest", "greedy-random", "greedy-info", "maximin", "random", "parity", "lookahead"];
+
+// Construct a registered strategy by name. seed drives any randomness the
+// strategy uses.
+pub fn by_name(name: &str, seed: u64) -> Option<Box<dyn Strategy>> {
+	match name {
+		"greedy" => Some(Box::new(Greedy::new(TieBreak::Lexicographic))),
+		"greedy-center" => Some(Box::new(Greedy::new(TieBreak::CenterMost))),
+		"greedy-farthest" => Some(Box::new(Greedy::new(TieBreak::FarthestFromShots))),
+		"greedy-random" => Some(Box::new(Greedy::new(TieBreak::Random(seed)))),
+		"greedy-info" => Some(Box::new(Greedy::with_information_gain(TieBreak::Lexicographic, DEFAULT_NEAR_TIE))),
+		"maximin" => Some(Box::new(Maximin::new(TieBreak::Lexicographic))),
+		"random" => Some(Box::new(RandomShots::new(seed))),
+		"parity" => Some(Box::new(ParityHunter::new(seed))),
+		"lookahead" => Some(Box::new(Lookahead::new(DEFAULT_BEAM))),
+		#[cfg(feature = "montecarlo")]
+		"montecarlo" => Some(Box::new(montecarlo::MonteCarlo::new(montecarlo::DEFAULT_MAX_SAMPLES, seed))),
+		_ => None,
+	}
+}
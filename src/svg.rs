@@ -0,0 +1,102 @@
+// SVG pictures of the AI's beliefs through a game: one frame per move, each
+// showing the heatmap, the shots so far, and the greedy strategy's next shot.
+// The frames can be written as separate files, or combined into one animated
+// SVG that steps through them.
+
+use super::{encode_shiptype, format_move, Board, BoardPos, GameState, ShotResult};
+use rules::Rules;
+use strategy::{Greedy, Strategy, TieBreak};
+
+// The side length of a square, in pixels
+const CELL: usize = 60;
+
+// The height of the caption above the board, in pixels
+const CAPTION: usize = 30;
+
+// The width and height of a frame of the board, in pixels
+fn dimensions(board: &Board) -> (usize, usize) {
+	(CELL * board.size() as usize, CAPTION + CELL * board.size() as usize)
+}
+
+// The elements drawing one frame: the state's heatmap and shots under a caption
+fn frame_elements(state: &GameState, caption: &str) -> String {
+	let heatmap = state.heatmap();
+	let best = Greedy::new(TieBreak::Lexicographic).choose_shot(state);
+	let (width, height) = dimensions(state.board());
+	let mut out = format!("<rect width=\"{}\" height=\"{}\" fill=\"#fff\"/>\n", width, height);
+	out += &format!("<text x=\"{}\" y=\"{}\" font-size=\"18\" text-anchor=\"middle\">{}</text>\n", width / 2, CAPTION * 2 / 3, caption);
+
+	for pos in state.board().squares() {
+		let (x, y) = (pos.col() as usize * CELL, CAPTION + pos.row() as usize * CELL);
+		let shot = state.shots().iter().find(|&&(shot, _)| shot == pos).map(|&(_, result)| result);
+		let (fill, label) = match shot {
+			Some(ShotResult::Miss) => (String::from("#9aa5b1"), String::from("o")),
+			Some(ShotResult::Hit(stype)) => (String::from("#c0392b"), stype.map_or('X', encode_shiptype).to_string()),
+			Some(ShotResult::Sunk(stype)) => (String::from("#7b241c"), encode_shiptype(stype).to_string()),
+			None => {
+				let probability = heatmap.probability(pos);
+				(format!("rgba(230,126,34,{:.3})", probability), format!("{:.0}%", probability * 100.0))
+			},
+		};
+
+		out += &format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#34495e\"/>\n", x, y, CELL, CELL, fill);
+		out += &format!("<text x=\"{}\" y=\"{}\" font-size=\"16\" text-anchor=\"middle\" dominant-baseline=\"middle\">{}</text>\n",
+		                x + CELL / 2, y + CELL / 2, label);
+	}
+
+	if let Some(pos) = best {
+		out += &format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#27ae60\" stroke-width=\"4\"/>\n",
+		                pos.col() as usize * CELL + 2, CAPTION + pos.row() as usize * CELL + 2, CELL - 4, CELL - 4);
+	}
+	out
+}
+
+fn document(board: &Board, body: &str) -> String {
+	let (width, height) = dimensions(board);
+	format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\">\n{}</svg>\n",
+	        width, height, width, height, body)
+}
+
+// The states and captions of each frame: the opening, then the state after each move
+fn frames(rules: &Rules, moves: &[(BoardPos, ShotResult)]) -> Vec<(GameState, String)> {
+	let mut state = rules.new_cached_game();
+	let mut frames = vec![(state.clone(), String::from("Opening"))];
+	for (turn, &cur_move) in moves.iter().enumerate() {
+		state.apply_move(cur_move);
+		frames.push((state.clone(), format!("Move {}: {}", turn + 1, format_move(cur_move))));
+	}
+
+	frames
+}
+
+// One standalone SVG per frame, for a game under the given rules
+pub fn frame_svgs(rules: &Rules, moves: &[(BoardPos, ShotResult)]) -> Vec<String> {
+	frames(rules, moves).iter().map(|(state, caption)| document(state.board(), &frame_elements(state, caption))).collect()
+}
+
+// A single SVG showing each frame for the given number of seconds in turn, looping
+pub fn animated_svg(rules: &Rules, moves: &[(BoardPos, ShotResult)], seconds_per_frame: f64) -> String {
+	let frames = frames(rules, moves);
+	let count = frames.len();
+	let mut body = String::new();
+	for (idx, (state, caption)) in frames.iter().enumerate() {
+		// When this frame appears and disappears, as fractions of the whole loop
+		let mut keys = Vec::new();
+		if idx > 0 {
+			keys.push((0.0, "hidden"));
+		}
+		keys.push((idx as f64 / count as f64, "visible"));
+		if idx + 1 < count {
+			keys.push(((idx + 1) as f64 / count as f64, "hidden"));
+		}
+		let times: Vec<String> = keys.iter().map(|&(time, _)| format!("{:.4}", time)).collect();
+		let values: Vec<&str> = keys.iter().map(|&(_, value)| value).collect();
+
+		body += &format!("<g visibility=\"hidden\">\n<animate attributeName=\"visibility\" calcMode=\"discrete\" dur=\"{:.2}s\" repeatCount=\"indefinite\" keyTimes=\"{}\" values=\"{}\"/>\n",
+		                 seconds_per_frame * count as f64, times.join(";"), values.join(";"));
+		body += &frame_elements(state, caption);
+		body += "</g>\n";
+	}
+
+	document(frames[0].0.board(), &body)
+}
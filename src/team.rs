@@ -0,0 +1,198 @@
+// Team play: several attackers (AI instances or humans) taking turns against
+// the same opponent grid, sharing one board through a file.
+//
+// The shared file holds one recorded shot per line, "<move> <party>", in the
+// moves file format. Each party syncs by merging its own board with the file's
+// and writing the union back, so shots recorded by either party survive in any
+// order. A shot recorded twice with compatible results (e.g. "C2H" and "C2D!")
+// merges into the more specific one; incompatible results are reported as
+// conflicts and the first recording is kept.
+
+use super::{format_move, parse_move, Board, BoardPos, GameState, Orientation, ShotResult};
+use durable;
+use std::path::Path;
+use text;
+use std::time::Duration;
+
+// A single shot, and who recorded it
+#[derive(Clone,Debug,PartialEq)]
+pub struct Entry {
+	pub pos: BoardPos,
+	pub result: ShotResult,
+	pub party: String,
+}
+
+// Two recordings of the same square that can't both be true
+#[derive(Clone,Debug,PartialEq)]
+pub struct Conflict {
+	pub kept: Entry,
+	pub rejected: Entry,
+}
+
+impl Conflict {
+	pub fn describe(&self) -> String {
+		format!("{} recorded {} but {} recorded {}; keeping {}'s result", self.kept.party, format_move((self.kept.pos, self.kept.result)),
+		        self.rejected.party, format_move((self.rejected.pos, self.rejected.result)), self.kept.party)
+	}
+}
+
+// Merge two results for the same square, or None if they are incompatible.
+// Labeling a hit, or announcing that it sank its ship, refines it.
+fn merge_results(a: ShotResult, b: ShotResult) -> Option<ShotResult> {
+	match (a, b) {
+		(ShotResult::Hit(None), hit) | (hit, ShotResult::Hit(None)) if hit != ShotResult::Miss => Some(hit),
+		(ShotResult::Hit(Some(hit)), ShotResult::Sunk(sunk)) | (ShotResult::Sunk(sunk), ShotResult::Hit(Some(hit))) if hit == sunk => {
+			Some(ShotResult::Sunk(sunk))
+		},
+		_ if a == b => Some(a),
+		_ => None,
+	}
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct SharedBoard {
+	// The opponent grid being shot at
+	board: Board,
+
+	// At most one entry per square, in the order the squares were first recorded
+	entries: Vec<Entry>,
+}
+
+impl SharedBoard {
+	pub fn new(board: &Board) -> SharedBoard {
+		SharedBoard { board: board.clone(), entries: Vec::new() }
+	}
+
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	// Record a shot, merging it with any earlier recording of the same square
+	pub fn record(&mut self, entry: Entry) -> Result<(), Conflict> {
+		match self.entries.iter_mut().find(|existing| existing.pos == entry.pos) {
+			None => {
+				self.entries.push(entry);
+				Ok(())
+			},
+			Some(existing) => match merge_results(existing.result, entry.result) {
+				Some(result) => {
+					if result != existing.result {
+						*existing = Entry { result, ..entry };
+					}
+					Ok(())
+				},
+				None => Err(Conflict { kept: existing.clone(), rejected: entry }),
+			},
+		}
+	}
+
+	// Merge another board's entries into this one, returning any conflicts
+	pub fn merge(&mut self, other: &SharedBoard) -> Vec<Conflict> {
+		other.entries.iter().filter_map(|entry| self.record(entry.clone()).err()).collect()
+	}
+
+	// The game state described by the shared shots, for ships with the given orientations
+	pub fn state(&self, orientations: &[Orientation]) -> GameState {
+		let mut state = GameState::new_cached_restricted(&self.board, orientations);
+		for entry in &self.entries {
+			state.apply_move((entry.pos, entry.result));
+		}
+
+		state
+	}
+
+	// The number of hits recorded so far
+	pub fn hits(&self) -> usize {
+		self.entries.iter().filter(|entry| entry.result != ShotResult::Miss).count()
+	}
+
+	// Whose turn it is, as an index into the seating order: shots alternate
+	// between the seats, starting with seat 0
+	pub fn turn(&self, seats: usize) -> usize {
+		self.entries.len() % seats
+	}
+
+	pub fn to_file_string(&self) -> String {
+		let mut out = String::from("# battleship_ai shared board\n");
+		for entry in &self.entries {
+			out.push_str(&format!("{} {}\n", format_move((entry.pos, entry.result)), entry.party));
+		}
+
+		out
+	}
+
+	// Parse a shared board file for shots at the given board. Conflicting lines
+	// (which an external editor may have introduced) are reported alongside it.
+	pub fn from_file_string(grid: &Board, contents: &str) -> Result<(SharedBoard, Vec<Conflict>), String> {
+		let mut board = SharedBoard::new(grid);
+		let mut conflicts = Vec::new();
+		for (line_num, line) in text::lines(contents).enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let mut parts = line.split_whitespace();
+			let (pos, result) = parse_move(grid, parts.next().unwrap_or("")).map_err(|e| format!("line {}: {}", line_num + 1, e))?;
+			let party = parts.next().ok_or_else(|| format!("line {}: missing the recording party", line_num + 1))?.to_string();
+			if let Err(conflict) = board.record(Entry { pos, result, party }) {
+				conflicts.push(conflict);
+			}
+		}
+
+		Ok((board, conflicts))
+	}
+
+	// Merge this board with the shared file at path (creating it if needed),
+	// leaving both holding the union. Returns the conflicts between them.
+	pub fn sync(&mut self, path: &Path) -> Result<Vec<Conflict>, String> {
+		let _lock = FileLock::acquire(path)?;
+		let mut conflicts = Vec::new();
+		let existing = match text::read(path) {
+			Ok(contents) => Some(contents),
+			Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => None,
+			Err(e) => return Err(format!("Unable to read {}: {}", path.display(), e)),
+		};
+		if let Some(contents) = existing {
+			let (theirs, file_conflicts) = SharedBoard::from_file_string(&self.board, &contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+			conflicts = file_conflicts;
+
+			// The file's entries go first, so every party agrees on the order
+			let mut merged = theirs;
+			conflicts.extend(merged.merge(self));
+			*self = merged;
+		}
+
+		durable::write_unsealed(path, self.to_file_string().as_bytes())
+			.map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+		Ok(conflicts)
+	}
+}
+
+// An exclusive lock on a shared file, held by creating "<file>.lock"
+struct FileLock {
+	path: std::ffi::OsString,
+}
+
+impl FileLock {
+	// Wait for the lock, giving up after a few seconds in case a crashed party left it behind
+	fn acquire(path: &Path) -> Result<FileLock, String> {
+		let mut lock_path = path.as_os_str().to_owned();
+		lock_path.push(".lock");
+		for _ in 0..100 {
+			match std::fs::OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+				Ok(_) => return Ok(FileLock { path: lock_path }),
+				Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => std::thread::sleep(Duration::from_millis(50)),
+				Err(e) => return Err(format!("Unable to lock {}: {}", path.display(), e)),
+			}
+		}
+
+		Err(format!("{} is locked; delete {} if no other party is running", path.display(), lock_path.to_string_lossy()))
+	}
+}
+
+impl Drop for FileLock {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_file(&self.path);
+	}
+}
@@ -0,0 +1,133 @@
+// Text files as editors save them. A moves file or transcript edited on
+// Windows may start with a byte order mark, be saved as UTF-16, end its lines
+// with CRLF (or a lone CR, from old Mac editors), and carry stray whitespace.
+// decode turns the bytes of any of these into plain text, and lines splits
+// text on any line ending, so every parser sees the same lines whichever
+// editor wrote the file.
+
+use std::path::Path;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const UTF16_LE_BOM: &[u8] = b"\xff\xfe";
+const UTF16_BE_BOM: &[u8] = b"\xfe\xff";
+
+// Decode a file's bytes: UTF-8 with or without a byte order mark, or UTF-16
+// in either byte order with one
+pub fn decode(bytes: &[u8]) -> Result<String, String> {
+	if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+		return String::from_utf8(rest.to_vec()).map_err(|e| format!("invalid UTF-8 at byte {}", e.utf8_error().valid_up_to() + UTF8_BOM.len()));
+	}
+	let utf16 = match (bytes.strip_prefix(UTF16_LE_BOM), bytes.strip_prefix(UTF16_BE_BOM)) {
+		(Some(rest), _) => Some((rest, u16::from_le_bytes as fn([u8; 2]) -> u16)),
+		(_, Some(rest)) => Some((rest, u16::from_be_bytes as fn([u8; 2]) -> u16)),
+		_ => None,
+	};
+	if let Some((rest, unit)) = utf16 {
+		if !rest.len().is_multiple_of(2) {
+			return Err(String::from("UTF-16 text with an odd number of bytes"));
+		}
+		let units = rest.chunks(2).map(|pair| unit([pair[0], pair[1]]));
+		return std::char::decode_utf16(units).collect::<Result<String, _>>().map_err(|e| format!("invalid UTF-16: unpaired surrogate {:04x}", e.unpaired_surrogate()));
+	}
+
+	String::from_utf8(bytes.to_vec()).map_err(|e| format!("invalid UTF-8 at byte {} (text must be UTF-8 or UTF-16)", e.utf8_error().valid_up_to()))
+}
+
+// Read and decode a text file
+pub fn read(path: &Path) -> std::io::Result<String> {
+	let bytes = std::fs::read(path)?;
+	decode(&bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+// The lines of the text, ended by LF, CRLF, or a lone CR, without their line
+// endings or a leading byte order mark. Unlike str::lines, a lone CR ends a
+// line, and a final line ending doesn't leave an empty line after it.
+pub fn lines(text: &str) -> Lines<'_> {
+	Lines { rest: text.strip_prefix('\u{feff}').unwrap_or(text) }
+}
+
+pub struct Lines<'a> {
+	rest: &'a str,
+}
+
+impl<'a> Iterator for Lines<'a> {
+	type Item = &'a str;
+
+	fn next(&mut self) -> Option<&'a str> {
+		if self.rest.is_empty() {
+			return None;
+		}
+
+		let (line, rest) = match self.rest.find(['\r', '\n']) {
+			Some(end) => {
+				let ending = if self.rest[end..].starts_with("\r\n") { 2 } else { 1 };
+				(&self.rest[..end], &self.rest[end + ending..])
+			},
+			None => (self.rest, ""),
+		};
+		self.rest = rest;
+		Some(line)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+		let mut bytes = if big_endian { UTF16_BE_BOM.to_vec() } else { UTF16_LE_BOM.to_vec() };
+		for unit in text.encode_utf16() {
+			bytes.extend_from_slice(&if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() });
+		}
+		bytes
+	}
+
+	#[test]
+	fn decodes_utf8_with_and_without_a_bom() {
+		assert_eq!(decode(b"A1 M\n"), Ok(String::from("A1 M\n")));
+		assert_eq!(decode(b"\xef\xbb\xbfA1 M\n"), Ok(String::from("A1 M\n")));
+		assert_eq!(decode(b"\xef\xbb\xbf"), Ok(String::new()));
+	}
+
+	#[test]
+	fn decodes_utf16_in_either_byte_order() {
+		assert_eq!(decode(&utf16("A1 M\r\nB2 H\r\n", false)), Ok(String::from("A1 M\r\nB2 H\r\n")));
+		assert_eq!(decode(&utf16("A1 M\r\nB2 H\r\n", true)), Ok(String::from("A1 M\r\nB2 H\r\n")));
+	}
+
+	#[test]
+	fn rejects_malformed_text() {
+		assert_eq!(decode(b"\xff\xfeA"), Err(String::from("UTF-16 text with an odd number of bytes")));
+		assert!(decode(b"\xff\xfe\x00\xd8").unwrap_err().contains("unpaired surrogate d800"));
+		assert!(decode(b"A1 \xc3").unwrap_err().contains("invalid UTF-8 at byte 3"));
+		assert!(decode(b"\xef\xbb\xbfA1 \xc3").unwrap_err().contains("invalid UTF-8 at byte 6"));
+	}
+
+	#[test]
+	fn splits_crlf_lines() {
+		assert_eq!(lines("A1 M\r\nB2 H\r\n").collect::<Vec<_>>(), ["A1 M", "B2 H"]);
+		assert_eq!(lines("A1 M\r\n\r\nB2 H").collect::<Vec<_>>(), ["A1 M", "", "B2 H"]);
+	}
+
+	#[test]
+	fn splits_lone_cr_lines() {
+		assert_eq!(lines("A1 M\rB2 H\r").collect::<Vec<_>>(), ["A1 M", "B2 H"]);
+		assert_eq!(lines("A1 M\rB2 H\nC3 S\r\n").collect::<Vec<_>>(), ["A1 M", "B2 H", "C3 S"]);
+		assert_eq!(lines("\r\r").collect::<Vec<_>>(), ["", ""]);
+	}
+
+	#[test]
+	fn drops_a_decoded_bom() {
+		assert_eq!(lines("\u{feff}A1 M\n").collect::<Vec<_>>(), ["A1 M"]);
+	}
+
+	// Whitespace is left for the parsers to trim, so blank lines still count
+	// towards line numbers
+	#[test]
+	fn keeps_whitespace() {
+		assert_eq!(lines("  A1 M\t\r\n \t \r\nB2 H ").collect::<Vec<_>>(), ["  A1 M\t", " \t ", "B2 H "]);
+		assert_eq!(lines(" \t").collect::<Vec<_>>(), [" \t"]);
+		assert_eq!(lines("").count(), 0);
+		assert_eq!(decode(&utf16(" \t\r\n", false)).map(|text| lines(&text).map(String::from).collect::<Vec<_>>()), Ok(vec![String::from(" \t")]));
+	}
+}
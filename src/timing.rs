@@ -0,0 +1,163 @@
+// Timing of the hot paths, in builds with the "profiling" feature: how long
+// each move spends pruning placements, counting configurations, sampling
+// layouts, and selecting a shot. Each phase's time excludes the phases nested
+// inside it (a greedy selection's heatmap count is counting, not selection),
+// so the phases of a move add up to the time measured.
+//
+// Times gather per thread until end_move files them under the move's number
+// in the game, and end_game starts the numbering over, so the report shows
+// the average per move number over every game played. Without the feature
+// every function here does nothing, and time just runs its closure.
+
+#[cfg(feature = "profiling")]
+use std::cell::RefCell;
+#[cfg(feature = "profiling")]
+use std::sync::Mutex;
+#[cfg(feature = "profiling")]
+use std::time::Instant;
+
+// Whether this build records timings
+pub const ENABLED: bool = cfg!(feature = "profiling");
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Phase {
+	// Applying results to the placement sets (see GameState::apply_move)
+	Pruning,
+
+	// Enumerating configurations for a heatmap
+	Counting,
+
+	// Drawing random layouts (see the forecast and montecarlo modules)
+	Sampling,
+
+	// A strategy choosing its shot, outside the phases above
+	Selection,
+}
+
+pub const PHASES: [Phase; 4] = [Phase::Pruning, Phase::Counting, Phase::Sampling, Phase::Selection];
+
+impl Phase {
+	pub fn name(self) -> &'static str {
+		match self {
+			Phase::Pruning => "pruning",
+			Phase::Counting => "counting",
+			Phase::Sampling => "sampling",
+			Phase::Selection => "selection",
+		}
+	}
+
+	#[cfg(feature = "profiling")]
+	fn index(self) -> usize {
+		PHASES.iter().position(|&phase| phase == self).expect("Unlisted phase")
+	}
+}
+
+// Each move number's total nanoseconds per phase, and how many moves of that
+// number were filed
+#[cfg(feature = "profiling")]
+static MOVES: Mutex<Vec<([u64; 4], u64)>> = Mutex::new(Vec::new());
+
+#[cfg(feature = "profiling")]
+struct Current {
+	// The nanoseconds per phase since the move started
+	nanos: [u64; 4],
+
+	// The phases running, innermost last, with when each started and how long
+	// the phases nested in it have taken
+	running: Vec<(Phase, Instant, u64)>,
+
+	// The move number within the game
+	move_num: usize,
+}
+
+#[cfg(feature = "profiling")]
+thread_local! {
+	static CURRENT: RefCell<Current> = const { RefCell::new(Current { nanos: [0; 4], running: Vec::new(), move_num: 0 }) };
+}
+
+// Run f, counting the time it takes towards the phase
+#[cfg(feature = "profiling")]
+pub fn time<T, F: FnOnce() -> T>(phase: Phase, f: F) -> T {
+	CURRENT.with(|current| current.borrow_mut().running.push((phase, Instant::now(), 0)));
+	let result = f();
+	CURRENT.with(|current| {
+		let mut current = current.borrow_mut();
+		let (phase, start, nested) = current.running.pop().expect("A timed phase ended twice");
+		let elapsed = start.elapsed().as_nanos() as u64;
+		current.nanos[phase.index()] += elapsed.saturating_sub(nested);
+		if let Some(outer) = current.running.last_mut() {
+			outer.2 += elapsed;
+		}
+	});
+	result
+}
+
+#[cfg(not(feature = "profiling"))]
+pub fn time<T, F: FnOnce() -> T>(_phase: Phase, f: F) -> T {
+	f()
+}
+
+// File the time since the last move under this move's number
+pub fn end_move() {
+	#[cfg(feature = "profiling")]
+	CURRENT.with(|current| {
+		let mut current = current.borrow_mut();
+		let mut moves = MOVES.lock().unwrap_or_else(|e| e.into_inner());
+		if moves.len() <= current.move_num {
+			moves.resize(current.move_num + 1, ([0; 4], 0));
+		}
+		let row = &mut moves[current.move_num];
+		for (total, &nanos) in row.0.iter_mut().zip(current.nanos.iter()) {
+			*total += nanos;
+		}
+		row.1 += 1;
+		current.nanos = [0; 4];
+		current.move_num += 1;
+	});
+}
+
+// Start numbering moves over for a new game, dropping any time not yet filed
+pub fn end_game() {
+	#[cfg(feature = "profiling")]
+	CURRENT.with(|current| {
+		let mut current = current.borrow_mut();
+		current.nanos = [0; 4];
+		current.move_num = 0;
+	});
+}
+
+// The average microseconds per phase for each move number, and the total
+// milliseconds over every move, or nothing if no move was filed
+pub fn report() -> String {
+	#[cfg(feature = "profiling")]
+	{
+		let moves = MOVES.lock().unwrap_or_else(|e| e.into_inner());
+		if moves.is_empty() {
+			return String::new();
+		}
+
+		let mut out = String::from("move");
+		for phase in &PHASES {
+			out += &format!(" {:>10}", phase.name());
+		}
+		out += "  (average µs)\n";
+		let mut totals = [0u64; 4];
+		for (move_num, &(nanos, count)) in moves.iter().enumerate().filter(|&(_, &(_, count))| count > 0) {
+			out += &format!("{:>4}", move_num + 1);
+			for (total, &phase_nanos) in totals.iter_mut().zip(nanos.iter()) {
+				*total += phase_nanos;
+				out += &format!(" {:>10.1}", phase_nanos as f64 / count as f64 / 1e3);
+			}
+			out.push('\n');
+		}
+		out += "total";
+		for &total in &totals {
+			out += &format!("{:>10.1}", total as f64 / 1e6);
+		}
+		out += "  (ms)\n";
+		out
+	}
+
+	#[cfg(not(feature = "profiling"))]
+	String::new()
+}
@@ -0,0 +1,156 @@
+// The connections the line protocols run over: TCP, or a Unix domain socket
+// for processes on the same machine, which needs no network access. An
+// address is "host:port" for TCP or "unix:<path>" for a socket file.
+//
+// Windows named pipes aren't in the standard library, so there a "unix:"
+// address is an error.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+// The prefix marking an address as a Unix domain socket's path
+pub const UNIX_PREFIX: &str = "unix:";
+
+pub enum Stream {
+	Tcp(TcpStream),
+	#[cfg(unix)]
+	Unix(UnixStream),
+}
+
+#[cfg(not(unix))]
+fn no_unix_sockets() -> String {
+	String::from("Unix domain sockets are only available on Unix systems")
+}
+
+impl Stream {
+	pub fn connect(addr: &str) -> Result<Stream, String> {
+		let unable = |e: io::Error| format!("Unable to connect to {}: {}", addr, e);
+		match addr.strip_prefix(UNIX_PREFIX) {
+			#[cfg(unix)]
+			Some(path) => UnixStream::connect(path).map(Stream::Unix).map_err(unable),
+			#[cfg(not(unix))]
+			Some(_) => Err(no_unix_sockets()),
+			None => TcpStream::connect(addr).map(Stream::Tcp).map_err(unable),
+		}
+	}
+
+	pub fn try_clone(&self) -> io::Result<Stream> {
+		match *self {
+			Stream::Tcp(ref stream) => stream.try_clone().map(Stream::Tcp),
+			#[cfg(unix)]
+			Stream::Unix(ref stream) => stream.try_clone().map(Stream::Unix),
+		}
+	}
+
+	pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+		match *self {
+			Stream::Tcp(ref stream) => stream.set_read_timeout(timeout),
+			#[cfg(unix)]
+			Stream::Unix(ref stream) => stream.set_read_timeout(timeout),
+		}
+	}
+
+	pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+		match *self {
+			Stream::Tcp(ref stream) => stream.set_nonblocking(nonblocking),
+			#[cfg(unix)]
+			Stream::Unix(ref stream) => stream.set_nonblocking(nonblocking),
+		}
+	}
+}
+
+impl Read for Stream {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match *self {
+			Stream::Tcp(ref mut stream) => stream.read(buf),
+			#[cfg(unix)]
+			Stream::Unix(ref mut stream) => stream.read(buf),
+		}
+	}
+}
+
+impl Write for Stream {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match *self {
+			Stream::Tcp(ref mut stream) => stream.write(buf),
+			#[cfg(unix)]
+			Stream::Unix(ref mut stream) => stream.write(buf),
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		match *self {
+			Stream::Tcp(ref mut stream) => stream.flush(),
+			#[cfg(unix)]
+			Stream::Unix(ref mut stream) => stream.flush(),
+		}
+	}
+}
+
+// A Unix domain socket's file is removed when its listener is dropped
+pub enum Listener {
+	Tcp(TcpListener),
+	#[cfg(unix)]
+	Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+	// Listen on the address. A socket file left behind at a Unix address is
+	// replaced, but any other file there is left alone.
+	pub fn bind(addr: &str) -> Result<Listener, String> {
+		let unable = |e: io::Error| format!("Unable to listen on {}: {}", addr, e);
+		match addr.strip_prefix(UNIX_PREFIX) {
+			#[cfg(unix)]
+			Some(path) => {
+				use std::os::unix::fs::FileTypeExt;
+				if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+					std::fs::remove_file(path).map_err(unable)?;
+				}
+				UnixListener::bind(path).map(|listener| Listener::Unix(listener, PathBuf::from(path))).map_err(unable)
+			},
+			#[cfg(not(unix))]
+			Some(_) => Err(no_unix_sockets()),
+			None => TcpListener::bind(addr).map(Listener::Tcp).map_err(unable),
+		}
+	}
+
+	// The address clients connect to, in the form Stream::connect takes
+	pub fn local_addr(&self) -> Result<String, String> {
+		match *self {
+			Listener::Tcp(ref listener) => listener.local_addr().map(|addr| addr.to_string()).map_err(|e| e.to_string()),
+			#[cfg(unix)]
+			Listener::Unix(_, ref path) => Ok(format!("{}{}", UNIX_PREFIX, path.display())),
+		}
+	}
+
+	pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+		match *self {
+			Listener::Tcp(ref listener) => listener.set_nonblocking(nonblocking),
+			#[cfg(unix)]
+			Listener::Unix(ref listener, _) => listener.set_nonblocking(nonblocking),
+		}
+	}
+
+	// Accept a connection, with a description of where it came from
+	pub fn accept(&self) -> io::Result<(Stream, String)> {
+		match *self {
+			Listener::Tcp(ref listener) => listener.accept().map(|(stream, addr)| (Stream::Tcp(stream), addr.to_string())),
+			#[cfg(unix)]
+			Listener::Unix(ref listener, ref path) => listener.accept().map(|(stream, _)| (Stream::Unix(stream), format!("on {}", path.display()))),
+		}
+	}
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+	fn drop(&mut self) {
+		if let Listener::Unix(_, ref path) = *self {
+			let _ = std::fs::remove_file(path);
+		}
+	}
+}
@@ -0,0 +1,11 @@
+#!/bin/sh
+# Build the browser demo into wasm-demo/pkg: the WebAssembly module, its JS
+# glue, and index.html. Needs the wasm32-unknown-unknown target and a
+# wasm-bindgen-cli matching the wasm-bindgen version in Cargo.lock.
+set -e
+cd "$(dirname "$0")"
+cargo build --release --target wasm32-unknown-unknown
+wasm-bindgen --target web --no-typescript --out-dir pkg \
+	target/wasm32-unknown-unknown/release/battleship_ai_wasm_demo.wasm
+cp index.html pkg/
+echo "Serve wasm-demo/pkg over HTTP (e.g. python3 -m http.server -d wasm-demo/pkg) and open index.html"
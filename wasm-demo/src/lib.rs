@@ -0,0 +1,134 @@
+// Browser demo: the AI's heatmap drawn on a canvas over a hidden random fleet.
+// Clicking a square fires at it, and the heatmap and recommended shot update
+// after every result. The page needs no JS beyond the generated glue; see
+// README.md for building it.
+
+use battleship_ai::placement::{Constraints, Layout};
+use battleship_ai::rng::Rng;
+use battleship_ai::strategy::{Greedy, Strategy, TieBreak};
+use battleship_ai::{encode_shiptype, Board, BoardPos, GameState, ShotResult, SHIP_TYPES};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, Element, HtmlCanvasElement, MouseEvent};
+
+// The side length of a square on the canvas, in pixels
+const CELL: f64 = 80.0;
+
+// The canvas and status line, created inside <body> if the page lacks them
+const CANVAS_ID: &str = "battleship-board";
+const STATUS_ID: &str = "battleship-status";
+
+struct Demo {
+	layout: Layout,
+	state: GameState,
+	strategy: Greedy,
+	context: CanvasRenderingContext2d,
+	status: Element,
+}
+
+impl Demo {
+	fn hits(&self) -> usize {
+		self.state.shots().iter().filter(|&&(_, result)| result != ShotResult::Miss).count()
+	}
+
+	fn is_won(&self) -> bool {
+		self.hits() as u64 == self.state.board().fleet_squares()
+	}
+
+	fn fire(&mut self, pos: BoardPos) {
+		if self.state.is_shot(pos) || self.is_won() {
+			return;
+		}
+
+		self.state.apply_move((pos, self.layout.shot_result(pos)));
+		self.draw();
+	}
+
+	fn draw(&mut self) {
+		let heatmap = self.state.heatmap();
+		let best = if self.is_won() { None } else { self.strategy.choose_shot(&self.state) };
+		let ctx = &self.context;
+		ctx.set_font("20px sans-serif");
+		ctx.set_text_align("center");
+		ctx.set_text_baseline("middle");
+
+		for pos in self.state.board().squares() {
+			let (x, y) = (pos.col() as f64 * CELL, pos.row() as f64 * CELL);
+			let shot = self.state.shots().iter().find(|&&(shot, _)| shot == pos).map(|&(_, result)| result);
+			let (fill, label) = match shot {
+				Some(ShotResult::Miss) => (String::from("#9aa5b1"), String::from("o")),
+				Some(ShotResult::Hit(stype)) => (String::from("#c0392b"), stype.map_or('X', encode_shiptype).to_string()),
+				Some(ShotResult::Sunk(stype)) => (String::from("#7b241c"), encode_shiptype(stype).to_string()),
+				None => {
+					let probability = heatmap.probability(pos);
+					(format!("rgba(230, 126, 34, {:.3})", probability), format!("{:.0}%", probability * 100.0))
+				},
+			};
+
+			ctx.set_fill_style_str(&fill);
+			ctx.fill_rect(x, y, CELL, CELL);
+			ctx.set_stroke_style_str("#34495e");
+			ctx.set_line_width(1.0);
+			ctx.stroke_rect(x, y, CELL, CELL);
+			ctx.set_fill_style_str("#000");
+			let _ = ctx.fill_text(&label, x + CELL / 2.0, y + CELL / 2.0);
+		}
+
+		if let Some(pos) = best {
+			ctx.set_stroke_style_str("#27ae60");
+			ctx.set_line_width(4.0);
+			ctx.stroke_rect(pos.col() as f64 * CELL + 2.0, pos.row() as f64 * CELL + 2.0, CELL - 4.0, CELL - 4.0);
+		}
+
+		let shots = self.state.shots().len();
+		self.status.set_text_content(Some(&match best {
+			Some(pos) => format!("{} shots so far. Best shot: {} (outlined)", shots, pos),
+			None => format!("Fleet sunk in {} shots. Reload for a new game.", shots),
+		}));
+	}
+}
+
+// Find the element with the given ID, or append a new one to <body>
+fn element(document: &Document, id: &str, tag: &str) -> Result<Element, JsValue> {
+	if let Some(existing) = document.get_element_by_id(id) {
+		return Ok(existing);
+	}
+
+	let created = document.create_element(tag)?;
+	created.set_id(id);
+	document.body().ok_or("The page has no <body>")?.append_child(&created)?;
+	Ok(created)
+}
+
+#[wasm_bindgen(start)]
+pub fn start() -> Result<(), JsValue> {
+	let window = web_sys::window().ok_or("No window")?;
+	let document = window.document().ok_or("No document")?;
+
+	let canvas: HtmlCanvasElement = element(&document, CANVAS_ID, "canvas")?.dyn_into()?;
+	let board = Board::new(5, &SHIP_TYPES)?;
+	canvas.set_width((CELL * board.size() as f64) as u32);
+	canvas.set_height((CELL * board.size() as f64) as u32);
+	let status = element(&document, STATUS_ID, "p")?;
+	let context: CanvasRenderingContext2d = canvas.get_context("2d")?.ok_or("No 2D canvas context")?.dyn_into()?;
+
+	let seed = window.performance().map_or(0.0, |performance| performance.now() * 1000.0) as u64;
+	let layout = Layout::random(&mut Rng::new(seed), Constraints::new(&board, true)).ok_or("No valid layout exists")?;
+	let demo = Rc::new(RefCell::new(Demo { layout, state: GameState::new(&board), strategy: Greedy::new(TieBreak::Lexicographic), context, status }));
+	demo.borrow_mut().draw();
+
+	let on_click = Closure::<dyn FnMut(MouseEvent)>::new(move |event: MouseEvent| {
+		let row = (event.offset_y() as f64 / CELL) as u8;
+		let col = (event.offset_x() as f64 / CELL) as u8;
+		if let Ok(pos) = board.square(row, col) {
+			demo.borrow_mut().fire(pos);
+		}
+	});
+	canvas.add_event_listener_with_callback("click", on_click.as_ref().unchecked_ref())?;
+
+	// The listener lives as long as the page
+	on_click.forget();
+	Ok(())
+}